@@ -1,8 +1,7 @@
 #[macro_use]
 extern crate bencher;
 extern crate minimax;
-#[path = "../examples/connect4.rs"]
-mod connect4;
+use minimax::games::connect4;
 
 use bencher::Bencher;
 use minimax::*;
@@ -10,7 +9,7 @@ use minimax::*;
 fn bench_negamax(b: &mut Bencher) {
     let board = connect4::Board::default();
     b.iter(|| {
-        let mut s = Negamax::new(connect4::BasicEvaluator::default(), 5);
+        let mut s = Negamax::new(connect4::BasicEvaluator, 5);
         let m = s.choose_move(&board);
         assert!(m.is_some());
     });
@@ -20,7 +19,7 @@ fn bench_iterative(b: &mut Bencher) {
     let board = connect4::Board::default();
     b.iter(|| {
         let mut s = IterativeSearch::new(
-            connect4::BasicEvaluator::default(),
+            connect4::BasicEvaluator,
             IterativeOptions::new().with_table_byte_size(32_000),
         );
         s.set_max_depth(5);
@@ -33,7 +32,7 @@ fn bench_parallel(b: &mut Bencher) {
     let board = connect4::Board::default();
     b.iter(|| {
         let mut s = ParallelSearch::new(
-            connect4::BasicEvaluator::default(),
+            connect4::BasicEvaluator,
             IterativeOptions::new().with_table_byte_size(32_000),
             ParallelOptions::new(),
         );
@@ -44,7 +44,7 @@ impl minimax::Game for Mancala {
     }
 
     fn apply(board: &mut Board, m: Move) -> Option<Board> {
-        let mut board = board.clone();
+        let mut board = *board;
         if board.skipped {
             board.skipped = false;
             board.to_move = !board.to_move;
@@ -58,10 +58,9 @@ impl minimax::Game for Mancala {
         board.pits[player][i] = 0;
         // At the beginning of each iteration, it points at the previous pit.
         while stones > 0 {
-            if player == board.to_move as usize && i == 0 {
-                i = 6;
-                player ^= 1;
-            } else if player != board.to_move as usize && i == 1 {
+            if (player == board.to_move as usize && i == 0)
+                || (player != board.to_move as usize && i == 1)
+            {
                 i = 6;
                 player ^= 1;
             } else {
@@ -156,7 +155,7 @@ impl minimax::Evaluator for Evaluator {
 fn main() {
     let mut board = Board::default();
     let opts = minimax::IterativeOptions::new().verbose();
-    let mut strategy = minimax::IterativeSearch::new(Evaluator::default(), opts);
+    let mut strategy = minimax::IterativeSearch::new(Evaluator, opts);
     strategy.set_timeout(std::time::Duration::from_secs(1));
     while Mancala::get_winner(&board).is_none() {
         println!("{}", board);
@@ -0,0 +1,202 @@
+//! Play a match between two strategies on one of the bundled games, and
+//! print the records of the games and the results.
+//!
+//! For example:
+//!
+//! ```text
+//! cargo run --release --example play -- connect4 iterative:time=1s mcts:time=1s --games 4
+//! ```
+
+extern crate minimax;
+
+use minimax::games::{connect4, nim, othello, ttt};
+use minimax::*;
+use std::path::Path;
+use std::process::exit;
+use std::time::Duration;
+
+const USAGE: &str = "usage: play <game> <strategy> <strategy> [options]
+
+games: ttt, connect4, nim, othello
+
+strategies: random, negamax, iterative, parallel, mcts
+  followed by comma-separated settings after a colon, e.g. iterative:depth=8
+  or mcts:time=500ms,threads=2. Settings are depth, time, rollouts, threads.
+
+options:
+  --games <n>             games to play, alternating who moves first (default 1)
+  --time <duration>       main time per game for each player, e.g. 10s
+  --increment <duration>  time added after each move
+  --byo-yomi <duration>   byo-yomi period once the main time runs out
+  --periods <n>           number of byo-yomi periods (default 1)
+  --max-moves <n>         declare a draw after this many moves
+  --checkpoint <file>     record results in this file, resuming from it if it exists";
+
+struct Args {
+    game: String,
+    strategies: [String; 2],
+    games: u32,
+    time_control: Option<TimeControl>,
+    max_moves: Option<u32>,
+    checkpoint: Option<String>,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(1);
+}
+
+// Parses durations like 10s, 500ms, or 2m.
+fn parse_duration(text: &str) -> Duration {
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(text.len());
+    let value = text[..split].parse::<f64>().unwrap_or_else(|_| fail("bad duration"));
+    let seconds = match &text[split..] {
+        "ms" => value / 1000.0,
+        "s" | "" => value,
+        "m" => value * 60.0,
+        _ => fail("bad duration unit"),
+    };
+    Duration::from_secs_f64(seconds)
+}
+
+fn parse_number(text: Option<String>) -> u32 {
+    text.and_then(|text| text.parse().ok()).unwrap_or_else(|| fail("expected a number"))
+}
+
+fn parse_args() -> Args {
+    let mut args = std::env::args().skip(1);
+    let mut positional = Vec::new();
+    let mut games = 1;
+    let mut time = None;
+    let mut increment = Duration::ZERO;
+    let mut byo_yomi = Duration::ZERO;
+    let mut periods = 1;
+    let mut max_moves = None;
+    let mut checkpoint = None;
+    while let Some(arg) = args.next() {
+        let mut value =
+            || args.next().unwrap_or_else(|| fail(&format!("missing value for {}", arg)));
+        match arg.as_str() {
+            "--games" => games = parse_number(Some(value())),
+            "--time" => time = Some(parse_duration(&value())),
+            "--increment" => increment = parse_duration(&value()),
+            "--byo-yomi" => byo_yomi = parse_duration(&value()),
+            "--periods" => periods = parse_number(Some(value())),
+            "--max-moves" => max_moves = Some(parse_number(Some(value()))),
+            "--checkpoint" => checkpoint = Some(value()),
+            "-h" | "--help" => fail(""),
+            _ if arg.starts_with("--") => fail(&format!("unknown option {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    let [game, first, second]: [String; 3] =
+        positional.try_into().unwrap_or_else(|_| fail("expected a game and two strategies"));
+    let time_control = time.map(|time| {
+        let mut control = TimeControl::new(time).with_increment(increment);
+        if byo_yomi > Duration::ZERO {
+            control = control.with_byo_yomi(byo_yomi, periods);
+        }
+        control
+    });
+    Args { game, strategies: [first, second], games, time_control, max_moves, checkpoint }
+}
+
+// Builds a strategy from a spec like "iterative:depth=8,threads=2".
+fn build_strategy<E>(spec: &str, eval: E) -> Box<dyn Strategy<E::G>>
+where
+    E: Evaluator + Clone + Send + Sync + 'static,
+    E::G: Sync + 'static,
+    <E::G as Game>::S: Clone + Send + Sync + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + Sync + 'static,
+{
+    let (name, settings) = spec.split_once(':').unwrap_or((spec, ""));
+    let mut depth = None;
+    let mut time = None;
+    let mut rollouts = None;
+    let mut threads = None;
+    for setting in settings.split(',').filter(|setting| !setting.is_empty()) {
+        let (key, value) =
+            setting.split_once('=').unwrap_or_else(|| fail(&format!("bad setting {}", setting)));
+        match key {
+            "depth" => depth = Some(parse_number(Some(value.to_string())) as u8),
+            "time" => time = Some(parse_duration(value)),
+            "rollouts" => rollouts = Some(parse_number(Some(value.to_string()))),
+            "threads" => threads = Some(parse_number(Some(value.to_string())) as usize),
+            _ => fail(&format!("unknown setting {}", key)),
+        }
+    }
+    let mut strategy: Box<dyn Strategy<E::G>> = match name {
+        "random" => Box::new(Random::new()),
+        "negamax" => Box::new(Negamax::new(eval, depth.unwrap_or(4))),
+        "iterative" => Box::new(IterativeSearch::new(eval, IterativeOptions::new())),
+        "parallel" => {
+            let mut opts = ParallelOptions::new();
+            if let Some(threads) = threads {
+                opts = opts.with_num_threads(threads);
+            }
+            Box::new(ParallelSearch::new(eval, IterativeOptions::new(), opts))
+        }
+        "mcts" => {
+            let mut opts = MCTSOptions::default();
+            if let Some(threads) = threads {
+                opts = opts.with_num_threads(threads);
+            }
+            let mut mcts = MonteCarloTreeSearch::new(opts);
+            if let Some(rollouts) = rollouts {
+                mcts.set_max_rollouts(rollouts);
+            }
+            Box::new(mcts)
+        }
+        _ => fail(&format!("unknown strategy {}", name)),
+    };
+    if name != "negamax" {
+        if let Some(depth) = depth {
+            strategy.set_max_depth(depth);
+        }
+    }
+    if let Some(time) = time {
+        strategy.set_timeout(time);
+    }
+    strategy
+}
+
+fn play<E>(args: &Args, start: <E::G as Game>::S, eval: E)
+where
+    E: Evaluator + Clone + Send + Sync + 'static,
+    E::G: Sync + 'static,
+    <E::G as Game>::S: Clone + Send + Sync + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + Sync + 'static,
+{
+    let [first, second] = &args.strategies;
+    let mut tournament = Tournament::new()
+        .with_player(first, build_strategy(first, eval.clone()))
+        .with_player(second, build_strategy(second, eval))
+        .with_games_per_pairing(args.games);
+    if let Some(time_control) = args.time_control {
+        tournament = tournament.with_time_control(time_control);
+    }
+    if let Some(max_moves) = args.max_moves {
+        tournament = tournament.with_max_moves(max_moves);
+    }
+    let results = match &args.checkpoint {
+        Some(path) => tournament
+            .run_with_checkpoint(&start, Path::new(path))
+            .unwrap_or_else(|err| fail(&format!("checkpoint {}: {}", path, err))),
+        None => tournament.run(&start),
+    };
+    for (i, record) in tournament.records().iter().enumerate() {
+        println!("game {}\n{}", i + 1, record.to_text());
+    }
+    print!("{}", results);
+}
+
+fn main() {
+    let args = parse_args();
+    match args.game.as_str() {
+        "ttt" => play(&args, ttt::Board::default(), ttt::Evaluator),
+        "connect4" => play(&args, connect4::Board::default(), connect4::BasicEvaluator),
+        "nim" => play(&args, nim::Board::default(), nim::Evaluator),
+        "othello" => play(&args, othello::Board::default(), othello::Evaluator),
+        game => fail(&format!("unknown game {}", game)),
+    }
+}
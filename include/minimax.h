@@ -0,0 +1,55 @@
+/* C API of the minimax crate, built with the `ffi` feature. See src/ffi.rs. */
+
+#ifndef MINIMAX_H
+#define MINIMAX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A function that panics returns its error value, and the engine it was
+ * given should then be freed. */
+typedef struct MinimaxEngine MinimaxEngine;
+
+typedef struct MinimaxStats {
+    /* Nodes searched, or simulations for Monte Carlo engines. */
+    uint64_t nodes;
+    /* Maximum depth completely searched. */
+    uint32_t depth;
+    /* Whether value was reported. */
+    bool has_value;
+    /* The value of the chosen move, for the player who was to move. */
+    int64_t value;
+    uint64_t elapsed_ms;
+} MinimaxStats;
+
+/* Strategies are "iterative", "parallel", "mcts", or "random". Returns NULL
+ * if the game or strategy is unknown. */
+MinimaxEngine *minimax_engine_new(const char *game, const char *strategy);
+
+/* Options are "time_ms", "depth", or "seed", with integer values. */
+bool minimax_engine_set_option(MinimaxEngine *engine, const char *name, const char *value);
+
+/* Returns false if engine is NULL. */
+bool minimax_engine_reset(MinimaxEngine *engine);
+
+/* Returns false if the move isn't legal. */
+bool minimax_engine_play(MinimaxEngine *engine, const char *move);
+
+/* Writes the chosen move to buf without playing it. Returns its length, or
+ * -1 if there's no move or it doesn't fit. */
+ptrdiff_t minimax_engine_choose_move(MinimaxEngine *engine, char *buf, size_t len);
+
+bool minimax_engine_stats(const MinimaxEngine *engine, MinimaxStats *stats);
+
+void minimax_engine_free(MinimaxEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
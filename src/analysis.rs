@@ -0,0 +1,130 @@
+//! Reviewing finished games: each move is compared with what a strategy
+//! would have played instead.
+
+use super::interface::*;
+use super::json;
+use super::record::GameRecord;
+use super::util::move_notation;
+
+use std::fmt::Write;
+
+/// A strategy's view of one move of a game.
+#[derive(Clone, Debug)]
+pub struct Annotation<M> {
+    /// The move that was played.
+    pub played: M,
+    /// The value of the played move for the player who made it, if the
+    /// strategy reports values.
+    pub played_value: Option<Evaluation>,
+    /// The move the strategy prefers in the same position.
+    pub best: Option<M>,
+    /// The value of the preferred move for the player to move.
+    pub best_value: Option<Evaluation>,
+    /// The search of the position before the move.
+    pub info: SearchInfo<M>,
+}
+
+impl<M: Eq> Annotation<M> {
+    /// Whether the played move was the strategy's choice.
+    pub fn agrees(&self) -> bool {
+        self.best.as_ref() == Some(&self.played)
+    }
+
+    /// How much worse the played move was than the preferred one, or None
+    /// if either has no value.
+    pub fn loss(&self) -> Option<Evaluation> {
+        Some(self.best_value?.saturating_sub(self.played_value?).max(0))
+    }
+}
+
+/// Search every position of a game with `strategy`, using whatever budget
+/// it has been given, and annotate each move with its value and the
+/// strategy's preferred alternative.
+///
+/// When the played move differs from the strategy's choice, the position
+/// after it is also searched to evaluate the played move.
+pub fn analyze<G, S>(record: &GameRecord<G>, strategy: &mut S) -> Vec<Annotation<G::M>>
+where
+    G: Game,
+    G::S: Clone,
+    G::M: Copy + Eq,
+    S: Strategy<G> + ?Sized,
+{
+    let positions = record.positions();
+    let mut annotations = Vec::with_capacity(record.moves.len());
+    for (recorded, pair) in record.moves.iter().zip(positions.windows(2)) {
+        let info = strategy.choose_move_with_info(&pair[0]);
+        let played_value = if info.best_move == Some(recorded.m) {
+            info.value
+        } else if let Some(winner) = G::get_winner(&pair[1]) {
+            Some(-winner.evaluate())
+        } else {
+            strategy.choose_move_with_info(&pair[1]).value.map(|value| -value)
+        };
+        annotations.push(Annotation {
+            played: recorded.m,
+            played_value,
+            best: info.best_move,
+            best_value: info.value,
+            info,
+        });
+    }
+    annotations
+}
+
+/// Write an analyzed game as text, with one line per move giving the
+/// move and its value, followed by the preferred move and its value when
+/// they differ.
+pub fn annotated_text<G>(record: &GameRecord<G>, annotations: &[Annotation<G::M>]) -> String
+where
+    G: Game,
+    G::S: Clone,
+    G::M: Copy + Eq,
+{
+    let value = |value: Option<Evaluation>| value.map_or("?".to_string(), |v| v.to_string());
+    let mut text = String::new();
+    for (i, (annotation, state)) in annotations.iter().zip(record.positions()).enumerate() {
+        let _ = write!(
+            text,
+            "{}. {} ({})",
+            i + 1,
+            move_notation::<G>(&state, annotation.played),
+            value(annotation.played_value)
+        );
+        if let Some(best) = annotation.best.filter(|_| !annotation.agrees()) {
+            let _ = write!(
+                text,
+                " best {} ({})",
+                move_notation::<G>(&state, best),
+                value(annotation.best_value)
+            );
+        }
+        let _ = writeln!(text);
+    }
+    text
+}
+
+/// Write an analyzed game as a line of JSON: an array with an object for
+/// each move, giving the played and preferred moves with their values and
+/// the depth, nodes, time in seconds, and principal variation of the search.
+pub fn annotated_json<G>(record: &GameRecord<G>, annotations: &[Annotation<G::M>]) -> String
+where
+    G: Game,
+    G::S: Clone,
+    G::M: Copy + Eq,
+{
+    let value = |value: Option<Evaluation>| value.map(|value| value as f64);
+    json::array(annotations.iter().zip(record.positions()).map(|(annotation, state)| {
+        let best = annotation.best.map(|best| json::string(&move_notation::<G>(&state, best)));
+        json::Object::new()
+            .string("played", &move_notation::<G>(&state, annotation.played))
+            .optional("value", value(annotation.played_value))
+            .raw("best", best.as_deref().unwrap_or("null"))
+            .optional("best_value", value(annotation.best_value))
+            .number("depth", f64::from(annotation.info.depth))
+            .number("nodes", annotation.info.nodes as f64)
+            .number("time", annotation.info.elapsed.as_secs_f64())
+            .raw("pv", &json::line::<G>(&state, &annotation.info.principal_variation))
+            .finish()
+    }))
+}
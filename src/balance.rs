@@ -0,0 +1,152 @@
+//! Measuring how fair a game's rules are, by playing a strategy against
+//! itself and comparing how often each seat wins.
+//!
+//! For game designers balancing their rules, e.g. with komi or a swap rule:
+//! measure the first player's score for each variant of the rules, and pick
+//! the one closest to even.
+
+use super::interface::*;
+use super::match_play::Match;
+use super::record::Outcome;
+use super::tournament::{estimate_elo, score_variance, EloEstimate};
+
+use std::fmt;
+
+/// The results of self-play games by seat.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeatResults {
+    /// Games won by the player who moved first, and by the other player.
+    pub wins: [u32; 2],
+    /// Games drawn, whoever moved first.
+    pub draws: u32,
+}
+
+impl SeatResults {
+    /// The number of games counted, won or drawn.
+    pub fn games(&self) -> u32 {
+        self.wins[0] + self.wins[1] + self.draws
+    }
+
+    /// The fraction of games won by the player who moved first (0) or the
+    /// other player (1).
+    pub fn win_rate(&self, seat: usize) -> f64 {
+        rate(self.wins[seat], self.games())
+    }
+
+    /// The fraction of games drawn.
+    pub fn draw_rate(&self) -> f64 {
+        rate(self.draws, self.games())
+    }
+
+    /// The 95% confidence interval of a seat's win rate, by the Wilson
+    /// score method, which stays within 0 and 1 even for lopsided results.
+    pub fn win_rate_interval(&self, seat: usize) -> (f64, f64) {
+        wilson_interval(self.win_rate(seat), self.games())
+    }
+
+    /// The first player's mean score, counting a draw as half a win. A fair
+    /// game scores 0.5.
+    pub fn first_player_score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        score_variance(self.wins[0] as f64, self.draws as f64, self.wins[1] as f64).0
+    }
+
+    /// The 95% confidence interval of the first player's score, by the
+    /// Wilson score method, like `win_rate_interval`.
+    pub fn first_player_score_interval(&self) -> (f64, f64) {
+        wilson_interval(self.first_player_score(), self.games())
+    }
+
+    /// The first player's advantage as a difference in Elo rating.
+    pub fn first_player_elo(&self) -> EloEstimate {
+        estimate_elo(self.wins[0], self.draws, self.wins[1])
+    }
+
+    pub(crate) fn add(&mut self, result: Outcome) {
+        match result {
+            Outcome::Win(seat) => self.wins[seat.min(1)] += 1,
+            Outcome::Draw => self.draws += 1,
+        }
+    }
+}
+
+// The Wilson score interval of a rate measured over `games`.
+fn wilson_interval(p: f64, games: u32) -> (f64, f64) {
+    if games == 0 {
+        return (0.0, 1.0);
+    }
+    let (z, games) = (1.96, games as f64);
+    let center = (p + z * z / (2.0 * games)) / (1.0 + z * z / games);
+    let margin =
+        z / (1.0 + z * z / games) * (p * (1.0 - p) / games + z * z / (4.0 * games * games)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+fn rate(count: u32, games: u32) -> f64 {
+    if games == 0 {
+        0.0
+    } else {
+        count as f64 / games as f64
+    }
+}
+
+impl fmt::Display for SeatResults {
+    /// Shows each seat's win rate with its confidence interval, the draw
+    /// rate, and the first player's score and Elo advantage.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "games: {}", self.games())?;
+        for (seat, name) in ["first player", "second player"].iter().enumerate() {
+            let (low, high) = self.win_rate_interval(seat);
+            writeln!(
+                f,
+                "{:13} wins {:5.1}% ({:.1}% to {:.1}%)",
+                name,
+                100.0 * self.win_rate(seat),
+                100.0 * low,
+                100.0 * high
+            )?;
+        }
+        writeln!(f, "{:13} {:5.1}%", "draws", 100.0 * self.draw_rate())?;
+        let (low, high) = self.first_player_score_interval();
+        let elo = self.first_player_elo();
+        writeln!(
+            f,
+            "first player scores {:.3} ({:.3} to {:.3}), {:.1} +/- {:.1} Elo",
+            self.first_player_score(),
+            low,
+            high,
+            elo.elo,
+            elo.error
+        )
+    }
+}
+
+/// Play `games` games of `strategy` against itself from `start`, with the
+/// settings of `game`, such as a move limit or a time control, and count
+/// the wins of each seat. Games left unfinished, e.g. at the move limit, are
+/// not counted.
+///
+/// Each seat of each game is played by a fresh copy of `strategy`, so with
+/// its own random numbers, which vary the games. A strategy that always
+/// plays the same moves plays the same game every time, so give it some
+/// randomness, like `Negamax`, which chooses among equally good moves at
+/// random.
+pub fn seat_results<G, S>(game: &Match, start: &G::S, strategy: &S, games: u32) -> SeatResults
+where
+    G: Game,
+    G::S: Clone,
+    G::M: Copy + Eq,
+    S: Strategy<G> + FreshCopy,
+{
+    let mut results = SeatResults::default();
+    for _ in 0..games {
+        let (mut first, mut second) = (strategy.fresh_copy(), strategy.fresh_copy());
+        if let Some(result) = game.play(start, &mut first, &mut second).result {
+            results.add(result);
+        }
+    }
+    results
+}
@@ -0,0 +1,216 @@
+//! Statistics over a corpus of game records, e.g. from self-play or a
+//! tournament: how often each move is played at each move number and how
+//! it scored, how long games last, and how they end.
+//!
+//! The statistics can be exported as CSV, e.g. for a heatmap of move
+//! popularity by move number, or as JSON.
+//!
+//! Moves are told apart by `Game::notation`, so only games that implement
+//! it have move statistics.
+
+use super::balance::SeatResults;
+use super::interface::*;
+use super::json;
+use super::record::GameRecord;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Counts of the moves, lengths, and results of a set of games.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CorpusStats {
+    /// Games counted.
+    pub games: u32,
+    /// Results of the finished games, by seat.
+    pub results: SeatResults,
+    /// The number of games of each length, in moves.
+    pub lengths: BTreeMap<usize, u32>,
+    /// For each move number, from 1, and move notation, from
+    /// `Game::notation`, the results of the finished games in which it was
+    /// played. `played` also counts unfinished games. Moves without
+    /// notation aren't counted, since the index that stands in for it in
+    /// text protocols names different moves in different positions.
+    /// Serialized as a list of move numbers, notations, and stats.
+    #[cfg_attr(feature = "serde", serde(with = "move_list"))]
+    pub moves: BTreeMap<(usize, String), MoveStats>,
+    first_moves: Option<usize>,
+}
+
+/// How often a move was played, and how the games went.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveStats {
+    pub played: u32,
+    pub results: SeatResults,
+}
+
+impl CorpusStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only count the first `moves` moves of each game, e.g. to study
+    /// openings. Lengths and results still count whole games.
+    pub fn with_first_moves(mut self, moves: usize) -> Self {
+        self.first_moves = Some(moves);
+        self
+    }
+
+    /// The statistics of these games.
+    pub fn from_records<'a, G>(records: impl IntoIterator<Item = &'a GameRecord<G>>) -> Self
+    where
+        G: Game + 'a,
+        G::S: Clone,
+    {
+        let mut stats = Self::new();
+        for record in records {
+            stats.add(record);
+        }
+        stats
+    }
+
+    /// Count another game.
+    pub fn add<G>(&mut self, record: &GameRecord<G>)
+    where
+        G: Game,
+        G::S: Clone,
+    {
+        self.games += 1;
+        *self.lengths.entry(record.moves.len()).or_insert(0) += 1;
+        if let Some(result) = record.result {
+            self.results.add(result);
+        }
+        let counted = self.first_moves.unwrap_or(usize::MAX);
+        for (i, (recorded, state)) in
+            record.moves.iter().zip(record.positions()).take(counted).enumerate()
+        {
+            let Some(notation) = G::notation(&state, recorded.m) else {
+                continue;
+            };
+            let stats = self.moves.entry((i + 1, notation)).or_default();
+            stats.played += 1;
+            if let Some(result) = record.result {
+                stats.results.add(result);
+            }
+        }
+    }
+
+    /// The mean number of moves per game.
+    pub fn mean_length(&self) -> f64 {
+        let moves: usize =
+            self.lengths.iter().map(|(&length, &games)| length * games as usize).sum();
+        if self.games == 0 {
+            0.0
+        } else {
+            moves as f64 / self.games as f64
+        }
+    }
+
+    /// The fraction of the games that reached move `number` in which `m` was
+    /// played, e.g. the popularity of an opening move.
+    pub fn popularity(&self, number: usize, m: &str) -> f64 {
+        let reached: u32 = self.lengths.range(number..).map(|(_, &games)| games).sum();
+        let played = self.moves.get(&(number, m.to_string())).map_or(0, |stats| stats.played);
+        if reached == 0 {
+            0.0
+        } else {
+            played as f64 / reached as f64
+        }
+    }
+
+    /// The moves as CSV, with a header and a row per move number and move:
+    /// "number,move,played,first_wins,draws,second_wins".
+    pub fn moves_csv(&self) -> String {
+        let mut csv = "number,move,played,first_wins,draws,second_wins\n".to_string();
+        for ((number, m), stats) in self.moves.iter() {
+            let results = &stats.results;
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                number,
+                csv_field(m),
+                stats.played,
+                results.wins[0],
+                results.draws,
+                results.wins[1]
+            );
+        }
+        csv
+    }
+
+    /// The game lengths as CSV, with a header and a row per length:
+    /// "moves,games".
+    pub fn lengths_csv(&self) -> String {
+        let mut csv = "moves,games\n".to_string();
+        for (length, games) in self.lengths.iter() {
+            let _ = writeln!(csv, "{},{}", length, games);
+        }
+        csv
+    }
+
+    /// The statistics as a line of JSON, with the number of games, their
+    /// results and mean length, the number of games of each length, and
+    /// each move with its number, how often it was played, and its results.
+    pub fn to_json(&self) -> String {
+        let lengths = self.lengths.iter().map(|(&length, &games)| {
+            json::Object::new()
+                .number("moves", length as f64)
+                .number("games", games as f64)
+                .finish()
+        });
+        let moves = self.moves.iter().map(|((number, m), stats)| {
+            json::Object::new()
+                .number("number", *number as f64)
+                .string("move", m)
+                .number("played", stats.played as f64)
+                .raw("results", &results_json(&stats.results))
+                .finish()
+        });
+        json::Object::new()
+            .number("games", self.games as f64)
+            .raw("results", &results_json(&self.results))
+            .number("mean_length", self.mean_length())
+            .raw("lengths", &json::array(lengths))
+            .raw("moves", &json::array(moves))
+            .finish()
+    }
+}
+
+fn results_json(results: &SeatResults) -> String {
+    json::Object::new()
+        .number("first_wins", results.wins[0] as f64)
+        .number("draws", results.draws as f64)
+        .number("second_wins", results.wins[1] as f64)
+        .finish()
+}
+
+// Quote a field that would otherwise break the row.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// JSON object keys can't be pairs, so the moves are a list of entries.
+#[cfg(feature = "serde")]
+mod move_list {
+    use super::MoveStats;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub(super) fn serialize<S: Serializer>(
+        moves: &BTreeMap<(usize, String), MoveStats>, serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(moves.iter().map(|((number, m), stats)| (number, m, stats)))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<(usize, String), MoveStats>, D::Error> {
+        let moves = Vec::<(usize, String, MoveStats)>::deserialize(deserializer)?;
+        Ok(moves.into_iter().map(|(number, m, stats)| ((number, m), stats)).collect())
+    }
+}
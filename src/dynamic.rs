@@ -0,0 +1,250 @@
+//! Games and strategies chosen at runtime, e.g. from a configuration file,
+//! instead of monomorphizing every combination at compile time.
+//!
+//! A `BoxedStrategy` is a strategy for one game chosen at runtime, and is
+//! itself a `Strategy`. A `DynEngine` erases the game too: it keeps its own
+//! position, and takes and returns moves as text, in the notation of
+//! `util::move_notation`. A `Registry` makes engines by game and strategy
+//! name.
+
+use super::interface::*;
+use super::strategies::iterative::{IterativeOptions, IterativeSearch};
+use super::strategies::mcts::{MCTSOptions, MonteCarloTreeSearch};
+use super::strategies::random::Random;
+#[cfg(not(target_arch = "wasm32"))]
+use super::strategies::ybw::{ParallelOptions, ParallelSearch};
+use super::util::{move_notation, parse_move};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A strategy chosen at runtime.
+pub type BoxedStrategy<G> = Box<dyn Strategy<G> + Send>;
+
+/// A strategy playing a game, both chosen at runtime, from its own
+/// position. Moves are text, in the notation of `util::move_notation`.
+pub trait DynEngine: Send {
+    /// Return to the starting position, for a new game.
+    fn reset(&mut self);
+
+    /// The legal moves in the position.
+    fn moves(&self) -> Vec<String>;
+
+    /// Play a move in the position. Returns false if it isn't legal.
+    fn play(&mut self, m: &str) -> bool;
+
+    /// The result, if the game is over.
+    fn winner(&self) -> Option<Winner>;
+
+    /// The position, as described by `Game::describe`.
+    fn describe(&self) -> Vec<(String, String)>;
+
+    /// Search for a move in the position, without playing it.
+    fn choose_move(&mut self) -> SearchInfo<String>;
+
+    /// Search for the `k` best moves in the position, as
+    /// `Strategy::analyze` does.
+    fn analyze(&mut self, k: usize) -> Vec<MoveAnalysis<String>>;
+
+    /// See `Strategy::set_timeout`.
+    fn set_timeout(&mut self, timeout: Duration);
+
+    /// See `Strategy::set_max_depth`.
+    fn set_max_depth(&mut self, depth: u8);
+
+    /// See `Strategy::set_rng_seed`.
+    fn set_rng_seed(&mut self, seed: u64);
+}
+
+/// A `DynEngine` for game `G`.
+pub struct Engine<G: Game> {
+    strategy: BoxedStrategy<G>,
+    start: G::S,
+    state: G::S,
+}
+
+impl<G: Game> Engine<G>
+where
+    G::S: Clone,
+{
+    /// Play with `strategy`, starting from `start`.
+    pub fn new(strategy: BoxedStrategy<G>, start: G::S) -> Self {
+        Self { strategy, state: start.clone(), start }
+    }
+
+    // Name each move of a line in the position it is played from.
+    fn line(&self, moves: &[G::M]) -> Vec<String>
+    where
+        G::M: Copy + Eq,
+    {
+        let mut state = self.state.clone();
+        let mut line = Vec::new();
+        for &m in moves.iter() {
+            line.push(move_notation::<G>(&state, m));
+            if let Some(new_state) = G::apply(&mut state, m) {
+                state = new_state;
+            }
+        }
+        line
+    }
+}
+
+impl<G: Game> DynEngine for Engine<G>
+where
+    G::S: Clone + Send,
+    G::M: Copy + Eq,
+{
+    fn reset(&mut self) {
+        self.strategy.new_game();
+        self.state = self.start.clone();
+    }
+
+    fn moves(&self) -> Vec<String> {
+        let mut moves = Vec::new();
+        G::generate_moves(&self.state, &mut moves);
+        moves.into_iter().map(|m| move_notation::<G>(&self.state, m)).collect()
+    }
+
+    fn play(&mut self, m: &str) -> bool {
+        let Some(m) = parse_move::<G>(&self.state, m) else {
+            return false;
+        };
+        self.strategy.notify_move(&self.state, m);
+        if let Some(new_state) = G::apply(&mut self.state, m) {
+            self.state = new_state;
+        }
+        true
+    }
+
+    fn winner(&self) -> Option<Winner> {
+        G::get_winner(&self.state)
+    }
+
+    fn describe(&self) -> Vec<(String, String)> {
+        G::describe(&self.state)
+    }
+
+    fn choose_move(&mut self) -> SearchInfo<String> {
+        let info = self.strategy.choose_move_with_info(&self.state);
+        SearchInfo {
+            best_move: info.best_move.map(|m| move_notation::<G>(&self.state, m)),
+            value: info.value,
+            win_probability: info.win_probability,
+            principal_variation: self.line(&info.principal_variation),
+            nodes: info.nodes,
+            depth: info.depth,
+            elapsed: info.elapsed,
+            recommendation: info.recommendation,
+        }
+    }
+
+    fn analyze(&mut self, k: usize) -> Vec<MoveAnalysis<String>> {
+        let lines = self.strategy.analyze(&self.state, k);
+        lines
+            .into_iter()
+            .map(|line| MoveAnalysis {
+                m: move_notation::<G>(&self.state, line.m),
+                value: line.value,
+                win_probability: line.win_probability,
+                principal_variation: self.line(&line.principal_variation),
+            })
+            .collect()
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.strategy.set_timeout(timeout);
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        self.strategy.set_max_depth(depth);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.strategy.set_rng_seed(seed);
+    }
+}
+
+type Constructor = Arc<dyn Fn(&str) -> Option<Box<dyn DynEngine>> + Send + Sync>;
+
+/// Games registered by name, to make engines for them by name.
+#[derive(Clone, Default)]
+pub struct Registry {
+    games: Vec<(String, Constructor)>,
+}
+
+impl Registry {
+    pub const fn new() -> Self {
+        Self { games: Vec::new() }
+    }
+
+    /// Register the reference games as `ttt`, `connect4`, `nim`, and
+    /// `othello`, with the standard strategies.
+    #[cfg(feature = "games")]
+    pub fn with_reference_games(self) -> Self {
+        use super::games::*;
+        self.with_game("ttt", ttt::Evaluator)
+            .with_game("connect4", connect4::BasicEvaluator)
+            .with_game("nim", nim::Evaluator)
+            .with_game("othello", othello::Evaluator)
+    }
+
+    /// Register a game under `name`, starting from `G::S::default()`, with
+    /// the standard strategies: `iterative`, `parallel`, `mcts`, and
+    /// `random`. The alpha-beta strategies use `evaluator`.
+    pub fn with_game<E>(self, name: &str, evaluator: E) -> Self
+    where
+        E: Evaluator + Clone + Send + Sync + 'static,
+        E::G: Send + Sync + 'static,
+        <E::G as Game>::S: Clone + Default + Send + Sync + 'static,
+        <E::G as Game>::M: Copy + Eq + Send + Sync + 'static,
+    {
+        let strategies = move |strategy: &str| -> Option<BoxedStrategy<E::G>> {
+            Some(match strategy {
+                "iterative" => {
+                    Box::new(IterativeSearch::new(evaluator.clone(), IterativeOptions::new()))
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                "parallel" => Box::new(ParallelSearch::new(
+                    evaluator.clone(),
+                    IterativeOptions::new(),
+                    ParallelOptions::new(),
+                )),
+                "mcts" => Box::new(MonteCarloTreeSearch::<E::G>::new(MCTSOptions::default())),
+                "random" => Box::new(Random::<E::G>::new()),
+                _ => return None,
+            })
+        };
+        self.with_strategies(name, Default::default(), strategies)
+    }
+
+    /// Register a game under `name`, starting from `start`, with the
+    /// strategies that `strategies` makes by name, or None for unknown
+    /// names. Replaces any game already registered under the name.
+    pub fn with_strategies<G, F>(mut self, name: &str, start: G::S, strategies: F) -> Self
+    where
+        G: Game + 'static,
+        G::S: Clone + Send + Sync + 'static,
+        G::M: Copy + Eq,
+        F: Fn(&str) -> Option<BoxedStrategy<G>> + Send + Sync + 'static,
+    {
+        let new = move |strategy: &str| -> Option<Box<dyn DynEngine>> {
+            let strategy = strategies(strategy)?;
+            Some(Box::new(Engine::<G>::new(strategy, start.clone())))
+        };
+        self.games.retain(|(other, _)| other != name);
+        self.games.push((name.to_string(), Arc::new(new)));
+        self
+    }
+
+    /// The names of the registered games.
+    pub fn games(&self) -> impl Iterator<Item = &str> {
+        self.games.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Make an engine playing `game` with `strategy` from the game's start,
+    /// or None if either is unknown.
+    pub fn new_engine(&self, game: &str, strategy: &str) -> Option<Box<dyn DynEngine>> {
+        let (_, new) = self.games.iter().find(|(name, _)| name == game)?;
+        new(strategy)
+    }
+}
@@ -0,0 +1,246 @@
+//! A C API, for embedding the engines in C, C++, or C# game clients.
+//!
+//! Games are looked up by name. The reference games are registered as
+//! `ttt`, `connect4`, `nim`, and `othello` when the `games` feature is
+//! enabled, and others can be added from Rust with `register_game`. Moves
+//! are passed as text, in the notation of `util::move_notation`.
+//!
+//! To build a library for other languages, make a crate with
+//! `crate-type = ["cdylib"]` or `["staticlib"]` that depends on this one with
+//! the `ffi` feature, and registers its games on startup. `include/minimax.h`
+//! declares the functions.
+//!
+//! Panics don't unwind into the caller: a function that panics returns its
+//! error value instead, and the engine it was given should then be freed.
+
+use super::dynamic::{DynEngine, Registry};
+use super::interface::*;
+
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "games")]
+use std::sync::Once;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Statistics about an engine's last search, filled in by
+/// `minimax_engine_stats`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MinimaxStats {
+    /// Nodes searched, or simulations for Monte Carlo engines.
+    pub nodes: u64,
+    /// Maximum depth completely searched.
+    pub depth: u32,
+    /// Whether `value` was reported.
+    pub has_value: bool,
+    /// The value of the chosen move, from the perspective of the player
+    /// who was to move.
+    pub value: i64,
+    pub elapsed_ms: u64,
+}
+
+/// An engine created by `minimax_engine_new`. Opaque to C.
+pub struct MinimaxEngine {
+    engine: Box<dyn DynEngine>,
+    last: SearchInfo<String>,
+}
+
+impl MinimaxEngine {
+    fn set_option(&mut self, name: &str, value: &str) -> bool {
+        let Ok(value) = value.parse::<u64>() else {
+            return false;
+        };
+        match name {
+            "time_ms" => self.engine.set_timeout(Duration::from_millis(value)),
+            "depth" => self.engine.set_max_depth(value.min(u8::MAX as u64) as u8),
+            "seed" => self.engine.set_rng_seed(value),
+            _ => return false,
+        }
+        true
+    }
+
+    // Evaluation is already i64 with the eval-i64 feature.
+    #[allow(clippy::unnecessary_cast)]
+    fn stats(&self) -> MinimaxStats {
+        MinimaxStats {
+            nodes: self.last.nodes,
+            depth: self.last.depth as u32,
+            has_value: self.last.value.is_some(),
+            value: self.last.value.unwrap_or(0) as i64,
+            elapsed_ms: self.last.elapsed.as_millis() as u64,
+        }
+    }
+}
+
+static GAMES: Mutex<Registry> = Mutex::new(Registry::new());
+
+// The registered games, starting with the reference games.
+fn games() -> MutexGuard<'static, Registry> {
+    #[cfg(feature = "games")]
+    {
+        static REFERENCE_GAMES: Once = Once::new();
+        REFERENCE_GAMES.call_once(|| {
+            let mut games = GAMES.lock().unwrap();
+            *games = std::mem::take(&mut *games).with_reference_games();
+        });
+    }
+    GAMES.lock().unwrap()
+}
+
+/// Make a game available to `minimax_engine_new` under `name`, starting
+/// from `G::S::default()`, and using `evaluator` for the alpha-beta
+/// strategies. Replaces any game already registered under the name.
+pub fn register_game<E>(name: &str, evaluator: E)
+where
+    E: Evaluator + Clone + Send + Sync + 'static,
+    E::G: Send + Sync + 'static,
+    <E::G as Game>::S: Clone + Default + Send + Sync + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + Sync + 'static,
+{
+    let mut games = games();
+    *games = std::mem::take(&mut *games).with_game(name, evaluator);
+}
+
+// Run `f`, or return `error` if it panics, as unwinding into C is undefined
+// behavior.
+fn guard<T>(error: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(error)
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Create an engine playing `game` with `strategy`, one of `iterative`,
+/// `parallel`, `mcts`, or `random`, from the game's starting position.
+/// Returns null if either is unknown.
+///
+/// # Safety
+///
+/// Both arguments must be null or nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_new(
+    game: *const c_char, strategy: *const c_char,
+) -> *mut MinimaxEngine {
+    let (Some(game), Some(strategy)) = (str_arg(game), str_arg(strategy)) else {
+        return std::ptr::null_mut();
+    };
+    guard(std::ptr::null_mut(), || match games().new_engine(game, strategy) {
+        Some(engine) => {
+            Box::into_raw(Box::new(MinimaxEngine { engine, last: SearchInfo::default() }))
+        }
+        None => std::ptr::null_mut(),
+    })
+}
+
+/// Set `time_ms`, `depth`, or `seed` to an integer value. Returns false if
+/// the option is unknown or the value isn't an integer.
+///
+/// # Safety
+///
+/// `engine` must come from `minimax_engine_new`, and the other arguments
+/// must be null or nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_set_option(
+    engine: *mut MinimaxEngine, name: *const c_char, value: *const c_char,
+) -> bool {
+    match (engine.as_mut(), str_arg(name), str_arg(value)) {
+        (Some(engine), Some(name), Some(value)) => guard(false, || engine.set_option(name, value)),
+        _ => false,
+    }
+}
+
+/// Return to the starting position. Returns false if `engine` is null.
+///
+/// # Safety
+///
+/// `engine` must come from `minimax_engine_new`.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_reset(engine: *mut MinimaxEngine) -> bool {
+    match engine.as_mut() {
+        Some(engine) => guard(false, || {
+            engine.engine.reset();
+            true
+        }),
+        None => false,
+    }
+}
+
+/// Play a move in the engine's position. Returns false if it isn't legal.
+///
+/// # Safety
+///
+/// `engine` must come from `minimax_engine_new`, and `m` must be null or a
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_play(engine: *mut MinimaxEngine, m: *const c_char) -> bool {
+    match (engine.as_mut(), str_arg(m)) {
+        (Some(engine), Some(m)) => guard(false, || engine.engine.play(m)),
+        _ => false,
+    }
+}
+
+/// Search for a move in the engine's position, without playing it, and
+/// write it to `buf` as a nul-terminated string. Returns its length, or -1
+/// if there's no move or it doesn't fit in `len` bytes.
+///
+/// # Safety
+///
+/// `engine` must come from `minimax_engine_new`, and `buf` must point to
+/// `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_choose_move(
+    engine: *mut MinimaxEngine, buf: *mut c_char, len: usize,
+) -> isize {
+    let Some(engine) = engine.as_mut() else {
+        return -1;
+    };
+    let Some(last) = guard(None, || Some(engine.engine.choose_move())) else {
+        return -1;
+    };
+    engine.last = last;
+    match engine.last.best_move.as_ref() {
+        Some(m) if m.len() < len && !buf.is_null() => {
+            std::ptr::copy_nonoverlapping(m.as_ptr(), buf as *mut u8, m.len());
+            *buf.add(m.len()) = 0;
+            m.len() as isize
+        }
+        _ => -1,
+    }
+}
+
+/// Fill in `stats` from the last search. Returns false for null arguments.
+///
+/// # Safety
+///
+/// `engine` must come from `minimax_engine_new`, and `stats` must be null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_stats(
+    engine: *const MinimaxEngine, stats: *mut MinimaxStats,
+) -> bool {
+    match (engine.as_ref(), stats.as_mut()) {
+        (Some(engine), Some(stats)) => guard(false, || {
+            *stats = engine.stats();
+            true
+        }),
+        _ => false,
+    }
+}
+
+/// Destroy an engine.
+///
+/// # Safety
+///
+/// `engine` must be null, or come from `minimax_engine_new` and not be used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn minimax_engine_free(engine: *mut MinimaxEngine) {
+    if !engine.is_null() {
+        guard((), || drop(Box::from_raw(engine)));
+    }
+}
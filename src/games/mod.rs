@@ -0,0 +1,12 @@
+//! Complete implementations of a few small games, enabled with the `games`
+//! feature.
+//!
+//! These are used by the crate's own tests and benchmarks, and are meant as
+//! examples of implementing `Game` and `Evaluator`, from a simple
+//! state with undo (`nim`) to bitboards with incremental hashing
+//! (`connect4`).
+
+pub mod connect4;
+pub mod nim;
+pub mod othello;
+pub mod ttt;
@@ -0,0 +1,124 @@
+//! Nim: players take turns removing any number of objects from a single
+//! heap, and whoever takes the last object wins.
+//!
+//! The outcome of every position is known from the nim-sum of the heaps, so
+//! searches can be checked against `Evaluator`, which plays perfectly.
+
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Board {
+    pub heaps: Vec<u8>,
+}
+
+impl Board {
+    pub fn new(heaps: &[u8]) -> Self {
+        Self { heaps: heaps.to_vec() }
+    }
+
+    /// The XOR of the heap sizes, which is zero exactly when the player to
+    /// move loses with perfect play.
+    pub fn nim_sum(&self) -> u8 {
+        self.heaps.iter().fold(0, |sum, heap| sum ^ heap)
+    }
+}
+
+impl Default for Board {
+    /// The common 1-3-5-7 starting position.
+    fn default() -> Self {
+        Self::new(&[1, 3, 5, 7])
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for (i, heap) in self.heaps.iter().enumerate() {
+            writeln!(f, "{}: {}", i, "|".repeat(*heap as usize))?;
+        }
+        Ok(())
+    }
+}
+
+/// Take `count` objects from heap `heap`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Take {
+    pub heap: u8,
+    pub count: u8,
+}
+
+impl Display for Take {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}-{}", self.heap, self.count)
+    }
+}
+
+pub struct Game;
+
+impl crate::Game for Game {
+    type S = Board;
+    type M = Take;
+
+    fn generate_moves(b: &Board, moves: &mut Vec<Take>) {
+        for (heap, &size) in b.heaps.iter().enumerate() {
+            for count in 1..=size {
+                moves.push(Take { heap: heap as u8, count });
+            }
+        }
+    }
+
+    fn get_winner(b: &Board) -> Option<crate::Winner> {
+        // The player who took the last object won.
+        b.heaps.iter().all(|&heap| heap == 0).then_some(crate::Winner::PlayerJustMoved)
+    }
+
+    fn apply(b: &mut Board, m: Take) -> Option<Board> {
+        b.heaps[m.heap as usize] -= m.count;
+        None
+    }
+
+    fn undo(b: &mut Board, m: Take) {
+        b.heaps[m.heap as usize] += m.count;
+    }
+
+    fn zobrist_hash(b: &Board) -> u64 {
+        b.heaps.iter().fold(0, |hash: u64, &heap| {
+            (hash ^ heap as u64).wrapping_mul(0x100000001b3).rotate_left(17)
+        })
+    }
+
+    fn notation(_: &Board, m: Take) -> Option<String> {
+        Some(m.to_string())
+    }
+
+    fn move_bits() -> Option<crate::MoveBits<Take>> {
+        Some((
+            |m| (m.heap as u32) << 8 | m.count as u32,
+            |bits| Take { heap: (bits >> 8) as u8, count: bits as u8 },
+        ))
+    }
+
+    fn describe(b: &Board) -> Vec<(String, String)> {
+        b.heaps
+            .iter()
+            .enumerate()
+            .map(|(i, heap)| (format!("heap {}", i), heap.to_string()))
+            .collect()
+    }
+}
+
+/// Scores positions by the nim-sum, which is exact.
+#[derive(Clone, Default)]
+pub struct Evaluator;
+
+impl crate::Evaluator for Evaluator {
+    type G = Game;
+    fn evaluate(&self, b: &Board) -> crate::Evaluation {
+        if b.nim_sum() == 0 {
+            -1
+        } else {
+            1
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Othello, on the standard 8x8 board, using bitboards.
+//!
+//! A player with no legal placement must pass, and the game ends when neither
+//! player can place a disc. Its perft counts are well known, which makes it a
+//! good check of move generation.
+
+use std::fmt::{Display, Formatter, Result};
+
+// Squares are numbered from a1 = 0 to h8 = 63, row by row.
+const NOT_A_FILE: u64 = 0xfefefefefefefefe;
+const NOT_H_FILE: u64 = 0x7f7f7f7f7f7f7f7f;
+
+// Shift every disc one square in one of the eight directions, dropping discs
+// that would wrap around the edge of the board.
+const DIRECTIONS: [fn(u64) -> u64; 8] = [
+    |b| b << 8,
+    |b| b >> 8,
+    |b| (b << 1) & NOT_A_FILE,
+    |b| (b >> 1) & NOT_H_FILE,
+    |b| (b << 9) & NOT_A_FILE,
+    |b| (b << 7) & NOT_H_FILE,
+    |b| (b >> 7) & NOT_A_FILE,
+    |b| (b >> 9) & NOT_H_FILE,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Board {
+    pub pieces_to_move: u64,
+    pub pieces_just_moved: u64,
+    pub black_to_move: bool,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            // d5 and e4.
+            pieces_to_move: 1 << 35 | 1 << 28,
+            // d4 and e5.
+            pieces_just_moved: 1 << 27 | 1 << 36,
+            black_to_move: true,
+        }
+    }
+}
+
+impl Board {
+    /// Bitmap of the squares where `player` may place a disc.
+    fn legal_moves(player: u64, opponent: u64) -> u64 {
+        let empty = !(player | opponent);
+        let mut moves = 0;
+        for shift in DIRECTIONS {
+            // Runs of opponent discs next to one of ours.
+            let mut run = shift(player) & opponent;
+            for _ in 0..5 {
+                run |= shift(run) & opponent;
+            }
+            moves |= shift(run) & empty;
+        }
+        moves
+    }
+
+    /// Bitmap of the opponent discs flipped by placing a disc at `square`.
+    fn flips(&self, square: u64) -> u64 {
+        let mut flips = 0;
+        for shift in DIRECTIONS {
+            let mut run = 0;
+            let mut next = shift(square);
+            while next & self.pieces_just_moved != 0 {
+                run |= next;
+                next = shift(next);
+            }
+            if next & self.pieces_to_move != 0 {
+                flips |= run;
+            }
+        }
+        flips
+    }
+
+    fn black_white(&self) -> (u64, u64) {
+        if self.black_to_move {
+            (self.pieces_to_move, self.pieces_just_moved)
+        } else {
+            (self.pieces_just_moved, self.pieces_to_move)
+        }
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let (black, white) = self.black_white();
+        for row in (0..8).rev() {
+            write!(f, "{} ", row + 1)?;
+            for col in 0..8 {
+                let bit = 1 << (row * 8 + col);
+                let c = if black & bit != 0 {
+                    'X'
+                } else if white & bit != 0 {
+                    'O'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "  abcdefgh")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Move {
+    /// Place a disc on this square, from a1 = 0 to h8 = 63.
+    Place(u8),
+    Pass,
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            Move::Place(square) => {
+                write!(f, "{}{}", (b'a' + square % 8) as char, square / 8 + 1)
+            }
+            Move::Pass => write!(f, "pass"),
+        }
+    }
+}
+
+pub struct Game;
+
+impl crate::Game for Game {
+    type S = Board;
+    type M = Move;
+
+    fn generate_moves(b: &Board, moves: &mut Vec<Move>) {
+        let mut legal = Board::legal_moves(b.pieces_to_move, b.pieces_just_moved);
+        if legal == 0 {
+            moves.push(Move::Pass);
+            return;
+        }
+        while legal != 0 {
+            moves.push(Move::Place(legal.trailing_zeros() as u8));
+            legal &= legal - 1;
+        }
+    }
+
+    fn get_winner(b: &Board) -> Option<crate::Winner> {
+        if Board::legal_moves(b.pieces_to_move, b.pieces_just_moved) != 0
+            || Board::legal_moves(b.pieces_just_moved, b.pieces_to_move) != 0
+        {
+            return None;
+        }
+        let margin = b.pieces_just_moved.count_ones() as i32 - b.pieces_to_move.count_ones() as i32;
+        Some(crate::Winner::from_margin(margin, 0))
+    }
+
+    fn apply(b: &mut Board, m: Move) -> Option<Board> {
+        let mut next = Board {
+            pieces_to_move: b.pieces_just_moved,
+            pieces_just_moved: b.pieces_to_move,
+            black_to_move: !b.black_to_move,
+        };
+        if let Move::Place(square) = m {
+            let square = 1 << square;
+            let flips = b.flips(square);
+            next.pieces_to_move ^= flips;
+            next.pieces_just_moved |= square | flips;
+        }
+        Some(next)
+    }
+
+    fn zobrist_hash(b: &Board) -> u64 {
+        // Not incremental, but cheap enough to recompute for each lookup.
+        let mix = |x: u64| {
+            let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+            x ^ (x >> 31)
+        };
+        mix(b.pieces_to_move) ^ mix(b.pieces_just_moved.rotate_left(32) ^ b.black_to_move as u64)
+    }
+
+    fn null_move(_: &Board) -> Option<Move> {
+        Some(Move::Pass)
+    }
+
+    fn notation(_: &Board, m: Move) -> Option<String> {
+        Some(m.to_string())
+    }
+
+    fn table_index(m: Move) -> u16 {
+        match m {
+            Move::Place(square) => square as u16,
+            Move::Pass => 64,
+        }
+    }
+
+    fn max_table_index() -> u16 {
+        64
+    }
+
+    fn move_bits() -> Option<crate::MoveBits<Move>> {
+        Some((
+            |m| Self::table_index(m) as u32,
+            |bits| if bits == 64 { Move::Pass } else { Move::Place(bits as u8) },
+        ))
+    }
+}
+
+/// Scores mobility, corners, and discs.
+#[derive(Clone, Default)]
+pub struct Evaluator;
+
+impl crate::Evaluator for Evaluator {
+    type G = Game;
+    fn evaluate(&self, b: &Board) -> crate::Evaluation {
+        const CORNERS: u64 = 0x8100000000000081;
+        let score = |player: u64, opponent: u64| {
+            let mobility = Board::legal_moves(player, opponent).count_ones() as crate::Evaluation;
+            let corners = (player & CORNERS).count_ones() as crate::Evaluation;
+            let discs = player.count_ones() as crate::Evaluation;
+            mobility * 4 + corners * 20 + discs
+        };
+        score(b.pieces_to_move, b.pieces_just_moved) - score(b.pieces_just_moved, b.pieces_to_move)
+    }
+}
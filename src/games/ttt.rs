@@ -0,0 +1,306 @@
+//! Tic-Tac-Toe.
+//!
+//! Small enough to solve completely, so a correctly-implemented strategy
+//! playing against itself should always draw, and should never lose against
+//! one that picks moves randomly.
+
+use std::default::Default;
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+#[repr(u8)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Square {
+    #[default]
+    Empty,
+    X,
+    O,
+}
+
+impl Square {
+    fn invert(&self) -> Self {
+        match *self {
+            Square::Empty => Square::Empty,
+            Square::X => Square::O,
+            Square::O => Square::X,
+        }
+    }
+}
+
+impl Display for Square {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                Square::Empty => ' ',
+                Square::X => 'X',
+                Square::O => 'O',
+            }
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Board {
+    squares: [Square; 9],
+    to_move: Square,
+}
+
+impl Board {
+    fn just_moved(&self) -> Square {
+        self.to_move.invert()
+    }
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board { squares: [Square::default(); 9], to_move: Square::X }
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "{} | {} | {}", self.squares[0], self.squares[1], self.squares[2])?;
+        writeln!(f, "{} | {} | {}", self.squares[3], self.squares[4], self.squares[5])?;
+        writeln!(f, "{} | {} | {}", self.squares[6], self.squares[7], self.squares[8])?;
+        Ok(())
+    }
+}
+
+pub struct Game;
+
+const HASH_MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl crate::Game for Game {
+    type S = Board;
+    type M = Place;
+
+    fn generate_moves(b: &Board, ms: &mut Vec<Place>) {
+        for i in 0..b.squares.len() {
+            if b.squares[i] == Square::Empty {
+                ms.push(Place { i: i as u8 });
+            }
+        }
+    }
+
+    fn get_winner(b: &Board) -> Option<crate::Winner> {
+        // A player can only cause themselves to win on their turn, so only check for that.
+
+        // horizontal wins
+        if b.squares[0] == b.just_moved()
+            && b.squares[0] == b.squares[1]
+            && b.squares[1] == b.squares[2]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        if b.squares[3] == b.just_moved()
+            && b.squares[3] == b.squares[4]
+            && b.squares[4] == b.squares[5]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        if b.squares[6] == b.just_moved()
+            && b.squares[6] == b.squares[7]
+            && b.squares[7] == b.squares[8]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        // vertical wins
+        if b.squares[0] == b.just_moved()
+            && b.squares[0] == b.squares[3]
+            && b.squares[3] == b.squares[6]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        if b.squares[1] == b.just_moved()
+            && b.squares[1] == b.squares[4]
+            && b.squares[4] == b.squares[7]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        if b.squares[2] == b.just_moved()
+            && b.squares[2] == b.squares[5]
+            && b.squares[5] == b.squares[8]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        // diagonal wins
+        if b.squares[0] == b.just_moved()
+            && b.squares[0] == b.squares[4]
+            && b.squares[4] == b.squares[8]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        if b.squares[2] == b.just_moved()
+            && b.squares[2] == b.squares[4]
+            && b.squares[4] == b.squares[6]
+        {
+            return Some(crate::Winner::PlayerJustMoved);
+        }
+        // draws
+        if b.squares.iter().all(|s| *s != Square::Empty) {
+            Some(crate::Winner::Draw)
+        } else {
+            // non-terminal state
+            None
+        }
+    }
+
+    fn apply(b: &mut Board, m: Place) -> Option<Board> {
+        b.squares[m.i as usize] = b.to_move;
+        b.to_move = b.to_move.invert();
+        None
+    }
+    fn undo(b: &mut Board, m: Place) {
+        b.squares[m.i as usize] = Square::Empty;
+        b.to_move = b.to_move.invert();
+    }
+
+    fn zobrist_hash(b: &Board) -> u64 {
+        // Small enough to number every position exactly. The number is
+        // spread over all the bits, as tables index by the low bits and
+        // check the high ones, but multiplying by an odd constant keeps
+        // positions distinct.
+        let number = b
+            .squares
+            .iter()
+            .fold((b.to_move == Square::X) as u64, |number, &s| number * 3 + s as u64);
+        number.wrapping_mul(HASH_MULTIPLIER)
+    }
+
+    fn zobrist_hash_after(hash: u64, b: &Board, m: Place) -> Option<u64> {
+        // Fill in the square's digit, and flip the leading digit for X to
+        // move. The multiplication distributes over these additions.
+        let digit = b.to_move as u64 * 3u64.pow(8 - m.i as u32);
+        let hash = hash.wrapping_add(digit.wrapping_mul(HASH_MULTIPLIER));
+        let flip = 3u64.pow(9).wrapping_mul(HASH_MULTIPLIER);
+        Some(if b.to_move == Square::X { hash.wrapping_sub(flip) } else { hash.wrapping_add(flip) })
+    }
+
+    fn max_moves_remaining(b: &Board) -> Option<u32> {
+        Some(b.squares.iter().filter(|&&s| s == Square::Empty).count() as u32)
+    }
+
+    fn ply(b: &Board) -> Option<u32> {
+        Some(b.squares.iter().filter(|&&s| s != Square::Empty).count() as u32)
+    }
+
+    fn notation(_: &Board, m: Place) -> Option<String> {
+        Some(m.to_string())
+    }
+
+    fn move_bits() -> Option<crate::MoveBits<Place>> {
+        Some((|m| m.i as u32, |bits| Place { i: bits as u8 }))
+    }
+
+    fn describe(b: &Board) -> Vec<(String, String)> {
+        let mut fields: Vec<_> = b
+            .squares
+            .chunks(3)
+            .enumerate()
+            .map(|(row, squares)| {
+                let squares = squares.iter().map(|&square| match square {
+                    Square::Empty => '.',
+                    Square::X => 'X',
+                    Square::O => 'O',
+                });
+                (format!("row {}", row + 1), squares.collect())
+            })
+            .collect();
+        fields.push(("to move".to_string(), b.to_move.to_string()));
+        fields
+    }
+}
+
+// Play on a 3x3 grid, with X as black.
+impl crate::gtp::GridGame for Game {
+    fn empty_board(size: u8) -> Option<Board> {
+        (size == 3).then(Board::default)
+    }
+
+    fn to_move(b: &Board) -> crate::gtp::Color {
+        if b.to_move == Square::X {
+            crate::gtp::Color::Black
+        } else {
+            crate::gtp::Color::White
+        }
+    }
+
+    fn point_move(_: &Board, x: u8, y: u8) -> Option<Place> {
+        (x < 3 && y < 3).then(|| Place { i: (2 - y) * 3 + x })
+    }
+
+    fn move_point(_: &Board, m: Place) -> Option<(u8, u8)> {
+        Some((m.i % 3, 2 - m.i / 3))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Place {
+    i: u8,
+}
+
+impl Display for Place {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "@{}", self.i)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Evaluator;
+
+impl crate::Evaluator for Evaluator {
+    type G = Game;
+    // adapted from http://www.cs.olemiss.edu/~dwilkins/CSCI531/tic.c
+    fn evaluate(&self, b: &Board) -> crate::Evaluation {
+        let mut score = 0;
+
+        // 3rd: check for doubles
+        for i in 0..3 {
+            let line = i * 3;
+            if b.squares[line] == b.squares[line + 1] {
+                if b.squares[line] == Square::X {
+                    score += 5;
+                } else if b.squares[line] == Square::O {
+                    score -= 5;
+                }
+            }
+            if b.squares[line + 1] == b.squares[line + 2] {
+                if b.squares[line + 1] == Square::X {
+                    score += 5;
+                } else if b.squares[line + 1] == Square::O {
+                    score -= 5;
+                }
+            }
+            if b.squares[i] == b.squares[3 + i] {
+                if b.squares[i] == Square::X {
+                    score += 5;
+                } else if b.squares[i] == Square::O {
+                    score -= 5;
+                }
+            }
+            if b.squares[3 + i] == b.squares[6 + i] {
+                if b.squares[3 + i] == Square::X {
+                    score += 5;
+                } else if b.squares[3 + i] == Square::O {
+                    score -= 5;
+                }
+            }
+        }
+        // 2nd: check for the middle square
+        if b.squares[4] == Square::X {
+            score += 5;
+        }
+        if b.squares[4] == Square::O {
+            score -= 5;
+        }
+        if b.to_move == Square::X {
+            score
+        } else {
+            -score
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! An adapter for the Go Text Protocol, so tools like GoGui and twogtp can
+//! drive engines for Go, Hex, and other games played on a grid.
+//!
+//! The supported commands are `protocol_version`, `name`, `version`,
+//! `known_command`, `list_commands`, `quit`, `boardsize`, `clear_board`,
+//! `komi`, `play`, `genmove`, `undo`, and `time_left`.
+
+use super::interface::*;
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+// Assumed number of moves left in the game if `time_left` gives no stones.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+const COMMANDS: &[&str] = &[
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "undo",
+    "time_left",
+];
+
+/// The two players, with black moving first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl Color {
+    fn parse(text: &str) -> Option<Self> {
+        match text.to_ascii_lowercase().as_str() {
+            "b" | "black" => Some(Color::Black),
+            "w" | "white" => Some(Color::White),
+            _ => None,
+        }
+    }
+}
+
+/// Games played by placing stones on the points of a square grid.
+///
+/// Points are given as `(x, y)` with `x` counting columns from the left and
+/// `y` counting rows from the bottom, both starting from 0.
+pub trait GridGame: Game {
+    /// An empty board with `size` rows and columns, or None if the game
+    /// can't be played at that size.
+    fn empty_board(size: u8) -> Option<Self::S>;
+
+    /// The color of the player to move.
+    fn to_move(state: &Self::S) -> Color;
+
+    /// The move that places a stone at this point, if it is legal.
+    fn point_move(state: &Self::S, x: u8, y: u8) -> Option<Self::M>;
+
+    /// The point where this move places a stone, or None for a pass.
+    fn move_point(state: &Self::S, m: Self::M) -> Option<(u8, u8)>;
+
+    /// The passing move, for games that allow passing.
+    fn pass_move(_state: &Self::S) -> Option<Self::M> {
+        None
+    }
+
+    /// Set the komi, for games that keep it in the state.
+    fn set_komi(_state: &mut Self::S, _komi: f32) {}
+}
+
+/// Runs a strategy as a GTP engine.
+pub struct GtpEngine<G: GridGame, S> {
+    name: String,
+    version: String,
+    strategy: S,
+    size: u8,
+    komi: f32,
+    state: G::S,
+    // States before each move, for undo.
+    history: Vec<G::S>,
+}
+
+impl<G, S> GtpEngine<G, S>
+where
+    G: GridGame,
+    G::S: Clone,
+    G::M: Copy + Eq,
+    S: Strategy<G>,
+{
+    /// Serve `strategy` under `name`, starting on a board of `size`.
+    ///
+    /// Panics if the game can't be played at that size.
+    pub fn new(name: &str, strategy: S, size: u8) -> Self {
+        let state = G::empty_board(size).expect("unsupported board size");
+        Self {
+            name: name.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            strategy,
+            size,
+            komi: 0.0,
+            state,
+            history: Vec::new(),
+        }
+    }
+
+    /// Report this version to the `version` command, instead of the crate
+    /// version.
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// The current position.
+    pub fn state(&self) -> &G::S {
+        &self.state
+    }
+
+    /// Serve commands from stdin, replying on stdout.
+    pub fn run_stdio(&mut self) -> io::Result<()> {
+        self.run(io::stdin().lock(), &mut io::stdout())
+    }
+
+    /// Serve commands from `input` until `quit` or the end of the input.
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace().peekable();
+            let id = words.next_if(|word| word.parse::<u32>().is_ok());
+            let Some(command) = words.next() else {
+                continue;
+            };
+            let args = words.collect::<Vec<_>>();
+            let id = id.unwrap_or("");
+            match self.execute(command, &args) {
+                Ok(response) => write!(output, "={} {}\n\n", id, response)?,
+                Err(error) => write!(output, "?{} {}\n\n", id, error)?,
+            }
+            output.flush()?;
+            if command == "quit" {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok(self.name.clone()),
+            "version" => Ok(self.version.clone()),
+            "known_command" => {
+                Ok(args.first().is_some_and(|arg| COMMANDS.contains(arg)).to_string())
+            }
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => Ok(String::new()),
+            "boardsize" => {
+                let size = args.first().and_then(|arg| arg.parse().ok()).ok_or("syntax error")?;
+                if G::empty_board(size).is_none() {
+                    return Err("unacceptable size".to_string());
+                }
+                self.size = size;
+                self.clear_board();
+                Ok(String::new())
+            }
+            "clear_board" => {
+                self.clear_board();
+                Ok(String::new())
+            }
+            "komi" => {
+                self.komi = args.first().and_then(|arg| arg.parse().ok()).ok_or("syntax error")?;
+                G::set_komi(&mut self.state, self.komi);
+                Ok(String::new())
+            }
+            "play" => {
+                let (color, vertex) = match args {
+                    [color, vertex, ..] => (Color::parse(color), *vertex),
+                    _ => (None, ""),
+                };
+                let color = color.ok_or("syntax error")?;
+                self.pass_until(color)?;
+                let m = self.parse_vertex(vertex).ok_or("illegal move")?;
+                self.play(m);
+                Ok(String::new())
+            }
+            "genmove" => {
+                let color = args.first().and_then(|arg| Color::parse(arg)).ok_or("syntax error")?;
+                self.pass_until(color)?;
+                if G::get_winner(&self.state).is_some() {
+                    return Ok("pass".to_string());
+                }
+                let info = self.strategy.choose_move_with_info(&self.state);
+                if info.recommendation == Some(Recommendation::Resign) {
+                    return Ok("resign".to_string());
+                }
+                match info.best_move {
+                    Some(m) => {
+                        let vertex = self.format_vertex(m);
+                        self.play(m);
+                        Ok(vertex)
+                    }
+                    None => Ok("resign".to_string()),
+                }
+            }
+            "undo" => {
+                self.state = self.history.pop().ok_or("cannot undo")?;
+                Ok(String::new())
+            }
+            "time_left" => {
+                let seconds = args.get(1).and_then(|arg| arg.parse::<f64>().ok());
+                let stones = args.get(2).and_then(|arg| arg.parse::<u32>().ok()).unwrap_or(0);
+                let seconds = seconds.ok_or("syntax error")?;
+                let moves = if stones == 0 { DEFAULT_MOVES_TO_GO } else { stones };
+                self.strategy.set_timeout(Duration::from_secs_f64(seconds.max(0.0) / moves as f64));
+                Ok(String::new())
+            }
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    fn clear_board(&mut self) {
+        self.strategy.new_game();
+        self.state = G::empty_board(self.size).unwrap();
+        G::set_komi(&mut self.state, self.komi);
+        self.history.clear();
+    }
+
+    fn play(&mut self, m: G::M) {
+        self.strategy.notify_move(&self.state, m);
+        self.history.push(self.state.clone());
+        if let Some(new_state) = G::apply(&mut self.state, m) {
+            self.state = new_state;
+        }
+    }
+
+    // Pass for the other player if they are to move, since GTP allows
+    // several moves in a row by the same color.
+    fn pass_until(&mut self, color: Color) -> Result<(), String> {
+        if G::to_move(&self.state) != color {
+            let pass = G::pass_move(&self.state)
+                .filter(|&pass| G::is_legal(&self.state, pass))
+                .ok_or("wrong color to move")?;
+            self.play(pass);
+        }
+        Ok(())
+    }
+
+    fn parse_vertex(&self, vertex: &str) -> Option<G::M> {
+        let vertex = vertex.to_ascii_uppercase();
+        let m = if vertex == "PASS" {
+            G::pass_move(&self.state)?
+        } else {
+            let column = vertex.chars().next()?;
+            // The letter I is skipped to avoid confusion with J.
+            let x = match column {
+                'A'..='H' => column as u8 - b'A',
+                'J'..='Z' => column as u8 - b'A' - 1,
+                _ => return None,
+            };
+            let y = vertex[1..].parse::<u8>().ok()?.checked_sub(1)?;
+            G::point_move(&self.state, x, y)?
+        };
+        G::is_legal(&self.state, m).then_some(m)
+    }
+
+    fn format_vertex(&self, m: G::M) -> String {
+        match G::move_point(&self.state, m) {
+            Some((x, y)) => {
+                let column = if x < 8 { b'A' + x } else { b'A' + x + 1 };
+                format!("{}{}", column as char, y + 1)
+            }
+            None => "pass".to_string(),
+        }
+    }
+}
@@ -1,27 +1,89 @@
 //! The common structures and traits.
 
-/// An assessment of a game state from the perspective of the player whose turn it is to play.
-/// Higher values mean a more favorable state.
-/// A draw is defined as a score of zero.
-pub type Evaluation = i16;
+use super::time::Instant;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+macro_rules! evaluation_type {
+    ($type:ty) => {
+        /// An assessment of a game state from the perspective of the player whose turn it is to play.
+        /// Higher values mean a more favorable state.
+        /// A draw is defined as a score of zero.
+        ///
+        /// This is i16 by default, or i32 or i64 with the `eval-i32` or
+        /// `eval-i64` features. Features add up across a build, so if both are
+        /// enabled, e.g. by two different dependents, it is i64.
+        pub type Evaluation = $type;
+    };
+}
+#[cfg(not(any(feature = "eval-i32", feature = "eval-i64")))]
+evaluation_type!(i16);
+#[cfg(all(feature = "eval-i32", not(feature = "eval-i64")))]
+evaluation_type!(i32);
+#[cfg(feature = "eval-i64")]
+evaluation_type!(i64);
 
 // These definitions ensure that they negate to each other, but it leaves
-// i16::MIN as a valid value less than WORST_EVAL. Don't use this value, and
-// any Strategy will panic when it tries to negate it.
+// Evaluation::MIN as a valid value less than WORST_EVAL. Don't use this
+// value, and any Strategy will panic when it tries to negate it.
 
 /// An absolutely wonderful outcome, e.g. a win.
-pub const BEST_EVAL: Evaluation = i16::MAX;
+pub const BEST_EVAL: Evaluation = Evaluation::MAX;
 /// An absolutely disastrous outcome, e.g. a loss.
 pub const WORST_EVAL: Evaluation = -BEST_EVAL;
 
+/// Map a value to the chance of winning for the player it's for, between 0
+/// and 1, with a logistic curve. `scale` is the value difference that counts
+/// as a clear advantage, a 73% chance of winning. Forced wins and losses map
+/// to about 1 and 0.
+#[cfg(feature = "std")]
+pub fn win_probability(value: Evaluation, scale: f32) -> f32 {
+    1.0 / (1.0 + (-(value as f32) / scale).exp())
+}
+
 /// Evaluates a game's positions.
 pub trait Evaluator {
     /// The type of game that can be evaluated.
     type G: Game;
     /// Evaluate the non-terminal state from the persective of the player to
-    /// move next.
+    /// move next, or of the first player if `is_absolute`.
     fn evaluate(&self, s: &<Self::G as Game>::S) -> Evaluation;
 
+    /// Whether evaluations are from the perspective of the first player,
+    /// player 0, instead of the player to move, as is common in evaluation
+    /// functions ported from elsewhere. Searches then negate them when the
+    /// other player is to move, which they tell from `Game::player_to_move`,
+    /// or else from the parity of `Game::ply`, or else of the moves they were
+    /// told about with `Strategy::notify_move`.
+    fn is_absolute(&self) -> bool {
+        false
+    }
+
+    /// Evaluate the non-terminal state, knowing where the search found it.
+    ///
+    /// Alpha-beta searches call this instead of `evaluate`, so evaluators can
+    /// use depth-aware heuristics, like a tempo bonus or a cheaper evaluation
+    /// at deep plies. Leaves collected for `evaluate_batch` are the exception,
+    /// as batches have no context. The default ignores the context.
+    fn evaluate_in_context(&self, s: &<Self::G as Game>::S, _context: &EvalContext) -> Evaluation {
+        self.evaluate(s)
+    }
+
+    /// Evaluate many non-terminal states at once, appending their evaluations
+    /// to `evals` in order.
+    ///
+    /// Evaluators that are faster in batches, such as vectorized or neural
+    /// network evaluators, can override this, and have searches collect their
+    /// leaves with `IterativeOptions::with_batched_leaves`.
+    fn evaluate_batch(&self, states: &[<Self::G as Game>::S], evals: &mut Vec<Evaluation>) {
+        evals.extend(states.iter().map(|s| self.evaluate(s)));
+    }
+
     /// Optional interface to support strategies using quiescence search.
     ///
     /// A "noisy" move is a threatening move that requires a response.
@@ -41,10 +103,61 @@ pub trait Evaluator {
         // immediately.
     }
 
+    /// Optional interface to extend the search past the horizon, enabled
+    /// with `IterativeOptions::with_unstable_extension`.
+    ///
+    /// Whether the static evaluation of this position can't be trusted, such
+    /// as with pieces en prise or in the middle of an exchange. Unstable
+    /// positions at the leaves are searched further among all moves, instead
+    /// of standing pat on the evaluation. This helps with the horizon effect
+    /// in games that can't list their noisy moves.
+    fn is_unstable(&self, _state: &<Self::G as Game>::S) -> bool {
+        false
+    }
+
+    /// Optional interface to support delta pruning in quiescence search,
+    /// enabled with `IterativeOptions::with_delta_pruning`.
+    ///
+    /// The most that noisy move `m` can raise the evaluation for the player
+    /// making it, such as the value of the piece it captures. Return `None`
+    /// when there's no bound, such as for moves that might win the game.
+    fn noisy_move_gain(
+        &self, _state: &<Self::G as Game>::S, _m: <Self::G as Game>::M,
+    ) -> Option<Evaluation> {
+        None
+    }
+
+    /// Optional interface to support staged move generation, enabled with
+    /// `IterativeOptions::with_staged_move_generation`.
+    ///
+    /// Generate the quiet moves: those that `generate_noisy_moves` doesn't.
+    /// The search only asks for them once the transposition table move and
+    /// the noisy moves have failed to cut off the node. The default
+    /// generates every move, which is correct, since moves that were already
+    /// searched are skipped, but wastes the work of generating them again.
+    fn generate_quiet_moves(
+        &self, state: &<Self::G as Game>::S, moves: &mut Vec<<Self::G as Game>::M>,
+    ) {
+        Self::G::generate_moves(state, moves);
+    }
+
     // TODO reorder moves by assigning value to each state and combining with countermoves table etc.
 }
 
+/// Where in a search a position is being evaluated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalContext {
+    /// Number of moves (including null moves and noisy moves) played since
+    /// the root of the search, up to 255.
+    pub ply: u8,
+    /// Remaining depth of the search below this position. This is zero at
+    /// the leaves, the remaining quiescence depth during quiescence search,
+    /// or the full remaining depth for null move pruning's static check.
+    pub depth: u8,
+}
+
 /// The result of playing a game until it finishes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Winner {
     /// The player who made the last move won.
@@ -67,6 +180,19 @@ impl Winner {
             Winner::Draw => 0,
         }
     }
+
+    /// Decide the result of a game that ends by comparing points.
+    ///
+    /// `margin` is the number of points the player who just moved is ahead
+    /// of the player to move, and `komi` is the offset in favor of the player
+    /// to move, as returned by `Game::komi` for the final state.
+    pub fn from_margin(margin: i32, komi: Evaluation) -> Winner {
+        match margin - komi as i32 {
+            m if m > 0 => Winner::PlayerJustMoved,
+            m if m < 0 => Winner::PlayerToMove,
+            _ => Winner::Draw,
+        }
+    }
 }
 
 /// Defines the rules for a two-player, perfect-knowledge game.
@@ -118,6 +244,45 @@ pub trait Game: Sized {
     /// Undo mutation done in apply, if any.
     fn undo(_state: &mut Self::S, _m: Self::M) {}
 
+    /// Generate the moves worth searching in this state, for games with too
+    /// many moves to search them all deep in the tree. Searches only use
+    /// this where enabled, e.g. with `IterativeOptions::with_plausible_moves`,
+    /// and fall back to all the moves if it generates none. By default, this
+    /// generates every move.
+    fn generate_plausible_moves(state: &Self::S, moves: &mut Vec<Self::M>) {
+        Self::generate_moves(state, moves)
+    }
+
+    /// Generate the moves in this state, along with a priority for each in
+    /// `priorities`, e.g. from what the generator already knows about
+    /// captures or threats. `IterativeSearch` and `ParallelSearch` search
+    /// higher priority moves first, before their own ordering heuristics
+    /// kick in, saving a separate ordering pass over the state. They don't
+    /// call this where they generate moves in stages, with
+    /// `IterativeOptions::with_staged_move_generation`, or only the plausible
+    /// moves, with `IterativeOptions::with_plausible_moves`.
+    ///
+    /// `priorities` starts empty. Leave it empty for no priorities, or push
+    /// one per move. By default, this just calls `generate_moves`.
+    fn generate_scored_moves(
+        state: &Self::S, moves: &mut Vec<Self::M>, _priorities: &mut Vec<i16>,
+    ) {
+        Self::generate_moves(state, moves)
+    }
+
+    /// Whether `m` is a legal move in this state. Moves from outside the
+    /// search, e.g. from a protocol or a predicted reply, are checked with
+    /// this before they are applied. By default, this generates the moves
+    /// and looks for `m`, which games can replace with a cheaper check.
+    fn is_legal(state: &Self::S, m: Self::M) -> bool
+    where
+        Self::M: PartialEq,
+    {
+        let mut moves = Vec::new();
+        Self::generate_moves(state, &mut moves);
+        moves.contains(&m)
+    }
+
     /// Returns `Some(PlayerJustMoved)` or `Some(PlayerToMove)` if there's a winner,
     /// `Some(Draw)` if the state is terminal without a winner, and `None` if
     /// the state is non-terminal.
@@ -129,6 +294,29 @@ pub trait Game: Sized {
         unimplemented!("game has not implemented zobrist hash");
     }
 
+    /// Hash of the state after playing `m` in `state`, whose hash is `hash`.
+    ///
+    /// Searches carry hashes down the tree with this, so games that don't
+    /// store the hash in their state can update it incrementally instead of
+    /// recomputing it for every position. By default, this returns None, and
+    /// `zobrist_hash` is called on the new state.
+    fn zobrist_hash_after(_hash: u64, _state: &Self::S, _m: Self::M) -> Option<u64> {
+        None
+    }
+
+    /// Score offset (komi) in favor of the player to move in this state.
+    ///
+    /// Games with a first-move advantage can compensate the other player
+    /// with a fixed number of points. Alpha-beta strategies add this to every
+    /// evaluation, so the Evaluator should not include it, but never turn a
+    /// heuristic evaluation into a proven win or loss. Games that end by
+    /// comparing points should also account for it in `get_winner`, e.g. with
+    /// `Winner::from_margin`, which is the only way Monte Carlo tree search,
+    /// which scores finished games, sees it.
+    fn komi(_state: &Self::S) -> Evaluation {
+        0
+    }
+
     /// Optional method to return a move that does not change the board state.
     /// This does not need to be a legal move from this position, but it is
     /// used in some strategies to reject a position early if even passing gives
@@ -137,10 +325,42 @@ pub trait Game: Sized {
         None
     }
 
+    /// An upper bound on the number of moves left in the game, counting both
+    /// players' moves, if the game has one, e.g. the empty squares of a
+    /// board that fills up. Used to budget time for each move.
+    fn max_moves_remaining(_state: &Self::S) -> Option<u32> {
+        None
+    }
+
+    /// How many plies, i.e. moves by either player, were played to reach this
+    /// state, if the state keeps count. Otherwise strategies count the moves
+    /// they're told about with `Strategy::notify_move`.
+    fn ply(_state: &Self::S) -> Option<u32> {
+        None
+    }
+
+    /// Which player is to move, for games where a player can move several
+    /// times in a row, e.g. with extra turns. Searches only switch sides
+    /// across moves that change the player to move. The default of `None`
+    /// means the players strictly alternate.
+    ///
+    /// For such games, `Winner`s and evaluations are relative to the player
+    /// this returns: `PlayerToMove` is that player and `PlayerJustMoved` is
+    /// their opponent, even if they made the last move themselves.
+    fn player_to_move(_state: &Self::S) -> Option<u8> {
+        None
+    }
+
     /// Return a human-readable notation for this move in this game state.
     fn notation(_state: &Self::S, _move: Self::M) -> Option<String> {
         None
     }
+    /// Describe this state as named fields, e.g. one per row of the board
+    /// and one for the player to move, for diagnostics such as
+    /// `util::diff_states`. By default, states aren't described.
+    fn describe(_state: &Self::S) -> Vec<(String, String)> {
+        Vec::new()
+    }
     /// Return a small index for this move for position-independent tables.
     fn table_index(_: Self::M) -> u16 {
         0
@@ -149,23 +369,628 @@ pub trait Game: Sized {
     fn max_table_index() -> u16 {
         0
     }
+    /// Optional functions to encode a move in 32 bits and decode it again,
+    /// which let `ParallelSearch` use smaller table entries.
+    fn move_bits() -> Option<MoveBits<Self::M>> {
+        None
+    }
+}
+
+/// Functions to encode a move in 32 bits, and to decode it again.
+pub type MoveBits<M> = (fn(M) -> u32, fn(u32) -> M);
+
+/// An exact result for a position, as stored in a tablebase.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TablebaseResult {
+    /// The result of the game with perfect play.
+    pub winner: Winner,
+    /// Number of moves until the game ends with perfect play, if known.
+    pub distance: Option<u8>,
+}
+
+impl TablebaseResult {
+    /// Evaluation for the player to move. Wins are valued higher, and losses
+    /// lower, the closer they are.
+    pub fn evaluate(&self) -> Evaluation {
+        let distance = self.distance.map_or(0, |d| d.min(99) as Evaluation);
+        match self.winner {
+            Winner::PlayerJustMoved => WORST_EVAL + distance,
+            Winner::PlayerToMove => BEST_EVAL - distance,
+            Winner::Draw => 0,
+        }
+    }
+}
+
+/// Precomputed exact results for some positions, e.g. endgames with few
+/// pieces left.
+///
+/// Search strategies probe it at every node, and stop searching below any
+/// position it knows the result of.
+pub trait Tablebase {
+    type G: Game;
+
+    /// Return the exact result of this position, if known.
+    fn probe(&self, state: &<Self::G as Game>::S) -> Option<TablebaseResult>;
+}
+
+/// A network's assessment of a position: how likely each move is to be best,
+/// and the expected result.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolicyValue {
+    /// The prior probability of each move, in the order of
+    /// `Game::generate_moves`, summing to 1.
+    pub policy: Vec<f32>,
+    /// The expected result for the player to move, from -1 for a loss to 1
+    /// for a win.
+    pub value: f32,
+}
+
+/// Evaluates positions with a policy over their moves and a value, as
+/// AlphaZero-style networks do, to guide Monte Carlo Tree Search in place of
+/// random rollouts.
+pub trait PolicyValueEvaluator {
+    /// The type of game that can be evaluated.
+    type G: Game;
+
+    /// Evaluate many non-terminal states at once, appending their results to
+    /// `results` in order.
+    fn evaluate_batch(&self, states: &[<Self::G as Game>::S], results: &mut Vec<PolicyValue>);
+}
+
+/// A signal that another thread can raise to stop a running search.
+///
+/// Strategies check it periodically while searching, and once it is raised
+/// they return promptly with the best move found so far. The signal is
+/// lowered again when `choose_move` returns, so the same signal can be used
+/// for every move of a game.
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the search using this signal to stop as soon as possible.
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the signal has been raised.
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Lower the signal, e.g. at the end of a search.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Details of the search behind a move, returned by
+/// `Strategy::choose_move_with_info`.
+///
+/// Fields that a strategy doesn't track are left at their defaults.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchInfo<M> {
+    /// The chosen move, or None if there was no move to choose.
+    pub best_move: Option<M>,
+    /// The value of the chosen move, from the perspective of the player to move.
+    pub value: Option<Evaluation>,
+    /// The chance that the player to move wins, between 0 and 1, from the
+    /// simulations of Monte Carlo strategies, or mapped from `value` by
+    /// strategies given `Strategy::set_win_probability_scale`.
+    pub win_probability: Option<f32>,
+    /// The best sequence of moves for both players.
+    pub principal_variation: Vec<M>,
+    /// Number of nodes searched, or simulations run for Monte Carlo strategies.
+    pub nodes: u64,
+    /// Maximum depth completely searched.
+    pub depth: u8,
+    /// Wall time spent choosing the move.
+    pub elapsed: Duration,
+    /// Whether to resign or agree to a draw instead of playing on, from
+    /// strategies that judge it, like `FlowStrategy`.
+    pub recommendation: Option<Recommendation>,
+}
+
+impl<M> Default for SearchInfo<M> {
+    fn default() -> Self {
+        Self {
+            best_move: None,
+            value: None,
+            win_probability: None,
+            principal_variation: Vec::new(),
+            nodes: 0,
+            depth: 0,
+            elapsed: Duration::default(),
+            recommendation: None,
+        }
+    }
+}
+
+/// What a strategy recommends doing about the game besides its move, in
+/// `SearchInfo::recommendation`. `Match` acts on these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Recommendation {
+    /// Resign instead of playing the move.
+    Resign,
+    /// Offer a draw, and accept one if offered.
+    OfferDraw,
+    /// Accept a draw if offered, but don't offer one.
+    AcceptDraw,
+}
+
+/// One of the best moves from a position, returned by `Strategy::analyze`,
+/// e.g. for analysis GUIs that show several lines.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveAnalysis<M> {
+    pub m: M,
+    /// The value of the move, from the perspective of the player to move,
+    /// from alpha-beta strategies.
+    pub value: Option<Evaluation>,
+    /// The chance that the player to move wins after the move, from Monte
+    /// Carlo strategies, or mapped from `value` by strategies given
+    /// `Strategy::set_win_probability_scale`.
+    pub win_probability: Option<f32>,
+    /// The best line of play, starting with this move.
+    pub principal_variation: Vec<M>,
+}
+
+impl<M> MoveAnalysis<M> {
+    /// The move chosen by a search, if any.
+    pub fn from_search(info: SearchInfo<M>) -> Option<Self> {
+        Some(Self {
+            m: info.best_move?,
+            value: info.value,
+            win_probability: info.win_probability,
+            principal_variation: info.principal_variation,
+        })
+    }
+}
+
+/// Memory held by a strategy, returned by `Strategy::memory_usage`, e.g. for
+/// servers that budget memory per game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryUsage {
+    /// Approximate total size in bytes.
+    pub bytes: usize,
+    /// Transposition table entries in use.
+    pub table_entries: usize,
+    /// Transposition table entries allocated.
+    pub table_capacity: usize,
+    /// Nodes in the tree built by the last search, for tree searches.
+    pub tree_nodes: usize,
+}
+
+impl core::ops::Add for MemoryUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            bytes: self.bytes + other.bytes,
+            table_entries: self.table_entries + other.table_entries,
+            table_capacity: self.table_capacity + other.table_capacity,
+            tree_nodes: self.tree_nodes + other.tree_nodes,
+        }
+    }
+}
+
+/// Strategies that can keep searching on the opponent's time.
+///
+/// After choosing a move, call `ponder` with the state after that move and
+/// the expected reply, e.g. the second move of the principal variation. If
+/// the opponent plays it, call `ponder_hit` and then `choose_move` from the
+/// resulting state, which continues the search started by `ponder`.
+/// Otherwise, call `stop_ponder` or just `choose_move`, which stops any
+/// pondering first. A `choose_move` after a ponder hit from a position other
+/// than the pondered one, as told by their hashes, searches it afresh.
+pub trait Ponder<G: Game>: Strategy<G> {
+    /// Start searching the state after `predicted_move` in the background,
+    /// and return immediately. An illegal `predicted_move` is ignored.
+    fn ponder(&mut self, state: &G::S, predicted_move: G::M);
+
+    /// The predicted move was played; start the clock on the ponder search.
+    fn ponder_hit(&mut self);
+
+    /// Stop and discard any ponder search.
+    fn stop_ponder(&mut self);
+}
+
+/// Strategies that search in the background until asked for a move, for
+/// applications that keep rendering or serving requests while the engine
+/// thinks.
+///
+/// `start` returns immediately. `best_so_far` can be polled at any time, and
+/// `stop` ends the search and returns its best move. Starting a new search
+/// stops any previous one.
+pub trait AnytimeStrategy<G: Game>: Strategy<G> {
+    /// Start searching `state` in the background, with no time limit.
+    fn start(&mut self, state: &G::S);
+
+    /// The best move found so far, if the search has found one yet.
+    fn best_so_far(&self) -> Option<G::M>;
+
+    /// Stop the search and return its best move.
+    fn stop(&mut self) -> Option<G::M>;
+}
+
+/// A strategy that can search in small steps on the calling thread, for
+/// environments without threads, such as the main thread of a web page,
+/// which can handle other events between steps.
+///
+/// Unlike the other strategy traits, this doesn't need the game's types to
+/// be `Send` or `Sync`.
+pub trait SteppedSearch<G: Game> {
+    /// Start searching `state`, abandoning any search in progress.
+    fn start_stepping(&mut self, state: &G::S);
+
+    /// Search about `work` more nodes or simulations. Returns true when the
+    /// search has reached its time or depth limit, or if there is no search.
+    ///
+    /// Searches that can only pause at certain points do more work: see the
+    /// implementations for how much.
+    fn step(&mut self, work: u32) -> bool;
+
+    /// End the search, and return the best move it found.
+    fn finish_stepping(&mut self) -> Option<G::M>;
+}
+
+/// Receives reports while a strategy is searching, e.g. to show the current
+/// best line in a UI.
+///
+/// Every strategy reports in the same format: alpha-beta searches report
+/// after each completed depth, and Monte Carlo searches report periodically
+/// with the number of simulations so far. Fields that a strategy can't track
+/// mid-search are left at their defaults.
+pub trait SearchProgress<M>: Send {
+    fn report(&mut self, info: &SearchInfo<M>);
+}
+
+impl<M, F: FnMut(&SearchInfo<M>) + Send> SearchProgress<M> for F {
+    fn report(&mut self, info: &SearchInfo<M>) {
+        self(info)
+    }
+}
+
+/// The state of a player's clock when it is their turn to move.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeLeft {
+    /// Main time remaining.
+    pub remaining: Duration,
+    /// Time added to the main time after each move.
+    pub increment: Duration,
+    /// Length of each byo-yomi period that can still be used after the main
+    /// time runs out, or zero if there are none left.
+    pub byo_yomi: Duration,
+}
+
+impl TimeLeft {
+    /// A simple time budget for the next move: a share of the main time and
+    /// most of the increment, plus most of a byo-yomi period.
+    pub fn move_budget(&self) -> Duration {
+        TimeAllocator::new().budget(self, None).soft
+    }
+}
+
+/// Time budgets for one move.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveBudget {
+    /// The time to aim for. Searches don't start another iteration after
+    /// this.
+    pub soft: Duration,
+    /// The time limit, for finishing an iteration that's almost done.
+    pub hard: Duration,
+}
+
+/// Divides a clock's time into budgets for each move.
+///
+/// Each move gets an equal share of the main time over the moves expected
+/// to be left, plus most of the increment and byo-yomi.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeAllocator {
+    moves_to_go: u32,
+    game_length: Option<u32>,
+    hard_ratio: f64,
+    safety_margin: Duration,
+}
+
+impl Default for TimeAllocator {
+    fn default() -> Self {
+        Self { moves_to_go: 30, game_length: None, hard_ratio: 3.0, safety_margin: Duration::ZERO }
+    }
+}
+
+impl TimeAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect at most this many more moves of our own, when the game gives
+    /// no closer bound. Defaults to 30.
+    pub fn with_moves_to_go(mut self, moves: u32) -> Self {
+        self.moves_to_go = moves.max(1);
+        self
+    }
+
+    /// Expect games to last about this many plies, so that each move gets a
+    /// larger share of the time left as the game goes on: our share of the
+    /// plies left, but never fewer than a quarter of `moves_to_go`. Only
+    /// used when the ply of the game is known.
+    pub fn with_game_length(mut self, plies: u32) -> Self {
+        self.game_length = Some(plies);
+        self
+    }
+
+    /// Allow this many times the soft budget as a hard limit, but never more
+    /// than half the main time. Defaults to 3.
+    pub fn with_hard_ratio(mut self, ratio: f64) -> Self {
+        self.hard_ratio = ratio.max(1.0);
+        self
+    }
+
+    /// Keep this much main time in reserve, for the overhead of each move
+    /// outside the search. Defaults to zero.
+    pub fn with_safety_margin(mut self, margin: Duration) -> Self {
+        self.safety_margin = margin;
+        self
+    }
+
+    /// Budget the next move, given the time left and, if known, at most how
+    /// many moves are left in the game, as from `Game::max_moves_remaining`.
+    pub fn budget(&self, time: &TimeLeft, max_moves_remaining: Option<u32>) -> MoveBudget {
+        self.budget_at_ply(time, max_moves_remaining, None)
+    }
+
+    /// Like `budget`, also given the ply of the game, if known, for
+    /// `with_game_length`.
+    pub fn budget_at_ply(
+        &self, time: &TimeLeft, max_moves_remaining: Option<u32>, ply: Option<u32>,
+    ) -> MoveBudget {
+        let remaining = time.remaining.saturating_sub(self.safety_margin);
+        let expected = match (self.game_length, ply) {
+            (Some(length), Some(ply)) => length
+                .saturating_sub(ply)
+                .div_ceil(2)
+                .clamp(self.moves_to_go.div_ceil(4), self.moves_to_go),
+            _ => self.moves_to_go,
+        };
+        // Our share of the moves left, counting this one.
+        let moves_to_go =
+            max_moves_remaining.map_or(expected, |moves| moves.div_ceil(2).min(expected)).max(1);
+        let soft = (remaining / moves_to_go + time.increment * 3 / 4).min(remaining / 2);
+        let hard = soft.mul_f64(self.hard_ratio).min(remaining / 2).max(soft);
+        let byo_yomi = time.byo_yomi * 9 / 10;
+        // Never zero, which would mean no time limit.
+        let at_least = Duration::from_millis(1);
+        MoveBudget { soft: (soft + byo_yomi).max(at_least), hard: (hard + byo_yomi).max(at_least) }
+    }
 }
 
 /// Defines a method of choosing a move for the current player.
 pub trait Strategy<G: Game> {
     fn choose_move(&mut self, state: &G::S) -> Option<G::M>;
 
-    /// For strategies that can ponder indefinitely, set the timeout.
+    /// Like choose_move, but also return details of the search in a format
+    /// common to all strategies.
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        let start = Instant::now();
+        let best_move = self.choose_move(state);
+        SearchInfo {
+            best_move,
+            principal_variation: self.principal_variation(),
+            elapsed: start.elapsed(),
+            ..SearchInfo::default()
+        }
+    }
+
+    /// Limit the time spent in each call to choose_move.
     /// This can be changed between calls to choose_move.
-    fn set_timeout(&mut self, _timeout: std::time::Duration) {}
+    ///
+    /// All the strategies in this crate honor this, so a game loop can impose
+    /// time controls on any of them. Strategies that search to a fixed depth
+    /// return the best move found so far when time runs out.
+    fn set_timeout(&mut self, _timeout: Duration) {}
+
+    /// Tell the strategy how much time is left on its clock before
+    /// choose_move, for strategies that budget their own time.
+    ///
+    /// By default, this sets a timeout of `time.move_budget()`. The
+    /// iterative and Monte Carlo searches instead budget each move with their
+    /// options' `TimeAllocator`, using the game's `max_moves_remaining`.
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.set_timeout(time.move_budget());
+    }
+
+    /// Stop each call to choose_move after searching about this many nodes
+    /// (instead of the timeout), or zero for no limit. This can be changed
+    /// between calls to choose_move.
+    ///
+    /// Unlike a timeout, a node budget doesn't depend on the speed of the
+    /// machine, so single-threaded searches with a fixed rng seed, e.g. in
+    /// tests and tuning runs, are exactly reproducible. The Monte Carlo
+    /// search is limited by `set_max_rollouts` instead.
+    fn set_max_nodes(&mut self, _nodes: u64) {}
 
     /// Set the maximum depth to evaluate (instead of the timeout).
     /// This can be changed between calls to choose_move.
     fn set_max_depth(&mut self, _depth: u8) {}
 
+    /// Set a signal that can be raised from another thread to stop
+    /// choose_move early, e.g. when a user asks for a move right now.
+    fn set_stop_signal(&mut self, _signal: StopSignal) {}
+
+    /// Set a callback to receive progress reports during choose_move.
+    fn set_progress(&mut self, _progress: Box<dyn SearchProgress<G::M>>) {}
+
+    /// Set a tablebase of exact results to consult during the search.
+    fn set_tablebase(&mut self, _tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {}
+
+    /// Tell the strategy that `m` was played in `state`, by either player,
+    /// so that it can follow the game between its turns. The searches count
+    /// these moves to know the ply of games without `Game::ply`, to budget
+    /// their time and to orient absolute evaluators, so call `new_game`
+    /// between such games. `Match` and `util::battle_royale` call this for
+    /// both players after every move, the GTP engine for every move played,
+    /// and the UCI engine for the moves of each position it hasn't seen.
+    fn notify_move(&mut self, _state: &G::S, _m: G::M) {}
+
+    /// Forget what the strategy learned from the current game, e.g. its
+    /// transposition table, move ordering tables, reused search tree, and
+    /// the moves it was told about, to play a new game with the same
+    /// configuration. `Match` calls this for both players before each game.
+    fn new_game(&mut self) {}
+
+    /// Report `SearchInfo::win_probability` by mapping values with
+    /// `win_probability` and this scale, so that UIs can show the same
+    /// percentages for alpha-beta and Monte Carlo strategies, which report
+    /// their win rate without it.
+    fn set_win_probability_scale(&mut self, _scale: f32) {}
+
+    /// Seed all of the strategy's randomness, e.g. move ordering and
+    /// tie-breaking, so that a newly created strategy reproduces the same
+    /// sequence of moves exactly. Multi-threaded searches may still vary
+    /// with thread timing, which is all that varies `ParallelSearch`, as it
+    /// makes no random choices.
+    fn set_rng_seed(&mut self, _seed: u64) {}
+
     /// From the last choose_move call, return the principal variation,
     /// i.e. the best sequence of moves for both players.
     fn principal_variation(&self) -> Vec<G::M> {
         Vec::new()
     }
+
+    /// Search for the `k` best moves from `state`, best first, with their
+    /// values and lines. `Negamax` and `IterativeSearch` search with the
+    /// window open for the `k` best moves, and `ParallelSearch` searches each
+    /// root move with a full window, so that each of their values is exact,
+    /// and `MonteCarloTreeSearch` reports its `k` most visited root moves.
+    /// Strategies that wrap others pass this on to them. By default, only
+    /// the move of `choose_move_with_info` is returned.
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        MoveAnalysis::from_search(self.choose_move_with_info(state)).into_iter().collect()
+    }
+
+    /// Approximately how much memory the strategy holds, such as its
+    /// transposition table, or the tree from its last search.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+}
+
+/// A boxed strategy, e.g. `Box<dyn Strategy<G>>` chosen at runtime, is a
+/// strategy too, so it can be passed wherever one is expected.
+impl<G: Game, S: Strategy<G> + ?Sized> Strategy<G> for Box<S> {
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        (**self).choose_move(state)
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        (**self).choose_move_with_info(state)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        (**self).set_timeout(timeout);
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        (**self).set_time_left(time);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        (**self).set_max_nodes(nodes);
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        (**self).set_max_depth(depth);
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        (**self).set_stop_signal(signal);
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<G::M>>) {
+        (**self).set_progress(progress);
+    }
+
+    fn set_tablebase(&mut self, tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {
+        (**self).set_tablebase(tablebase);
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        (**self).notify_move(state, m);
+    }
+
+    fn new_game(&mut self) {
+        (**self).new_game();
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        (**self).set_win_probability_scale(scale);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        (**self).set_rng_seed(seed);
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        (**self).principal_variation()
+    }
+
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        (**self).analyze(state, k)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        (**self).memory_usage()
+    }
+}
+
+/// Strategies that can make a fresh copy of themselves, to play several
+/// games at once in one process, e.g. one per thread.
+///
+/// This isn't `Clone`, as a copy doesn't have the original's state. It has
+/// the original's settings and shares its read-only resources, such as
+/// evaluators and tablebases behind an `Arc` and books in an `Arc`, but has
+/// its own search state: an empty transposition table or tree, its own stop
+/// signal, and no progress reporter. It doesn't share the original's random
+/// numbers either, so call `set_rng_seed` on it for reproducible games.
+///
+/// `Negamax`, `IterativeSearch`, `ParallelSearch`, and `Random` can be
+/// copied when their evaluator can be cloned, and so can
+/// `MonteCarloTreeSearch`; `BookStrategy`, `FlowStrategy`, and
+/// `ValidatingStrategy` can when the strategy they wrap can. `Ensemble` and
+/// `Fallback`, which own boxed strategies, and `Human` can't.
+pub trait FreshCopy {
+    /// A copy with this one's settings and resources, and a fresh state.
+    fn fresh_copy(&self) -> Self;
+}
+
+/// A strategy that can be copied with `FreshCopy`, boxed so that strategies
+/// chosen at runtime can be copied too. Every strategy that implements
+/// `FreshCopy` and `Send` is one.
+pub trait ClonableStrategy<G: Game>: Strategy<G> + Send {
+    /// A fresh copy of this strategy, boxed.
+    fn boxed_fresh_copy(&self) -> Box<dyn ClonableStrategy<G>>;
+}
+
+impl<G: Game, S: Strategy<G> + FreshCopy + Send + 'static> ClonableStrategy<G> for S {
+    fn boxed_fresh_copy(&self) -> Box<dyn ClonableStrategy<G>> {
+        Box::new(self.fresh_copy())
+    }
+}
+
+impl<G: Game> FreshCopy for Box<dyn ClonableStrategy<G>> {
+    fn fresh_copy(&self) -> Self {
+        (**self).boxed_fresh_copy()
+    }
 }
@@ -0,0 +1,100 @@
+// Minimal JSON output for records and statistics, so that results can be
+// processed by other tools without depending on a serialization framework.
+
+use super::interface::Game;
+use super::util::move_notation;
+
+use std::fmt::Write;
+
+// A JSON string literal.
+pub(crate) fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// A JSON number, or null for values JSON can't represent.
+pub(crate) fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+pub(crate) fn array<I: IntoIterator<Item = String>>(items: I) -> String {
+    let items: Vec<String> = items.into_iter().collect();
+    format!("[{}]", items.join(","))
+}
+
+// The notation of a line of moves from `state`, as an array of strings.
+pub(crate) fn line<G: Game>(state: &G::S, moves: &[G::M]) -> String
+where
+    G::S: Clone,
+    G::M: Copy + Eq,
+{
+    let mut state = state.clone();
+    let mut names = Vec::with_capacity(moves.len());
+    for &m in moves {
+        names.push(string(&move_notation::<G>(&state, m)));
+        if let Some(new_state) = G::apply(&mut state, m) {
+            state = new_state;
+        }
+    }
+    array(names)
+}
+
+// Builds a JSON object one field at a time.
+pub(crate) struct Object(String);
+
+impl Object {
+    pub(crate) fn new() -> Self {
+        Object(String::from("{"))
+    }
+
+    // Add a field whose value is already JSON.
+    pub(crate) fn raw(mut self, name: &str, value: &str) -> Self {
+        if self.0.len() > 1 {
+            self.0.push(',');
+        }
+        self.0.push_str(&string(name));
+        self.0.push(':');
+        self.0.push_str(value);
+        self
+    }
+
+    pub(crate) fn string(self, name: &str, value: &str) -> Self {
+        self.raw(name, &string(value))
+    }
+
+    pub(crate) fn number(self, name: &str, value: f64) -> Self {
+        self.raw(name, &number(value))
+    }
+
+    // Add a field that is null when absent.
+    pub(crate) fn optional(self, name: &str, value: Option<f64>) -> Self {
+        match value {
+            Some(value) => self.number(name, value),
+            None => self.raw(name, "null"),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> String {
+        self.0.push('}');
+        self.0
+    }
+}
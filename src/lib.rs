@@ -65,17 +65,95 @@
 //! let mut strategy = minimax::Negamax::new(Eval{}, 3);
 //! let best_move = strategy.choose_move(&start).unwrap();
 //! ```
+//!
+//! Without the default `std` feature, the crate is `no_std` and only needs
+//! `alloc`. Only the game traits, `Negamax`, `Random`, `util::validate`, and
+//! `util::fuzz` are available: the other strategies, including
+//! `IterativeSearch` and `MonteCarloTreeSearch`, and the tools for playing
+//! matches and tournaments need `std`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod balance;
+#[cfg(feature = "std")]
+pub mod corpus;
+#[cfg(feature = "std")]
+pub mod dynamic;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+#[cfg(feature = "games")]
+pub mod games;
+#[cfg(feature = "std")]
+pub mod gtp;
 pub mod interface;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+pub mod match_play;
+#[cfg(feature = "nn")]
+pub mod nn;
+#[cfg(feature = "std")]
+pub mod record;
 pub mod strategies;
+#[cfg(feature = "std")]
+pub mod suite;
+#[cfg(feature = "std")]
+pub mod tablebase;
+pub mod time;
+#[cfg(feature = "std")]
+pub mod tournament;
+#[cfg(feature = "std")]
+pub mod tuning;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod uci;
 pub mod util;
 
+#[cfg(feature = "std")]
+pub use dynamic::{BoxedStrategy, DynEngine, Registry};
 pub use interface::*;
-pub use strategies::iterative::{IterativeOptions, IterativeSearch, Replacement};
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+pub use match_play::{Clock, Match, MoveLog, TimeControl};
+#[cfg(feature = "std")]
+pub use record::{GameRecord, Outcome, RecordedMove, Termination};
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub use strategies::asynchronous::{AsyncStrategy, MoveFuture};
+#[cfg(feature = "std")]
+pub use strategies::book::{Book, BookFile, BookStrategy, OpeningBook};
+#[cfg(feature = "std")]
+pub use strategies::ensemble::{Ensemble, Voting};
+#[cfg(feature = "std")]
+pub use strategies::fallback::Fallback;
+#[cfg(feature = "std")]
+pub use strategies::flow::{FlowOptions, FlowStrategy};
+#[cfg(feature = "std")]
+pub use strategies::human::HumanStrategy;
+#[cfg(feature = "std")]
+pub use strategies::iterative::{HistoryOptions, IterativeOptions, IterativeSearch, Replacement};
+#[cfg(feature = "std")]
 pub use strategies::mcts::{MCTSOptions, MonteCarloTreeSearch, RolloutPolicy};
 pub use strategies::negamax::Negamax;
 pub use strategies::random::Random;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+pub use strategies::trace::{SearchTrace, TraceEvent, TraceKind};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub use strategies::ybw::{ParallelOptions, ParallelSearch};
-pub use util::perft;
+#[cfg(feature = "std")]
+pub use tablebase::RetrogradeTablebase;
+#[cfg(feature = "std")]
+pub use time::StdTimeSource;
+pub use time::{set_time_source, TimeSource};
+#[cfg(feature = "std")]
+pub use tournament::{
+    EloEstimate, Sprt, SprtDecision, Tournament, TournamentFormat, TournamentResults,
+};
+#[cfg(feature = "std")]
+pub use tuning::{Parameter, Spsa};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub use uci::UciEngine;
+#[cfg(feature = "std")]
+pub use util::{perft, perft_divide};
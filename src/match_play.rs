@@ -0,0 +1,522 @@
+//! Games between two strategies under real time controls.
+//!
+//! Each player has a clock with main time, an optional increment, and
+//! optional byo-yomi periods. Before each move, the strategy is told how much
+//! time it has left with `Strategy::set_time_left`, and a player whose clock
+//! runs out loses.
+//!
+//! Games can be written to a `MoveLog` as they are played, so that a crash
+//! in a strategy loses at most the move it was choosing, and the position
+//! it crashed in can be replayed from the log.
+
+use super::interface::*;
+use super::record::{GameRecord, Outcome, RecordedMove, Termination};
+use super::time::Instant;
+use super::util::{move_notation, parse_move};
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time allowed to one player for a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeControl {
+    pub main_time: Duration,
+    /// Added to the main time after each move made within it.
+    pub increment: Duration,
+    /// After the main time runs out, each move must be made within a
+    /// byo-yomi period, or the period is used up.
+    pub byo_yomi: Duration,
+    pub byo_yomi_periods: u32,
+}
+
+impl TimeControl {
+    /// Sudden death: `main_time` for the whole game.
+    pub fn new(main_time: Duration) -> Self {
+        Self {
+            main_time,
+            increment: Duration::default(),
+            byo_yomi: Duration::default(),
+            byo_yomi_periods: 0,
+        }
+    }
+
+    pub fn with_increment(mut self, increment: Duration) -> Self {
+        self.increment = increment;
+        self
+    }
+
+    /// Add `periods` byo-yomi periods of length `period`.
+    pub fn with_byo_yomi(mut self, period: Duration, periods: u32) -> Self {
+        self.byo_yomi = period;
+        self.byo_yomi_periods = periods;
+        self
+    }
+
+    /// This time control with every time multiplied by `factor`, for giving
+    /// a player time odds. A factor of 2 is one doubling of thinking time.
+    pub fn scaled(self, factor: f64) -> Self {
+        Self {
+            main_time: self.main_time.mul_f64(factor),
+            increment: self.increment.mul_f64(factor),
+            byo_yomi: self.byo_yomi.mul_f64(factor),
+            byo_yomi_periods: self.byo_yomi_periods,
+        }
+    }
+}
+
+/// A running game clock for one player.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Duration,
+    periods: u32,
+    flagged: bool,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: control.main_time,
+            periods: control.byo_yomi_periods,
+            flagged: false,
+        }
+    }
+
+    /// The time left, as given to strategies.
+    pub fn time_left(&self) -> TimeLeft {
+        TimeLeft {
+            remaining: self.remaining,
+            increment: self.control.increment,
+            byo_yomi: if self.periods > 0 { self.control.byo_yomi } else { Duration::default() },
+        }
+    }
+
+    /// Main time remaining.
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// Byo-yomi periods remaining.
+    pub fn periods(&self) -> u32 {
+        self.periods
+    }
+
+    /// Whether the player has run out of time.
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+
+    /// Charge the clock for a move that took `elapsed`. Returns false if
+    /// the player ran out of time.
+    pub fn punch(&mut self, elapsed: Duration) -> bool {
+        if let Some(left) = self.remaining.checked_sub(elapsed) {
+            self.remaining = left + self.control.increment;
+            return true;
+        }
+        let mut over = elapsed - self.remaining;
+        self.remaining = Duration::default();
+        while self.periods > 0 {
+            if over <= self.control.byo_yomi {
+                return true;
+            }
+            over -= self.control.byo_yomi;
+            self.periods -= 1;
+        }
+        self.flagged = true;
+        false
+    }
+}
+
+// Writes a move in a log.
+pub(crate) type Notation<G> = fn(&<G as Game>::S, <G as Game>::M) -> String;
+
+/// Plays games between two strategies with clocks.
+///
+/// A player whose search recommends resigning loses. A player whose search
+/// recommends offering a draw gets one if the other player's last search
+/// recommended offering or accepting one. See `SearchInfo::recommendation`.
+pub struct Match {
+    time_controls: [Option<TimeControl>; 2],
+    max_moves: Option<u32>,
+    players: [String; 2],
+    // The index of the tournament opening the game starts from, for the log.
+    pub(crate) opening: Option<usize>,
+}
+
+impl Match {
+    /// Play with the same time control for both players.
+    pub fn new(time_control: TimeControl) -> Self {
+        Self { time_controls: [Some(time_control); 2], ..Self::untimed() }
+    }
+
+    /// Play without clocks, leaving each strategy to its own limits.
+    pub fn untimed() -> Self {
+        Self {
+            time_controls: [None; 2],
+            max_moves: None,
+            players: Default::default(),
+            opening: None,
+        }
+    }
+
+    /// Give the player who moves first and the other player different time
+    /// controls.
+    pub fn with_time_controls(mut self, first: TimeControl, second: TimeControl) -> Self {
+        self.time_controls = [Some(first), Some(second)];
+        self
+    }
+
+    /// Declare a game drawn after this many moves, for games that can go on
+    /// forever.
+    pub fn with_max_moves(mut self, moves: u32) -> Self {
+        self.max_moves = Some(moves);
+        self
+    }
+
+    /// Name the player who moves first and the other player in the records
+    /// and the log.
+    pub fn with_players(mut self, first: &str, second: &str) -> Self {
+        self.players = [first.to_string(), second.to_string()];
+        self
+    }
+
+    /// Play a game from `start`, with `first` moving first, after starting a
+    /// new game for both. Returns the record of the game, including the
+    /// clock after each move.
+    pub fn play<G: Game>(
+        &self, start: &G::S, first: &mut dyn Strategy<G>, second: &mut dyn Strategy<G>,
+    ) -> GameRecord<G>
+    where
+        G::S: Clone,
+    {
+        self.play_with_log(start, first, second, None)
+    }
+
+    /// Like `play`, but writing each move to `log` as soon as it is chosen.
+    pub fn play_logged<G: Game>(
+        &self, start: &G::S, first: &mut dyn Strategy<G>, second: &mut dyn Strategy<G>,
+        log: &MoveLog,
+    ) -> GameRecord<G>
+    where
+        G::S: Clone,
+        G::M: Eq,
+    {
+        self.play_with_log(start, first, second, Some((log, move_notation::<G>)))
+    }
+
+    // Play a game, writing its moves to the log, if any, in the given
+    // notation.
+    pub(crate) fn play_with_log<G: Game>(
+        &self, start: &G::S, first: &mut dyn Strategy<G>, second: &mut dyn Strategy<G>,
+        log: Option<(&MoveLog, Notation<G>)>,
+    ) -> GameRecord<G>
+    where
+        G::S: Clone,
+    {
+        first.new_game();
+        second.new_game();
+        let mut record =
+            GameRecord::new(start.clone()).with_players(&self.players[0], &self.players[1]);
+        let mut state = start.clone();
+        let log =
+            log.map(|(log, notation)| (log, notation, log.start_game(&self.players, self.opening)));
+        let (result, termination) = self.play_game(&mut state, [first, second], &mut record, log);
+        if let Some((log, _, game)) = log {
+            log.finish_game(game, result, termination);
+        }
+        record.finish(result, termination);
+        record
+    }
+
+    fn play_game<G: Game>(
+        &self, state: &mut G::S, mut players: [&mut dyn Strategy<G>; 2],
+        record: &mut GameRecord<G>, log: Option<(&MoveLog, Notation<G>, u64)>,
+    ) -> (Outcome, Termination) {
+        let mut clocks = self.time_controls.map(|control| control.map(Clock::new));
+        // What each player recommended with its last move.
+        let mut recommendations = [None; 2];
+        let mut s = 0;
+        let mut moves = 0;
+        loop {
+            if let Some(winner) = G::get_winner(state) {
+                let result = match winner {
+                    Winner::Draw => Outcome::Draw,
+                    Winner::PlayerJustMoved => Outcome::Win(1 - s),
+                    Winner::PlayerToMove => Outcome::Win(s),
+                };
+                return (result, Termination::GameOver);
+            }
+            if self.max_moves.is_some_and(|max| moves >= max) {
+                return (Outcome::Draw, Termination::MoveLimit);
+            }
+            let strategy = &mut players[s];
+            if let Some(clock) = clocks[s].as_ref() {
+                strategy.set_time_left(&clock.time_left());
+            }
+            let start_time = Instant::now();
+            let info = strategy.choose_move_with_info(state);
+            let elapsed = start_time.elapsed();
+            let mut clock_left = None;
+            if let Some(clock) = clocks[s].as_mut() {
+                if !clock.punch(elapsed) {
+                    return (Outcome::Win(1 - s), Termination::TimeForfeit);
+                }
+                clock_left = Some(clock.remaining());
+            }
+            match info.recommendation {
+                Some(Recommendation::Resign) => {
+                    return (Outcome::Win(1 - s), Termination::Resignation);
+                }
+                Some(Recommendation::OfferDraw)
+                    if matches!(
+                        recommendations[1 - s],
+                        Some(Recommendation::OfferDraw | Recommendation::AcceptDraw)
+                    ) =>
+                {
+                    return (Outcome::Draw, Termination::DrawAgreed);
+                }
+                _ => {}
+            }
+            recommendations[s] = info.recommendation;
+            let Some(m) = info.best_move else {
+                return (Outcome::Draw, Termination::NoMove);
+            };
+            record.moves.push(RecordedMove {
+                elapsed,
+                clock: clock_left,
+                ..RecordedMove::from_search(m, &info)
+            });
+            if let (Some((log, notation, game)), Some(recorded)) = (log, record.moves.last()) {
+                log.write_move(game, record.moves.len(), recorded, &notation(state, m));
+            }
+            for player in players.iter_mut() {
+                player.notify_move(state, m);
+            }
+            let to_move = G::player_to_move(state);
+            if let Some(new_state) = G::apply(state, m) {
+                *state = new_state;
+            }
+            moves += 1;
+            // The same player may move again.
+            if to_move.is_none() || G::player_to_move(state) != to_move {
+                s = 1 - s;
+            }
+        }
+    }
+}
+
+// Ends every line of a log, so that a missing one marks a cut off line.
+const LINE_END: char = ';';
+
+struct LogWriter {
+    writer: Box<dyn Write + Send>,
+    next_game: u64,
+}
+
+/// An append-only text log of games, written a line at a time as they are
+/// played, and flushed after every line.
+///
+/// Each game has a line when it starts, such as "game 3 first vs second;",
+/// with " opening 1" before the ";" if it started from a tournament's
+/// opening, then a line per move, such as "move 3 12 0.250 -40 e4;", with
+/// the move number, the time taken in seconds, the mover's value or "-",
+/// and the move's notation from `util::move_notation`, and finally a line
+/// with the result, such as "result 3 1-0 time forfeit;". Every line ends
+/// with ";", so that a line cut off by a crash can be told apart. Games are
+/// numbered from the first in the log, and the lines of games played at the
+/// same time may be interleaved.
+///
+/// Copies of a log write to the same place, so one log can be shared by
+/// all the games of a tournament. Errors writing the log are ignored, so
+/// that it can't stop a game.
+#[derive(Clone)]
+pub struct MoveLog(Arc<Mutex<LogWriter>>);
+
+impl MoveLog {
+    /// Log to `writer`, numbering games from 0.
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self(Arc::new(Mutex::new(LogWriter { writer, next_game: 0 })))
+    }
+
+    /// Append to the log file at `path`, creating it if needed. Games are
+    /// numbered after those already in the file.
+    pub fn append(path: &Path) -> io::Result<Self> {
+        let mut games = 0;
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                if line?.starts_with("game ") {
+                    games += 1;
+                }
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        // Start a new line, in case the last one was cut off.
+        if games > 0 {
+            writeln!(file)?;
+        }
+        let log = Self::new(Box::new(file));
+        log.lock().next_game = games;
+        Ok(log)
+    }
+
+    // A strategy that panicked while another thread was writing can't have
+    // left the writer in a worse state than a cut off line.
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogWriter> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn write_line(writer: &mut LogWriter, line: &str) {
+        let _ = writeln!(writer.writer, "{}{}", line, LINE_END);
+        let _ = writer.writer.flush();
+    }
+
+    fn start_game(&self, players: &[String; 2], opening: Option<usize>) -> u64 {
+        let mut writer = self.lock();
+        let game = writer.next_game;
+        writer.next_game += 1;
+        let mut line = format!("game {} {} vs {}", game, players[0], players[1]);
+        if let Some(opening) = opening {
+            line += &format!(" opening {}", opening);
+        }
+        Self::write_line(&mut writer, &line);
+        game
+    }
+
+    fn write_move<M>(&self, game: u64, number: usize, m: &RecordedMove<M>, notation: &str) {
+        let value = m.value.map_or("-".to_string(), |value| value.to_string());
+        let line = format!(
+            "move {} {} {:.3} {} {}",
+            game,
+            number,
+            m.elapsed.as_secs_f64(),
+            value,
+            notation
+        );
+        Self::write_line(&mut self.lock(), &line);
+    }
+
+    fn finish_game(&self, game: u64, result: Outcome, termination: Termination) {
+        let result = match result {
+            Outcome::Win(0) => "1-0",
+            Outcome::Win(_) => "0-1",
+            Outcome::Draw => "draw",
+        };
+        let termination = match termination {
+            Termination::GameOver => "game over",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::MoveLimit => "move limit",
+            Termination::NoMove => "no move",
+            Termination::Resignation => "resignation",
+            Termination::DrawAgreed => "draw agreed",
+        };
+        Self::write_line(&mut self.lock(), &format!("result {} {} {}", game, result, termination));
+    }
+
+    /// Replay the games in a log, in the order they started. Games that
+    /// never finished, e.g. because a strategy crashed, have no result, and
+    /// the last of their `positions` is where the crash happened.
+    ///
+    /// `starts` are the positions the games started from: the first for
+    /// games that didn't start from an opening, and otherwise the
+    /// tournament's openings, in order, followed by the positions after its
+    /// opening lines. Lines that were cut off are skipped, along with the
+    /// game whose first line was cut off, but a complete line that can't be
+    /// read is an error.
+    pub fn read<G: Game>(reader: impl BufRead, starts: &[G::S]) -> io::Result<Vec<GameRecord<G>>>
+    where
+        G::S: Clone,
+        G::M: Copy,
+    {
+        let invalid = |line: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("bad log line: {}", line))
+        };
+        // Each game's record and current position, by number. A game whose
+        // line was cut off is missing, but later games keep their numbers.
+        let mut games: BTreeMap<usize, (GameRecord<G>, G::S)> = BTreeMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            // Without its end, the line was cut off.
+            let Some(content) = line.strip_suffix(LINE_END) else {
+                continue;
+            };
+            let mut fields = content.splitn(2, ' ');
+            let (Some(kind), Some(rest)) = (fields.next(), fields.next()) else {
+                return Err(invalid(&line));
+            };
+            let Some((game, rest)) = rest.split_once(' ') else {
+                return Err(invalid(&line));
+            };
+            let Ok(game) = game.parse::<usize>() else {
+                return Err(invalid(&line));
+            };
+            if kind == "game" {
+                let (players, opening) = match rest.rsplit_once(" opening ") {
+                    Some((players, opening)) => {
+                        (players, Some(opening.parse::<usize>().map_err(|_| invalid(&line))?))
+                    }
+                    None => (rest, None),
+                };
+                let Some((first, second)) = players.split_once(" vs ") else {
+                    return Err(invalid(&line));
+                };
+                let start = starts.get(opening.unwrap_or(0)).ok_or_else(|| invalid(&line))?;
+                if games.contains_key(&game) {
+                    return Err(invalid(&line));
+                }
+                let record = GameRecord::new(start.clone()).with_players(first, second);
+                games.insert(game, (record, start.clone()));
+                continue;
+            }
+            let Some((record, state)) = games.get_mut(&game) else {
+                return Err(invalid(&line));
+            };
+            if kind == "move" {
+                let fields: Vec<&str> = rest.splitn(4, ' ').collect();
+                let [number, elapsed, value, notation] = fields[..] else {
+                    return Err(invalid(&line));
+                };
+                let (Ok(number), Some(elapsed)) = (
+                    number.parse::<usize>(),
+                    elapsed.parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+                ) else {
+                    return Err(invalid(&line));
+                };
+                if number != record.moves.len() + 1 {
+                    return Err(invalid(&line));
+                }
+                let m = parse_move::<G>(state, notation).ok_or_else(|| invalid(&line))?;
+                record.add_move(m, value.parse().ok(), elapsed);
+                if let Some(new_state) = G::apply(state, m) {
+                    *state = new_state;
+                }
+            } else if kind == "result" {
+                let Some((result, termination)) = rest.split_once(' ') else {
+                    return Err(invalid(&line));
+                };
+                let result = match result {
+                    "1-0" => Outcome::Win(0),
+                    "0-1" => Outcome::Win(1),
+                    "draw" => Outcome::Draw,
+                    _ => return Err(invalid(&line)),
+                };
+                let termination = match termination {
+                    "game over" => Termination::GameOver,
+                    "time forfeit" => Termination::TimeForfeit,
+                    "move limit" => Termination::MoveLimit,
+                    "no move" => Termination::NoMove,
+                    "resignation" => Termination::Resignation,
+                    "draw agreed" => Termination::DrawAgreed,
+                    _ => return Err(invalid(&line)),
+                };
+                record.finish(result, termination);
+            }
+        }
+        Ok(games.into_values().map(|(record, _)| record).collect())
+    }
+}
@@ -0,0 +1,145 @@
+//! Adapters for running neural networks as policy/value evaluators.
+//!
+//! The network itself is run by an external runtime, such as ONNX Runtime or
+//! tch, behind the `NeuralNetwork` trait, and an `Encoder` converts the
+//! game's positions and moves to and from its tensors. Together they make a
+//! `NeuralEvaluator`, which can guide `MonteCarloTreeSearch` with
+//! `new_with_evaluator`, for AlphaZero-style engines.
+
+use super::interface::*;
+
+use std::marker::PhantomData;
+
+/// Where a network runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Device {
+    Cpu,
+    /// A GPU or other accelerator, by index.
+    Gpu(usize),
+}
+
+/// A network loaded in an external runtime, run on batches of flat `f32`
+/// tensors.
+pub trait NeuralNetwork {
+    /// Run the network on `batch` positions, whose encoded inputs are laid
+    /// out one after another in `input`. Write each position's policy logits
+    /// one after another into `policy`, and its value, from -1 for a loss to
+    /// 1 for a win for the player to move, into `value`.
+    fn run(&self, input: &[f32], batch: usize, policy: &mut [f32], value: &mut [f32]);
+
+    /// The largest batch the network accepts. Larger batches are split.
+    fn max_batch_size(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Move the network to `device`, for later runs. Returns false if the
+    /// device isn't available, leaving the network where it was.
+    fn set_device(&mut self, device: Device) -> bool {
+        device == Device::Cpu
+    }
+}
+
+/// Converts a game's positions and moves to and from a network's tensors.
+pub trait Encoder {
+    /// The type of game that can be encoded.
+    type G: Game;
+
+    /// The number of inputs for each position.
+    fn input_size(&self) -> usize;
+
+    /// The number of policy logits for each position.
+    fn policy_size(&self) -> usize;
+
+    /// Write the network's inputs for this position into `input`, which has
+    /// `input_size` elements.
+    fn encode(&self, state: &<Self::G as Game>::S, input: &mut [f32]);
+
+    /// The index of this move's logit in the policy output.
+    fn move_index(&self, state: &<Self::G as Game>::S, m: <Self::G as Game>::M) -> usize;
+}
+
+/// A policy/value evaluator that encodes positions and runs them through a
+/// network in batches.
+///
+/// The policy over the legal moves is the softmax of their logits.
+pub struct NeuralEvaluator<N, E: Encoder> {
+    network: N,
+    encoder: E,
+    max_batch_size: usize,
+    game_type: PhantomData<E::G>,
+}
+
+impl<N: NeuralNetwork, E: Encoder> NeuralEvaluator<N, E> {
+    pub fn new(network: N, encoder: E) -> Self {
+        let max_batch_size = network.max_batch_size().max(1);
+        Self { network, encoder, max_batch_size, game_type: PhantomData }
+    }
+
+    /// Run at most this many positions at a time, below the network's own
+    /// limit, e.g. to bound the memory used on the device.
+    pub fn with_max_batch_size(mut self, batch_size: usize) -> Self {
+        self.max_batch_size = batch_size.clamp(1, self.network.max_batch_size().max(1));
+        self
+    }
+
+    /// Move the network to `device`. Returns false if it isn't available.
+    pub fn set_device(&mut self, device: Device) -> bool {
+        self.network.set_device(device)
+    }
+
+    pub fn network(&self) -> &N {
+        &self.network
+    }
+}
+
+impl<N: NeuralNetwork, E: Encoder> PolicyValueEvaluator for NeuralEvaluator<N, E> {
+    type G = E::G;
+
+    fn evaluate_batch(&self, states: &[<E::G as Game>::S], results: &mut Vec<PolicyValue>) {
+        let input_size = self.encoder.input_size();
+        let policy_size = self.encoder.policy_size();
+        let mut input = Vec::new();
+        let mut policy = Vec::new();
+        let mut value = Vec::new();
+        let mut moves = Vec::new();
+        for chunk in states.chunks(self.max_batch_size) {
+            input.clear();
+            input.resize(chunk.len() * input_size, 0.0);
+            for (state, input) in chunk.iter().zip(input.chunks_mut(input_size.max(1))) {
+                self.encoder.encode(state, input);
+            }
+            policy.clear();
+            policy.resize(chunk.len() * policy_size, 0.0);
+            value.clear();
+            value.resize(chunk.len(), 0.0);
+            self.network.run(&input, chunk.len(), &mut policy, &mut value);
+
+            for (i, state) in chunk.iter().enumerate() {
+                let logits = &policy[i * policy_size..(i + 1) * policy_size];
+                moves.clear();
+                E::G::generate_moves(state, &mut moves);
+                let logits = moves
+                    .iter()
+                    .map(|&m| logits.get(self.encoder.move_index(state, m)).copied().unwrap_or(0.0))
+                    .collect::<Vec<_>>();
+                results.push(PolicyValue { policy: softmax(&logits), value: value[i] });
+            }
+        }
+    }
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps = logits.iter().map(|&logit| (logit - max).exp()).collect::<Vec<_>>();
+    let sum = exps.iter().sum::<f32>();
+    exps.into_iter().map(|exp| exp / sum).collect()
+}
+
+#[test]
+fn test_softmax() {
+    let policy = softmax(&[0.0, 0.0, 2.0_f32.ln()]);
+    for (p, expected) in policy.into_iter().zip([0.25, 0.25, 0.5]) {
+        assert!((p - expected).abs() < 1e-6, "{p}");
+    }
+    assert!(softmax(&[]).is_empty());
+}
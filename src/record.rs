@@ -0,0 +1,266 @@
+//! Records of played games, for storing and replaying matches.
+
+use super::interface::*;
+use super::json;
+use super::util::move_notation;
+
+use std::fmt::Write;
+use std::time::Duration;
+
+/// How a finished game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    /// The player with this index won: 0 for the player who moved first,
+    /// 1 for the other.
+    Win(usize),
+    Draw,
+}
+
+/// Why a finished game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Termination {
+    /// The rules of the game ended it.
+    GameOver,
+    /// A player ran out of time, and lost.
+    TimeForfeit,
+    /// It was drawn after reaching a limit on the number of moves.
+    MoveLimit,
+    /// A player had no move to play, and it was drawn.
+    NoMove,
+    /// A player resigned, and lost.
+    Resignation,
+    /// A player offered a draw, and the other accepted.
+    DrawAgreed,
+}
+
+/// A move played in a recorded game.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordedMove<M> {
+    pub m: M,
+    /// The mover's evaluation of the move, if it reported one.
+    pub value: Option<Evaluation>,
+    /// Time spent choosing the move.
+    pub elapsed: Duration,
+    /// Main time left on the mover's clock after the move, in timed games.
+    pub clock: Option<Duration>,
+    /// Depth reached by the search, or 0 if unknown.
+    pub depth: u8,
+    /// Nodes or simulations searched, or 0 if unknown.
+    pub nodes: u64,
+    /// The line the mover expected, starting with this move, if reported.
+    pub principal_variation: Vec<M>,
+}
+
+impl<M: Clone> RecordedMove<M> {
+    /// A move chosen by a search, with its statistics.
+    pub fn from_search(m: M, info: &SearchInfo<M>) -> Self {
+        Self {
+            m,
+            value: info.value,
+            elapsed: info.elapsed,
+            clock: None,
+            depth: info.depth,
+            nodes: info.nodes,
+            principal_variation: info.principal_variation.clone(),
+        }
+    }
+}
+
+/// The moves of a game from its starting position, with the players'
+/// evaluations and thinking times, and the result once it is over.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "G::S: serde::Serialize, G::M: serde::Serialize",
+        deserialize = "G::S: serde::Deserialize<'de>, G::M: serde::Deserialize<'de>"
+    ))
+)]
+pub struct GameRecord<G: Game> {
+    /// Names of the player who moved first and the other player.
+    pub players: [String; 2],
+    pub start: G::S,
+    pub moves: Vec<RecordedMove<G::M>>,
+    /// None until the game is over.
+    pub result: Option<Outcome>,
+    /// How the game ended, if known.
+    pub termination: Option<Termination>,
+}
+
+impl<G: Game> GameRecord<G> {
+    pub fn new(start: G::S) -> Self {
+        Self {
+            players: Default::default(),
+            start,
+            moves: Vec::new(),
+            result: None,
+            termination: None,
+        }
+    }
+
+    /// Record the names of the player who moves first and the other player.
+    pub fn with_players(mut self, first: &str, second: &str) -> Self {
+        self.players = [first.to_string(), second.to_string()];
+        self
+    }
+
+    /// Record the next move.
+    pub fn add_move(&mut self, m: G::M, value: Option<Evaluation>, elapsed: Duration) {
+        self.moves.push(RecordedMove {
+            m,
+            value,
+            elapsed,
+            clock: None,
+            depth: 0,
+            nodes: 0,
+            principal_variation: Vec::new(),
+        });
+    }
+
+    /// Record the next move, from the details of the search that chose it.
+    pub fn add_search(&mut self, info: &SearchInfo<G::M>)
+    where
+        G::M: Copy,
+    {
+        if let Some(m) = info.best_move {
+            self.moves.push(RecordedMove::from_search(m, info));
+        }
+    }
+
+    pub fn set_result(&mut self, result: Outcome) {
+        self.result = Some(result);
+    }
+
+    /// Record the result and how the game came to end.
+    pub fn finish(&mut self, result: Outcome, termination: Termination) {
+        self.result = Some(result);
+        self.termination = Some(termination);
+    }
+
+    /// Replay the game, returning the position before each move followed by
+    /// the final position.
+    pub fn positions(&self) -> Vec<G::S>
+    where
+        G::S: Clone,
+        G::M: Copy,
+    {
+        let mut state = self.start.clone();
+        let mut positions = Vec::with_capacity(self.moves.len() + 1);
+        for recorded in self.moves.iter() {
+            positions.push(state.clone());
+            if let Some(new_state) = G::apply(&mut state, recorded.m) {
+                state = new_state;
+            }
+        }
+        positions.push(state);
+        positions
+    }
+
+    /// Export the game as text, with one line per move giving the move's
+    /// notation, value, and time, followed by the result.
+    ///
+    /// Moves are named by `util::move_notation`, as in `to_json`, so moves
+    /// without a `Game::notation` are written as their 1-based index in the
+    /// generated moves, which `util::parse_move` reads back.
+    pub fn to_text(&self) -> String
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        let mut text = String::new();
+        if !self.players.iter().all(String::is_empty) {
+            let _ = writeln!(text, "players: {} vs {}", self.players[0], self.players[1]);
+        }
+        let positions = self.positions();
+        for (i, (recorded, state)) in self.moves.iter().zip(positions.iter()).enumerate() {
+            let _ = write!(text, "{}. {}", i + 1, move_notation::<G>(state, recorded.m));
+            if let Some(value) = recorded.value {
+                let _ = write!(text, " {}", value);
+            }
+            let _ = write!(text, " {:.3}s", recorded.elapsed.as_secs_f64());
+            if let Some(clock) = recorded.clock {
+                let _ = write!(text, " clock {:.3}s", clock.as_secs_f64());
+            }
+            let _ = writeln!(text);
+        }
+        let result = match self.result {
+            None => "unfinished",
+            Some(Outcome::Win(0)) => "first player wins",
+            Some(Outcome::Win(_)) => "second player wins",
+            Some(Outcome::Draw) => "draw",
+        };
+        let termination = match self.termination {
+            Some(Termination::TimeForfeit) => " on time",
+            Some(Termination::MoveLimit) => " by move limit",
+            Some(Termination::NoMove) => " with no move",
+            Some(Termination::Resignation) => " by resignation",
+            Some(Termination::DrawAgreed) => " by agreement",
+            Some(Termination::GameOver) | None => "",
+        };
+        let _ = writeln!(text, "result: {}{}", result, termination);
+        text
+    }
+
+    /// Export the game as a single line of JSON, with the players, the
+    /// result, and the statistics of each move's search: its value, depth,
+    /// nodes, time in seconds, and principal variation.
+    pub fn to_json(&self) -> String
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        let positions = self.positions();
+        let moves = self.moves.iter().zip(positions.iter()).map(|(recorded, state)| {
+            json::Object::new()
+                .string("move", &move_notation::<G>(state, recorded.m))
+                .optional("value", recorded.value.map(|value| value as f64))
+                .number("depth", recorded.depth as f64)
+                .number("nodes", recorded.nodes as f64)
+                .number("time", recorded.elapsed.as_secs_f64())
+                .optional("clock", recorded.clock.map(|clock| clock.as_secs_f64()))
+                .raw("pv", &json::line::<G>(state, &recorded.principal_variation))
+                .finish()
+        });
+        let result = match self.result {
+            None => "null",
+            Some(Outcome::Win(0)) => "\"1-0\"",
+            Some(Outcome::Win(_)) => "\"0-1\"",
+            Some(Outcome::Draw) => "\"draw\"",
+        };
+        let termination = match self.termination {
+            None => "null",
+            Some(Termination::GameOver) => "\"game over\"",
+            Some(Termination::TimeForfeit) => "\"time forfeit\"",
+            Some(Termination::MoveLimit) => "\"move limit\"",
+            Some(Termination::NoMove) => "\"no move\"",
+            Some(Termination::Resignation) => "\"resignation\"",
+            Some(Termination::DrawAgreed) => "\"draw agreed\"",
+        };
+        json::Object::new()
+            .raw("players", &json::array(self.players.iter().map(|name| json::string(name))))
+            .raw("result", result)
+            .raw("termination", termination)
+            .raw("moves", &json::array(moves))
+            .finish()
+    }
+}
+
+impl<G: Game> Clone for GameRecord<G>
+where
+    G::S: Clone,
+    G::M: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            players: self.players.clone(),
+            start: self.start.clone(),
+            moves: self.moves.clone(),
+            result: self.result,
+            termination: self.termination,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! An adapter to run any strategy on a background thread, exposing its moves
+//! as futures for use in async game servers.
+//!
+//! This doesn't depend on any particular async runtime. Dropping a pending
+//! future cancels its search, via a stop signal of its own, which is given to
+//! the strategy when that search starts.
+
+use super::super::interface::*;
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+struct Shared<M> {
+    // Set once the search finishes.
+    done: bool,
+    result: Option<M>,
+    waker: Option<Waker>,
+}
+
+/// Runs a wrapped strategy on background threads.
+pub struct AsyncStrategy<G: Game, S> {
+    strategy: Arc<Mutex<S>>,
+    // The stop signal of the search running now, or of the last one.
+    current: Arc<Mutex<StopSignal>>,
+    game_type: PhantomData<G>,
+}
+
+impl<G, S> AsyncStrategy<G, S>
+where
+    G: Game + 'static,
+    G::S: Clone + Send + 'static,
+    G::M: Send + 'static,
+    S: Strategy<G> + Send + 'static,
+{
+    pub fn new(strategy: S) -> Self {
+        Self {
+            strategy: Arc::new(Mutex::new(strategy)),
+            current: Arc::new(Mutex::new(StopSignal::new())),
+            game_type: PhantomData,
+        }
+    }
+
+    /// Start choosing a move on a background thread.
+    ///
+    /// If another search is still running, this one starts when it finishes.
+    pub fn choose_move(&self, state: &G::S) -> MoveFuture<G::M> {
+        let shared = Arc::new(Mutex::new(Shared { done: false, result: None, waker: None }));
+        let strategy = self.strategy.clone();
+        let current = self.current.clone();
+        let stop = StopSignal::new();
+        let thread_stop = stop.clone();
+        let state = state.clone();
+        let thread_shared = shared.clone();
+        thread::spawn(move || {
+            let mut strategy = strategy.lock().unwrap();
+            *current.lock().unwrap() = thread_stop.clone();
+            strategy.set_stop_signal(thread_stop);
+            let m = strategy.choose_move(&state);
+            let mut shared = thread_shared.lock().unwrap();
+            shared.done = true;
+            shared.result = m;
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        });
+        MoveFuture { shared, stop }
+    }
+
+    /// Ask the running search, if any, to return its best move so far.
+    pub fn stop(&self) {
+        self.current.lock().unwrap().stop();
+    }
+
+    /// Run a closure on the wrapped strategy, e.g. to change its settings.
+    /// Waits for any running search to finish.
+    pub fn with_strategy<R, F: FnOnce(&mut S) -> R>(&self, f: F) -> R {
+        f(&mut self.strategy.lock().unwrap())
+    }
+}
+
+/// A move being chosen in the background by an AsyncStrategy.
+///
+/// Dropping this before it completes cancels the search, and only this one:
+/// other searches queued or running on the same strategy carry on.
+pub struct MoveFuture<M> {
+    shared: Arc<Mutex<Shared<M>>>,
+    stop: StopSignal,
+}
+
+impl<M> MoveFuture<M> {
+    /// Ask the search to return its best move so far.
+    pub fn cancel(&self) {
+        let shared = self.shared.lock().unwrap();
+        if !shared.done {
+            self.stop.stop();
+        }
+    }
+}
+
+impl<M> Future for MoveFuture<M> {
+    type Output = Option<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<M>> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.done {
+            Poll::Ready(shared.result.take())
+        } else {
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<M> Drop for MoveFuture<M> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
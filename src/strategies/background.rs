@@ -0,0 +1,120 @@
+//! Support for running a strategy's search on a background thread, e.g.
+//! while the opponent is thinking.
+
+use super::super::interface::*;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Shared between a background search and its owner.
+struct Tracker<M> {
+    best_move: Option<M>,
+    // The strategy's own progress callback.
+    progress: Option<Box<dyn SearchProgress<M>>>,
+}
+
+// Records the best move reported by the search, and forwards the reports.
+struct TrackerProgress<M>(Arc<Mutex<Tracker<M>>>);
+
+impl<M: Clone + Send> SearchProgress<M> for TrackerProgress<M> {
+    fn report(&mut self, info: &SearchInfo<M>) {
+        let mut tracker = self.0.lock().unwrap();
+        if info.best_move.is_some() {
+            tracker.best_move.clone_from(&info.best_move);
+        }
+        if let Some(progress) = tracker.progress.as_mut() {
+            progress.report(info);
+        }
+    }
+}
+
+// The strategy returned from a background search.
+pub(super) struct Finished<T, M> {
+    pub(super) searcher: T,
+    pub(super) best_move: Option<M>,
+    // The progress callback the strategy had when the search started.
+    pub(super) progress: Option<Box<dyn SearchProgress<M>>>,
+}
+
+// A search running in the background, which owns the strategy until it is
+// stopped.
+pub(super) struct BackgroundSearch<T, M> {
+    handle: Option<thread::JoinHandle<(T, Option<M>)>>,
+    stop: StopSignal,
+    tracker: Arc<Mutex<Tracker<M>>>,
+    hit: bool,
+    // The hash of the position being pondered, if known, to check a ponder
+    // hit against the position actually reached.
+    pub(super) position: Option<u64>,
+}
+
+impl<T: Send + 'static, M: Clone + Send + 'static> BackgroundSearch<T, M> {
+    // Run `search` on a new thread. It should install the given stop signal
+    // and progress callback, then call choose_move.
+    pub(super) fn start<F>(
+        mut searcher: T, progress: Option<Box<dyn SearchProgress<M>>>, search: F,
+    ) -> Self
+    where
+        F: FnOnce(&mut T, StopSignal, Box<dyn SearchProgress<M>>) -> Option<M> + Send + 'static,
+    {
+        let stop = StopSignal::new();
+        let tracker = Arc::new(Mutex::new(Tracker { best_move: None, progress }));
+        let thread_stop = stop.clone();
+        let thread_progress = Box::new(TrackerProgress(tracker.clone()));
+        let handle = thread::spawn(move || {
+            let m = search(&mut searcher, thread_stop, thread_progress);
+            (searcher, m)
+        });
+        Self { handle: Some(handle), stop, tracker, hit: false, position: None }
+    }
+
+    // The predicted move was played. Stop the search after `max_time`, or let
+    // it run to its depth limit if that's zero.
+    pub(super) fn hit(&mut self, max_time: Duration) {
+        self.hit = true;
+        if max_time != Duration::new(0, 0) {
+            let stop = self.stop.clone();
+            thread::spawn(move || {
+                thread::sleep(max_time);
+                stop.stop();
+            });
+        }
+    }
+}
+
+impl<T, M: Clone> BackgroundSearch<T, M> {
+    pub(super) fn is_hit(&self) -> bool {
+        self.hit
+    }
+
+    // The best move reported by the search so far.
+    pub(super) fn best_so_far(&self) -> Option<M> {
+        self.tracker.lock().unwrap().best_move.clone()
+    }
+
+    // Stop the search now, and return the strategy and the move it found.
+    pub(super) fn stop(mut self) -> Finished<T, M> {
+        self.stop.stop();
+        self.join()
+    }
+
+    // Wait for the search to finish on its own.
+    pub(super) fn finish(mut self) -> Finished<T, M> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Finished<T, M> {
+        let (searcher, m) = self.handle.take().unwrap().join().unwrap();
+        let mut tracker = self.tracker.lock().unwrap();
+        let best_move = m.or_else(|| tracker.best_move.clone());
+        Finished { searcher, best_move, progress: tracker.progress.take() }
+    }
+}
+
+impl<T, M> Drop for BackgroundSearch<T, M> {
+    fn drop(&mut self) {
+        // Don't leave an abandoned search running.
+        self.stop.stop();
+    }
+}
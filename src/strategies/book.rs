@@ -0,0 +1,502 @@
+//! An opening book, and a strategy wrapper that plays from it.
+//!
+//! The book maps positions, by `zobrist_hash`, to weighted moves. It can be
+//! built from records of previous games, or by having a strategy play itself.
+//! The game must implement `zobrist_hash` for the book to be useful.
+//!
+//! Books can be saved in a compact binary format, and opened as a `BookFile`,
+//! which probes the file's bytes directly instead of building a table, so
+//! even large books load quickly.
+
+use super::super::interface::*;
+use super::util::new_rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"MMBK";
+// The newest version of the book file format. Files are laid out as:
+//   header: magic, version: u32, positions: u64, moves: u64
+//   index:  (hash: u64, first move: u32, move count: u32) per position,
+//           sorted by hash
+//   moves:  (move code: u32, weight: u32) per move
+// with every number little-endian.
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 24;
+const INDEX_ENTRY_SIZE: usize = 16;
+const MOVE_SIZE: usize = 8;
+
+/// Weighted moves to play from known positions, in memory or in a book file.
+pub trait Book<M: Clone> {
+    /// Return the weighted moves for the position with this `zobrist_hash`.
+    fn moves(&self, hash: u64) -> Option<Cow<'_, [(M, u32)]>>;
+
+    /// Bytes of memory used by the book.
+    fn memory_bytes(&self) -> usize;
+}
+
+/// A shared book, e.g. for copies of a `BookStrategy` playing at the same
+/// time.
+impl<M: Clone, B: Book<M> + ?Sized> Book<M> for Arc<B> {
+    fn moves(&self, hash: u64) -> Option<Cow<'_, [(M, u32)]>> {
+        (**self).moves(hash)
+    }
+
+    fn memory_bytes(&self) -> usize {
+        (**self).memory_bytes()
+    }
+}
+
+// Choose a book move at random, in proportion to the weights, skipping moves
+// that aren't legal in this position, e.g. from hash collisions.
+fn choose_book_move<G: Game, B: Book<G::M> + ?Sized, R: Rng + ?Sized>(
+    book: &B, state: &G::S, rng: &mut R,
+) -> Option<G::M>
+where
+    G::M: Copy + Eq,
+{
+    let book_moves = book.moves(G::zobrist_hash(state))?;
+    let mut moves = Vec::new();
+    G::generate_moves(state, &mut moves);
+    let legal = book_moves.iter().filter(|(m, w)| *w > 0 && moves.contains(m)).collect::<Vec<_>>();
+    legal.choose_weighted(rng, |(_, w)| *w).ok().map(|(m, _)| *m)
+}
+
+/// Weighted moves to play from known positions.
+pub struct OpeningBook<M> {
+    entries: HashMap<u64, Vec<(M, u32)>>,
+}
+
+impl<M> Default for OpeningBook<M> {
+    fn default() -> Self {
+        Self { entries: HashMap::new() }
+    }
+}
+
+impl<M: Copy + Eq> OpeningBook<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add weight to a move from this position.
+    pub fn add<G: Game<M = M>>(&mut self, state: &G::S, m: M, weight: u32) {
+        let moves = self.entries.entry(G::zobrist_hash(state)).or_default();
+        match moves.iter_mut().find(|(book_move, _)| *book_move == m) {
+            Some((_, w)) => *w = w.saturating_add(weight),
+            None => moves.push((m, weight)),
+        }
+    }
+
+    /// Add the first `max_ply` moves of a game played from `start`, each with
+    /// a weight of one.
+    pub fn add_game<G: Game<M = M>>(&mut self, start: &G::S, moves: &[M], max_ply: usize)
+    where
+        G::S: Clone,
+    {
+        let mut state = start.clone();
+        for &m in moves.iter().take(max_ply) {
+            self.add::<G>(&state, m, 1);
+            if let Some(new_state) = G::apply(&mut state, m) {
+                state = new_state;
+            }
+        }
+    }
+
+    /// Build a book from records of games played from `start`.
+    pub fn from_games<G: Game<M = M>>(start: &G::S, games: &[Vec<M>], max_ply: usize) -> Self
+    where
+        G::S: Clone,
+    {
+        let mut book = Self::new();
+        for game in games.iter() {
+            book.add_game::<G>(start, game, max_ply);
+        }
+        book
+    }
+
+    /// Build a book from the first `max_ply` moves of games the strategy
+    /// plays against itself from `start`.
+    ///
+    /// The strategy should have some randomness, or every game will be the same.
+    pub fn from_self_play<G: Game<M = M>, S: Strategy<G>>(
+        start: &G::S, strategy: &mut S, num_games: usize, max_ply: usize,
+    ) -> Self
+    where
+        G::S: Clone,
+    {
+        let mut book = Self::new();
+        for _ in 0..num_games {
+            let mut state = start.clone();
+            for _ in 0..max_ply {
+                if G::get_winner(&state).is_some() {
+                    break;
+                }
+                let m = match strategy.choose_move(&state) {
+                    Some(m) => m,
+                    None => break,
+                };
+                book.add::<G>(&state, m, 1);
+                if let Some(new_state) = G::apply(&mut state, m) {
+                    state = new_state;
+                }
+            }
+        }
+        book
+    }
+
+    /// Return the weighted moves for this position, if it's in the book.
+    pub fn probe<G: Game<M = M>>(&self, state: &G::S) -> Option<&[(M, u32)]> {
+        self.entries.get(&G::zobrist_hash(state)).map(|moves| &moves[..])
+    }
+
+    /// Choose a book move at random, in proportion to the weights.
+    ///
+    /// Moves that aren't legal in this position, e.g. from hash collisions,
+    /// are skipped.
+    pub fn choose<G: Game<M = M>, R: Rng + ?Sized>(&self, state: &G::S, rng: &mut R) -> Option<M> {
+        choose_book_move::<G, _, _>(self, state, rng)
+    }
+
+    /// Add all the weights from another book, such as one built from a
+    /// separate run of games.
+    pub fn merge(&mut self, other: &OpeningBook<M>) {
+        for (&hash, other_moves) in other.entries.iter() {
+            let moves = self.entries.entry(hash).or_default();
+            for &(m, weight) in other_moves.iter() {
+                match moves.iter_mut().find(|(book_move, _)| *book_move == m) {
+                    Some((_, w)) => *w = w.saturating_add(weight),
+                    None => moves.push((m, weight)),
+                }
+            }
+        }
+    }
+
+    /// Write the book in the binary format read by `BookFile`, with each
+    /// move converted to a number by `encode`.
+    pub fn save<W: Write>(&self, mut writer: W, encode: impl Fn(M) -> u32) -> io::Result<()> {
+        let mut hashes = self.entries.keys().copied().collect::<Vec<_>>();
+        hashes.sort_unstable();
+        let num_moves = self.entries.values().map(|moves| moves.len()).sum::<usize>();
+        if num_moves > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many book moves"));
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(hashes.len() as u64).to_le_bytes())?;
+        writer.write_all(&(num_moves as u64).to_le_bytes())?;
+        let mut first = 0u32;
+        for hash in hashes.iter() {
+            let count = self.entries[hash].len() as u32;
+            writer.write_all(&hash.to_le_bytes())?;
+            writer.write_all(&first.to_le_bytes())?;
+            writer.write_all(&count.to_le_bytes())?;
+            first += count;
+        }
+        for hash in hashes.iter() {
+            for &(m, weight) in self.entries[hash].iter() {
+                writer.write_all(&encode(m).to_le_bytes())?;
+                writer.write_all(&weight.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<M: Copy + Eq> Book<M> for OpeningBook<M> {
+    fn moves(&self, hash: u64) -> Option<Cow<'_, [(M, u32)]>> {
+        self.entries.get(&hash).map(|moves| Cow::Borrowed(&moves[..]))
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.entries
+            .values()
+            .map(|moves| {
+                8 + std::mem::size_of_val(moves)
+                    + moves.capacity() * std::mem::size_of::<(M, u32)>()
+            })
+            .sum()
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// A read-only book in the format written by `OpeningBook::save`.
+///
+/// Positions are found by binary search of the file's sorted index, so
+/// opening a book only needs its bytes. Any bytes will do, including a
+/// memory map of the file, which pages in only the parts that are probed.
+pub struct BookFile<M> {
+    data: Box<dyn AsRef<[u8]> + Send + Sync>,
+    positions: usize,
+    decode: Box<dyn Fn(u32) -> M + Send + Sync>,
+}
+
+impl<M: Copy + Eq> BookFile<M> {
+    /// Check the header and index of a book file's bytes, and use `decode` to
+    /// convert the numbers back into moves.
+    pub fn new(
+        data: impl AsRef<[u8]> + Send + Sync + 'static,
+        decode: impl Fn(u32) -> M + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(invalid("not a book file"));
+        }
+        if read_u32(bytes, 4) > VERSION {
+            return Err(invalid("unsupported book version"));
+        }
+        let positions = read_u64(bytes, 8) as usize;
+        let moves = read_u64(bytes, 16) as usize;
+        let size = positions
+            .checked_mul(INDEX_ENTRY_SIZE)
+            .zip(moves.checked_mul(MOVE_SIZE))
+            .and_then(|(index, moves)| index.checked_add(moves)?.checked_add(HEADER_SIZE));
+        if size != Some(bytes.len()) {
+            return Err(invalid("truncated book file"));
+        }
+        // Check the index once, so that probes can't read past the moves or
+        // miss positions.
+        let mut last_hash = None;
+        for i in 0..positions {
+            let offset = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+            let hash = read_u64(bytes, offset);
+            if last_hash.is_some_and(|last| last >= hash) {
+                return Err(invalid("book positions out of order"));
+            }
+            last_hash = Some(hash);
+            let first = read_u32(bytes, offset + 8) as usize;
+            let count = read_u32(bytes, offset + 12) as usize;
+            if first.checked_add(count).is_none_or(|end| end > moves) {
+                return Err(invalid("book moves out of range"));
+            }
+        }
+        Ok(Self { data: Box::new(data), positions, decode: Box::new(decode) })
+    }
+
+    /// Read a book file into memory.
+    pub fn open(
+        path: impl AsRef<Path>, decode: impl Fn(u32) -> M + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        Self::new(std::fs::read(path)?, decode)
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions == 0
+    }
+
+    /// Return the weighted moves for this position, if it's in the book.
+    pub fn probe<G: Game<M = M>>(&self, state: &G::S) -> Option<Vec<(M, u32)>> {
+        self.moves(G::zobrist_hash(state)).map(Cow::into_owned)
+    }
+
+    /// Choose a book move at random, in proportion to the weights.
+    pub fn choose<G: Game<M = M>, R: Rng + ?Sized>(&self, state: &G::S, rng: &mut R) -> Option<M> {
+        choose_book_move::<G, _, _>(self, state, rng)
+    }
+
+    /// Load the whole book into memory, to merge with others.
+    pub fn to_book(&self) -> OpeningBook<M> {
+        let bytes = (*self.data).as_ref();
+        let mut entries = HashMap::with_capacity(self.positions);
+        for i in 0..self.positions {
+            let offset = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+            entries.insert(read_u64(bytes, offset), self.read_moves(i));
+        }
+        OpeningBook { entries }
+    }
+
+    // The moves of the position at this index.
+    fn read_moves(&self, i: usize) -> Vec<(M, u32)> {
+        let bytes = (*self.data).as_ref();
+        let offset = HEADER_SIZE + i * INDEX_ENTRY_SIZE;
+        let first = read_u32(bytes, offset + 8) as usize;
+        let count = read_u32(bytes, offset + 12) as usize;
+        let moves_start = HEADER_SIZE + self.positions * INDEX_ENTRY_SIZE;
+        (first..first + count)
+            .map(|j| {
+                let offset = moves_start + j * MOVE_SIZE;
+                ((self.decode)(read_u32(bytes, offset)), read_u32(bytes, offset + 4))
+            })
+            .collect()
+    }
+}
+
+impl<M: Copy + Eq> Book<M> for BookFile<M> {
+    fn moves(&self, hash: u64) -> Option<Cow<'_, [(M, u32)]>> {
+        let bytes = (*self.data).as_ref();
+        let (mut lo, mut hi) = (0, self.positions);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mid_hash = read_u64(bytes, HEADER_SIZE + mid * INDEX_ENTRY_SIZE);
+            match mid_hash.cmp(&hash) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(Cow::Owned(self.read_moves(mid))),
+            }
+        }
+        None
+    }
+
+    fn memory_bytes(&self) -> usize {
+        (*self.data).as_ref().len()
+    }
+}
+
+/// Plays from an opening book while the position is in it, and otherwise
+/// falls back to searching with the wrapped strategy.
+pub struct BookStrategy<G: Game, S, B = OpeningBook<<G as Game>::M>> {
+    book: B,
+    inner: S,
+    // Set if the last move came from the book.
+    book_move: Option<G::M>,
+    rng: StdRng,
+    game_type: PhantomData<G>,
+}
+
+impl<G: Game, S: Strategy<G>, B: Book<G::M>> BookStrategy<G, S, B>
+where
+    G::M: Copy + Eq,
+{
+    /// Play from `book`, which can be an `OpeningBook` or a `BookFile`.
+    pub fn new(book: B, inner: S) -> Self {
+        Self { book, inner, book_move: None, rng: new_rng(), game_type: PhantomData }
+    }
+
+    pub fn book(&self) -> &B {
+        &self.book
+    }
+
+    /// Return the wrapped strategy.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Copies copy the book, so put a large one in an `Arc` to share it.
+impl<G: Game, S: FreshCopy, B: Clone> FreshCopy for BookStrategy<G, S, B> {
+    fn fresh_copy(&self) -> Self {
+        Self {
+            book: self.book.clone(),
+            inner: self.inner.fresh_copy(),
+            book_move: None,
+            rng: new_rng(),
+            game_type: PhantomData,
+        }
+    }
+}
+
+impl<G: Game, S: Strategy<G>, B: Book<G::M>> Strategy<G> for BookStrategy<G, S, B>
+where
+    G::M: Copy + Eq,
+{
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        self.book_move = choose_book_move::<G, _, _>(&self.book, state, &mut self.rng);
+        self.book_move.or_else(|| self.inner.choose_move(state))
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        self.book_move = choose_book_move::<G, _, _>(&self.book, state, &mut self.rng);
+        match self.book_move {
+            Some(m) => SearchInfo {
+                best_move: Some(m),
+                principal_variation: vec![m],
+                ..SearchInfo::default()
+            },
+            None => self.inner.choose_move_with_info(state),
+        }
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.set_timeout(timeout);
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        self.inner.notify_move(state, m);
+    }
+
+    fn new_game(&mut self) {
+        self.book_move = None;
+        self.inner.new_game();
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.inner.set_win_probability_scale(scale);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.inner.set_max_nodes(nodes);
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        self.inner.set_max_depth(depth);
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.inner.set_stop_signal(signal);
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<G::M>>) {
+        self.inner.set_progress(progress);
+    }
+
+    // The wrapped strategy analyzes every position, as the book has no
+    // values for its moves.
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        self.book_move = None;
+        self.inner.analyze(state, k)
+    }
+
+    fn set_tablebase(&mut self, tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {
+        self.inner.set_tablebase(tablebase);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.inner.set_rng_seed(seed);
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.inner.set_time_left(time);
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        match self.book_move {
+            Some(m) => vec![m],
+            None => self.inner.principal_variation(),
+        }
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let book = self.book.memory_bytes();
+        let inner = self.inner.memory_usage();
+        MemoryUsage { bytes: inner.bytes + book, ..inner }
+    }
+}
@@ -0,0 +1,196 @@
+//! A strategy that asks several strategies for a move and picks one by vote.
+//!
+//! The strategies run one after another, each with its share of the time
+//! limit, e.g. to mix Monte Carlo tree search with alpha-beta search.
+
+use super::super::interface::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How the ensemble combines the moves of its strategies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Voting {
+    /// The move chosen by the most strategies wins.
+    Majority,
+    /// Each vote is weighted by the chosen move's value, mapped to between 0
+    /// and 1 with a logistic curve. `scale` is the value difference that
+    /// counts as a clear advantage. Strategies that don't report values
+    /// vote with weight 0.5.
+    ScoreWeighted { scale: f32 },
+}
+
+struct Member<G: Game> {
+    strategy: Box<dyn Strategy<G>>,
+    time_share: f32,
+}
+
+pub struct Ensemble<G: Game> {
+    members: Vec<Member<G>>,
+    voting: Voting,
+    pv: Vec<G::M>,
+}
+
+impl<G: Game> Ensemble<G> {
+    pub fn new(voting: Voting) -> Self {
+        Self { members: Vec::new(), voting, pv: Vec::new() }
+    }
+
+    /// Add a strategy, which gets `time_share` of each time limit, e.g. 0.5
+    /// for half of it. Earlier strategies win ties.
+    pub fn with_strategy(mut self, strategy: Box<dyn Strategy<G>>, time_share: f32) -> Self {
+        self.members.push(Member { strategy, time_share });
+        self
+    }
+
+    // Tally the votes for each strategy's move and value, and return the
+    // first strategy to choose the winning move.
+    fn winner(
+        &self, choices: impl Iterator<Item = (Option<G::M>, Option<Evaluation>)>,
+    ) -> Option<usize>
+    where
+        G::M: Eq,
+    {
+        // Tally votes in the order moves were first chosen.
+        let mut votes: Vec<(G::M, f32, usize)> = Vec::new();
+        for (i, (m, value)) in choices.enumerate() {
+            if let Some(m) = m {
+                let weight = self.vote_weight(value);
+                match votes.iter_mut().find(|(vote, _, _)| *vote == m) {
+                    Some((_, total, _)) => *total += weight,
+                    None => votes.push((m, weight, i)),
+                }
+            }
+        }
+        let mut winner: Option<&(G::M, f32, usize)> = None;
+        for vote in votes.iter() {
+            if winner.is_none_or(|best| vote.1 > best.1) {
+                winner = Some(vote);
+            }
+        }
+        winner.map(|&(_, _, i)| i)
+    }
+
+    fn vote_weight(&self, value: Option<Evaluation>) -> f32 {
+        match (self.voting, value) {
+            (Voting::Majority, _) => 1.0,
+            (Voting::ScoreWeighted { .. }, None) => 0.5,
+            (Voting::ScoreWeighted { scale }, Some(value)) => win_probability(value, scale),
+        }
+    }
+}
+
+impl<G: Game> Strategy<G> for Ensemble<G>
+where
+    G::M: Copy + Eq,
+{
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        self.choose_move_with_info(state).best_move
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        let mut infos = Vec::with_capacity(self.members.len());
+        for member in self.members.iter_mut() {
+            infos.push(member.strategy.choose_move_with_info(state));
+        }
+        let winner = self.winner(infos.iter().map(|info| (info.best_move, info.value)));
+
+        let mut result = SearchInfo::default();
+        for info in infos.iter() {
+            result.nodes += info.nodes;
+            result.depth = result.depth.max(info.depth);
+            result.elapsed += info.elapsed;
+        }
+        // Report the details of the first strategy to choose the winning move.
+        if let Some(i) = winner {
+            result.best_move = infos[i].best_move;
+            result.value = infos[i].value;
+            result.win_probability = infos[i].win_probability;
+            result.principal_variation = infos[i].principal_variation.clone();
+        }
+        self.pv.clone_from(&result.principal_variation);
+        result
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_timeout(timeout.mul_f32(member.time_share));
+        }
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        for member in self.members.iter_mut() {
+            member.strategy.notify_move(state, m);
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.pv.clear();
+        for member in self.members.iter_mut() {
+            member.strategy.new_game();
+        }
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_win_probability_scale(scale);
+        }
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_max_nodes(nodes);
+        }
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_max_depth(depth);
+        }
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_stop_signal(signal.clone());
+        }
+    }
+
+    fn set_tablebase(&mut self, tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {
+        for member in self.members.iter_mut() {
+            member.strategy.set_tablebase(tablebase.clone());
+        }
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        for (i, member) in self.members.iter_mut().enumerate() {
+            member.strategy.set_rng_seed(seed.wrapping_add(i as u64));
+        }
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        self.pv.clone()
+    }
+
+    // Every strategy analyzes the position, and their best moves vote as in
+    // choose_move, for the lines of the first strategy to choose the winner.
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        let mut analyses = Vec::with_capacity(self.members.len());
+        for member in self.members.iter_mut() {
+            analyses.push(member.strategy.analyze(state, k));
+        }
+        let best = analyses
+            .iter()
+            .map(|lines| lines.first().map_or((None, None), |line| (Some(line.m), line.value)));
+        let winner = self.winner(best);
+        let lines = winner.map_or_else(Vec::new, |i| analyses.swap_remove(i));
+        self.pv = lines.first().map_or_else(Vec::new, |line| line.principal_variation.clone());
+        lines
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.members
+            .iter()
+            .map(|member| member.strategy.memory_usage())
+            .fold(MemoryUsage::default(), |a, b| a + b)
+    }
+}
@@ -0,0 +1,156 @@
+//! A strategy that tries several strategies in order, for assembling an
+//! engine out of parts.
+//!
+//! For example, an opening book, then a tablebase, then the main search, and
+//! finally a random mover in case everything else gives up.
+
+use super::super::interface::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Asks each strategy for a move in turn, and plays the first move offered.
+///
+/// Strategies that have nothing to offer for a position, like an opening
+/// book out of book, should return `None` from choose_move to pass to the
+/// next strategy.
+pub struct Fallback<G: Game> {
+    strategies: Vec<Box<dyn Strategy<G>>>,
+    // The strategy that chose the last move.
+    chosen: Option<usize>,
+}
+
+impl<G: Game> Fallback<G> {
+    pub fn new() -> Self {
+        Self { strategies: Vec::new(), chosen: None }
+    }
+
+    /// Add a strategy to try after all those added before it.
+    pub fn with_strategy(mut self, strategy: Box<dyn Strategy<G>>) -> Self {
+        self.strategies.push(strategy);
+        self
+    }
+
+    /// The position in the chain of the strategy that chose the last move.
+    pub fn chosen(&self) -> Option<usize> {
+        self.chosen
+    }
+}
+
+impl<G: Game> Default for Fallback<G> {
+    fn default() -> Self {
+        Fallback::new()
+    }
+}
+
+impl<G: Game> Strategy<G> for Fallback<G> {
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        self.choose_move_with_info(state).best_move
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        self.chosen = None;
+        let mut nodes = 0;
+        let mut elapsed = Duration::default();
+        for (i, strategy) in self.strategies.iter_mut().enumerate() {
+            let mut info = strategy.choose_move_with_info(state);
+            nodes += info.nodes;
+            elapsed += info.elapsed;
+            if info.best_move.is_some() {
+                self.chosen = Some(i);
+                info.nodes = nodes;
+                info.elapsed = elapsed;
+                return info;
+            }
+        }
+        SearchInfo { nodes, elapsed, ..SearchInfo::default() }
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_timeout(timeout);
+        }
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.notify_move(state, m);
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.chosen = None;
+        for strategy in self.strategies.iter_mut() {
+            strategy.new_game();
+        }
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_win_probability_scale(scale);
+        }
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_max_nodes(nodes);
+        }
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_max_depth(depth);
+        }
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_stop_signal(signal.clone());
+        }
+    }
+
+    fn set_tablebase(&mut self, tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_tablebase(tablebase.clone());
+        }
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        for (i, strategy) in self.strategies.iter_mut().enumerate() {
+            strategy.set_rng_seed(seed.wrapping_add(i as u64));
+        }
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        for strategy in self.strategies.iter_mut() {
+            strategy.set_time_left(time);
+        }
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        match self.chosen {
+            Some(i) => self.strategies[i].principal_variation(),
+            None => Vec::new(),
+        }
+    }
+
+    // The first strategy with any lines for the position analyzes it.
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        self.chosen = None;
+        for (i, strategy) in self.strategies.iter_mut().enumerate() {
+            let lines = strategy.analyze(state, k);
+            if !lines.is_empty() {
+                self.chosen = Some(i);
+                return lines;
+            }
+        }
+        Vec::new()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.strategies
+            .iter()
+            .map(|strategy| strategy.memory_usage())
+            .fold(MemoryUsage::default(), |a, b| a + b)
+    }
+}
@@ -0,0 +1,172 @@
+//! A strategy that recommends resigning and agreeing to draws, for match
+//! runners and servers that support them.
+//!
+//! Judgements are made from `SearchInfo::win_probability`, so they mean the
+//! same for every strategy. Alpha-beta strategies only report it when given
+//! `Strategy::set_win_probability_scale`.
+
+use super::super::interface::*;
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When `FlowStrategy` recommends resigning or a draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlowOptions {
+    resign: Option<(f32, u32)>,
+    draw: Option<(f32, u32)>,
+}
+
+impl FlowOptions {
+    /// Never recommend anything, until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resign once the chance of winning has been below `win_probability`
+    /// for `moves` searches in a row.
+    pub fn with_resignation(mut self, win_probability: f32, moves: u32) -> Self {
+        self.resign = Some((win_probability, moves.max(1)));
+        self
+    }
+
+    /// Offer a draw once the chance of winning has been within `margin` of
+    /// even for `moves` searches in a row, and accept one whenever it's no
+    /// more than `margin` above even.
+    pub fn with_draw_offers(mut self, margin: f32, moves: u32) -> Self {
+        self.draw = Some((margin, moves.max(1)));
+        self
+    }
+}
+
+/// Fills in `SearchInfo::recommendation` for the wrapped strategy's
+/// searches, as configured by `FlowOptions`.
+pub struct FlowStrategy<G: Game, S> {
+    inner: S,
+    options: FlowOptions,
+    // Searches in a row that were losing badly enough to resign.
+    losing: u32,
+    // Searches in a row that were even enough to offer a draw.
+    even: u32,
+    game_type: PhantomData<G>,
+}
+
+impl<G: Game, S: Strategy<G>> FlowStrategy<G, S> {
+    pub fn new(inner: S, options: FlowOptions) -> Self {
+        Self { inner, options, losing: 0, even: 0, game_type: PhantomData }
+    }
+
+    /// Return the wrapped strategy.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn recommend(&mut self, win_probability: Option<f32>) -> Option<Recommendation> {
+        let Some(p) = win_probability else {
+            self.losing = 0;
+            self.even = 0;
+            return None;
+        };
+        let mut recommendation = None;
+        if let Some((margin, moves)) = self.options.draw {
+            if p <= 0.5 + margin {
+                recommendation = Some(Recommendation::AcceptDraw);
+            }
+            self.even = if (p - 0.5).abs() <= margin { self.even + 1 } else { 0 };
+            if self.even >= moves {
+                recommendation = Some(Recommendation::OfferDraw);
+            }
+        }
+        if let Some((threshold, moves)) = self.options.resign {
+            self.losing = if p < threshold { self.losing + 1 } else { 0 };
+            if self.losing >= moves {
+                recommendation = Some(Recommendation::Resign);
+            }
+        }
+        recommendation
+    }
+}
+
+impl<G: Game, S: FreshCopy> FreshCopy for FlowStrategy<G, S> {
+    fn fresh_copy(&self) -> Self {
+        Self {
+            inner: self.inner.fresh_copy(),
+            options: self.options,
+            losing: 0,
+            even: 0,
+            game_type: PhantomData,
+        }
+    }
+}
+
+impl<G: Game, S: Strategy<G>> Strategy<G> for FlowStrategy<G, S> {
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        self.choose_move_with_info(state).best_move
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> SearchInfo<G::M> {
+        let mut info = self.inner.choose_move_with_info(state);
+        info.recommendation = self.recommend(info.win_probability);
+        info
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.inner.set_timeout(timeout);
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.inner.set_time_left(time);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.inner.set_max_nodes(nodes);
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        self.inner.set_max_depth(depth);
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.inner.set_stop_signal(signal);
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<G::M>>) {
+        self.inner.set_progress(progress);
+    }
+
+    fn set_tablebase(&mut self, tablebase: Arc<dyn Tablebase<G = G> + Send + Sync>) {
+        self.inner.set_tablebase(tablebase);
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        self.inner.notify_move(state, m);
+    }
+
+    fn new_game(&mut self) {
+        self.losing = 0;
+        self.even = 0;
+        self.inner.new_game();
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.inner.set_win_probability_scale(scale);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.inner.set_rng_seed(seed);
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        self.inner.principal_variation()
+    }
+
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        self.inner.analyze(state, k)
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.inner.memory_usage()
+    }
+}
@@ -0,0 +1,79 @@
+//! A strategy that asks a person for each move, for playing against engines
+//! by hand.
+
+use super::super::interface::*;
+
+use std::io::{self, BufRead, Write};
+use std::marker::PhantomData;
+
+/// Prompts for moves, by default on stdin and stdout.
+///
+/// Moves can be entered by their `Game::notation`, or by their number in the
+/// list of legal moves that is printed with each prompt. End of input
+/// returns no move.
+pub struct HumanStrategy<G> {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    game_type: PhantomData<G>,
+}
+
+impl<G: Game> HumanStrategy<G> {
+    pub fn new() -> Self {
+        Self::with_io(Box::new(io::BufReader::new(io::stdin())), Box::new(io::stdout()))
+    }
+
+    /// Read moves from `input` and write prompts to `output`.
+    pub fn with_io(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Self { input, output, game_type: PhantomData }
+    }
+}
+
+impl<G: Game> Default for HumanStrategy<G> {
+    fn default() -> Self {
+        HumanStrategy::new()
+    }
+}
+
+impl<G: Game> Strategy<G> for HumanStrategy<G>
+where
+    G::M: Copy,
+{
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        let mut moves = Vec::new();
+        G::generate_moves(state, &mut moves);
+        if moves.is_empty() {
+            return None;
+        }
+        let names = moves
+            .iter()
+            .enumerate()
+            .map(|(i, &m)| G::notation(state, m).unwrap_or_else(|| (i + 1).to_string()))
+            .collect::<Vec<_>>();
+
+        loop {
+            let mut prompt = String::from("Moves:");
+            for (i, name) in names.iter().enumerate() {
+                prompt.push_str(&format!(" {}) {}", i + 1, name));
+            }
+            // Failures to write the prompt shouldn't stop the game.
+            let _ = write!(self.output, "{}\nYour move: ", prompt);
+            let _ = self.output.flush();
+
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => {}
+            }
+            let line = line.trim();
+            if let Some(i) = names.iter().position(|name| name.eq_ignore_ascii_case(line)) {
+                return Some(moves[i]);
+            }
+            if let Ok(n) = line.parse::<usize>() {
+                if n >= 1 && n <= moves.len() {
+                    return Some(moves[n - 1]);
+                }
+            }
+            let _ = writeln!(self.output, "Unrecognized move: {}", line);
+        }
+    }
+}
@@ -5,14 +5,21 @@
 //! a transposition table to reuse information from previous iterations.
 
 use super::super::interface::*;
+use super::super::json;
+use super::super::time::Instant;
 use super::super::util::*;
 #[cfg(not(target_arch = "wasm32"))]
+use super::background::{BackgroundSearch, Finished};
+#[cfg(not(target_arch = "wasm32"))]
 use super::sync_util::timeout_signal;
+pub use super::table::HistoryOptions;
 use super::table::*;
+use super::trace::{SearchTrace, TraceKind, Tracer};
 use super::util::*;
 
-use instant::Instant;
 use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::cmp::max;
 #[cfg(not(target_arch = "wasm32"))]
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -20,8 +27,12 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+// Root moves to list in a snapshot.
+const SNAPSHOT_MOVES: usize = 10;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 /// Strategies for when to overwrite entries in the transition table.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Replacement {
     Always,
     DepthPreferred,
@@ -113,23 +124,47 @@ impl<M: Copy> Table<M> for TranspositionTable<M> {
     fn advance_generation(&mut self) {
         self.generation = self.generation.wrapping_add(1);
     }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let entries = self.table.iter().filter(|entry| entry.best_move.is_some()).count();
+        table_usage::<Entry<M>>(entries, self.table.len())
+    }
+}
+
+// The settings of multi-cut pruning.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(super) struct MultiCut {
+    pub(super) moves: u8,
+    pub(super) cuts: u8,
+    pub(super) depth_reduction: u8,
 }
 
 /// Options to use for the iterative search engines.
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IterativeOptions {
     pub table_byte_size: usize,
     pub(super) strategy: Replacement,
     pub(super) null_window_search: bool,
     pub(super) null_move_depth: Option<u8>,
+    pub(super) multi_cut: Option<MultiCut>,
     pub(super) singular_extension: bool,
     pub(super) aspiration_window: Option<Evaluation>,
     pub(super) mtdf: bool,
     pub(super) step_increment: u8,
     pub(super) max_quiescence_depth: u8,
+    pub(super) delta_margin: Option<Evaluation>,
+    pub(super) unstable_extension: u8,
+    pub(super) plausible_moves_ply: Option<u8>,
     pub(super) min_reorder_moves_depth: u8,
     pub(super) countermove_table: bool,
     pub(super) countermove_history_table: bool,
+    pub(super) history: HistoryOptions,
+    pub(super) batched_leaves: bool,
+    pub(super) staged_move_generation: bool,
+    pub(super) fail_soft: bool,
+    pub(super) time_allocator: TimeAllocator,
     pub verbose: bool,
 }
 
@@ -140,19 +175,59 @@ impl IterativeOptions {
             strategy: Replacement::TwoTier,
             null_window_search: true,
             null_move_depth: None,
+            multi_cut: None,
             singular_extension: false,
             aspiration_window: None,
             mtdf: false,
             step_increment: 1,
             max_quiescence_depth: 0,
+            delta_margin: None,
+            unstable_extension: 0,
+            plausible_moves_ply: None,
             min_reorder_moves_depth: u8::MAX,
             countermove_table: false,
             countermove_history_table: false,
+            history: HistoryOptions::new(),
+            batched_leaves: false,
+            staged_move_generation: false,
+            fail_soft: true,
+            time_allocator: TimeAllocator::new(),
             verbose: false,
         }
     }
 }
 
+impl IterativeOptions {
+    // MTD(f) only converges quickly on fail-soft values.
+    pub(super) fn fails_soft(&self) -> bool {
+        self.fail_soft || self.mtdf
+    }
+
+    // Batches skip evaluating the leaves one at a time.
+    pub(super) fn batches_leaves(&self) -> bool {
+        self.batched_leaves && self.max_quiescence_depth == 0 && self.unstable_extension == 0
+    }
+
+    // Whether only the plausible moves are searched at `ply`.
+    pub(super) fn plausible_only(&self, ply: u8) -> bool {
+        self.plausible_moves_ply.is_some_and(|from| ply >= from)
+    }
+
+    // Generate the moves to search at `ply`, using `priorities` as scratch
+    // space.
+    pub(super) fn generate_moves<G: Game>(
+        &self, s: &G::S, ply: u8, moves: &mut Vec<G::M>, priorities: &mut Vec<i16>,
+    ) {
+        if self.plausible_only(ply) {
+            G::generate_plausible_moves(s, moves);
+            if !moves.is_empty() {
+                return;
+            }
+        }
+        generate_prioritized_moves::<G>(s, moves, priorities);
+    }
+}
+
 impl Default for IterativeOptions {
     fn default() -> Self {
         Self::new()
@@ -189,6 +264,15 @@ impl IterativeOptions {
         self
     }
 
+    /// Whether to try multi-cut pruning at null-window nodes, where a cutoff
+    /// is expected: search the first `moves` moves at a depth reduced by
+    /// `depth_reduction`, and cut off the node if `cuts` of them are still
+    /// at least beta. Ignored with staged move generation.
+    pub fn with_multi_cut(mut self, moves: u8, cuts: u8, depth_reduction: u8) -> Self {
+        self.multi_cut = Some(MultiCut { moves, cuts, depth_reduction });
+        self
+    }
+
     /// Whether to extend a branch of the search (by 1) if there is only one
     /// move (or only one reasonable move).
     pub fn with_singular_extension(mut self) -> Self {
@@ -227,6 +311,33 @@ impl IterativeOptions {
         self
     }
 
+    /// Enable delta pruning in quiescence search: skip each noisy move whose
+    /// `Evaluator::noisy_move_gain`, plus this margin, can't raise the
+    /// static evaluation above alpha. A larger margin prunes less, but is
+    /// safer against positional gains the bound doesn't cover.
+    pub fn with_delta_pruning(mut self, margin: Evaluation) -> Self {
+        self.delta_margin = Some(margin);
+        self
+    }
+
+    /// Extend the search by up to this many plies past the horizon while
+    /// `Evaluator::is_unstable` flags the position, searching every move
+    /// instead of standing pat on the evaluation.
+    pub fn with_unstable_extension(mut self, plies: u8) -> Self {
+        self.unstable_extension = plies;
+        self
+    }
+
+    /// Search only `Game::generate_plausible_moves` at nodes this many plies
+    /// or more below the root, which is always searched in full. This
+    /// forward pruning can miss the best move, but makes deep searches
+    /// possible in games with hundreds of moves. Moves aren't staged at
+    /// those nodes.
+    pub fn with_plausible_moves(mut self, from_ply: u8) -> Self {
+        self.plausible_moves_ply = Some(from_ply.max(1));
+        self
+    }
+
     /// Enable the Evaluator's move reordering after generating moves for all
     /// nodes at this depth or higher. Reordering can be an expensive
     /// operation, but it could cut off a lot of nodes if done well high in
@@ -243,14 +354,62 @@ impl IterativeOptions {
         self
     }
 
-    /// Enable the countermove history table. It keeps a counter for moves
-    /// that have caused beta cutoffs in other branches, and reorders moves
-    /// based on this counter.
+    /// Enable the countermove history table. It keeps a score for moves,
+    /// raised when they cause beta cutoffs in other branches and lowered when
+    /// they don't, and reorders moves based on this score.
     pub fn with_countermove_history(mut self) -> Self {
         self.countermove_history_table = true;
         self
     }
 
+    /// Tune the updates and aging of the countermove history table.
+    pub fn with_history_options(mut self, history: HistoryOptions) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Evaluate the leaves below each node at depth one in a single call to
+    /// `Evaluator::evaluate_batch`, for evaluators that are faster in
+    /// batches. Leaves are no longer pruned individually, so this is slower
+    /// with ordinary evaluators, and batched leaves aren't passed to
+    /// `Evaluator::evaluate_in_context`. Ignored when quiescence search or
+    /// unstable extension is enabled.
+    pub fn with_batched_leaves(mut self) -> Self {
+        self.batched_leaves = true;
+        self
+    }
+
+    /// Whether nodes return values outside the window they were searched
+    /// with (fail-soft), or clamp them to the window (fail-hard). Fail-soft
+    /// stores tighter bounds in the transposition table and tells aspiration
+    /// and MTD(f) searches how far they missed, but fail-hard values may be
+    /// what older code expects. Defaults to fail-soft, and MTD(f) always
+    /// fails soft.
+    pub fn with_fail_soft(mut self, soft: bool) -> Self {
+        self.fail_soft = soft;
+        self
+    }
+
+    /// Generate the moves of each node in stages: the transposition table
+    /// move, then `Evaluator::generate_noisy_moves`, then
+    /// `Evaluator::generate_quiet_moves`, so that a cutoff early in a node
+    /// skips generating the rest. Ignored with singular extension and at
+    /// batched leaves, which need every move up front. The table move is
+    /// checked with `Game::is_legal`, which games should implement cheaply
+    /// for this. Only used by `IterativeSearch`.
+    pub fn with_staged_move_generation(mut self) -> Self {
+        self.staged_move_generation = true;
+        self
+    }
+
+    /// Budget each move with this allocator when told the time left on the
+    /// clock with `Strategy::set_time_left`. Each search stops deepening
+    /// after the soft budget, and abandons an iteration at the hard budget.
+    pub fn with_time_allocator(mut self, allocator: TimeAllocator) -> Self {
+        self.time_allocator = allocator;
+        self
+    }
+
     /// Enable verbose print statements of the ongoing performance of the search.
     pub fn verbose(mut self) -> Self {
         self.verbose = true;
@@ -263,6 +422,9 @@ pub(crate) struct Stats {
     pub(crate) nodes_explored: u64,
     pub(crate) total_generate_move_calls: u64,
     pub(crate) total_generated_moves: u64,
+    pub(crate) table_probes: u64,
+    // Probes that found an entry for the position.
+    pub(crate) table_hits: u64,
 }
 
 impl Stats {
@@ -270,6 +432,8 @@ impl Stats {
         self.nodes_explored = 0;
         self.total_generate_move_calls = 0;
         self.total_generated_moves = 0;
+        self.table_probes = 0;
+        self.table_hits = 0;
     }
     pub(crate) fn explore_node(&mut self) {
         self.nodes_explored += 1;
@@ -280,11 +444,22 @@ impl Stats {
         self.total_generated_moves += num_moves as u64;
     }
 
+    pub(crate) fn probe_table(&mut self, hit: bool) {
+        self.table_probes += 1;
+        self.table_hits += hit as u64;
+    }
+
+    pub(crate) fn table_hit_rate(&self) -> f64 {
+        self.table_hits as f64 / self.table_probes.max(1) as f64
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub(crate) fn add(&mut self, other: &Self) {
         self.nodes_explored += other.nodes_explored;
         self.total_generate_move_calls += other.total_generate_move_calls;
         self.total_generated_moves += other.total_generated_moves;
+        self.table_probes += other.table_probes;
+        self.table_hits += other.table_hits;
     }
 }
 
@@ -292,13 +467,25 @@ pub(super) struct Negamaxer<E: Evaluator, T> {
     #[cfg(not(target_arch = "wasm32"))]
     timeout: Arc<AtomicBool>,
     #[cfg(target_arch = "wasm32")]
-    deadline: Instant,
+    deadline: Option<Instant>,
     #[cfg(target_arch = "wasm32")]
     timeout_counter: u32,
+    pub(super) stop: StopSignal,
     pub(super) table: T,
     pub(super) countermoves: CounterMoves<E::G>,
     move_pool: MovePool<<E::G as Game>::M>,
+    priorities: Vec<i16>,
+    leaf_batch: LeafBatch<<E::G as Game>::S>,
+    pub(super) tablebase: Option<SharedTablebase<E::G>>,
     eval: E,
+    pub(super) tracer: Option<Tracer<<E::G as Game>::M>>,
+    // Stop after searching this many nodes, if nonzero.
+    pub(super) max_nodes: u64,
+    nodes: u64,
+    // The number of root moves to find exact values for.
+    pub(super) multi_pv: usize,
+    // Moves played before the root.
+    pub(super) root_ply: u32,
 
     opts: IterativeOptions,
     pub(crate) stats: Stats,
@@ -306,6 +493,7 @@ pub(super) struct Negamaxer<E: Evaluator, T> {
 
 impl<E: Evaluator, T: Table<<E::G as Game>::M>> Negamaxer<E, T>
 where
+    <E::G as Game>::S: Clone,
     <E::G as Game>::M: Copy + Eq,
 {
     pub(super) fn new(table: T, eval: E, opts: IterativeOptions) -> Self {
@@ -313,13 +501,26 @@ where
             #[cfg(not(target_arch = "wasm32"))]
             timeout: Arc::new(AtomicBool::new(false)),
             #[cfg(target_arch = "wasm32")]
-            deadline: Instant::now(),
+            deadline: None,
             #[cfg(target_arch = "wasm32")]
-            timeout_counter: 1000,
+            timeout_counter: 0,
+            stop: StopSignal::new(),
             table,
-            countermoves: CounterMoves::new(opts.countermove_table, opts.countermove_history_table),
+            countermoves: CounterMoves::new(
+                opts.countermove_table,
+                opts.countermove_history_table,
+                opts.history,
+            ),
             eval,
             move_pool: MovePool::default(),
+            priorities: Vec::new(),
+            leaf_batch: LeafBatch::default(),
+            tablebase: None,
+            tracer: None,
+            max_nodes: 0,
+            nodes: 0,
+            multi_pv: 1,
+            root_ply: 0,
             opts,
             stats: Stats::default(),
         }
@@ -332,13 +533,9 @@ where
 
     #[cfg(target_arch = "wasm32")]
     fn reset_timeout(&mut self, duration: Duration) {
-        self.timeout_counter = if duration == Duration::new(0, 0) {
-            // Too high counter that never hits the maximum.
-            1000
-        } else {
-            0
-        };
-        self.deadline = Instant::now() + duration;
+        self.timeout_counter = 0;
+        self.deadline =
+            if duration == Duration::new(0, 0) { None } else { Some(Instant::now() + duration) };
     }
     #[cfg(not(target_arch = "wasm32"))]
     fn reset_timeout(&mut self, duration: Duration) {
@@ -351,20 +548,27 @@ where
 
     #[cfg(target_arch = "wasm32")]
     fn timeout_check(&mut self) -> bool {
+        self.nodes += 1;
+        if self.max_nodes != 0 && self.nodes > self.max_nodes {
+            return true;
+        }
         self.timeout_counter += 1;
         if self.timeout_counter != 100 {
             return false;
         }
         self.timeout_counter = 0;
-        Instant::now() >= self.deadline
+        self.stop.is_stopped() || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
     }
     #[cfg(not(target_arch = "wasm32"))]
     fn timeout_check(&mut self) -> bool {
-        self.timeout.load(Ordering::Relaxed)
+        self.nodes += 1;
+        (self.max_nodes != 0 && self.nodes > self.max_nodes)
+            || self.timeout.load(Ordering::Relaxed)
+            || self.stop.is_stopped()
     }
 
     fn null_move_check(
-        &mut self, s: &mut <E::G as Game>::S, depth: u8, beta: Evaluation,
+        &mut self, s: &mut <E::G as Game>::S, hash: u64, ply: u8, depth: u8, beta: Evaluation,
     ) -> Option<Evaluation> {
         if let (Some(depth_reduction), Some(null_move)) =
             (self.opts.null_move_depth, E::G::null_move(s))
@@ -372,12 +576,20 @@ where
             // Default to a minimum of depth=1 after null moving.
             if depth > depth_reduction &&
 	    // If the position already seems pretty awesome.
-	      self.eval.evaluate(s) >= beta
+	      evaluate(&self.eval, s, self.root_ply, ply, depth) >= beta
             {
                 // If we just pass and let the opponent play this position (at reduced depth),
+                let nulled_hash = E::G::zobrist_hash_after(hash, s, null_move);
                 let mut nulled = AppliedMove::<E::G>::new(s, null_move);
-                let value =
-                    -self.negamax(&mut nulled, None, depth - depth_reduction, -beta, -beta + 1)?;
+                let value = -self.negamax(
+                    &mut nulled,
+                    nulled_hash,
+                    None,
+                    ply.saturating_add(1),
+                    depth - depth_reduction,
+                    -beta,
+                    -beta + 1,
+                )?;
                 // is the result still so good that we shouldn't bother with a full search?
                 if value >= beta {
                     return Some(value);
@@ -388,9 +600,53 @@ where
         Some(WORST_EVAL)
     }
 
-    // Negamax only among noisy moves.
+    #[allow(clippy::too_many_arguments)]
+    fn multi_cut_check(
+        &mut self, s: &mut <E::G as Game>::S, hash: u64, ply: u8, depth: u8, alpha: Evaluation,
+        beta: Evaluation, moves: &[<E::G as Game>::M],
+    ) -> Option<Evaluation> {
+        if let Some(cut) = self.opts.multi_cut {
+            // Only expect a cutoff at null-window nodes, and default to a
+            // minimum of depth=1 after the reduction.
+            if alpha + 1 == beta && depth > cut.depth_reduction && cut.cuts > 0 {
+                let player = E::G::player_to_move(s);
+                let mut cuts = 0;
+                for (i, &m) in moves.iter().take(cut.moves as usize).enumerate() {
+                    // Give up once too few moves are left to reach the cuts.
+                    if cut.moves as usize - i < (cut.cuts - cuts) as usize {
+                        break;
+                    }
+                    let new_hash = E::G::zobrist_hash_after(hash, s, m);
+                    let mut new = AppliedMove::<E::G>::new(s, m);
+                    let turn = Turn::after::<E::G>(player, &new);
+                    let (a, b) = turn.window(alpha, beta);
+                    let value = turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - cut.depth_reduction,
+                        a,
+                        b,
+                    )?);
+                    if value >= beta {
+                        cuts += 1;
+                        if cuts == cut.cuts {
+                            return Some(beta);
+                        }
+                    }
+                }
+            }
+        }
+        // If we didn't cut, return a low value that won't trigger beta cutoff.
+        Some(WORST_EVAL)
+    }
+
+    // Negamax only among noisy moves, or among all moves for up to
+    // `extension` plies while the position is unstable.
     fn noisy_negamax(
-        &mut self, s: &mut <E::G as Game>::S, depth: u8, mut alpha: Evaluation, beta: Evaluation,
+        &mut self, s: &mut <E::G as Game>::S, ply: u8, depth: u8, extension: u8,
+        mut alpha: Evaluation, beta: Evaluation,
     ) -> Option<Evaluation> {
         if self.timeout_check() {
             return None;
@@ -398,21 +654,76 @@ where
         if let Some(winner) = E::G::get_winner(s) {
             return Some(winner.evaluate());
         }
+        if let Some(value) = probe_tablebase(&self.tablebase, s) {
+            return Some(value);
+        }
+        let player = E::G::player_to_move(s);
+        if extension > 0 && self.eval.is_unstable(s) {
+            let mut moves = self.move_pool.alloc();
+            E::G::generate_moves(s, &mut moves);
+            let mut best = WORST_EVAL;
+            for m in moves.iter() {
+                let mut new = AppliedMove::<E::G>::new(s, *m);
+                let turn = Turn::after::<E::G>(player, &new);
+                let (a, b) = turn.window(alpha, beta);
+                let value = turn.value(self.noisy_negamax(
+                    &mut new,
+                    ply.saturating_add(1),
+                    depth,
+                    extension - 1,
+                    a,
+                    b,
+                )?);
+                best = max(best, value);
+                alpha = max(alpha, value);
+                if alpha >= beta {
+                    break;
+                }
+            }
+            let stuck = moves.is_empty();
+            self.move_pool.free(moves);
+            if !stuck {
+                return Some(best);
+            }
+        }
         if depth == 0 {
-            return Some(self.eval.evaluate(s));
+            return Some(evaluate(&self.eval, s, self.root_ply, ply, depth));
         }
 
         let mut moves = self.move_pool.alloc();
         self.eval.generate_noisy_moves(s, &mut moves);
         if moves.is_empty() {
             self.move_pool.free(moves);
-            return Some(self.eval.evaluate(s));
+            return Some(evaluate(&self.eval, s, self.root_ply, ply, depth));
         }
 
+        // With delta pruning, skip moves that can't raise alpha, even with
+        // the margin.
+        let delta_base = self.opts.delta_margin.map(|margin| {
+            evaluate(&self.eval, s, self.root_ply, ply, depth).saturating_add(margin)
+        });
         let mut best = WORST_EVAL;
         for m in moves.iter() {
+            if let Some(bound) = delta_base
+                .zip(self.eval.noisy_move_gain(s, *m))
+                .map(|(base, gain)| base.saturating_add(gain))
+            {
+                if bound <= alpha {
+                    best = max(best, bound);
+                    continue;
+                }
+            }
             let mut new = AppliedMove::<E::G>::new(s, *m);
-            let value = -self.noisy_negamax(&mut new, depth - 1, -beta, -alpha)?;
+            let turn = Turn::after::<E::G>(player, &new);
+            let (a, b) = turn.window(alpha, beta);
+            let value = turn.value(self.noisy_negamax(
+                &mut new,
+                ply.saturating_add(1),
+                depth - 1,
+                extension,
+                a,
+                b,
+            )?);
             best = max(best, value);
             alpha = max(alpha, value);
             if alpha >= beta {
@@ -423,78 +734,182 @@ where
         Some(best)
     }
 
-    // Recursively compute negamax on the game state. Returns None if it hits the timeout.
+    // Recursively compute negamax on the game state, whose hash is computed
+    // only if it isn't given. Returns None if it hits the timeout.
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn negamax(
-        &mut self, s: &mut <E::G as Game>::S, prev_move: Option<<E::G as Game>::M>, mut depth: u8,
-        mut alpha: Evaluation, mut beta: Evaluation,
+        &mut self, s: &mut <E::G as Game>::S, hash: Option<u64>,
+        prev_move: Option<<E::G as Game>::M>, ply: u8, mut depth: u8, mut alpha: Evaluation,
+        mut beta: Evaluation,
     ) -> Option<Evaluation> {
         if self.timeout_check() {
             return None;
         }
 
         self.stats.explore_node();
+        let traced = self.tracer.as_mut().is_some_and(|tracer| tracer.enter(ply, prev_move));
+        let window = (alpha, beta);
 
         if depth == 0 {
             // Evaluate quiescence search on leaf nodes.
             // Will just return the node's evaluation if quiescence search is disabled.
-            return self.noisy_negamax(s, self.opts.max_quiescence_depth, alpha, beta);
+            let value = self.noisy_negamax(
+                s,
+                ply,
+                self.opts.max_quiescence_depth,
+                self.opts.unstable_extension,
+                alpha,
+                beta,
+            )?;
+            if traced {
+                self.trace(ply, depth, window, TraceKind::Leaf { value });
+            }
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
         if let Some(winner) = E::G::get_winner(s) {
-            return Some(winner.evaluate());
+            return Some(fail(winner.evaluate(), window, self.opts.fails_soft()));
+        }
+        if let Some(value) = probe_tablebase(&self.tablebase, s) {
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
 
         let alpha_orig = alpha;
-        let hash = E::G::zobrist_hash(s);
+        let hash = hash.unwrap_or_else(|| E::G::zobrist_hash(s));
         let mut good_move = None;
-        if let Some(value) = self.table.check(hash, depth, &mut good_move, &mut alpha, &mut beta) {
-            return Some(value);
+        let table_value = self.table.check(hash, depth, &mut good_move, &mut alpha, &mut beta);
+        self.stats.probe_table(table_value.is_some() || good_move.is_some());
+        if let Some(value) = table_value {
+            if traced {
+                self.trace(ply, depth, window, TraceKind::TableHit { value });
+            }
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
 
-        if self.null_move_check(s, depth, beta)? >= beta {
-            return Some(beta);
+        let null_value = self.null_move_check(s, hash, ply, depth, beta)?;
+        if null_value >= beta {
+            if traced {
+                self.trace(ply, depth, window, TraceKind::NullMoveCutoff { value: null_value });
+            }
+            return Some(fail(null_value, window, self.opts.fails_soft()));
         }
 
-        let mut moves = self.move_pool.alloc();
-        E::G::generate_moves(s, &mut moves);
-        self.stats.generate_moves(moves.len());
-        if moves.is_empty() {
-            self.move_pool.free(moves);
-            return Some(WORST_EVAL);
-        }
+        let batched = depth == 1 && self.opts.batches_leaves();
+        // Singular extension and batched leaves need every move up front.
+        let staged = self.opts.staged_move_generation
+            && !self.opts.singular_extension
+            && !batched
+            && !self.opts.plausible_only(ply);
+        let mut moves = if staged {
+            StagedMoves::staged::<E::G>(self.move_pool.alloc(), good_move, s)
+        } else {
+            let mut moves = self.move_pool.alloc();
+            self.opts.generate_moves::<E::G>(s, ply, &mut moves, &mut self.priorities);
+            self.stats.generate_moves(moves.len());
+            if moves.is_empty() {
+                self.move_pool.free(moves);
+                return Some(WORST_EVAL);
+            }
 
-        // TODO: Also do a pre-search to look for moves much better than others.
-        if self.opts.singular_extension && moves.len() == 1 {
-            depth += 1;
-        }
+            // TODO: Also do a pre-search to look for moves much better than others.
+            if self.opts.singular_extension && moves.len() == 1 {
+                depth = depth.saturating_add(1);
+            }
 
-        // Reorder moves.
-        if depth >= self.opts.min_reorder_moves_depth {
-            // TODO reorder moves
-        }
-        self.countermoves.reorder(prev_move, &mut moves);
-        if let Some(good) = good_move {
-            move_to_front(good, &mut moves);
-        }
+            // Reorder moves.
+            if depth >= self.opts.min_reorder_moves_depth {
+                // TODO reorder moves
+            }
+            self.countermoves.reorder(prev_move, &mut moves);
+            if let Some(good) = good_move {
+                move_to_front(good, &mut moves);
+            }
+
+            let cut_value = self.multi_cut_check(s, hash, ply, depth, alpha, beta, &moves)?;
+            if cut_value >= beta {
+                if traced {
+                    self.trace(ply, depth, window, TraceKind::MultiCut { value: cut_value });
+                }
+                self.move_pool.free(moves);
+                return Some(fail(cut_value, window, self.opts.fails_soft()));
+            }
+
+            if batched {
+                let (best, i) = self.leaf_batch.search(
+                    &self.eval,
+                    &self.tablebase,
+                    s,
+                    self.root_ply + ply as u32,
+                    &moves,
+                );
+                for _ in 0..moves.len() {
+                    self.stats.explore_node();
+                }
+                if best >= beta {
+                    self.countermoves.update(prev_move, moves[i], depth, &moves[..i]);
+                }
+                self.table.update(hash, alpha_orig, beta, depth, best, moves[i]);
+                self.move_pool.free(moves);
+                return Some(fail(clamp_value(best), window, self.opts.fails_soft()));
+            }
+            StagedMoves::generated(moves)
+        };
 
+        let player = E::G::player_to_move(s);
         let mut best = WORST_EVAL;
-        let mut best_move = moves[0];
+        let mut best_move = None;
         let mut null_window = false;
-        for &m in moves.iter() {
+        let mut searched = 0;
+        while let Some(m) =
+            moves.next(&self.eval, s, |stage| self.countermoves.reorder(prev_move, stage))
+        {
+            searched += 1;
+            let new_hash = E::G::zobrist_hash_after(hash, s, m);
             let mut new = AppliedMove::<E::G>::new(s, m);
+            let turn = Turn::after::<E::G>(player, &new);
             let value = if null_window {
-                let probe = -self.negamax(&mut new, Some(m), depth - 1, -alpha - 1, -alpha)?;
+                let (a, b) = turn.window(alpha, alpha + 1);
+                let probe = turn.value(self.negamax(
+                    &mut new,
+                    new_hash,
+                    Some(m),
+                    ply.saturating_add(1),
+                    depth - 1,
+                    a,
+                    b,
+                )?);
                 if probe > alpha && probe < beta {
+                    if traced {
+                        self.trace(ply, depth, window, TraceKind::ReSearch { m, probe });
+                    }
                     // Full search fallback.
-                    -self.negamax(&mut new, Some(m), depth - 1, -beta, -probe)?
+                    let (a, b) = turn.window(probe, beta);
+                    turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - 1,
+                        a,
+                        b,
+                    )?)
                 } else {
                     probe
                 }
             } else {
-                -self.negamax(&mut new, Some(m), depth - 1, -beta, -alpha)?
+                let (a, b) = turn.window(alpha, beta);
+                turn.value(self.negamax(
+                    &mut new,
+                    new_hash,
+                    Some(m),
+                    ply.saturating_add(1),
+                    depth - 1,
+                    a,
+                    b,
+                )?)
             };
-            if value > best {
+            if value > best || best_move.is_none() {
                 best = value;
-                best_move = m;
+                best_move = Some(m);
             }
             if value > alpha {
                 alpha = value;
@@ -503,14 +918,37 @@ where
                 null_window = self.opts.null_window_search;
             }
             if alpha >= beta {
-                self.countermoves.update(prev_move, m);
+                if traced {
+                    self.trace(ply, depth, window, TraceKind::BetaCutoff { m, value, searched });
+                }
+                self.countermoves.update(prev_move, m, depth, moves.searched());
                 break;
             }
         }
 
-        self.table.update(hash, alpha_orig, beta, depth, best, best_move);
+        let moves = moves.into_inner();
+        if staged {
+            self.stats.generate_moves(moves.len());
+        }
         self.move_pool.free(moves);
-        Some(clamp_value(best))
+        // Staged generation only finds out here that there are no moves.
+        let Some(best_move) = best_move else {
+            return Some(WORST_EVAL);
+        };
+        if traced && best < beta {
+            self.trace(ply, depth, window, TraceKind::Searched { best_move, value: best });
+        }
+        self.table.update(hash, alpha_orig, beta, depth, best, best_move);
+        Some(fail(clamp_value(best), window, self.opts.fails_soft()))
+    }
+
+    fn trace(
+        &mut self, ply: u8, depth: u8, window: (Evaluation, Evaluation),
+        kind: TraceKind<<E::G as Game>::M>,
+    ) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.record(ply, depth, window, kind);
+        }
     }
 
     // Try to find the value within a window around the estimated value.
@@ -524,31 +962,85 @@ where
         }
         let alpha = max(target.saturating_sub(window), WORST_EVAL);
         let beta = target.saturating_add(window);
-        self.negamax(s, None, depth, alpha, beta)?;
+        self.negamax(s, None, None, 0, depth, alpha, beta)?;
         Some(())
     }
 
     pub(super) fn search_and_reorder(
         &mut self, s: &mut <E::G as Game>::S, moves: &mut [ValueMove<<E::G as Game>::M>], depth: u8,
     ) -> Option<Evaluation> {
+        let hash = E::G::zobrist_hash(s);
         let mut alpha = WORST_EVAL;
         let beta = BEST_EVAL;
+        let player = E::G::player_to_move(s);
+        // The best values so far. The window stays open for moves that could
+        // be among the `multi_pv` best, so that their values are exact.
+        let mut best = Vec::with_capacity(self.multi_pv + 1);
         for value_move in moves.iter_mut() {
+            let new_hash = E::G::zobrist_hash_after(hash, s, value_move.m);
             let mut new = AppliedMove::<E::G>::new(s, value_move.m);
-            let value = -self.negamax(&mut new, Some(value_move.m), depth - 1, -beta, -alpha)?;
-
-            alpha = max(alpha, value);
+            let turn = Turn::after::<E::G>(player, &new);
+            let (a, b) = turn.window(alpha, beta);
+            let value = turn.value(self.negamax(
+                &mut new,
+                new_hash,
+                Some(value_move.m),
+                1,
+                depth - 1,
+                a,
+                b,
+            )?);
+
+            let i = best.partition_point(|&other| other >= value);
+            if i < self.multi_pv {
+                best.insert(i, value);
+                best.truncate(self.multi_pv);
+            }
+            if best.len() == self.multi_pv {
+                alpha = best[self.multi_pv - 1];
+            }
             value_move.value = value;
         }
         moves.sort_by_key(|vm| -vm.value);
-        self.table.update(E::G::zobrist_hash(s), alpha, beta, depth, moves[0].value, moves[0].m);
+        if self.tracer.as_mut().is_some_and(|tracer| tracer.enter(0, None)) {
+            let kind = TraceKind::Searched { best_move: moves[0].m, value: moves[0].value };
+            self.trace(0, depth, (WORST_EVAL, BEST_EVAL), kind);
+        }
+        self.table.update(hash, alpha, beta, depth, moves[0].value, moves[0].m);
         Some(moves[0].value)
     }
 }
 
+// A move in the search log, or null.
+fn json_move<G: Game>(s: &G::S, m: Option<G::M>) -> String
+where
+    G::M: Eq,
+{
+    m.map_or("null".to_string(), |m| json::string(&move_notation::<G>(s, m)))
+}
+
+// A search in progress, between steps.
+struct Stepping<G: Game> {
+    state: G::S,
+    moves: Vec<ValueMove<G::M>>,
+    root_hash: u64,
+    // The next depth to search.
+    depth: u8,
+    best_move: Option<G::M>,
+    start_time: Instant,
+    done: bool,
+}
+
 pub struct IterativeSearch<E: Evaluator> {
     max_depth: u8,
     max_time: Duration,
+    max_nodes: u64,
+    // Don't start another depth after this, if nonzero.
+    soft_time: Duration,
+    // The clock to budget each move from, instead of a fixed timeout.
+    time_left: Option<TimeLeft>,
+    // Moves played in the game, as told by notify_move.
+    ply: u32,
     negamaxer: Negamaxer<E, TranspositionTable<<E::G as Game>::M>>,
     prev_value: Evaluation,
     opts: IterativeOptions,
@@ -560,7 +1052,18 @@ pub struct IterativeSearch<E: Evaluator> {
     // Nodes explored at each depth.
     nodes_explored: Vec<u64>,
     pv: Vec<<E::G as Game>::M>,
+    // The best root moves of the last completed depth and their values,
+    // when analyzing several lines.
+    lines: Vec<(Evaluation, <E::G as Game>::M)>,
     wall_time: Duration,
+    progress: Option<Box<dyn SearchProgress<<E::G as Game>::M>>>,
+    win_scale: Option<f32>,
+    snapshots: Option<Snapshots>,
+    search_log: Option<SearchLog>,
+    rng: StdRng,
+    stepping: Option<Stepping<E::G>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    background: Option<BackgroundSearch<IterativeSearch<E>, <E::G as Game>::M>>,
 }
 
 impl<E: Evaluator> IterativeSearch<E>
@@ -574,13 +1077,26 @@ where
         IterativeSearch {
             max_depth: 99,
             max_time: Duration::from_secs(5),
+            max_nodes: 0,
+            soft_time: Duration::ZERO,
+            time_left: None,
+            ply: 0,
             prev_value: 0,
             negamaxer,
             opts,
             actual_depth: 0,
             nodes_explored: Vec::new(),
             pv: Vec::new(),
+            lines: Vec::new(),
             wall_time: Duration::default(),
+            progress: None,
+            win_scale: None,
+            snapshots: None,
+            search_log: None,
+            rng: new_rng(),
+            stepping: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            background: None,
         }
     }
 
@@ -604,6 +1120,142 @@ where
         unclamp_value(self.prev_value)
     }
 
+    /// Write a snapshot of the root moves and the principal variation to
+    /// `writer` after each depth completed at least `interval` after the
+    /// last snapshot, and at the end of each search.
+    ///
+    /// The root moves are listed best first, with their values. Only the
+    /// value of the best move is exact; the others may be upper bounds.
+    pub fn set_snapshots(&mut self, writer: Box<dyn std::io::Write + Send>, interval: Duration) {
+        self.snapshots = Some(Snapshots::new(writer, interval));
+    }
+
+    /// Record why the search cut off each node along `line`, a sequence of
+    /// moves from the root of each search, to debug unexpected moves. The
+    /// trace of the last search is returned by `trace`, and prints as an
+    /// annotated tree.
+    pub fn set_trace_line(&mut self, line: Vec<<E::G as Game>::M>) {
+        self.negamaxer.tracer = Some(Tracer::new(line));
+    }
+
+    /// The trace of the last search along the line given to `set_trace_line`.
+    pub fn trace(&self) -> Option<&SearchTrace<<E::G as Game>::M>> {
+        self.negamaxer.tracer.as_ref().map(|tracer| &tracer.trace)
+    }
+
+    /// Write the events of each search to `writer` as JSON lines, as a
+    /// record for debugging. Every event has an `"event"` field:
+    ///
+    /// - `start`, with `max_depth` and `max_time` in seconds, or null.
+    /// - `depth` after each completed depth, with the `depth`, `value`, and
+    ///   resources used so far: `nodes`, `elapsed` seconds,
+    ///   `nodes_per_second`, and `table_hit_rate`.
+    /// - `best_move` and `pv`, after a depth that changed them.
+    /// - `finish`, with the chosen `move`, `depth`, `nodes`, and `elapsed`.
+    ///
+    /// Moves are written in the notation of `util::move_notation`.
+    pub fn set_search_log(&mut self, writer: Box<dyn std::io::Write + Send>) {
+        self.search_log = Some(SearchLog::new(writer));
+    }
+
+    // Log the events of a completed depth.
+    fn log_depth(
+        &mut self, s: &<E::G as Game>::S, best_move: Option<<E::G as Game>::M>, value: Evaluation,
+        start_time: Instant,
+    ) {
+        let Some(log) = self.search_log.as_mut() else {
+            return;
+        };
+        let nodes = self.nodes_explored.iter().sum::<u64>();
+        let elapsed = start_time.elapsed().as_secs_f64();
+        log.write(
+            &json::Object::new()
+                .string("event", "depth")
+                .number("depth", self.actual_depth as f64)
+                .number("value", unclamp_value(value) as f64)
+                .number("nodes", nodes as f64)
+                .number("elapsed", elapsed)
+                .number("nodes_per_second", nodes as f64 / elapsed)
+                .number("table_hit_rate", self.negamaxer.stats.table_hit_rate())
+                .finish(),
+        );
+        let best_move = json_move::<E::G>(s, best_move);
+        if log.best_move_changed(&best_move) {
+            log.write(
+                &json::Object::new()
+                    .string("event", "best_move")
+                    .number("depth", self.actual_depth as f64)
+                    .raw("move", &best_move)
+                    .finish(),
+            );
+        }
+        let pv = json::line::<E::G>(s, &self.pv);
+        if log.pv_changed(&pv) {
+            log.write(
+                &json::Object::new()
+                    .string("event", "pv")
+                    .number("depth", self.actual_depth as f64)
+                    .raw("pv", &pv)
+                    .finish(),
+            );
+        }
+    }
+
+    // Write a snapshot if one is due, or regardless if `last`.
+    fn snapshot(
+        &mut self, s: &<E::G as Game>::S, moves: &[ValueMove<<E::G as Game>::M>],
+        start_time: Instant, last: bool,
+    ) {
+        let Some(mut snapshots) = self.snapshots.take() else {
+            return;
+        };
+        if snapshots.due() || last {
+            let mut text = format!(
+                "snapshot {:.3}s depth {} nodes {}\n",
+                start_time.elapsed().as_secs_f64(),
+                self.actual_depth,
+                self.nodes_explored.iter().sum::<u64>() + self.negamaxer.stats.nodes_explored
+            );
+            for vm in moves.iter().take(SNAPSHOT_MOVES) {
+                let value = unclamp_value(vm.value);
+                text += &format!("  {:<12} {:>7}\n", move_id::<E::G>(s, Some(vm.m)), value);
+            }
+            text += &format!("pv: {}\n", pv_string::<E::G>(&self.pv, s));
+            snapshots.write(&text);
+        }
+        self.snapshots = Some(snapshots);
+    }
+
+    // Take back the searcher from a background search, keeping any settings
+    // changed in the meantime, and return the move it found.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore_from_background(
+        &mut self, finished: Finished<Self, <E::G as Game>::M>,
+    ) -> Option<<E::G as Game>::M> {
+        let mut searcher = finished.searcher;
+        searcher.max_depth = self.max_depth;
+        searcher.max_time = self.max_time;
+        searcher.max_nodes = self.max_nodes;
+        searcher.win_scale = self.win_scale;
+        searcher.soft_time = self.soft_time;
+        searcher.time_left = self.time_left;
+        searcher.ply = self.ply;
+        searcher.negamaxer.stop = self.negamaxer.stop.clone();
+        searcher.negamaxer.tablebase = self.negamaxer.tablebase.take();
+        searcher.progress = self.progress.take().or(finished.progress);
+        searcher.snapshots = self.snapshots.take().or(searcher.snapshots);
+        searcher.search_log = self.search_log.take().or(searcher.search_log);
+        *self = searcher;
+        finished.best_move
+    }
+
+    // Stop any background search, and return the move it found.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stop_background(&mut self) -> Option<<E::G as Game>::M> {
+        let finished = self.background.take()?.stop();
+        self.restore_from_background(finished)
+    }
+
     fn mtdf(
         &mut self, s: &mut <E::G as Game>::S, depth: u8, mut guess: Evaluation,
     ) -> Option<Evaluation> {
@@ -617,7 +1269,7 @@ where
                     depth, beta, lowerbound, upperbound
                 );
             }
-            guess = self.negamaxer.negamax(s, None, depth, beta - 1, beta)?;
+            guess = self.negamaxer.negamax(s, None, None, 0, depth, beta - 1, beta)?;
             if guess < beta {
                 upperbound = guess;
             } else {
@@ -626,6 +1278,114 @@ where
         }
         Some(guess)
     }
+
+    // Search one more depth of a stepped search, marking it done when it
+    // times out, reaches the maximum depth, or finds a forced result.
+    fn search_next_depth(&mut self, stepping: &mut Stepping<E::G>) {
+        let depth = stepping.depth;
+        let root_hash = stepping.root_hash;
+        let s_clone = &mut stepping.state;
+        let mut interval_start = Instant::now();
+        let search = if self.opts.mtdf {
+            self.mtdf(s_clone, depth, self.prev_value)
+        } else {
+            if let Some(window) = self.opts.aspiration_window {
+                // Results of the search are stored in the table.
+                if self
+                    .negamaxer
+                    .aspiration_search(s_clone, depth, self.prev_value, window)
+                    .is_none()
+                {
+                    // Timeout.
+                    stepping.done = true;
+                    return;
+                }
+                if self.opts.verbose {
+                    if let Some(entry) = self.negamaxer.table.lookup(root_hash) {
+                        let end = Instant::now();
+                        let interval = end - interval_start;
+                        eprintln!(
+                            "Iterative aspiration depth{:>2} took{:>5}ms; bounds{:>5} bestmove={}",
+                            depth,
+                            interval.as_millis(),
+                            entry.bounds(),
+                            move_id::<E::G>(s_clone, entry.best_move)
+                        );
+                        interval_start = end;
+                    }
+                }
+            }
+
+            self.negamaxer.search_and_reorder(s_clone, &mut stepping.moves[..], depth)
+        };
+        if search.is_none() {
+            // Timeout. Keep the best move from the previous depth.
+            stepping.done = true;
+            return;
+        }
+        let entry = self.negamaxer.table.lookup(root_hash).unwrap();
+        stepping.best_move = entry.best_move;
+        if self.negamaxer.multi_pv > 1 {
+            // The moves are sorted by their values from this depth.
+            let lines = stepping.moves.iter().take(self.negamaxer.multi_pv);
+            self.lines = lines.map(|vm| (vm.value, vm.m)).collect();
+        }
+
+        if self.opts.verbose {
+            let interval = Instant::now() - interval_start;
+            eprintln!(
+                "Iterative fullsearch depth{:>2} took{:>5}ms; value{:>6} bestmove={}",
+                depth,
+                interval.as_millis(),
+                entry.value_string(),
+                move_id::<E::G>(s_clone, stepping.best_move)
+            );
+        }
+
+        self.actual_depth = max(self.actual_depth, depth);
+        self.nodes_explored.push(self.negamaxer.stats.nodes_explored);
+        self.negamaxer.stats.nodes_explored = 0;
+        self.prev_value = entry.value;
+        stepping.depth += self.opts.step_increment;
+        self.negamaxer.table.populate_pv::<E::G>(&mut self.pv, s_clone);
+        let start_time = stepping.start_time;
+        if let Some(progress) = self.progress.as_mut() {
+            let value = Some(unclamp_value(entry.value));
+            progress.report(&SearchInfo {
+                best_move: stepping.best_move,
+                value,
+                win_probability: scaled_win_probability(value, self.win_scale),
+                principal_variation: self.pv.clone(),
+                nodes: self.nodes_explored.iter().sum(),
+                depth: self.actual_depth,
+                elapsed: start_time.elapsed(),
+                ..SearchInfo::default()
+            });
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            depth = self.actual_depth,
+            value = unclamp_value(entry.value),
+            nodes = self.nodes_explored.last().copied().unwrap_or(0),
+            elapsed_ms = start_time.elapsed().as_millis() as u64,
+            "completed depth"
+        );
+        self.log_depth(&stepping.state, stepping.best_move, entry.value, start_time);
+        self.snapshot(&stepping.state, &stepping.moves, start_time, false);
+        // When analyzing several lines, keep going until all of them are
+        // decided, not just the best.
+        let decided = if self.negamaxer.multi_pv > 1 {
+            self.lines.iter().all(|&(value, _)| unclamp_value(value).abs() == BEST_EVAL)
+        } else {
+            unclamp_value(entry.value).abs() == BEST_EVAL
+        };
+        if decided
+            || stepping.depth > self.max_depth
+            || (self.soft_time > Duration::ZERO && start_time.elapsed() >= self.soft_time)
+        {
+            stepping.done = true;
+        }
+    }
 }
 
 impl<E: Evaluator> Strategy<E::G> for IterativeSearch<E>
@@ -634,115 +1394,432 @@ where
     <E::G as Game>::M: Copy + Eq,
 {
     fn choose_move(&mut self, s: &<E::G as Game>::S) -> Option<<E::G as Game>::M> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("iterative_search", max_depth = self.max_depth).entered();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(background) = self.background.take() {
+            // Continue a search after a ponder hit on this position, or
+            // discard any other.
+            let hit = background.is_hit()
+                && background.position.is_none_or(|hash| hash == E::G::zobrist_hash(s));
+            let m = self.restore_from_background(if hit {
+                background.finish()
+            } else {
+                background.stop()
+            });
+            if hit && m.is_some() {
+                return m;
+            }
+        }
+        self.start_stepping(s);
+        while !self.step(u32::MAX) {}
+        self.finish_stepping()
+    }
+
+    fn choose_move_with_info(&mut self, s: &<E::G as Game>::S) -> SearchInfo<<E::G as Game>::M> {
+        let best_move = self.choose_move(s);
+        let value = if self.actual_depth > 0 { Some(self.root_value()) } else { None };
+        SearchInfo {
+            best_move,
+            value,
+            win_probability: scaled_win_probability(value, self.win_scale),
+            principal_variation: self.pv.clone(),
+            nodes: self.nodes_explored.iter().sum::<u64>() + self.negamaxer.stats.nodes_explored,
+            depth: self.actual_depth,
+            elapsed: self.wall_time,
+            ..SearchInfo::default()
+        }
+    }
+
+    fn set_timeout(&mut self, max_time: Duration) {
+        self.max_time = max_time;
+        self.soft_time = Duration::ZERO;
+        self.time_left = None;
+        self.max_depth = 99;
+        self.max_nodes = 0;
+    }
+
+    fn notify_move(&mut self, _state: &<E::G as Game>::S, _m: <E::G as Game>::M) {
+        self.ply += 1;
+    }
+
+    fn new_game(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stop_background();
+        self.stepping = None;
+        self.ply = 0;
+        self.prev_value = 0;
+        self.pv.clear();
+        self.negamaxer.table =
+            TranspositionTable::new(self.opts.table_byte_size, self.opts.strategy);
+        self.negamaxer.countermoves = CounterMoves::new(
+            self.opts.countermove_table,
+            self.opts.countermove_history_table,
+            self.opts.history,
+        );
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.set_timeout(time.move_budget());
+        self.time_left = Some(*time);
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.win_scale = Some(scale);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.max_nodes = nodes;
+        self.max_time = Duration::new(0, 0);
+        self.soft_time = Duration::ZERO;
+        self.time_left = None;
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        self.max_depth = depth;
+        self.max_time = Duration::new(0, 0);
+        self.soft_time = Duration::ZERO;
+        self.time_left = None;
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.negamaxer.stop = signal;
+    }
+
+    fn set_tablebase(&mut self, tablebase: SharedTablebase<E::G>) {
+        self.negamaxer.tablebase = Some(tablebase);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<<E::G as Game>::M>>) {
+        self.progress = Some(progress);
+    }
+
+    fn principal_variation(&self) -> Vec<<E::G as Game>::M> {
+        self.pv.clone()
+    }
+
+    fn analyze(&mut self, s: &<E::G as Game>::S, k: usize) -> Vec<MoveAnalysis<<E::G as Game>::M>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // MTD(f) doesn't search the root moves one by one.
+        self.negamaxer.multi_pv = if self.opts.mtdf { 1 } else { k };
+        self.lines.clear();
+        let info = self.choose_move_with_info(s);
+        self.negamaxer.multi_pv = 1;
+        if self.lines.is_empty() {
+            // Only the best move is known, e.g. from a tablebase.
+            return MoveAnalysis::from_search(info).into_iter().collect();
+        }
+        let lines = std::mem::take(&mut self.lines);
+        lines
+            .into_iter()
+            .map(|(value, m)| {
+                let value = Some(unclamp_value(value));
+                let principal_variation = if self.pv.first() == Some(&m) {
+                    self.pv.clone()
+                } else {
+                    // The rest of the line is what the table remembers after
+                    // the move.
+                    let mut state = s.clone();
+                    if let Some(new_state) = E::G::apply(&mut state, m) {
+                        state = new_state;
+                    }
+                    let mut pv = Vec::new();
+                    self.negamaxer.table.populate_pv::<E::G>(&mut pv, &state);
+                    pv.insert(0, m);
+                    pv
+                };
+                MoveAnalysis {
+                    m,
+                    value,
+                    win_probability: scaled_win_probability(value, self.win_scale),
+                    principal_variation,
+                }
+            })
+            .collect()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.negamaxer.table.memory_usage()
+    }
+}
+
+/// Steps only pause between depths, so each one finishes the depth it is
+/// on, however much work that takes: deeper steps take longer, and a step
+/// may take as long as a whole search to that depth. Set a timeout to bound
+/// the last one.
+impl<E: Evaluator> SteppedSearch<E::G> for IterativeSearch<E>
+where
+    <E::G as Game>::S: Clone,
+    <E::G as Game>::M: Copy + Eq,
+{
+    fn start_stepping(&mut self, s: &<E::G as Game>::S) {
+        self.stepping = None;
         if E::G::get_winner(s).is_some() {
-            return None;
+            return;
         }
         self.negamaxer.table.advance_generation();
         self.negamaxer.countermoves.advance_generation(E::G::null_move(s));
         // Reset stats.
         self.nodes_explored.clear();
         self.negamaxer.stats.reset();
+        if let Some(tracer) = self.negamaxer.tracer.as_mut() {
+            tracer.reset();
+        }
         self.actual_depth = 0;
         let start_time = Instant::now();
-        // Start timer if configured.
+        if let Some(time) = self.time_left {
+            let budget = self.opts.time_allocator.budget_at_ply(
+                &time,
+                E::G::max_moves_remaining(s),
+                Some(E::G::ply(s).unwrap_or(self.ply)),
+            );
+            self.max_time = budget.hard;
+            self.soft_time = budget.soft;
+        }
+        // Start timer and node count if configured.
         self.negamaxer.reset_timeout(self.max_time);
+        self.negamaxer.max_nodes = self.max_nodes;
+        self.negamaxer.root_ply = E::G::ply(s).unwrap_or(self.ply);
+        self.negamaxer.nodes = 0;
+        if let Some(snapshots) = self.snapshots.as_mut() {
+            snapshots.reset();
+        }
+        if let Some(log) = self.search_log.as_mut() {
+            log.reset();
+            let max_time = (self.max_time > Duration::ZERO).then_some(self.max_time.as_secs_f64());
+            log.write(
+                &json::Object::new()
+                    .string("event", "start")
+                    .number("max_depth", self.max_depth as f64)
+                    .optional("max_time", max_time)
+                    .finish(),
+            );
+        }
 
-        let root_hash = E::G::zobrist_hash(s);
-        let mut s_clone = s.clone();
-        let mut best_move = None;
-        let mut interval_start;
+        let mut state = s.clone();
+        if let Some((m, value)) = tablebase_root(&self.negamaxer.tablebase, &mut state) {
+            self.prev_value = value;
+            self.pv = vec![m];
+            self.stepping = Some(Stepping {
+                state,
+                moves: Vec::new(),
+                root_hash: 0,
+                depth: 0,
+                best_move: Some(m),
+                start_time,
+                done: true,
+            });
+            return;
+        }
         // Store the moves so they can be reordered every iteration.
         let mut moves = Vec::new();
-        E::G::generate_moves(&s_clone, &mut moves);
-        // Start in a random order.
-        moves.shuffle(&mut rand::thread_rng());
-        let mut moves = moves.into_iter().map(|m| ValueMove::new(0, m)).collect::<Vec<_>>();
+        let mut priorities = Vec::new();
+        E::G::generate_scored_moves(&state, &mut moves, &mut priorities);
+        priorities.resize(moves.len(), 0);
+        let mut moves = priorities.into_iter().zip(moves).collect::<Vec<_>>();
+        // Start in a random order, highest priority first.
+        moves.shuffle(&mut self.rng);
+        moves.sort_by_key(|&(priority, _)| std::cmp::Reverse(priority));
+        let moves = moves.into_iter().map(|(_, m)| ValueMove::new(0, m)).collect::<Vec<_>>();
 
         // Start at 1 or 2 to hit the max depth.
         let mut depth = self.max_depth % self.opts.step_increment;
         if depth == 0 {
             depth = self.opts.step_increment;
         }
-        while depth <= self.max_depth {
-            interval_start = Instant::now();
-            let search = if self.opts.mtdf {
-                self.mtdf(&mut s_clone, depth, self.prev_value)
-            } else {
-                if let Some(window) = self.opts.aspiration_window {
-                    // Results of the search are stored in the table.
-                    if self
-                        .negamaxer
-                        .aspiration_search(&mut s_clone, depth, self.prev_value, window)
-                        .is_none()
-                    {
-                        // Timeout.
-                        break;
-                    }
-                    if self.opts.verbose {
-                        if let Some(entry) = self.negamaxer.table.lookup(root_hash) {
-                            let end = Instant::now();
-                            let interval = end - interval_start;
-                            eprintln!(
-                                "Iterative aspiration depth{:>2} took{:>5}ms; bounds{:>5} bestmove={}",
-                                depth,
-                                interval.as_millis(),
-                                entry.bounds(),
-                                move_id::<E::G>(&s_clone, entry.best_move)
-                            );
-                            interval_start = end;
-                        }
-                    }
-                }
-
-                self.negamaxer.search_and_reorder(&mut s_clone, &mut moves[..], depth)
-            };
-            if search.is_none() {
-                // Timeout. Return the best move from the previous depth.
-                break;
-            }
-            let entry = self.negamaxer.table.lookup(root_hash).unwrap();
-            best_move = entry.best_move;
-
-            if self.opts.verbose {
-                let interval = Instant::now() - interval_start;
-                eprintln!(
-                    "Iterative fullsearch depth{:>2} took{:>5}ms; value{:>6} bestmove={}",
-                    depth,
-                    interval.as_millis(),
-                    entry.value_string(),
-                    move_id::<E::G>(&s_clone, best_move)
-                );
-            }
+        self.stepping = Some(Stepping {
+            root_hash: E::G::zobrist_hash(&state),
+            state,
+            moves,
+            depth,
+            best_move: None,
+            start_time,
+            done: depth > self.max_depth,
+        });
+    }
 
-            self.actual_depth = max(self.actual_depth, depth);
-            self.nodes_explored.push(self.negamaxer.stats.nodes_explored);
-            self.negamaxer.stats.nodes_explored = 0;
-            self.prev_value = entry.value;
-            depth += self.opts.step_increment;
-            self.negamaxer.table.populate_pv::<E::G>(&mut self.pv, &s_clone);
-            if unclamp_value(entry.value).abs() == BEST_EVAL {
-                break;
-            }
+    fn step(&mut self, work: u32) -> bool {
+        let Some(mut stepping) = self.stepping.take() else {
+            return true;
+        };
+        // Only whole depths are searched, so a step may do much more work.
+        let target = self.nodes_explored.iter().sum::<u64>() + work as u64;
+        while !stepping.done && self.nodes_explored.iter().sum::<u64>() < target {
+            self.search_next_depth(&mut stepping);
         }
-        self.wall_time = start_time.elapsed();
+        let done = stepping.done;
+        self.stepping = Some(stepping);
+        done
+    }
+
+    fn finish_stepping(&mut self) -> Option<<E::G as Game>::M> {
+        let stepping = self.stepping.take()?;
+        self.wall_time = stepping.start_time.elapsed();
+        self.snapshot(&stepping.state, &stepping.moves, stepping.start_time, true);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            depth = self.actual_depth,
+            nodes = self.nodes_explored.iter().sum::<u64>(),
+            table_hit_rate = self.negamaxer.stats.table_hit_rate(),
+            elapsed_ms = self.wall_time.as_millis() as u64,
+            "finished search"
+        );
+        #[cfg(feature = "metrics")]
+        record_search_metrics(
+            "iterative",
+            self.actual_depth,
+            self.nodes_explored.iter().sum::<u64>() + self.negamaxer.stats.nodes_explored,
+            self.wall_time,
+            Some(self.negamaxer.stats.table_hit_rate()),
+        );
         if self.opts.verbose {
-            let mut s_clone = s.clone();
+            let mut s_clone = stepping.state.clone();
             eprintln!("{}", self.stats(&mut s_clone));
         }
+        self.negamaxer.stop.reset();
+        // If stopped before finishing the first iteration, any move will do.
+        let best_move = stepping.best_move.or_else(|| stepping.moves.first().map(|vm| vm.m));
+        if let Some(log) = self.search_log.as_mut() {
+            log.write(
+                &json::Object::new()
+                    .string("event", "finish")
+                    .raw("move", &json_move::<E::G>(&stepping.state, best_move))
+                    .number("depth", self.actual_depth as f64)
+                    .number(
+                        "nodes",
+                        (self.nodes_explored.iter().sum::<u64>()
+                            + self.negamaxer.stats.nodes_explored) as f64,
+                    )
+                    .number("elapsed", self.wall_time.as_secs_f64())
+                    .finish(),
+            );
+        }
         best_move
     }
+}
 
-    fn set_timeout(&mut self, max_time: Duration) {
-        self.max_time = max_time;
-        self.max_depth = 99;
+impl<E: Evaluator + Clone> IterativeSearch<E>
+where
+    <E::G as Game>::M: Copy + Eq,
+    <E::G as Game>::S: Clone,
+{
+    // A new searcher with these options and this one's settings, evaluator,
+    // and tablebase.
+    fn with_same_settings(&self, opts: IterativeOptions) -> Self {
+        let mut searcher = IterativeSearch::new(self.negamaxer.eval.clone(), opts);
+        searcher.max_depth = self.max_depth;
+        searcher.max_time = self.max_time;
+        searcher.max_nodes = self.max_nodes;
+        searcher.win_scale = self.win_scale;
+        searcher.soft_time = self.soft_time;
+        searcher.time_left = self.time_left;
+        searcher.negamaxer.tablebase = self.negamaxer.tablebase.clone();
+        searcher
     }
+}
 
-    fn set_max_depth(&mut self, depth: u8) {
-        self.max_depth = depth;
-        self.max_time = Duration::new(0, 0);
+/// Copies have the same settings, tablebase, and evaluator, and their own
+/// empty transposition table of the same size.
+impl<E: Evaluator + Clone> FreshCopy for IterativeSearch<E>
+where
+    <E::G as Game>::M: Copy + Eq,
+    <E::G as Game>::S: Clone,
+{
+    fn fresh_copy(&self) -> Self {
+        self.with_same_settings(self.opts)
     }
+}
 
-    fn principal_variation(&self) -> Vec<<E::G as Game>::M> {
-        self.pv.clone()
+#[cfg(not(target_arch = "wasm32"))]
+impl<E: Evaluator> IterativeSearch<E>
+where
+    <E::G as Game>::S: Clone + Send + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + 'static,
+    E: Clone + Send + 'static,
+{
+    // Move the searcher and its table to a background thread to search this
+    // state, leaving a small stand-in with the same settings in its place.
+    fn search_in_background(&mut self, state: <E::G as Game>::S) {
+        self.stop_background();
+        let mut placeholder = self.with_same_settings(self.opts.with_table_byte_size(1024));
+        placeholder.ply = self.ply;
+        placeholder.negamaxer.stop = self.negamaxer.stop.clone();
+        let mut searcher = std::mem::replace(self, placeholder);
+        if searcher.max_time != Duration::new(0, 0) {
+            // Search without a time limit until stopped or a ponder hit
+            // starts the clock.
+            searcher.max_depth = 99;
+            searcher.max_time = Duration::new(0, 0);
+            searcher.soft_time = Duration::ZERO;
+            searcher.time_left = None;
+        }
+        let progress = searcher.progress.take();
+        self.background =
+            Some(BackgroundSearch::start(searcher, progress, move |searcher, stop, progress| {
+                searcher.negamaxer.stop = stop;
+                searcher.progress = Some(progress);
+                searcher.choose_move(&state)
+            }));
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<E: Evaluator> Ponder<E::G> for IterativeSearch<E>
+where
+    <E::G as Game>::S: Clone + Send + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + 'static,
+    E: Clone + Send + 'static,
+{
+    fn ponder(&mut self, s: &<E::G as Game>::S, predicted_move: <E::G as Game>::M) {
+        if !E::G::is_legal(s, predicted_move) {
+            return;
+        }
+        let mut state = s.clone();
+        if let Some(new_state) = E::G::apply(&mut state, predicted_move) {
+            state = new_state;
+        }
+        if E::G::get_winner(&state).is_none() {
+            let hash = E::G::zobrist_hash(&state);
+            self.search_in_background(state);
+            if let Some(background) = self.background.as_mut() {
+                background.position = Some(hash);
+            }
+        }
+    }
+
+    fn ponder_hit(&mut self) {
+        if let Some(background) = self.background.as_mut() {
+            background.hit(self.max_time);
+        }
+    }
+
+    fn stop_ponder(&mut self) {
+        self.stop_background();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<E: Evaluator> AnytimeStrategy<E::G> for IterativeSearch<E>
+where
+    <E::G as Game>::S: Clone + Send + 'static,
+    <E::G as Game>::M: Copy + Eq + Send + 'static,
+    E: Clone + Send + 'static,
+{
+    fn start(&mut self, s: &<E::G as Game>::S) {
+        self.search_in_background(s.clone());
+    }
+
+    fn best_so_far(&self) -> Option<<E::G as Game>::M> {
+        self.background.as_ref()?.best_so_far()
+    }
+
+    fn stop(&mut self) -> Option<<E::G as Game>::M> {
+        self.stop_background()
     }
 }
@@ -1,123 +1,863 @@
 use super::super::interface::*;
-use super::super::util::AppliedMove;
+use super::super::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use super::background::{BackgroundSearch, Finished};
 use super::sync_util::*;
-use super::util::{move_id, pv_string, random_best};
+#[cfg(feature = "metrics")]
+use super::util::record_search_metrics;
+use super::util::{
+    move_id, new_rng, passes_turn, pv_string, random_best, SharedTablebase, Snapshots,
+};
 
-use rand::rngs::ThreadRng;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::marker::PhantomData;
-use std::sync::atomic::Ordering::{Relaxed, SeqCst};
-use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32};
-use std::sync::Arc;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64};
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 const WIN: i32 = i32::MAX;
 // Make sure they negate to each other, unlike i32::MIN.
 const LOSS: i32 = -WIN;
 
+// The nodes of a search, addressed by index. The root is node 0.
+type Tree<M> = Arena<Node<M>>;
+
+// Each node gets its own cache line, so threads updating the counters of
+// sibling nodes don't contend with each other.
+#[repr(align(64))]
 struct Node<M> {
-    // The Move to get from the parent to here.
-    // Only None at the root.
-    m: Option<M>,
-    visits: AtomicU32,
-    // +1 for wins, -1 for losses, +0 for draws.
-    // From perspective of the player that made this move.
-    score: AtomicI32,
+    // The visit count in the low 32 bits, and the score in the high 32 bits,
+    // so both are updated together by one atomic add.
+    // The score is +1 for wins, -1 for losses, +0 for draws,
+    // from perspective of the player that made this move.
+    stats: AtomicU64,
     // Lazily populated if this node guarantees a particular end state.
     // WIN for a guaranteed win, LOSS for a guaranteed loss.
     // Not bothering with draws.
     winner: AtomicI32,
+    // The root's visit count when a simulation last passed through here.
+    last_visit: AtomicU32,
     // Lazily populated.
     expansion: AtomicBox<NodeExpansion<M>>,
 }
 
 struct NodeExpansion<M> {
-    children: Vec<Node<M>>,
+    children: Box<[Child<M>]>,
+}
+
+// A move from an expanded node. Most are never visited, so their nodes are
+// only created when first chosen.
+struct Child<M> {
+    m: M,
+    // The probability that this is the best move, from the evaluator if
+    // there is one, and otherwise uniform.
+    prior: f32,
+    // Index of the node in the tree, 0 if it hasn't been created yet, or
+    // CREATING while a thread creates it.
+    node: AtomicU32,
+    // The evaluator's result for the position after this move, from the
+    // batch evaluated with its siblings, until the move is expanded.
+    evaluation: Option<Box<PolicyValue>>,
+}
+
+const CREATING: u32 = u32::MAX;
+
+impl<M: Copy> Child<M> {
+    fn get<'a>(&self, tree: &'a Tree<M>) -> Option<&'a Node<M>> {
+        // Acquire, to see the node written by whichever thread created it.
+        match self.node.load(Acquire) {
+            0 | CREATING => None,
+            index => Some(tree.get(index)),
+        }
+    }
+
+    // The node for this move, creating it if needed. Only one thread
+    // creates it, so racing threads don't leave unused nodes in the tree.
+    fn node<'a>(&self, tree: &'a Tree<M>) -> &'a Node<M> {
+        loop {
+            match self.node.load(Acquire) {
+                0 => {
+                    if self.node.compare_exchange(0, CREATING, Acquire, Relaxed).is_ok() {
+                        let index = tree.alloc(Node::new());
+                        self.node.store(index, Release);
+                        return tree.get(index);
+                    }
+                }
+                // Another thread is just allocating it.
+                CREATING => std::hint::spin_loop(),
+                index => return tree.get(index),
+            }
+        }
+    }
+
+    fn stats(&self, tree: &Tree<M>) -> (u32, i32) {
+        self.get(tree).map_or((0, 0), Node::stats)
+    }
+
+    fn winner(&self, tree: &Tree<M>) -> i32 {
+        self.get(tree).map_or(0, |node| node.winner.load(Relaxed))
+    }
 }
 
-fn new_expansion<G: Game>(state: &G::S) -> Box<NodeExpansion<G::M>> {
-    let mut moves = Vec::new();
-    G::generate_moves(state, &mut moves);
-    let children = moves.into_iter().map(|m| Node::new(Some(m))).collect::<Vec<_>>();
+// Uses `moves` for scratch space, leaving it empty.
+fn new_expansion<G: Game>(state: &G::S, moves: &mut Vec<G::M>) -> Box<NodeExpansion<G::M>> {
+    moves.clear();
+    G::generate_moves(state, moves);
+    let prior = 1.0 / moves.len() as f32;
+    let children = moves
+        .drain(..)
+        .map(|m| Child { m, prior, node: AtomicU32::new(0), evaluation: None })
+        .collect();
     Box::new(NodeExpansion { children })
 }
 
-impl<M> Node<M> {
-    fn new(m: Option<M>) -> Self {
+impl<M: Copy> Node<M> {
+    fn new() -> Self {
         Node {
-            m,
             expansion: AtomicBox::default(),
-            visits: AtomicU32::new(0),
-            score: AtomicI32::new(0),
+            stats: AtomicU64::new(0),
             winner: AtomicI32::new(0),
+            last_visit: AtomicU32::new(0),
         }
     }
 
-    // Choose best child based on UCT.
-    fn best_child(&self, exploration_score: f32) -> Option<&Node<M>> {
-        let mut log_visits = (self.visits.load(SeqCst) as f32).log2();
-        // Keep this numerator non-negative.
-        if log_visits < 0.0 {
-            log_visits = 0.0;
-        }
-
-        let expansion = self.expansion.get()?;
-        random_best(expansion.children.as_slice(), |node| {
-            node.uct_score(exploration_score, log_visits)
-        })
+    fn subtree_info(&self, depth: u32) -> SubtreeInfo {
+        SubtreeInfo { visits: self.visits(), depth, last_visit: self.last_visit.load(Relaxed) }
     }
 
-    fn uct_score(&self, exploration_score: f32, log_parent_visits: f32) -> f32 {
-        let winner = self.winner.load(Relaxed);
-        if winner < 0 {
-            // Large enough to be returned from best_move, smaller than any other value.
-            // This effectively ignores any moves that we've proved guarantee losses.
-            // The MCTS-Solver paper says not to do this, but I don't buy their argument.
-            // Those moves effectivey won't exist in our search, and we'll
-            // have to see if the remaining moves make the parent moves worthwhile.
-            return -1.0;
+    // Number of moves in the expansions of this subtree.
+    fn count_children(&self, tree: &Tree<M>) -> usize {
+        let mut children = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            if let Some(expansion) = node.expansion.get() {
+                children += expansion.children.len();
+                stack.extend(expansion.children.iter().filter_map(|child| child.get(tree)));
+            }
         }
-        if winner > 0 {
-            return f32::INFINITY;
+        children
+    }
+
+    // The shape of the subtree below this node.
+    fn shape(&self, tree: &Tree<M>) -> TreeShape {
+        let mut shape = TreeShape::default();
+        let (mut expanded, mut branches) = (0, 0);
+        let mut stack = vec![(self, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if shape.nodes_per_depth.len() <= depth {
+                shape.nodes_per_depth.resize(depth + 1, 0);
+            }
+            shape.nodes_per_depth[depth] += 1;
+            if let Some(expansion) = node.expansion.get() {
+                let len = stack.len();
+                stack.extend(
+                    expansion
+                        .children
+                        .iter()
+                        .filter_map(|child| Some((child.get(tree)?, depth + 1))),
+                );
+                expanded += 1;
+                branches += stack.len() - len;
+            }
         }
-        let visits = self.visits.load(Relaxed) as f32;
-        let score = self.score.load(Relaxed) as f32;
-        if visits == 0.0 {
-            // Avoid NaNs.
-            return if exploration_score > 0.0 { f32::INFINITY } else { 0.0 };
+        if expanded > 0 {
+            shape.mean_branching = branches as f64 / expanded as f64;
         }
-        let win_ratio = (score + visits) / (2.0 * visits);
-        win_ratio + exploration_score * (2.0 * log_parent_visits / visits).sqrt()
+        shape
+    }
+
+    fn visits(&self) -> u32 {
+        self.stats.load(Relaxed) as u32
+    }
+
+    // The visit count and score.
+    fn stats(&self) -> (u32, i32) {
+        let stats = self.stats.load(Relaxed);
+        (stats as u32, (stats >> 32) as i32)
+    }
+
+    // Add to the visit count and score.
+    fn add_stats(&self, visits: u32, score: i32) {
+        self.stats.fetch_add((score as u64) << 32 | visits as u64, SeqCst);
+    }
+
+    // Choose the child with the best score from the policy, or the proven
+    // win if there is one, and never a proven loss unless there's no other.
+    // Only the first `width` children are considered.
+    fn best_child<'a>(
+        &'a self, tree: &'a Tree<M>, policy: &dyn TreePolicy, width: usize, rng: &mut StdRng,
+    ) -> Option<(&'a Child<M>, &'a Node<M>)> {
+        let expansion = self.expansion.get()?;
+        let parent_visits = self.visits();
+        let children = &expansion.children[..width.min(expansion.children.len())];
+        let child = random_best(
+            children,
+            |child| match child.winner(tree) {
+                // This effectively ignores any moves that we've proved guarantee losses.
+                // The MCTS-Solver paper says not to do this, but I don't buy their argument.
+                // Those moves effectivey won't exist in our search, and we'll
+                // have to see if the remaining moves make the parent moves worthwhile.
+                winner if winner < 0 => f32::MIN,
+                winner if winner > 0 => f32::INFINITY,
+                _ => {
+                    let (visits, score) = child.stats(tree);
+                    policy.score(&ChildStats { visits, score, prior: child.prior }, parent_visits)
+                }
+            },
+            rng,
+        )?;
+        Some((child, child.node(tree)))
     }
 
     fn pre_update_stats(&self) {
         // Use a technicque called virtual loss to assume we've lost any
         // ongoing simulation to bias concurrent threads against exploring it.
-        self.visits.fetch_add(1, SeqCst);
-        self.score.fetch_add(-1, SeqCst);
+        self.add_stats(1, -1);
     }
 
     fn update_stats(&self, result: i32) -> Option<i32> {
+        self.update_weighted_stats(result, 1)
+    }
+
+    // Like update_stats, for the sum of `weight` results from a single
+    // simulation.
+    fn update_weighted_stats(&self, result: i32, weight: u32) -> Option<i32> {
         if result == WIN || result == LOSS {
             self.winner.store(result, SeqCst);
         } else {
             // Adjust for virtual loss.
-            self.score.fetch_add(result + 1, SeqCst);
+            self.add_stats(weight - 1, result + 1);
         }
         // Always return Some, as we aren't timed out.
         Some(result)
     }
 }
 
+/// A move from a node in the search tree, as seen by a [TreePolicy].
+#[derive(Clone, Copy, Debug)]
+pub struct ChildStats {
+    /// Simulations that passed through this move, including ones still
+    /// running.
+    pub visits: u32,
+    /// The sum of the results of those simulations for the player making
+    /// this move: +1 for a win, -1 for a loss, and 0 for a draw. Simulations
+    /// still running count as losses, to steer other threads elsewhere.
+    pub score: i32,
+    /// The probability that this is the best move, from the evaluator if
+    /// there is one, and otherwise uniform.
+    pub prior: f32,
+}
+
+impl ChildStats {
+    /// The average result from 0 for all losses to 1 for all wins, or 0.5
+    /// for an unvisited move.
+    pub fn win_ratio(&self) -> f32 {
+        if self.visits == 0 {
+            return 0.5;
+        }
+        let (visits, score) = (self.visits as f32, self.score as f32);
+        (score + visits) / (2.0 * visits)
+    }
+}
+
+/// The shape of the search tree at the end of a search, for judging whether
+/// it explores too widely or too narrowly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeShape {
+    /// The number of nodes at each depth, starting with the root.
+    pub nodes_per_depth: Vec<usize>,
+    /// The average number of moves explored from each expanded node.
+    pub mean_branching: f64,
+}
+
+impl TreeShape {
+    /// The depth of the deepest node, in moves from the root.
+    pub fn max_depth(&self) -> usize {
+        self.nodes_per_depth.len().saturating_sub(1)
+    }
+}
+
+/// How a candidate move fared in [MonteCarloTreeSearch::compare_moves].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CandidateInfo<M> {
+    pub m: M,
+    /// The share of the simulations after this move won by the player
+    /// making it, from 0 for all losses to 1 for all wins.
+    pub win_ratio: f32,
+    /// Simulations run after this move.
+    pub simulations: u64,
+    /// The best line of play, starting with this move.
+    pub principal_variation: Vec<M>,
+}
+
+/// The visits and score of a move from the root of a search, for
+/// warm-starting a later search with [MonteCarloTreeSearch::warm_start].
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveStats<M> {
+    /// The move, from the root position.
+    pub m: M,
+    /// Simulations that passed through this move.
+    pub visits: u32,
+    /// The sum of their results for the player making this move, as in
+    /// [ChildStats].
+    pub score: i32,
+}
+
+/// Chooses which move to follow from each node on the way down the search
+/// tree. The move with the highest score is followed, with ties broken
+/// randomly. Moves proven to win or lose are followed or avoided without
+/// being scored.
+pub trait TreePolicy: Send + Sync {
+    /// Score a move from a node that `parent_visits` simulations have passed
+    /// through.
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32;
+
+    /// Like `score`, but with this exploration constant in place of the
+    /// policy's own. Policies without an exploration constant ignore it.
+    fn score_with_exploration(
+        &self, child: &ChildStats, parent_visits: u32, _exploration: f32,
+    ) -> f32 {
+        self.score(child, parent_visits)
+    }
+}
+
+/// Upper Confidence bounds applied to Trees, the classic selection rule.
+/// This is the default without an evaluator.
+#[derive(Clone, Copy, Debug)]
+pub struct Uct {
+    /// How strongly to favor less visited moves. Defaults to 1.
+    pub exploration: f32,
+}
+
+impl Default for Uct {
+    fn default() -> Self {
+        Uct { exploration: 1.0 }
+    }
+}
+
+impl TreePolicy for Uct {
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32 {
+        self.score_with_exploration(child, parent_visits, self.exploration)
+    }
+
+    fn score_with_exploration(
+        &self, child: &ChildStats, parent_visits: u32, exploration: f32,
+    ) -> f32 {
+        if child.visits == 0 {
+            // Avoid NaNs.
+            return if exploration > 0.0 { f32::INFINITY } else { 0.0 };
+        }
+        // Keep this numerator non-negative.
+        let log_parent_visits = (parent_visits as f32).log2().max(0.0);
+        child.win_ratio() + exploration * (2.0 * log_parent_visits / child.visits as f32).sqrt()
+    }
+}
+
+/// Like [Uct], but exploring in proportion to the prior, as AlphaZero does.
+/// Unvisited moves are assumed to be even. This is the default with an
+/// evaluator.
+#[derive(Clone, Copy, Debug)]
+pub struct Puct {
+    /// How strongly to favor moves with high priors and few visits. Defaults
+    /// to 1. With no exploration, the most visited move is chosen.
+    pub exploration: f32,
+}
+
+impl Default for Puct {
+    fn default() -> Self {
+        Puct { exploration: 1.0 }
+    }
+}
+
+impl TreePolicy for Puct {
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32 {
+        self.score_with_exploration(child, parent_visits, self.exploration)
+    }
+
+    fn score_with_exploration(
+        &self, child: &ChildStats, parent_visits: u32, exploration: f32,
+    ) -> f32 {
+        let visits = child.visits as f32;
+        if exploration == 0.0 {
+            return visits;
+        }
+        child.win_ratio()
+            + exploration * child.prior * (parent_visits as f32).sqrt() / (1.0 + visits)
+    }
+}
+
+/// UCB1-Tuned, which narrows [Uct]'s exploration for moves whose results
+/// vary little. The variance of each move's results is estimated as that of
+/// a win or a loss, from its win ratio.
+#[derive(Clone, Copy, Debug)]
+pub struct Ucb1Tuned {
+    /// How strongly to favor less visited moves. Defaults to 1.
+    pub exploration: f32,
+}
+
+impl Default for Ucb1Tuned {
+    fn default() -> Self {
+        Ucb1Tuned { exploration: 1.0 }
+    }
+}
+
+impl TreePolicy for Ucb1Tuned {
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32 {
+        self.score_with_exploration(child, parent_visits, self.exploration)
+    }
+
+    fn score_with_exploration(
+        &self, child: &ChildStats, parent_visits: u32, exploration: f32,
+    ) -> f32 {
+        if child.visits == 0 {
+            return if exploration > 0.0 { f32::INFINITY } else { 0.0 };
+        }
+        let visits = child.visits as f32;
+        let log_parent_visits = (parent_visits as f32).ln().max(0.0);
+        let win_ratio = child.win_ratio();
+        let variance = win_ratio * (1.0 - win_ratio) + (2.0 * log_parent_visits / visits).sqrt();
+        win_ratio + exploration * (log_parent_visits / visits * variance.min(0.25)).sqrt()
+    }
+}
+
+/// Anneals the exploration constant of the tree policy over each search,
+/// from `start` on the first simulation to `end` after `horizon`
+/// simulations, to explore early and exploit late.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExplorationSchedule {
+    pub start: f32,
+    pub end: f32,
+    /// Simulations over which to decay linearly from `start` to `end`.
+    pub horizon: u32,
+}
+
+impl ExplorationSchedule {
+    /// The exploration constant after this many simulations.
+    pub fn exploration(&self, simulations: u32) -> f32 {
+        let progress = (simulations as f32 / self.horizon.max(1) as f32).min(1.0);
+        self.start + (self.end - self.start) * progress
+    }
+}
+
+/// When to admit each move from a node under progressive unpruning. A node
+/// starts with `initial` moves, and admits another after `visits`
+/// simulations, and each further move after `growth` times as many as the
+/// last.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgressiveUnpruning {
+    pub initial: u32,
+    pub visits: u32,
+    pub growth: f32,
+}
+
+impl ProgressiveUnpruning {
+    /// The number of moves admitted from a node with this many visits.
+    pub fn width(&self, node_visits: u32) -> usize {
+        let initial = self.initial.max(1) as usize;
+        if node_visits < self.visits.max(1) {
+            return initial;
+        }
+        let ratio = node_visits as f32 / self.visits.max(1) as f32;
+        let admitted = if self.growth > 1.0 {
+            ratio.log(self.growth).floor() as usize + 1
+        } else {
+            usize::MAX - initial
+        };
+        initial + admitted
+    }
+}
+
+// Scores with the exploration constant from a schedule.
+struct Scheduled<'a> {
+    policy: &'a dyn TreePolicy,
+    exploration: f32,
+}
+
+impl TreePolicy for Scheduled<'_> {
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32 {
+        self.policy.score_with_exploration(child, parent_visits, self.exploration)
+    }
+}
+
+// Follows the root moves with fewer than their forced simulations first,
+// and otherwise the policy.
+struct ForcedPlayouts<'a> {
+    policy: &'a dyn TreePolicy,
+    k: f32,
+}
+
+// The simulations to force through a root move.
+fn forced_visits(k: f32, prior: f32, parent_visits: u32) -> f32 {
+    (k * prior * parent_visits as f32).sqrt()
+}
+
+impl TreePolicy for ForcedPlayouts<'_> {
+    fn score(&self, child: &ChildStats, parent_visits: u32) -> f32 {
+        if (child.visits as f32) < forced_visits(self.k, child.prior, parent_visits) {
+            return f32::INFINITY;
+        }
+        self.policy.score(child, parent_visits)
+    }
+}
+
+// The visit distribution of these root moves. With forced playouts, each
+// move but the most visited loses as many of its forced visits as it can
+// while still scoring below the most visited, and moves left with a single
+// visit are dropped.
+fn policy_target(
+    children: &[ChildStats], policy: &dyn TreePolicy, forced_playouts: Option<f32>,
+) -> Vec<f32> {
+    let parent_visits = children.iter().map(|child| child.visits).sum::<u32>();
+    let mut visits = children.iter().map(|child| child.visits as f32).collect::<Vec<_>>();
+    let best = (0..children.len()).max_by_key(|&i| children[i].visits);
+    if let (Some(k), Some(best)) = (forced_playouts, best) {
+        let best_score = policy.score(&children[best], parent_visits);
+        for (i, child) in children.iter().enumerate() {
+            if i == best || child.visits == 0 {
+                continue;
+            }
+            let forced = forced_visits(k, child.prior, parent_visits) as u32;
+            let win_ratio = child.win_ratio();
+            let pruned = (0..=forced.min(child.visits))
+                .rev()
+                .map(|pruned| child.visits - pruned)
+                .find(|&left| {
+                    // Keep the win ratio of the pruned visits.
+                    let score = ((2.0 * win_ratio - 1.0) * left as f32).round() as i32;
+                    let stats = ChildStats { visits: left, score, prior: child.prior };
+                    left == child.visits || policy.score(&stats, parent_visits) < best_score
+                })
+                .unwrap_or(child.visits);
+            visits[i] = if pruned <= 1 { 0.0 } else { pruned as f32 };
+        }
+    }
+    let total = visits.iter().sum::<f32>();
+    if total > 0.0 {
+        visits.iter_mut().for_each(|v| *v /= total);
+    }
+    visits
+}
+
+#[test]
+fn test_policy_target() {
+    let children = [
+        ChildStats { visits: 80, score: 40, prior: 0.5 },
+        ChildStats { visits: 18, score: -10, prior: 0.25 },
+        ChildStats { visits: 2, score: -2, prior: 0.05 },
+    ];
+    let puct = Puct::default();
+    assert_eq!(policy_target(&children, &puct, None), vec![0.8, 0.18, 0.02]);
+    // Up to sqrt(2 * prior * 100) visits are pruned from the others.
+    let target = policy_target(&children, &puct, Some(2.0));
+    assert_eq!(target, vec![80.0 / 91.0, 11.0 / 91.0, 0.0]);
+    assert!((target.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_tree_policies() {
+    let unvisited = ChildStats { visits: 0, score: 0, prior: 0.5 };
+    let winning = ChildStats { visits: 10, score: 10, prior: 0.1 };
+    let losing = ChildStats { visits: 10, score: -10, prior: 0.4 };
+    let uct = Uct::default();
+    assert_eq!(uct.score(&unvisited, 20), f32::INFINITY);
+    assert!(uct.score(&winning, 20) > uct.score(&losing, 20));
+    assert_eq!(Uct { exploration: 0.0 }.score(&winning, 20), 1.0);
+    let puct = Puct::default();
+    assert_eq!(puct.score(&unvisited, 16), 0.5 + 0.5 * 4.0);
+    assert_eq!(Puct { exploration: 0.0 }.score(&losing, 20), 10.0);
+    let tuned = Ucb1Tuned::default();
+    assert_eq!(tuned.score(&unvisited, 20), f32::INFINITY);
+    // Exploration is capped by the variance of a win or a loss.
+    assert!(tuned.score(&winning, 20) < uct.score(&winning, 20));
+    assert!(tuned.score(&winning, 20) > tuned.score(&losing, 20));
+
+    let unpruning = ProgressiveUnpruning { initial: 2, visits: 10, growth: 2.0 };
+    assert_eq!(unpruning.width(9), 2);
+    assert_eq!(unpruning.width(10), 3);
+    assert_eq!(unpruning.width(39), 4);
+    assert_eq!(unpruning.width(40), 5);
+
+    let schedule = ExplorationSchedule { start: 2.0, end: 0.5, horizon: 100 };
+    assert_eq!(schedule.exploration(0), 2.0);
+    assert_eq!(schedule.exploration(50), 1.25);
+    assert_eq!(schedule.exploration(1000), 0.5);
+    let scheduled = Scheduled { policy: &uct, exploration: 0.0 };
+    assert_eq!(scheduled.score(&winning, 20), 1.0);
+}
+
+#[test]
+fn test_node_stats() {
+    assert_eq!(std::mem::size_of::<Node<[u8; 8]>>(), 64);
+    let node = Node::<u8>::new();
+    node.pre_update_stats();
+    node.pre_update_stats();
+    assert_eq!(node.stats(), (2, -2));
+    node.update_stats(1);
+    node.update_stats(-1);
+    assert_eq!(node.stats(), (2, 0));
+}
+
+#[test]
+fn test_child_race() {
+    let tree = Tree::<u8>::default();
+    let root = tree.get(tree.alloc(Node::new()));
+    root.expansion.try_set(Box::new(NodeExpansion {
+        children: (0..100)
+            .map(|m| Child { m, prior: 0.0, node: AtomicU32::new(0), evaluation: None })
+            .collect(),
+    }));
+    let children = &root.expansion.get().unwrap().children;
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for child in children.iter() {
+                    child.node(&tree).add_stats(1, 0);
+                }
+            });
+        }
+    });
+    // Every node created is reachable from the root.
+    assert_eq!(tree.len(), 101);
+    assert!(children.iter().all(|child| child.stats(&tree) == (4, 0)));
+}
+
+/// A subtree of the search tree, as seen by a [GcPolicy].
+#[derive(Clone, Copy, Debug)]
+pub struct SubtreeInfo {
+    /// Simulations that passed through the root of the subtree.
+    pub visits: u32,
+    /// Moves from the root of the search to the root of the subtree.
+    pub depth: u32,
+    /// How many simulations the search had run when one last passed through
+    /// the subtree.
+    pub last_visit: u32,
+}
+
+/// Chooses which subtrees to evict when the search tree outgrows
+/// [MCTSOptions::with_max_tree_nodes].
+pub trait GcPolicy: Send + Sync {
+    /// How strongly to keep this subtree. Subtrees are kept from the highest
+    /// retention down until the tree is small enough, and a subtree is only
+    /// ever kept along with its parent.
+    fn retention(&self, subtree: &SubtreeInfo) -> f64;
+}
+
+/// Evict the least visited subtrees first. This is the default.
+pub struct EvictLeastVisited;
+
+impl GcPolicy for EvictLeastVisited {
+    fn retention(&self, subtree: &SubtreeInfo) -> f64 {
+        subtree.visits as f64
+    }
+}
+
+/// Evict the subtrees that went longest without a visit first.
+pub struct EvictOldest;
+
+impl GcPolicy for EvictOldest {
+    fn retention(&self, subtree: &SubtreeInfo) -> f64 {
+        subtree.last_visit as f64
+    }
+}
+
+/// Evict the deepest subtrees first, keeping the top of the tree intact.
+pub struct EvictDeepest;
+
+impl GcPolicy for EvictDeepest {
+    fn retention(&self, subtree: &SubtreeInfo) -> f64 {
+        -(subtree.depth as f64)
+    }
+}
+
+// A node that could be kept in the collected tree.
+struct Candidate {
+    retention: f64,
+    depth: u32,
+    // Index in the old tree.
+    old: u32,
+    // Its parent's index in the new tree, and its position among the
+    // parent's children.
+    parent: u32,
+    child: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.retention.total_cmp(&other.retention)
+    }
+}
+
+// Rebuild the tree from at most `keep` of its nodes, choosing them by the
+// policy. Evicted children are forgotten, and start over on their next visit.
+fn collect_garbage<M: Copy>(mut old: Tree<M>, policy: &dyn GcPolicy, keep: usize) -> Tree<M> {
+    let tree = Tree::default();
+    let mut candidates = BinaryHeap::new();
+    candidates.push(Candidate { retention: f64::INFINITY, depth: 0, old: 0, parent: 0, child: 0 });
+    while tree.len() < keep.max(1) {
+        let Some(candidate) = candidates.pop() else {
+            break;
+        };
+        let node = old.get_mut(candidate.old);
+        let index = tree.alloc(Node {
+            stats: AtomicU64::new(*node.stats.get_mut()),
+            winner: AtomicI32::new(*node.winner.get_mut()),
+            last_visit: AtomicU32::new(*node.last_visit.get_mut()),
+            expansion: AtomicBox::default(),
+        });
+        let expansion = node.expansion.take();
+        if index != 0 {
+            let parent = tree.get(candidate.parent).expansion.get().unwrap();
+            parent.children[candidate.child].node.store(index, Relaxed);
+        }
+        if let Some(mut expansion) = expansion {
+            for (i, child) in expansion.children.iter_mut().enumerate() {
+                let old_child = std::mem::take(child.node.get_mut());
+                if old_child != 0 {
+                    let depth = candidate.depth + 1;
+                    let info = old.get(old_child).subtree_info(depth);
+                    candidates.push(Candidate {
+                        retention: policy.retention(&info),
+                        depth,
+                        old: old_child,
+                        parent: index,
+                        child: i,
+                    });
+                }
+            }
+            tree.get(index).expansion.try_set(expansion);
+        }
+    }
+    tree
+}
+
+#[test]
+fn test_collect_garbage() {
+    // A root with two children, the first of which has a child.
+    let build = || {
+        let tree = Tree::<u8>::default();
+        let root = tree.get(tree.alloc(Node::new()));
+        root.expansion.try_set(Box::new(NodeExpansion {
+            children: (0..2)
+                .map(|m| Child { m, prior: 0.0, node: AtomicU32::new(0), evaluation: None })
+                .collect(),
+        }));
+        let children = &root.expansion.get().unwrap().children;
+        let first = children[0].node(&tree);
+        first.add_stats(10, 0);
+        first.last_visit.store(1, Relaxed);
+        first.expansion.try_set(Box::new(NodeExpansion {
+            children: Box::new([Child {
+                m: 0,
+                prior: 0.0,
+                node: AtomicU32::new(0),
+                evaluation: None,
+            }]),
+        }));
+        first.expansion.get().unwrap().children[0].node(&tree).add_stats(5, 0);
+        children[1].node(&tree).add_stats(1, 0);
+        children[1].node(&tree).last_visit.store(2, Relaxed);
+        tree
+    };
+    // The visits of the children and grandchild, if they were kept.
+    let visited = |tree: Tree<u8>| {
+        let root = tree.get(0).expansion.get().unwrap();
+        let grandchild = root.children[0].get(&tree).and_then(|node| {
+            node.expansion.get().unwrap().children[0].get(&tree).map(Node::visits)
+        });
+        (root.children[0].stats(&tree).0, root.children[1].stats(&tree).0, grandchild)
+    };
+    assert_eq!(visited(collect_garbage(build(), &EvictDeepest, 4)), (10, 1, Some(5)));
+    assert_eq!(visited(collect_garbage(build(), &EvictLeastVisited, 3)), (10, 0, Some(5)));
+    assert_eq!(visited(collect_garbage(build(), &EvictOldest, 3)), (10, 1, None));
+    assert_eq!(visited(collect_garbage(build(), &EvictDeepest, 3)), (10, 1, None));
+    assert_eq!(visited(collect_garbage(build(), &EvictDeepest, 1)), (0, 0, None));
+}
+
+#[test]
+fn test_tree_shape() {
+    // A root with three children, the first two of which have two children
+    // each, only one of which has been allocated.
+    let tree = Tree::<u8>::default();
+    let root = tree.get(tree.alloc(Node::new()));
+    let expansion = |moves: u8| {
+        Box::new(NodeExpansion {
+            children: (0..moves)
+                .map(|m| Child { m, prior: 0.0, node: AtomicU32::new(0), evaluation: None })
+                .collect(),
+        })
+    };
+    root.expansion.try_set(expansion(3));
+    let children = &root.expansion.get().unwrap().children;
+    for child in &children[..2] {
+        let node = child.node(&tree);
+        node.expansion.try_set(expansion(2));
+        node.expansion.get().unwrap().children[0].node(&tree);
+    }
+    children[2].node(&tree);
+    let shape = root.shape(&tree);
+    assert_eq!(shape.nodes_per_depth, vec![1, 3, 2]);
+    assert_eq!(shape.max_depth(), 2);
+    // Five children under three expanded nodes.
+    assert_eq!(shape.mean_branching, 5.0 / 3.0);
+    assert_eq!(
+        Node::<u8>::new().shape(&tree),
+        TreeShape { nodes_per_depth: vec![1], mean_branching: 0.0 }
+    );
+}
+
 /// Options for MonteCarloTreeSearch.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MCTSOptions {
     pub verbose: bool,
     max_rollout_depth: u32,
     rollouts_before_expanding: u32,
+    rollouts_per_leaf: u32,
+    rollout_cycle_detection: bool,
     // None means use num_cpus.
     num_threads: Option<usize>,
+    max_tree_nodes: Option<usize>,
+    max_expansion_depth: Option<u32>,
+    tree_shape: bool,
+    // Not serialized, so deserialized options use the default policy.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_gc_policy"))]
+    gc_policy: Arc<dyn GcPolicy>,
+    // None means Uct, or Puct with an evaluator. Not serialized either.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    tree_policy: Option<Arc<dyn TreePolicy>>,
+    forced_playouts: Option<f32>,
+    exploration_schedule: Option<ExplorationSchedule>,
+    progressive_unpruning: Option<ProgressiveUnpruning>,
+    time_allocator: TimeAllocator,
+}
+
+fn default_gc_policy() -> Arc<dyn GcPolicy> {
+    Arc::new(EvictLeastVisited)
 }
 
 impl Default for MCTSOptions {
@@ -126,7 +866,18 @@ impl Default for MCTSOptions {
             verbose: false,
             max_rollout_depth: 100,
             rollouts_before_expanding: 0,
+            rollouts_per_leaf: 1,
+            rollout_cycle_detection: false,
             num_threads: None,
+            max_tree_nodes: None,
+            max_expansion_depth: None,
+            tree_shape: false,
+            gc_policy: default_gc_policy(),
+            tree_policy: None,
+            forced_playouts: None,
+            exploration_schedule: None,
+            progressive_unpruning: None,
+            time_allocator: TimeAllocator::new(),
         }
     }
 }
@@ -153,11 +904,105 @@ impl MCTSOptions {
         self
     }
 
+    /// Score rollouts that repeat a position as draws as soon as they do,
+    /// instead of letting them cycle until the maximum rollout depth. This
+    /// needs `Game::zobrist_hash`.
+    pub fn with_rollout_cycle_detection(mut self) -> Self {
+        self.rollout_cycle_detection = true;
+        self
+    }
+
+    /// How many rollouts to run from each leaf a simulation reaches,
+    /// backing up their mean as that many visits. The thread that reached
+    /// the leaf runs them one after another, while the other threads keep
+    /// descending. When rollouts are cheap, this spends more of each
+    /// thread's time rolling out and less contending for the shared tree,
+    /// at the cost of fewer leaves explored. Each rollout counts toward the
+    /// limit of `set_max_rollouts`. Defaults to 1.
+    pub fn with_rollouts_per_leaf(mut self, rollouts: u32) -> Self {
+        self.rollouts_per_leaf = rollouts.max(1);
+        self
+    }
+
     /// How many threads to run. Defaults to num_cpus.
     pub fn with_num_threads(mut self, threads: usize) -> Self {
         self.num_threads = Some(threads);
         self
     }
+
+    /// Limit the size of the search tree. Whenever it grows past this many
+    /// nodes, the search pauses to evict subtrees until it's down to half
+    /// this size. Limits below 2 are raised to 2, to leave room for the root
+    /// and the next node searched.
+    pub fn with_max_tree_nodes(mut self, nodes: usize) -> Self {
+        self.max_tree_nodes = Some(nodes.max(2));
+        self
+    }
+
+    /// Only expand nodes up to this many moves from the root. Simulations
+    /// that reach deeper leaves roll out or evaluate them in place, which
+    /// bounds the tree for very deep games while the top stays well
+    /// explored.
+    pub fn with_max_expansion_depth(mut self, depth: u32) -> Self {
+        self.max_expansion_depth = Some(depth);
+        self
+    }
+
+    /// Choose which subtrees to evict when the tree outgrows its limit.
+    /// Defaults to [EvictLeastVisited].
+    pub fn with_gc_policy(mut self, policy: Arc<dyn GcPolicy>) -> Self {
+        self.gc_policy = policy;
+        self
+    }
+
+    /// Choose which move to follow from each node during the search.
+    /// Defaults to [Uct], or to [Puct] for searches with an evaluator.
+    pub fn with_tree_policy(mut self, policy: Arc<dyn TreePolicy>) -> Self {
+        self.tree_policy = Some(policy);
+        self
+    }
+
+    /// Anneal the tree policy's exploration constant over each search on
+    /// this schedule, instead of keeping it fixed. Policies without an
+    /// exploration constant ignore it.
+    pub fn with_exploration_schedule(mut self, schedule: ExplorationSchedule) -> Self {
+        self.exploration_schedule = Some(schedule);
+        self
+    }
+
+    /// Consider only the moves with the highest priors from each node at
+    /// first, and admit the others one by one as the node is visited more.
+    /// Without an evaluator, the priors are uniform, and moves are admitted
+    /// in the order they are generated.
+    pub fn with_progressive_unpruning(mut self, unpruning: ProgressiveUnpruning) -> Self {
+        self.progressive_unpruning = Some(unpruning);
+        self
+    }
+
+    /// Force at least `sqrt(k * prior * visits)` simulations through each
+    /// root move, as KataGo does with `k = 2`, so that the policy target
+    /// sees every move, and prune the forced simulations back out of
+    /// [MonteCarloTreeSearch::policy_target].
+    pub fn with_forced_playouts(mut self, k: f32) -> Self {
+        self.forced_playouts = Some(k);
+        self
+    }
+
+    /// Record the shape of the tree at the end of each search, for
+    /// [MonteCarloTreeSearch::tree_shape]. This walks the whole tree, so it
+    /// is off by default.
+    pub fn with_tree_shape(mut self) -> Self {
+        self.tree_shape = true;
+        self
+    }
+
+    /// Budget each move with this allocator when told the time left on the
+    /// clock with `Strategy::set_time_left`. With no iterations to finish,
+    /// each search runs for the soft budget.
+    pub fn with_time_allocator(mut self, allocator: TimeAllocator) -> Self {
+        self.time_allocator = allocator;
+        self
+    }
 }
 
 /// Advanced random rollout policy for Monte Carlo Tree Search.
@@ -170,19 +1015,33 @@ pub trait RolloutPolicy {
     /// The provided move vec is for scratch space.
     fn random_move(
         &self, state: &mut <Self::G as Game>::S, move_scratch: &mut Vec<<Self::G as Game>::M>,
-        rng: &mut ThreadRng,
+        rng: &mut StdRng,
     ) -> <Self::G as Game>::M;
 
+    /// Declare a rollout decided before the game ends, e.g. by a mercy rule
+    /// when one side's lead is too large to overcome, to save playing it
+    /// out. Checked before each move of a rollout. Unlike the end of the
+    /// game, this never proves a node won or lost. By default, rollouts are
+    /// played to the end.
+    fn decided(&self, _state: &<Self::G as Game>::S) -> Option<Winner> {
+        None
+    }
+
     /// Implementation of a rollout over many random moves. Not needed to be overridden.
-    fn rollout(&self, options: &MCTSOptions, state: &<Self::G as Game>::S) -> i32
+    /// The provided move vec is for scratch space, and is reused across
+    /// rollouts by the same thread.
+    fn rollout(
+        &self, options: &MCTSOptions, state: &<Self::G as Game>::S,
+        moves: &mut Vec<<Self::G as Game>::M>, rng: &mut StdRng,
+    ) -> i32
     where
         <Self::G as Game>::S: Clone,
     {
-        let mut rng = rand::thread_rng();
         let mut depth = options.max_rollout_depth;
         let mut state = state.clone();
-        let mut moves = Vec::new();
         let mut sign = 1;
+        // Hashes of the positions so far, if detecting cycles.
+        let mut seen = Vec::new();
         loop {
             if let Some(winner) = Self::G::get_winner(&state) {
                 let first = depth == options.max_rollout_depth;
@@ -204,17 +1063,35 @@ pub trait RolloutPolicy {
                     Winner::Draw => 0,
                 } * sign;
             }
+            if let Some(winner) = self.decided(&state) {
+                return match winner {
+                    Winner::PlayerJustMoved => 1,
+                    Winner::PlayerToMove => -1,
+                    Winner::Draw => 0,
+                } * sign;
+            }
 
             if depth == 0 {
                 return 0;
             }
+            if options.rollout_cycle_detection {
+                let hash = Self::G::zobrist_hash(&state);
+                if seen.contains(&hash) {
+                    // A repetition, which could go on forever.
+                    return 0;
+                }
+                seen.push(hash);
+            }
 
             moves.clear();
-            let m = self.random_move(&mut state, &mut moves, &mut rng);
+            let player = Self::G::player_to_move(&state);
+            let m = self.random_move(&mut state, moves, rng);
             if let Some(new_state) = Self::G::apply(&mut state, m) {
                 state = new_state;
             }
-            sign = -sign;
+            if passes_turn::<Self::G>(player, &state) {
+                sign = -sign;
+            }
             depth -= 1;
         }
     }
@@ -228,34 +1105,269 @@ impl<G: Game> RolloutPolicy for DumbRolloutPolicy<G> {
     type G = G;
     fn random_move(
         &self, state: &mut <Self::G as Game>::S, moves: &mut Vec<<Self::G as Game>::M>,
-        rng: &mut ThreadRng,
+        rng: &mut StdRng,
     ) -> <Self::G as Game>::M {
         G::generate_moves(state, moves);
         *moves.choose(rng).unwrap()
     }
 }
 
+// How often to report progress during a search.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// The most visited line from this node.
+fn principal_variation<'a, M: Copy>(
+    tree: &'a Tree<M>, mut node: &'a Node<M>, rng: &mut StdRng,
+) -> Vec<M> {
+    let mut pv = Vec::new();
+    while let Some((child, best)) =
+        node.best_child(tree, &Uct { exploration: 0.0 }, usize::MAX, rng)
+    {
+        pv.push(child.m);
+        node = best;
+    }
+    pv
+}
+
+// The `k` most visited root moves, with their lines.
+fn top_lines<M: Copy>(tree: &Tree<M>, k: usize, rng: &mut StdRng) -> Vec<MoveAnalysis<M>> {
+    let Some(expansion) = tree.get(0).expansion.get().filter(|_| k > 0) else {
+        return Vec::new();
+    };
+    let mut children =
+        expansion.children.iter().filter(|child| child.stats(tree).0 > 0).collect::<Vec<_>>();
+    children.sort_by_key(|child| !child.stats(tree).0);
+    children
+        .into_iter()
+        .take(k)
+        .map(|child| {
+            let (visits, score) = child.stats(tree);
+            let win_probability = match child.winner(tree) {
+                WIN => 1.0,
+                LOSS => 0.0,
+                _ => ChildStats { visits, score, prior: child.prior }.win_ratio(),
+            };
+            let mut line = vec![child.m];
+            if let Some(node) = child.get(tree) {
+                line.extend(principal_variation(tree, node, rng));
+            }
+            MoveAnalysis {
+                m: child.m,
+                value: None,
+                win_probability: Some(win_probability),
+                principal_variation: line,
+            }
+        })
+        .collect()
+}
+
+// The most visited root moves and the principal variation.
+fn snapshot<G: Game>(tree: &Tree<G::M>, s: &G::S, pv: &[G::M], start_time: Instant) -> String
+where
+    G::S: Clone,
+    G::M: Copy,
+{
+    let root = tree.get(0);
+    let mut text = format!(
+        "snapshot {:.3}s simulations {}\n",
+        start_time.elapsed().as_secs_f64(),
+        root.visits()
+    );
+    if let Some(expansion) = root.expansion.get() {
+        let mut children = expansion
+            .children
+            .iter()
+            .map(|child| {
+                let (visits, score) = child.stats(tree);
+                (visits, score, Some(child.m))
+            })
+            .collect::<Vec<_>>();
+        children.sort_by_key(|t| !t.0);
+        for (visits, score, m) in children.into_iter().take(10) {
+            let win_rate = (score as f64 + visits as f64) / (visits.max(1) as f64 * 2.0);
+            text += &format!(
+                "  {:<12} {:>8} visits {:>6.2}% wins\n",
+                move_id::<G>(s, m),
+                visits,
+                win_rate * 100.0
+            );
+        }
+    }
+    text += &format!("pv: {}\n", pv_string::<G>(pv, s));
+    text
+}
+
+// The chance that the player to move at the root wins, from a proof or
+// from the results of the simulations of its moves.
+fn root_win_probability<M: Copy>(tree: &Tree<M>) -> Option<f32> {
+    let root = tree.get(0);
+    // The root's winner is for the player who moved into it.
+    match root.winner.load(Relaxed) {
+        WIN => return Some(0.0),
+        LOSS => return Some(1.0),
+        _ => {}
+    }
+    let (visits, score) =
+        root.expansion.get()?.children.iter().fold((0, 0), |(visits, score), child| {
+            let (child_visits, child_score) = child.stats(tree);
+            (visits + child_visits as i64, score + child_score as i64)
+        });
+    (visits > 0)
+        .then(|| ChildStats { visits: visits as u32, score: score as i32, prior: 0.0 }.win_ratio())
+}
+
+// Report the progress of a search, and write a snapshot if one is due.
+fn report<G: Game>(
+    tree: &Tree<G::M>, s: &G::S, start_time: Instant, rng: &mut StdRng,
+    progress: &mut Option<Box<dyn SearchProgress<G::M>>>, snapshots: &mut Option<Snapshots>,
+) where
+    G::S: Clone,
+    G::M: Copy,
+{
+    let root = tree.get(0);
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        simulations = root.visits(),
+        elapsed_ms = start_time.elapsed().as_millis() as u64,
+        "simulated"
+    );
+    if let Some(progress) = progress.as_mut() {
+        let pv = principal_variation(tree, tree.get(0), rng);
+        progress.report(&SearchInfo {
+            best_move: pv.first().copied(),
+            win_probability: root_win_probability(tree),
+            nodes: root.visits() as u64,
+            depth: pv.len().min(u8::MAX as usize) as u8,
+            principal_variation: pv,
+            elapsed: start_time.elapsed(),
+            ..SearchInfo::default()
+        });
+    }
+    if let Some(snapshots) = snapshots.as_mut() {
+        if snapshots.due() {
+            let pv = principal_variation(tree, tree.get(0), rng);
+            snapshots.write(&snapshot::<G>(tree, s, &pv, start_time));
+        }
+    }
+}
+
+// A search in progress on the calling thread, between steps.
+struct Stepping<G: Game> {
+    tree: Tree<G::M>,
+    state: G::S,
+    moves: Vec<G::M>,
+    rng: StdRng,
+    // Simulations left to run.
+    remaining: u32,
+    start_time: Instant,
+    last_report: Instant,
+}
+
+// Looks up a root move's stats for a warm start.
+type WarmStart<M> = Arc<dyn Fn(M) -> Option<MoveStats<M>> + Send + Sync>;
+
 /// A strategy that uses random playouts to explore the game tree to decide on the best move.
 /// This can be used without an Evaluator, just using the rules of the game.
 pub struct MonteCarloTreeSearch<G: Game> {
     options: MCTSOptions,
     max_rollouts: u32,
     max_time: Duration,
+    // The clock to budget each move from, instead of a fixed timeout.
+    time_left: Option<TimeLeft>,
+    // Moves played in the game, as told by notify_move.
+    ply: u32,
     timeout: Arc<AtomicBool>,
-    rollout_policy: Option<Box<dyn RolloutPolicy<G = G> + Sync>>,
+    stop: StopSignal,
+    // Shared with copies of the searcher.
+    rollout_policy: Option<Arc<dyn RolloutPolicy<G = G> + Send + Sync>>,
+    evaluator: Option<Arc<dyn PolicyValueEvaluator<G = G> + Send + Sync>>,
     pv: Vec<G::M>,
+    // The number of root moves to report lines for, when analyzing.
+    multi_pv: usize,
+    // The lines of the last search, when analyzing.
+    lines: Vec<MoveAnalysis<G::M>>,
+    // Looks up the stats to seed the next search's root moves with.
+    warm_start: Option<WarmStart<G::M>>,
+    // Visits seeded into the root of the current search.
+    seeded_visits: u32,
+    // Stats from the last search.
+    root_stats: Vec<MoveStats<G::M>>,
+    // WIN or LOSS if the search proved the root won or lost for the player
+    // who moved into it.
+    root_winner: i32,
+    win_probability: Option<f32>,
+    policy_target: Vec<(G::M, f32)>,
+    simulations: u64,
+    tree_nodes: usize,
+    tree_children: usize,
+    tree_bytes: usize,
+    tree_shape: Option<TreeShape>,
+    wall_time: Duration,
+    // Only used from the calling thread, but the workers share self.
+    progress: Mutex<Option<Box<dyn SearchProgress<G::M>>>>,
+    snapshots: Mutex<Option<Snapshots>>,
+    stepping: Mutex<Option<Stepping<G>>>,
+    rng: StdRng,
+    tablebase: Option<SharedTablebase<G>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    background: Option<BackgroundSearch<MonteCarloTreeSearch<G>, G::M>>,
+    // Workers kept alive between searches, created by the first one.
+    #[cfg(not(target_arch = "wasm32"))]
+    thread_pool: Option<rayon::ThreadPool>,
     game_type: PhantomData<G>,
 }
 
+/// Copies have the same options, limits, rollout policy, evaluator, and
+/// tablebase, and their own tree, threads, and random numbers.
+impl<G: Game> FreshCopy for MonteCarloTreeSearch<G> {
+    fn fresh_copy(&self) -> Self {
+        let mut search = MonteCarloTreeSearch::new(self.options.clone());
+        search.max_rollouts = self.max_rollouts;
+        search.max_time = self.max_time;
+        search.time_left = self.time_left;
+        search.rollout_policy = self.rollout_policy.clone();
+        search.evaluator = self.evaluator.clone();
+        search.tablebase = self.tablebase.clone();
+        search
+    }
+}
+
 impl<G: Game> MonteCarloTreeSearch<G> {
     pub fn new(options: MCTSOptions) -> Self {
         Self {
             options,
             max_rollouts: 0,
             max_time: Duration::from_secs(5),
+            time_left: None,
+            ply: 0,
             timeout: Arc::new(AtomicBool::new(false)),
+            stop: StopSignal::new(),
             rollout_policy: None,
+            evaluator: None,
             pv: Vec::new(),
+            multi_pv: 0,
+            lines: Vec::new(),
+            warm_start: None,
+            seeded_visits: 0,
+            root_stats: Vec::new(),
+            root_winner: 0,
+            win_probability: None,
+            policy_target: Vec::new(),
+            simulations: 0,
+            tree_nodes: 0,
+            tree_children: 0,
+            tree_bytes: 0,
+            tree_shape: None,
+            wall_time: Duration::default(),
+            progress: Mutex::new(None),
+            snapshots: Mutex::new(None),
+            stepping: Mutex::new(None),
+            rng: new_rng(),
+            tablebase: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            background: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            thread_pool: None,
             game_type: PhantomData,
         }
     }
@@ -264,116 +1376,483 @@ impl<G: Game> MonteCarloTreeSearch<G> {
     /// random move generation to prefer certain kinds of moves, always choose
     /// winning moves, etc.
     pub fn new_with_policy(
-        options: MCTSOptions, policy: Box<dyn RolloutPolicy<G = G> + Sync>,
+        options: MCTSOptions, policy: Box<dyn RolloutPolicy<G = G> + Send + Sync>,
     ) -> Self {
-        Self {
-            options,
-            max_rollouts: 0,
-            max_time: Duration::from_secs(5),
-            timeout: Arc::new(AtomicBool::new(false)),
-            rollout_policy: Some(policy),
-            pv: Vec::new(),
-            game_type: PhantomData,
-        }
+        Self { rollout_policy: Some(Arc::from(policy)), ..Self::new(options) }
+    }
+
+    /// Create a searcher guided by a policy/value evaluator, such as a
+    /// `nn::NeuralEvaluator`. Each new node is expanded and evaluated instead
+    /// of rolled out, and its moves are explored in proportion to their
+    /// priors. The positions after a node's moves are evaluated together in
+    /// one batch when it is expanded, and the evaluator's policy must have
+    /// one prior per move.
+    pub fn new_with_evaluator(
+        options: MCTSOptions, evaluator: Box<dyn PolicyValueEvaluator<G = G> + Send + Sync>,
+    ) -> Self {
+        Self { evaluator: Some(Arc::from(evaluator)), ..Self::new(options) }
     }
 
     /// Instead of a timeout, run this many rollouts to choose a move.
     pub fn set_max_rollouts(&mut self, rollouts: u32) {
         self.max_time = Duration::default();
+        self.time_left = None;
         self.max_rollouts = rollouts;
     }
 
-    fn rollout(&self, state: &G::S) -> i32
+    /// The visits and scores of the root's moves at the end of the last
+    /// search.
+    pub fn root_stats(&self) -> &[MoveStats<G::M>] {
+        &self.root_stats
+    }
+
+    /// The shape of the search tree at the end of the last search, if it
+    /// was recorded with [MCTSOptions::with_tree_shape] or verbose output.
+    pub fn tree_shape(&self) -> Option<&TreeShape> {
+        self.tree_shape.as_ref()
+    }
+
+    /// The share of the visits to each of the root's moves at the end of the
+    /// last search, as a training target for an evaluator's policy. With
+    /// forced playouts, the forced visits are pruned from it.
+    pub fn policy_target(&self) -> &[(G::M, f32)] {
+        &self.policy_target
+    }
+
+    /// Seed the root's moves in the next search with these stats, scaled by
+    /// `weight`, as if that many simulations had already passed through
+    /// them. This warm-starts a search that can't reuse the tree of an
+    /// earlier one, e.g. with the `root_stats` of a search of the same
+    /// position by another engine, or before a restart. Moves without stats
+    /// start cold.
+    pub fn warm_start(&mut self, stats: &[MoveStats<G::M>], weight: f32)
+    where
+        G::M: PartialEq + Send + Sync + 'static,
+    {
+        let stats = stats
+            .iter()
+            .map(|stats| MoveStats {
+                m: stats.m,
+                visits: (stats.visits as f32 * weight).round() as u32,
+                score: (stats.score as f32 * weight).round() as i32,
+            })
+            .collect::<Vec<_>>();
+        self.warm_start = Some(Arc::new(move |m| stats.iter().find(|stats| stats.m == m).copied()));
+    }
+
+    fn tree_policy(&self) -> &dyn TreePolicy {
+        const UCT: Uct = Uct { exploration: 1.0 };
+        const PUCT: Puct = Puct { exploration: 1.0 };
+        match self.options.tree_policy.as_deref() {
+            Some(policy) => policy,
+            None if self.evaluator.is_some() => &PUCT,
+            None => &UCT,
+        }
+    }
+
+    // Budget this move from the clock, if one was given.
+    fn budget_time(&mut self, s: &G::S) {
+        if let Some(time) = self.time_left {
+            let ply = G::ply(s).unwrap_or(self.ply);
+            self.max_time = self
+                .options
+                .time_allocator
+                .budget_at_ply(&time, G::max_moves_remaining(s), Some(ply))
+                .soft;
+        }
+    }
+
+    /// Write a snapshot of the most visited root moves and the principal
+    /// variation to `writer` about every `interval` during searches, and at
+    /// the end of each search.
+    pub fn set_snapshots(&mut self, writer: Box<dyn std::io::Write + Send>, interval: Duration) {
+        *self.snapshots.get_mut().unwrap() = Some(Snapshots::new(writer, interval));
+    }
+
+    // Take back the searcher from a background search, keeping any settings
+    // changed in the meantime, and return the move it found.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore_from_background(&mut self, finished: Finished<Self, G::M>) -> Option<G::M> {
+        let mut searcher = finished.searcher;
+        searcher.max_rollouts = self.max_rollouts;
+        searcher.max_time = self.max_time;
+        searcher.time_left = self.time_left;
+        searcher.ply = self.ply;
+        searcher.warm_start = self.warm_start.take();
+        searcher.stop = self.stop.clone();
+        searcher.tablebase = self.tablebase.take();
+        *searcher.progress.get_mut().unwrap() =
+            self.progress.get_mut().unwrap().take().or(finished.progress);
+        if let Some(snapshots) = self.snapshots.get_mut().unwrap().take() {
+            *searcher.snapshots.get_mut().unwrap() = Some(snapshots);
+        }
+        *self = searcher;
+        finished.best_move
+    }
+
+    // Stop any background search, and return the move it found.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stop_background(&mut self) -> Option<G::M> {
+        let finished = self.background.take()?.stop();
+        self.restore_from_background(finished)
+    }
+
+    fn rollout(&self, state: &G::S, moves: &mut Vec<G::M>, rng: &mut StdRng) -> i32
     where
-        G: Sync,
         G::S: Clone,
     {
-        self.rollout_policy.as_ref().map(|p| p.rollout(&self.options, state)).unwrap_or_else(|| {
-            DumbRolloutPolicy::<G> { game_type: PhantomData }.rollout(&self.options, state)
-        })
+        match self.rollout_policy.as_ref() {
+            Some(policy) => policy.rollout(&self.options, state, moves, rng),
+            None => DumbRolloutPolicy::<G> { game_type: PhantomData }.rollout(
+                &self.options,
+                state,
+                moves,
+                rng,
+            ),
+        }
+    }
+
+    // Roll out from a leaf `rollouts_per_leaf` times, and return the sum of
+    // the results, setting `weight` to how many there were. A leaf that is
+    // already decided is only rolled out once.
+    fn leaf_rollouts(
+        &self, state: &G::S, moves: &mut Vec<G::M>, rng: &mut StdRng, weight: &mut u32,
+    ) -> i32
+    where
+        G::S: Clone,
+    {
+        let first = self.rollout(state, moves, rng);
+        if first == WIN || first == LOSS {
+            return first;
+        }
+        *weight = self.options.rollouts_per_leaf.max(1);
+        (1..*weight).fold(first, |sum, _| sum + self.rollout(state, moves, rng))
     }
 
-    // Explore the tree, make a new node, rollout, backpropagate.
-    fn simulate(&self, node: &Node<G::M>, state: &mut G::S, mut force_rollout: bool) -> Option<i32>
+    // Make the children of a node, with priors from the evaluator if there is
+    // one, and return the evaluator's value for the player to move. The node's
+    // evaluation is passed in if it was batched with its siblings', and its
+    // children are evaluated in one batch, with the node if needed, for when
+    // they are expanded, if `batch_children` is set.
+    fn expand(
+        &self, state: &G::S, moves: &mut Vec<G::M>, evaluation: Option<&PolicyValue>,
+        batch_children: bool,
+    ) -> (Box<NodeExpansion<G::M>>, Option<f32>)
     where
-        G: Sync,
         G::S: Clone,
     {
-        if self.timeout.load(Relaxed) {
-            return None;
+        let mut expansion = new_expansion::<G>(state, moves);
+        let Some(evaluator) = self.evaluator.as_ref() else {
+            return (expansion, None);
+        };
+        let mut states = Vec::new();
+        if evaluation.is_none() {
+            states.push(state.clone());
+        }
+        // The children evaluated, which are those that aren't over.
+        let mut batched = Vec::new();
+        if batch_children {
+            for (i, child) in expansion.children.iter().enumerate() {
+                let mut child_state = state.clone();
+                if let Some(new_state) = G::apply(&mut child_state, child.m) {
+                    child_state = new_state;
+                }
+                if G::get_winner(&child_state).is_none() {
+                    batched.push(i);
+                    states.push(child_state);
+                }
+            }
         }
-        let winner = node.winner.load(Relaxed);
-        if winner != 0 {
-            return Some(winner);
+        let mut results = Vec::with_capacity(states.len());
+        if !states.is_empty() {
+            evaluator.evaluate_batch(&states, &mut results);
         }
-        node.pre_update_stats();
+        let mut results = results.into_iter();
+        let own;
+        let result = match evaluation {
+            Some(result) => result,
+            None => {
+                own = results.next().unwrap_or_default();
+                &own
+            }
+        };
+        for (i, child_result) in batched.into_iter().zip(results) {
+            expansion.children[i].evaluation = Some(Box::new(child_result));
+        }
+        debug_assert_eq!(
+            result.policy.len(),
+            expansion.children.len(),
+            "the evaluator's policy doesn't match the moves"
+        );
+        if result.policy.len() == expansion.children.len() {
+            for (child, &prior) in expansion.children.iter_mut().zip(result.policy.iter()) {
+                child.prior = prior;
+            }
+            if self.options.progressive_unpruning.is_some() {
+                // Admit the likeliest moves first.
+                expansion.children.sort_by(|a, b| b.prior.total_cmp(&a.prior));
+            }
+        }
+        (expansion, Some(result.value))
+    }
 
-        if force_rollout {
-            return node.update_stats(self.rollout(state));
+    // A new tree for searching this state, with its root expanded and
+    // seeded with any warm start.
+    fn new_tree(&mut self, state: &G::S) -> Tree<G::M>
+    where
+        G::S: Clone,
+    {
+        let tree = Tree::<G::M>::default();
+        let root = tree.get(tree.alloc(Node::new()));
+        root.expansion.try_set(self.expand(state, &mut Vec::new(), None, true).0);
+        self.seeded_visits = 0;
+        if let Some(warm_start) = self.warm_start.take() {
+            for child in root.expansion.get().unwrap().children.iter() {
+                if let Some(stats) = warm_start(child.m).filter(|stats| stats.visits > 0) {
+                    // Keep the score within the range of the visits.
+                    let score = stats.score.clamp(-(stats.visits as i32), stats.visits as i32);
+                    child.node(&tree).add_stats(stats.visits, score);
+                    self.seeded_visits += stats.visits;
+                }
+            }
+            root.add_stats(self.seeded_visits, 0);
         }
+        tree
+    }
 
-        let expansion = match node.expansion.get() {
-            Some(expansion) => expansion,
-            None => {
+    // Run one simulation from the root: descend the tree by UCT, expand or
+    // roll out at the end of the path, and back the result up the path.
+    // Returns None if the search was stopped. Each thread passes its own
+    // `moves` buffer, to avoid allocating in every rollout and expansion.
+    fn simulate<'a>(
+        &self, tree: &'a Tree<G::M>, state: &mut G::S, path: &mut Path<'a, G>,
+        moves: &mut Vec<G::M>, rng: &mut StdRng,
+    ) -> Option<i32>
+    where
+        G::S: Clone,
+    {
+        path.nodes.clear();
+        path.passed.clear();
+        path.weight = 1;
+        let result = self.descend(tree, state, path, moves, rng);
+
+        // Restore the state.
+        while let Some((m, old)) = path.applied.pop() {
+            match old {
+                Some(old) => *state = old,
+                None => G::undo(state, m),
+            }
+        }
+
+        // Propagate up forced wins and losses, and backpropagate.
+        let mut child_result = result?;
+        while let Some(node) = path.nodes.pop() {
+            // The child's result is for the player to move at this node. If
+            // they also moved into it, the result is theirs too.
+            let passed = path.nodes.is_empty() || path.passed[path.nodes.len() - 1];
+            let result = if child_result == WIN {
+                // Having a guaranteed win child makes you a loser parent.
+                LOSS
+            } else if child_result == LOSS {
+                // Having all guaranteed loser children makes you a winner parent.
+                let expansion = node.expansion.get().unwrap();
+                if expansion.children.iter().all(|child| child.winner(tree) == LOSS) {
+                    WIN
+                } else {
+                    -1
+                }
+            } else {
+                -child_result
+            };
+            let result = if passed { result } else { -result };
+            child_result = node.update_weighted_stats(result, path.weight)?;
+        }
+        Some(child_result)
+    }
+
+    // Walk down from the root, leaving the expanded nodes on the path in
+    // `path.nodes`, and return the result for the child of the last one.
+    fn descend<'a>(
+        &self, tree: &'a Tree<G::M>, state: &mut G::S, path: &mut Path<'a, G>,
+        moves: &mut Vec<G::M>, rng: &mut StdRng,
+    ) -> Option<i32>
+    where
+        G::S: Clone,
+    {
+        let mut node = tree.get(0);
+        // The node's evaluation, if it was batched with its siblings'.
+        let mut evaluation = None;
+        let stamp = node.visits();
+        let mut force_rollout = false;
+        // Results from the state are for the opponent of the player to move,
+        // who moved into the node unless the last move kept the turn.
+        let mut passed = true;
+        let for_mover = |passed: bool, result: i32| if passed { result } else { -result };
+        loop {
+            if self.timeout.load(Relaxed) || self.stop.is_stopped() {
+                return None;
+            }
+            let winner = node.winner.load(Relaxed);
+            if winner != 0 {
+                return Some(winner);
+            }
+            node.pre_update_stats();
+            node.last_visit.store(stamp, Relaxed);
+
+            if force_rollout {
+                let result = self.leaf_rollouts(state, moves, rng, &mut path.weight);
+                return node.update_weighted_stats(for_mover(passed, result), path.weight);
+            }
+
+            if node.expansion.get().is_none() {
                 // This is a leaf node.
-                if node.visits.load(SeqCst) <= self.options.rollouts_before_expanding {
+                let capped = self
+                    .options
+                    .max_expansion_depth
+                    .is_some_and(|depth| path.nodes.len() as u32 >= depth);
+                if self.evaluator.is_none()
+                    && (capped || node.visits() <= self.options.rollouts_before_expanding)
+                {
                     // Just rollout from here.
-                    return node.update_stats(self.rollout(state));
-                } else {
-                    // Check for terminal node.
-                    match G::get_winner(state) {
-                        Some(Winner::PlayerJustMoved) => return node.update_stats(WIN),
-                        Some(Winner::PlayerToMove) => return node.update_stats(LOSS),
-                        Some(Winner::Draw) => return node.update_stats(0),
-                        _ => {}
+                    let result = self.leaf_rollouts(state, moves, rng, &mut path.weight);
+                    return node.update_weighted_stats(for_mover(passed, result), path.weight);
+                }
+                // Check for terminal or solved node.
+                let winner = G::get_winner(state)
+                    .or_else(|| self.tablebase.as_ref()?.probe(state).map(|result| result.winner));
+                match winner {
+                    Some(Winner::PlayerJustMoved) => {
+                        return node.update_stats(for_mover(passed, WIN))
+                    }
+                    Some(Winner::PlayerToMove) => {
+                        return node.update_stats(for_mover(passed, LOSS))
                     }
-                    // Expand this node, and force a rollout when we recurse.
-                    force_rollout = true;
-                    node.expansion.try_set(new_expansion::<G>(state))
+                    Some(Winner::Draw) => return node.update_stats(0),
+                    _ => {}
+                }
+                let (expansion, value) = self.expand(state, moves, evaluation, !capped);
+                if !capped {
+                    node.expansion.try_set(expansion);
+                }
+                if let Some(value) = value {
+                    // Back up a win or loss with the odds of the value, to
+                    // keep integer scores while averaging to the value.
+                    let result = if rng.gen::<f32>() * 2.0 - 1.0 < value { 1 } else { -1 };
+                    return node.update_stats(for_mover(passed, -result));
                 }
+                // Force a rollout from the next node.
+                force_rollout = true;
             }
-        };
 
-        // Recurse.
-        let next = match node.best_child(1.) {
-            Some(child) => child,
-            // TODO: Weird race condition?
-            None => return Some(0),
-        };
-        let m = next.m.as_ref().unwrap();
-        let mut new = AppliedMove::<G>::new(state, *m);
-        let child_result = self.simulate(next, &mut new, force_rollout)?;
-
-        // Propagate up forced wins and losses.
-        let result = if child_result == WIN {
-            // Having a guaranteed win child makes you a loser parent.
-            LOSS
-        } else if child_result == LOSS {
-            // Having all guaranteed loser children makes you a winner parent.
-            if expansion.children.iter().all(|node| node.winner.load(Relaxed) == LOSS) {
-                WIN
-            } else {
-                -1
+            let (scheduled, forced);
+            let mut policy = self.tree_policy();
+            if let Some(schedule) = self.options.exploration_schedule {
+                scheduled = Scheduled { policy, exploration: schedule.exploration(stamp) };
+                policy = &scheduled;
             }
-        } else {
-            -child_result
-        };
-
-        // Backpropagate.
-        node.update_stats(result)
+            if let (Some(k), true) = (self.options.forced_playouts, path.nodes.is_empty()) {
+                forced = ForcedPlayouts { policy, k };
+                policy = &forced;
+            }
+            let width = self
+                .options
+                .progressive_unpruning
+                .map_or(usize::MAX, |unpruning| unpruning.width(node.visits()));
+            let (child, next) = match node.best_child(tree, policy, width, rng) {
+                Some(child) => child,
+                // TODO: Weird race condition?
+                None => return Some(0),
+            };
+            let m = child.m;
+            evaluation = child.evaluation.as_deref();
+            path.nodes.push(node);
+            let player = G::player_to_move(state);
+            match G::apply(state, m) {
+                Some(new) => path.applied.push((m, Some(std::mem::replace(state, new)))),
+                None => path.applied.push((m, None)),
+            }
+            passed = passes_turn::<G>(player, state);
+            path.passed.push(passed);
+            node = next;
+        }
     }
 }
 
-impl<G: Game> Strategy<G> for MonteCarloTreeSearch<G>
+/// `Send`, except on wasm32, where searches stay on the calling thread and
+/// the game's types don't need to be thread-safe.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+// Searches on wasm32 stay on the calling thread.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+/// `Sync`, except on wasm32, like `MaybeSend`.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSync: Sync {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Sync> MaybeSync for T {}
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSync {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSync for T {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<G: Game> MonteCarloTreeSearch<G>
 where
     G: Sync,
     G::S: Clone + Send,
-    G::M: Copy + Sync,
+    G::M: Copy + Send + Sync,
 {
-    fn choose_move(&mut self, s: &G::S) -> Option<G::M> {
+    // Run the workers until they finish their simulations, the search stops,
+    // or the tree outgrows its limit. Meanwhile, if `reporting`, call
+    // `report` periodically from this thread.
+    fn search_round(
+        &self, tree: &Tree<G::M>, s: &G::S, rngs: &mut [StdRng], remaining: &mut [u32],
+        reporting: bool, report: &mut dyn FnMut(&Tree<G::M>),
+    ) {
+        let max_tree_nodes = self.options.max_tree_nodes.unwrap_or(usize::MAX);
+        let running = AtomicU32::new(rngs.len() as u32);
+        // Runs here, while the workers run on the pool.
+        self.thread_pool.as_ref().unwrap().in_place_scope(|scope| {
+            let main_thread = thread::current();
+            for (rng, remaining) in rngs.iter_mut().zip(remaining.iter_mut()) {
+                let mut state = s.clone();
+                let running = &running;
+                let main_thread = main_thread.clone();
+                scope.spawn(move |_| {
+                    let mut moves = Vec::new();
+                    let mut path = Path::default();
+                    while *remaining > 0 && tree.len() < max_tree_nodes {
+                        if self.simulate(tree, &mut state, &mut path, &mut moves, rng).is_none() {
+                            break;
+                        }
+                        *remaining = remaining.saturating_sub(path.weight);
+                    }
+                    running.fetch_sub(1, SeqCst);
+                    main_thread.unpark();
+                });
+            }
+
+            // Report progress from this thread while the workers search.
+            if reporting || cfg!(feature = "tracing") {
+                loop {
+                    thread::park_timeout(PROGRESS_INTERVAL);
+                    report(tree);
+                    if running.load(SeqCst) == 0 {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    fn search(&mut self, s: &G::S) -> Option<G::M> {
         let start_time = Instant::now();
-        let root = Box::new(Node::<G::M>::new(None));
-        root.expansion.try_set(new_expansion::<G>(s));
+        let mut tree = self.new_tree(s);
 
         let num_threads = self.options.num_threads.unwrap_or_else(num_cpus::get) as u32;
         let (rollouts_per_thread, extra) = if self.max_rollouts == 0 {
@@ -382,39 +1861,130 @@ where
             let rollouts_per_thread = self.max_rollouts / num_threads;
             (rollouts_per_thread, self.max_rollouts - rollouts_per_thread * num_threads)
         };
+        self.budget_time(s);
         self.timeout = if self.max_time == Duration::default() {
             Arc::new(AtomicBool::new(false))
         } else {
             timeout_signal(self.max_time)
         };
 
-        thread::scope(|scope| {
-            for i in 0..num_threads {
-                let node = &*root;
-                let mtcs = &*self;
-                let mut state = s.clone();
-                scope.spawn(move || {
-                    let rollouts = rollouts_per_thread + (i < extra) as u32;
-                    for _ in 0..rollouts {
-                        if mtcs.simulate(node, &mut state, false).is_none() {
-                            break;
-                        }
-                    }
-                });
+        // Seed each thread from the strategy's rng, so seeded single-threaded
+        // searches are reproducible.
+        let mut worker_rngs =
+            (0..num_threads).map(|_| StdRng::seed_from_u64(self.rng.gen())).collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(self.rng.gen());
+        let mut progress = self.progress.get_mut().unwrap().take();
+        let mut snapshots = self.snapshots.get_mut().unwrap().take();
+        if let Some(snapshots) = snapshots.as_mut() {
+            snapshots.reset();
+        }
+        let mut remaining =
+            (0..num_threads).map(|i| rollouts_per_thread + (i < extra) as u32).collect::<Vec<_>>();
+        if self.thread_pool.is_none() {
+            self.thread_pool = Some(
+                rayon::ThreadPoolBuilder::new().num_threads(num_threads as usize).build().unwrap(),
+            );
+        }
+        let max_tree_nodes = self.options.max_tree_nodes.unwrap_or(usize::MAX);
+        // Search until done, pausing to collect garbage whenever the tree
+        // outgrows its limit.
+        loop {
+            let reporting = progress.is_some() || snapshots.is_some();
+            self.search_round(&tree, s, &mut worker_rngs, &mut remaining, reporting, &mut |tree| {
+                report::<G>(tree, s, start_time, &mut rng, &mut progress, &mut snapshots)
+            });
+            if tree.len() < max_tree_nodes
+                || remaining.iter().all(|&r| r == 0)
+                || self.timeout.load(Relaxed)
+                || self.stop.is_stopped()
+            {
+                break;
             }
-        });
+            tree = collect_garbage(tree, &*self.options.gc_policy, max_tree_nodes / 2);
+        }
+        *self.progress.get_mut().unwrap() = progress;
+        *self.snapshots.get_mut().unwrap() = snapshots;
+        self.finish_search(&tree, s, start_time, &mut rng, num_threads)
+    }
+}
 
-        // Compute PV.
-        self.pv.clear();
-        let mut node = &*root;
-        while let Some(best) = node.best_child(0.0) {
-            self.pv.push(best.m.unwrap());
-            node = best;
+#[cfg(target_arch = "wasm32")]
+impl<G: Game> MonteCarloTreeSearch<G>
+where
+    G::S: Clone,
+    G::M: Copy,
+{
+    // Without threads, search in one big step.
+    fn search(&mut self, s: &G::S) -> Option<G::M> {
+        self.start_stepping(s);
+        while !self.step(u32::MAX) {}
+        self.finish_stepping()
+    }
+}
+
+impl<G: Game> MonteCarloTreeSearch<G>
+where
+    G::S: Clone,
+    G::M: Copy,
+{
+    // Record the stats of a finished search, and return its best move.
+    fn finish_search(
+        &mut self, tree: &Tree<G::M>, s: &G::S, start_time: Instant, rng: &mut StdRng,
+        num_threads: u32,
+    ) -> Option<G::M> {
+        let root = tree.get(0);
+        self.simulations = root.visits().saturating_sub(self.seeded_visits) as u64;
+        let moves = root.expansion.get().map_or(&[][..], |expansion| &expansion.children[..]);
+        let children = moves
+            .iter()
+            .map(|child| {
+                let (visits, score) = child.stats(tree);
+                ChildStats { visits, score, prior: child.prior }
+            })
+            .collect::<Vec<_>>();
+        self.root_stats = moves
+            .iter()
+            .zip(children.iter())
+            .map(|(child, stats)| MoveStats {
+                m: child.m,
+                visits: stats.visits,
+                score: stats.score,
+            })
+            .collect();
+        let target = policy_target(&children, self.tree_policy(), self.options.forced_playouts);
+        self.policy_target = moves.iter().map(|child| child.m).zip(target).collect();
+        self.tree_nodes = tree.len();
+        self.tree_children = root.count_children(tree);
+        self.tree_bytes = tree.capacity_bytes();
+        self.tree_shape =
+            (self.options.tree_shape || self.options.verbose).then(|| root.shape(tree));
+        self.root_winner = root.winner.load(Relaxed);
+        self.win_probability = root_win_probability(tree);
+        self.pv = principal_variation(tree, root, rng);
+        self.lines = top_lines(tree, self.multi_pv, rng);
+        if let Some(snapshots) = self.snapshots.get_mut().unwrap().as_mut() {
+            snapshots.write(&snapshot::<G>(tree, s, &self.pv, start_time));
         }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            simulations = self.simulations,
+            depth = self.pv.len(),
+            max_depth = self.tree_shape.as_ref().map(TreeShape::max_depth),
+            elapsed_ms = start_time.elapsed().as_millis() as u64,
+            "finished search"
+        );
+        #[cfg(feature = "metrics")]
+        record_search_metrics(
+            "mcts",
+            self.pv.len().min(u8::MAX as usize) as u8,
+            self.simulations,
+            start_time.elapsed(),
+            None,
+        );
 
         if self.options.verbose {
-            let total_visits = root.visits.load(Relaxed);
-            let duration = Instant::now().duration_since(start_time);
+            let total_visits = root.visits();
+            let duration = start_time.elapsed();
             let rate = total_visits as f64 / num_threads as f64 / duration.as_secs_f64();
             eprintln!(
                 "Using {} threads, did {} total simulations with {:.1} rollouts/sec/core",
@@ -426,7 +1996,10 @@ where
                 .get()?
                 .children
                 .iter()
-                .map(|node| (node.visits.load(Relaxed), node.score.load(Relaxed), node.m))
+                .map(|child| {
+                    let (visits, score) = child.stats(tree);
+                    (visits, score, Some(child.m))
+                })
                 .collect::<Vec<_>>();
             children.sort_by_key(|t| !t.0);
 
@@ -444,26 +2017,429 @@ where
 
             // Dump PV.
             eprintln!("Principal variation: {}", pv_string::<G>(&self.pv[..], s));
+            if let Some(shape) = &self.tree_shape {
+                eprintln!(
+                    "Tree: {} nodes, max depth {}, mean branching {:.1}, nodes per depth {:?}",
+                    self.tree_nodes,
+                    shape.max_depth(),
+                    shape.mean_branching,
+                    shape.nodes_per_depth
+                );
+            }
+        }
+
+        self.stop.reset();
+        self.wall_time = start_time.elapsed();
+        // Just get best node: the most visited with priors, and otherwise
+        // the best win ratio.
+        let policy: &dyn TreePolicy = if self.evaluator.is_some() {
+            &Puct { exploration: 0.0 }
+        } else {
+            &Uct { exploration: 0.0 }
+        };
+        root.best_child(tree, policy, usize::MAX, rng).map(|(child, _)| child.m)
+    }
+}
+
+// A simulation's path down the tree, kept to reuse its buffers across
+// simulations by the same thread.
+struct Path<'a, G: Game> {
+    // The expanded nodes passed through, from the root down.
+    nodes: Vec<&'a Node<G::M>>,
+    // The moves applied to the state on the way, with the state from before
+    // each move if the game doesn't update in place.
+    applied: Vec<(G::M, Option<G::S>)>,
+    // Whether each applied move passed the turn to the opponent.
+    passed: Vec<bool>,
+    // The number of results summed in the simulation's result.
+    weight: u32,
+}
+
+impl<G: Game> Default for Path<'_, G> {
+    fn default() -> Self {
+        Self { nodes: Vec::new(), applied: Vec::new(), passed: Vec::new(), weight: 1 }
+    }
+}
+
+impl<G: Game> MonteCarloTreeSearch<G>
+where
+    G: MaybeSync,
+    G::S: Clone + MaybeSend,
+    G::M: Copy + MaybeSend + MaybeSync,
+{
+    /// Compare candidate moves from this state, e.g. two plans under
+    /// consideration, by splitting the search's time or rollouts evenly
+    /// between a dedicated search after each of them. A pending
+    /// [warm start](MonteCarloTreeSearch::warm_start) seeds each of those
+    /// searches alike. Returns how each candidate fared, in the order given.
+    pub fn compare_moves(&mut self, s: &G::S, candidates: &[G::M]) -> Vec<CandidateInfo<G::M>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stop_background();
+        self.budget_time(s);
+        let (max_time, max_rollouts, time_left) =
+            (self.max_time, self.max_rollouts, self.time_left.take());
+        let shares = candidates.len().max(1) as u32;
+        self.max_time = max_time / shares;
+        if max_rollouts > 0 {
+            self.max_rollouts = (max_rollouts / shares).max(1);
+        }
+        let warm_start = self.warm_start.take();
+        let results = candidates
+            .iter()
+            .map(|&m| {
+                let mut state = s.clone();
+                if let Some(new_state) = G::apply(&mut state, m) {
+                    state = new_state;
+                }
+                // The ratios below are for the opponent of the player to
+                // move, who is the mover unless they move again.
+                let passed = passes_turn::<G>(G::player_to_move(s), &state);
+                let for_mover = |win_ratio: f32| if passed { win_ratio } else { 1.0 - win_ratio };
+                if let Some(winner) = G::get_winner(&state) {
+                    let win_ratio = match winner {
+                        Winner::PlayerJustMoved => 1.0,
+                        Winner::PlayerToMove => 0.0,
+                        Winner::Draw => 0.5,
+                    };
+                    return CandidateInfo {
+                        m,
+                        win_ratio: for_mover(win_ratio),
+                        simulations: 0,
+                        principal_variation: vec![m],
+                    };
+                }
+                self.warm_start = warm_start.clone();
+                self.search(&state);
+                // The replies' scores are for the opponent.
+                let (visits, score) =
+                    self.root_stats.iter().fold((0, 0), |(visits, score), stats| {
+                        (visits + stats.visits as i64, score + stats.score as i64)
+                    });
+                let win_ratio = match self.root_winner {
+                    WIN => 1.0,
+                    LOSS => 0.0,
+                    _ => ChildStats { visits: visits as u32, score: -score as i32, prior: 0.0 }
+                        .win_ratio(),
+                };
+                let mut principal_variation = vec![m];
+                principal_variation.extend_from_slice(&self.pv);
+                CandidateInfo {
+                    m,
+                    win_ratio: for_mover(win_ratio),
+                    simulations: self.simulations,
+                    principal_variation,
+                }
+            })
+            .collect();
+        self.max_time = max_time;
+        self.max_rollouts = max_rollouts;
+        self.time_left = time_left;
+        results
+    }
+}
+
+impl<G: Game> Strategy<G> for MonteCarloTreeSearch<G>
+where
+    G: MaybeSync,
+    G::S: Clone + MaybeSend,
+    G::M: Copy + MaybeSend + MaybeSync,
+{
+    fn choose_move(&mut self, s: &G::S) -> Option<G::M> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "mcts",
+            max_rollouts = self.max_rollouts,
+            max_time_ms = self.max_time.as_millis() as u64
+        )
+        .entered();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(background) = self.background.take() {
+            // Continue a search after a ponder hit on this position, or
+            // discard any other.
+            let hit = background.is_hit()
+                && background.position.is_none_or(|hash| hash == G::zobrist_hash(s));
+            let m = self.restore_from_background(if hit {
+                background.finish()
+            } else {
+                background.stop()
+            });
+            if hit && m.is_some() {
+                return m;
+            }
         }
+        self.search(s)
+    }
 
-        let exploration = 0.0; // Just get best node.
-        root.best_child(exploration).map(|node| node.m.unwrap())
+    fn choose_move_with_info(&mut self, s: &G::S) -> SearchInfo<G::M> {
+        self.simulations = 0;
+        self.pv.clear();
+        let best_move = self.choose_move(s);
+        SearchInfo {
+            best_move,
+            win_probability: self.win_probability,
+            principal_variation: self.pv.clone(),
+            nodes: self.simulations,
+            depth: self.pv.len().min(u8::MAX as usize) as u8,
+            elapsed: self.wall_time,
+            ..SearchInfo::default()
+        }
     }
 
     fn set_timeout(&mut self, timeout: Duration) {
         self.max_rollouts = 0;
         self.max_time = timeout;
+        self.time_left = None;
+    }
+
+    fn notify_move(&mut self, _state: &G::S, _m: G::M) {
+        self.ply += 1;
+    }
+
+    fn new_game(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.stop_background();
+        *self.stepping.get_mut().unwrap() = None;
+        self.ply = 0;
+        self.pv.clear();
+        self.lines.clear();
+        self.root_stats.clear();
+        self.root_winner = 0;
+        self.win_probability = None;
+        self.warm_start = None;
+        self.seeded_visits = 0;
+        self.policy_target.clear();
+        self.tree_nodes = 0;
+        self.tree_children = 0;
+        self.tree_bytes = 0;
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.set_timeout(time.move_budget());
+        self.time_left = Some(*time);
     }
 
     fn set_max_depth(&mut self, depth: u8) {
         // Set some arbitrary function of rollouts.
         self.max_time = Duration::default();
+        self.time_left = None;
         self.max_rollouts = 5u32
             .saturating_pow(depth as u32)
             .saturating_mul(self.options.rollouts_before_expanding + 1);
     }
 
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.stop = signal;
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<G::M>>) {
+        *self.progress.get_mut().unwrap() = Some(progress);
+    }
+
+    fn set_tablebase(&mut self, tablebase: SharedTablebase<G>) {
+        self.tablebase = Some(tablebase);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
     fn principal_variation(&self) -> Vec<G::M> {
         self.pv.clone()
     }
+
+    fn analyze(&mut self, s: &G::S, k: usize) -> Vec<MoveAnalysis<G::M>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        self.multi_pv = k;
+        self.lines.clear();
+        let info = self.choose_move_with_info(s);
+        self.multi_pv = 0;
+        if self.lines.is_empty() {
+            // Only the best move is known, e.g. from a tablebase.
+            return MoveAnalysis::from_search(info).into_iter().collect();
+        }
+        std::mem::take(&mut self.lines)
+    }
+
+    // The tree is freed after each search, so this is its size at the end of
+    // the last one.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            bytes: self.tree_bytes + self.tree_children * std::mem::size_of::<Child<G::M>>(),
+            tree_nodes: self.tree_nodes,
+            ..MemoryUsage::default()
+        }
+    }
+}
+
+/// Each step runs about the given number of simulations, counting each of a
+/// leaf's rollouts as one.
+impl<G: Game> SteppedSearch<G> for MonteCarloTreeSearch<G>
+where
+    G::S: Clone,
+    G::M: Copy,
+{
+    fn start_stepping(&mut self, s: &G::S) {
+        let tree = self.new_tree(s);
+        // Steps check the time themselves, instead of a timer thread.
+        self.budget_time(s);
+        self.timeout = Arc::new(AtomicBool::new(false));
+        if let Some(snapshots) = self.snapshots.get_mut().unwrap().as_mut() {
+            snapshots.reset();
+        }
+        let start_time = Instant::now();
+        *self.stepping.get_mut().unwrap() = Some(Stepping {
+            tree,
+            state: s.clone(),
+            moves: Vec::new(),
+            rng: StdRng::seed_from_u64(self.rng.gen()),
+            remaining: if self.max_rollouts == 0 { u32::MAX } else { self.max_rollouts },
+            start_time,
+            last_report: start_time,
+        });
+    }
+
+    fn step(&mut self, mut work: u32) -> bool {
+        let Some(mut stepping) = self.stepping.get_mut().unwrap().take() else {
+            return true;
+        };
+        let max_tree_nodes = self.options.max_tree_nodes.unwrap_or(usize::MAX);
+        while work > 0 && stepping.remaining > 0 {
+            if stepping.tree.len() >= max_tree_nodes {
+                let tree = std::mem::take(&mut stepping.tree);
+                stepping.tree = collect_garbage(tree, &*self.options.gc_policy, max_tree_nodes / 2);
+            }
+            let Stepping { tree, state, moves, rng, remaining, start_time, last_report } =
+                &mut stepping;
+            let mut path = Path::default();
+            while work > 0 && *remaining > 0 && tree.len() < max_tree_nodes {
+                let timed_out =
+                    self.max_time != Duration::default() && start_time.elapsed() >= self.max_time;
+                if timed_out || self.simulate(tree, state, &mut path, moves, rng).is_none() {
+                    *remaining = 0;
+                    break;
+                }
+                *remaining = remaining.saturating_sub(path.weight);
+                work = work.saturating_sub(path.weight);
+                let reporting = self.progress.get_mut().unwrap().is_some()
+                    || self.snapshots.get_mut().unwrap().is_some();
+                if (reporting || cfg!(feature = "tracing"))
+                    && last_report.elapsed() >= PROGRESS_INTERVAL
+                {
+                    *last_report = Instant::now();
+                    report::<G>(
+                        tree,
+                        state,
+                        *start_time,
+                        rng,
+                        self.progress.get_mut().unwrap(),
+                        self.snapshots.get_mut().unwrap(),
+                    );
+                }
+            }
+        }
+        let done = stepping.remaining == 0;
+        *self.stepping.get_mut().unwrap() = Some(stepping);
+        done
+    }
+
+    fn finish_stepping(&mut self) -> Option<G::M> {
+        let mut stepping = self.stepping.get_mut().unwrap().take()?;
+        let Stepping { tree, state, rng, start_time, .. } = &mut stepping;
+        self.finish_search(tree, state, *start_time, rng, 1)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<G: Game> MonteCarloTreeSearch<G>
+where
+    G: Send + Sync + 'static,
+    G::S: Clone + Send + 'static,
+    G::M: Copy + Send + Sync + 'static,
+{
+    // Move the searcher to a background thread to search this state, leaving
+    // a stand-in with the same settings in its place.
+    fn search_in_background(&mut self, state: G::S) {
+        self.stop_background();
+        let mut placeholder = MonteCarloTreeSearch::new(self.options.clone());
+        placeholder.max_rollouts = self.max_rollouts;
+        placeholder.max_time = self.max_time;
+        placeholder.time_left = self.time_left;
+        placeholder.ply = self.ply;
+        // Keep any warm start for the position actually searched next.
+        placeholder.warm_start = self.warm_start.take();
+        placeholder.stop = self.stop.clone();
+        placeholder.tablebase = self.tablebase.clone();
+        let mut searcher = std::mem::replace(self, placeholder);
+        // Search without a time limit until stopped or a ponder hit starts
+        // the clock.
+        searcher.max_time = Duration::default();
+        searcher.time_left = None;
+        let progress = searcher.progress.get_mut().unwrap().take();
+        self.background =
+            Some(BackgroundSearch::start(searcher, progress, move |searcher, stop, progress| {
+                searcher.stop = stop;
+                *searcher.progress.get_mut().unwrap() = Some(progress);
+                searcher.choose_move(&state)
+            }));
+    }
+}
+
+/// Pondering needs `Game::zobrist_hash`, to check that the position searched
+/// after a ponder hit is the one pondered.
+#[cfg(not(target_arch = "wasm32"))]
+impl<G: Game> Ponder<G> for MonteCarloTreeSearch<G>
+where
+    G: Send + Sync + 'static,
+    G::S: Clone + Send + 'static,
+    G::M: Copy + PartialEq + Send + Sync + 'static,
+{
+    fn ponder(&mut self, s: &G::S, predicted_move: G::M) {
+        self.stop_background();
+        if !G::is_legal(s, predicted_move) {
+            return;
+        }
+        let mut state = s.clone();
+        if let Some(new_state) = G::apply(&mut state, predicted_move) {
+            state = new_state;
+        }
+        if G::get_winner(&state).is_none() {
+            let hash = G::zobrist_hash(&state);
+            self.search_in_background(state);
+            if let Some(background) = self.background.as_mut() {
+                background.position = Some(hash);
+            }
+        }
+    }
+
+    fn ponder_hit(&mut self) {
+        if let Some(background) = self.background.as_mut() {
+            background.hit(self.max_time);
+        }
+    }
+
+    fn stop_ponder(&mut self) {
+        self.stop_background();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<G: Game> AnytimeStrategy<G> for MonteCarloTreeSearch<G>
+where
+    G: Send + Sync + 'static,
+    G::S: Clone + Send + 'static,
+    G::M: Copy + Send + Sync + 'static,
+{
+    fn start(&mut self, state: &G::S) {
+        self.search_in_background(state.clone());
+    }
+
+    fn best_so_far(&self) -> Option<G::M> {
+        self.background.as_ref()?.best_so_far()
+    }
+
+    fn stop(&mut self) -> Option<G::M> {
+        self.stop_background()
+    }
 }
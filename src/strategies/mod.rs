@@ -1,14 +1,32 @@
 //! Strategy implementations.
 
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub mod asynchronous;
+#[cfg(feature = "std")]
+pub mod book;
+#[cfg(feature = "std")]
+pub mod ensemble;
+#[cfg(feature = "std")]
+pub mod fallback;
+#[cfg(feature = "std")]
+pub mod flow;
+#[cfg(feature = "std")]
+pub mod human;
+#[cfg(feature = "std")]
 pub mod iterative;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
 pub mod mcts;
 pub mod negamax;
 pub mod random;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod ybw;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+mod background;
+#[cfg(feature = "std")]
 mod sync_util;
+#[cfg(feature = "std")]
 mod table;
 mod util;
@@ -2,18 +2,50 @@
 //!
 //! With only the basic alpha-pruning implemented. This picks randomly among
 //! the "best" moves, so that it's non-deterministic.
+//!
+//! The search always goes to the full depth, but if a timeout is set, it
+//! stops when time runs out and returns the best move found so far. Without
+//! the standard library there is no clock, and timeouts are ignored, but a
+//! node limit still works.
 
 use super::super::interface::*;
+use super::super::time::Instant;
 use super::super::util::*;
 use super::util::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::max;
+use core::time::Duration;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use std::cmp::max;
+use rand::SeedableRng;
 
 pub struct Negamax<E: Evaluator> {
     max_depth: u8,
+    max_time: Duration,
+    deadline: Option<Instant>,
+    timeout_counter: u32,
+    stop: StopSignal,
     move_pool: MovePool<<E::G as Game>::M>,
-    rng: rand::rngs::ThreadRng,
+    rng: StdRng,
     prev_value: Evaluation,
+    nodes: u64,
+    // Stop after searching this many nodes, if nonzero.
+    max_nodes: u64,
+    // Whether the last search finished without a timeout.
+    completed: bool,
+    // The number of best moves to find exact values for.
+    multi_pv: usize,
+    // Moves played in the game, as told by notify_move.
+    ply: u32,
+    // Moves played before the root.
+    root_ply: u32,
+    // The best lines of the last search and their values, best first.
+    lines: Vec<(Evaluation, Vec<<E::G as Game>::M>)>,
+    progress: Option<Box<dyn SearchProgress<<E::G as Game>::M>>>,
+    tablebase: Option<SharedTablebase<E::G>>,
+    win_scale: Option<f32>,
     eval: E,
 }
 
@@ -21,9 +53,23 @@ impl<E: Evaluator> Negamax<E> {
     pub fn new(eval: E, depth: u8) -> Negamax<E> {
         Negamax {
             max_depth: depth,
+            max_time: Duration::new(0, 0),
+            deadline: None,
+            timeout_counter: 0,
+            stop: StopSignal::new(),
             move_pool: MovePool::<_>::default(),
-            rng: rand::thread_rng(),
+            rng: new_rng(),
             prev_value: 0,
+            nodes: 0,
+            max_nodes: 0,
+            completed: false,
+            multi_pv: 1,
+            ply: 0,
+            root_ply: 0,
+            lines: Vec::new(),
+            progress: None,
+            tablebase: None,
+            win_scale: None,
             eval,
         }
     }
@@ -33,72 +79,269 @@ impl<E: Evaluator> Negamax<E> {
         unclamp_value(self.prev_value)
     }
 
+    fn timeout_check(&mut self) -> bool {
+        if self.max_nodes != 0 && self.nodes >= self.max_nodes {
+            return true;
+        }
+        self.timeout_counter += 1;
+        if self.timeout_counter < 1000 {
+            return false;
+        }
+        self.timeout_counter = 0;
+        self.stop.is_stopped() || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // Returns None if it hits the timeout. Leaves the best line from `s` in
+    // `pv`, if its value is within the window.
     fn negamax(
-        &mut self, s: &mut <E::G as Game>::S, depth: u8, mut alpha: Evaluation, beta: Evaluation,
-    ) -> Evaluation
+        &mut self, s: &mut <E::G as Game>::S, ply: u8, depth: u8, mut alpha: Evaluation,
+        beta: Evaluation, pv: &mut Vec<<E::G as Game>::M>,
+    ) -> Option<Evaluation>
     where
         <<E as Evaluator>::G as Game>::M: Copy,
     {
+        pv.clear();
+        if self.timeout_check() {
+            return None;
+        }
+        self.nodes += 1;
         if let Some(winner) = E::G::get_winner(s) {
-            return winner.evaluate();
+            return Some(winner.evaluate());
+        }
+        if let Some(value) = probe_tablebase(&self.tablebase, s) {
+            return Some(value);
         }
         if depth == 0 {
-            return self.eval.evaluate(s);
+            return Some(evaluate(&self.eval, s, self.root_ply, ply, depth));
         }
         let mut moves = self.move_pool.alloc();
         E::G::generate_moves(s, &mut moves);
+        let player = E::G::player_to_move(s);
         let mut best = WORST_EVAL;
+        let mut child_pv = self.move_pool.alloc();
         for m in moves.iter() {
             let mut new = AppliedMove::<E::G>::new(s, *m);
-            let value = -self.negamax(&mut new, depth - 1, -beta, -alpha);
+            let turn = Turn::after::<E::G>(player, &new);
+            let (a, b) = turn.window(alpha, beta);
+            let Some(value) =
+                self.negamax(&mut new, ply.saturating_add(1), depth - 1, a, b, &mut child_pv)
+            else {
+                self.move_pool.free(moves);
+                self.move_pool.free(child_pv);
+                return None;
+            };
+            let value = turn.value(value);
             best = max(best, value);
-            alpha = max(alpha, value);
+            if value > alpha {
+                alpha = value;
+                pv.clear();
+                pv.push(*m);
+                pv.extend_from_slice(&child_pv);
+            }
             if alpha >= beta {
                 break;
             }
         }
         self.move_pool.free(moves);
-        clamp_value(best)
+        self.move_pool.free(child_pv);
+        Some(clamp_value(best))
     }
 }
 
-impl<E: Evaluator> Strategy<E::G> for Negamax<E>
+/// Copies have the same settings, tablebase, and evaluator, and their own
+/// stop signal and random numbers.
+impl<E: Evaluator + Clone> FreshCopy for Negamax<E> {
+    fn fresh_copy(&self) -> Self {
+        let mut negamax = Negamax::new(self.eval.clone(), self.max_depth);
+        negamax.max_time = self.max_time;
+        negamax.max_nodes = self.max_nodes;
+        negamax.tablebase = self.tablebase.clone();
+        negamax.win_scale = self.win_scale;
+        negamax
+    }
+}
+
+impl<E: Evaluator> Negamax<E>
 where
     <E::G as Game>::S: Clone,
-    <E::G as Game>::M: Copy,
 {
-    fn choose_move(&mut self, s: &<E::G as Game>::S) -> Option<<E::G as Game>::M> {
+    // Choose a move from `s`, leaving the stop signal as it is.
+    fn search(&mut self, s: &<E::G as Game>::S) -> Option<<E::G as Game>::M> {
         if self.max_depth == 0 {
             return None;
         }
         if E::G::get_winner(s).is_some() {
             return None;
         }
-        let mut best = WORST_EVAL;
+        let start_time = Instant::now();
+        self.deadline = if self.max_time == Duration::new(0, 0) {
+            None
+        } else {
+            Some(start_time + self.max_time)
+        };
+        self.nodes = 0;
+        self.root_ply = E::G::ply(s).unwrap_or(self.ply);
+        self.completed = false;
+        self.lines.clear();
+        if let Some((m, value)) = tablebase_root(&self.tablebase, &mut s.clone()) {
+            self.prev_value = value;
+            self.completed = true;
+            self.lines.push((value, vec![m]));
+            return Some(m);
+        }
         let mut moves = self.move_pool.alloc();
         E::G::generate_moves(s, &mut moves);
         // Randomly permute order that we look at the moves.
         // We'll pick the first best score from this list.
         moves.shuffle(&mut self.rng);
 
-        let mut best_move = *moves.first()?;
+        let Some(&first) = moves.first() else {
+            self.move_pool.free(moves);
+            return None;
+        };
         let mut s_clone = s.clone();
+        let player = E::G::player_to_move(s);
+        let mut pv = Vec::new();
+        self.completed = true;
         for &m in moves.iter() {
-            // determine value for this move
+            // Only moves that could be among the best need exact values.
+            let worst_line = match self.lines.get(self.multi_pv - 1) {
+                Some(&(value, _)) => value,
+                None => WORST_EVAL,
+            };
             let mut new = AppliedMove::<E::G>::new(&mut s_clone, m);
-            let value = -self.negamax(&mut new, self.max_depth - 1, WORST_EVAL, -best);
-            // Strictly better than any move found so far.
-            if value > best {
-                best = value;
-                best_move = m;
+            let turn = Turn::after::<E::G>(player, &new);
+            let (alpha, beta) = turn.window(worst_line, BEST_EVAL);
+            let value = match self.negamax(&mut new, 1, self.max_depth - 1, alpha, beta, &mut pv) {
+                Some(value) => turn.value(value),
+                // Timeout. Return the best move found so far.
+                None => {
+                    self.completed = false;
+                    break;
+                }
+            };
+            // Strictly better than the worst line so far, so that ties go
+            // to the move found first.
+            let i = self.lines.partition_point(|&(line, _)| line >= value);
+            if i < self.multi_pv {
+                pv.insert(0, m);
+                self.lines.insert(i, (value, core::mem::take(&mut pv)));
+                self.lines.truncate(self.multi_pv);
             }
         }
         self.move_pool.free(moves);
+        let (best, best_move) =
+            self.lines.first().map_or((WORST_EVAL, first), |(value, line)| (*value, line[0]));
         self.prev_value = best;
+        let principal_variation = self.principal_variation();
+        if let Some(progress) = self.progress.as_mut() {
+            progress.report(&SearchInfo {
+                best_move: Some(best_move),
+                value: Some(unclamp_value(best)),
+                win_probability: scaled_win_probability(Some(unclamp_value(best)), self.win_scale),
+                principal_variation,
+                nodes: self.nodes,
+                depth: if self.completed { self.max_depth } else { 0 },
+                elapsed: start_time.elapsed(),
+                ..SearchInfo::default()
+            });
+        }
         Some(best_move)
     }
+}
+
+impl<E: Evaluator> Strategy<E::G> for Negamax<E>
+where
+    <E::G as Game>::S: Clone,
+    <E::G as Game>::M: Copy,
+{
+    fn choose_move(&mut self, s: &<E::G as Game>::S) -> Option<<E::G as Game>::M> {
+        let best_move = self.search(s);
+        // Lower the signal however the search ended.
+        self.stop.reset();
+        best_move
+    }
+
+    fn choose_move_with_info(&mut self, s: &<E::G as Game>::S) -> SearchInfo<<E::G as Game>::M> {
+        let start = Instant::now();
+        let best_move = self.choose_move(s);
+        let value = best_move.map(|_| self.root_value());
+        SearchInfo {
+            best_move,
+            value,
+            win_probability: scaled_win_probability(value, self.win_scale),
+            principal_variation: self.principal_variation(),
+            nodes: self.nodes,
+            depth: if self.completed { self.max_depth } else { 0 },
+            elapsed: start.elapsed(),
+            ..SearchInfo::default()
+        }
+    }
+
+    fn set_timeout(&mut self, max_time: Duration) {
+        self.max_time = max_time;
+    }
+
+    fn notify_move(&mut self, _state: &<E::G as Game>::S, _m: <E::G as Game>::M) {
+        self.ply += 1;
+    }
+
+    fn new_game(&mut self) {
+        self.ply = 0;
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.win_scale = Some(scale);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.max_nodes = nodes;
+        self.max_time = Duration::new(0, 0);
+    }
 
     fn set_max_depth(&mut self, depth: u8) {
         self.max_depth = depth;
+        self.max_time = Duration::new(0, 0);
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.stop = signal;
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<<E::G as Game>::M>>) {
+        self.progress = Some(progress);
+    }
+
+    fn set_tablebase(&mut self, tablebase: SharedTablebase<E::G>) {
+        self.tablebase = Some(tablebase);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    fn principal_variation(&self) -> Vec<<E::G as Game>::M> {
+        self.lines.first().map_or_else(Vec::new, |(_, line)| line.clone())
+    }
+
+    fn analyze(&mut self, s: &<E::G as Game>::S, k: usize) -> Vec<MoveAnalysis<<E::G as Game>::M>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        self.multi_pv = k;
+        self.choose_move(s);
+        self.multi_pv = 1;
+        self.lines
+            .iter()
+            .map(|(value, line)| {
+                let value = Some(unclamp_value(*value));
+                MoveAnalysis {
+                    m: line[0],
+                    value,
+                    win_probability: scaled_win_probability(value, self.win_scale),
+                    principal_variation: line.clone(),
+                }
+            })
+            .collect()
     }
 }
@@ -1,17 +1,24 @@
 //! A strategy that randomly chooses a move, for use in tests.
+//!
+//! It also makes a baseline opponent for benchmarks and tournaments.
 
 use super::super::interface::*;
+use super::util::new_rng;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use std::marker::PhantomData;
+use rand::SeedableRng;
 
+/// Chooses uniformly among the legal moves.
 pub struct Random<G: Game> {
-    rng: rand::rngs::ThreadRng,
+    rng: StdRng,
     game_type: PhantomData<G>,
 }
 
 impl<G: Game> Random<G> {
     pub fn new() -> Self {
-        Self { rng: rand::thread_rng(), game_type: PhantomData }
+        Self { rng: new_rng(), game_type: PhantomData }
     }
 }
 
@@ -21,13 +28,28 @@ impl<G: Game> Default for Random<G> {
     }
 }
 
+/// Copies get their own random numbers.
+impl<G: Game> FreshCopy for Random<G> {
+    fn fresh_copy(&self) -> Self {
+        Random::new()
+    }
+}
+
 impl<G: Game> Strategy<G> for Random<G>
 where
     G::M: Copy,
 {
     fn choose_move(&mut self, s: &G::S) -> Option<G::M> {
+        // Like the search strategies, don't move after the game is over.
+        if G::get_winner(s).is_some() {
+            return None;
+        }
         let mut moves = Vec::new();
         G::generate_moves(s, &mut moves);
         moves.choose(&mut self.rng).copied()
     }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
 }
@@ -1,9 +1,16 @@
+#[cfg(not(target_arch = "wasm32"))]
 use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+#[cfg(any(test, not(target_arch = "wasm32")))]
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
 use std::thread::{sleep, spawn};
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Duration;
 
+#[cfg(not(target_arch = "wasm32"))]
 pub(super) fn timeout_signal(dur: Duration) -> Arc<AtomicBool> {
     // Theoretically we could include an async runtime to do this and use
     // fewer threads, but the stdlib implementation is only a few lines...
@@ -16,7 +23,8 @@ pub(super) fn timeout_signal(dur: Duration) -> Arc<AtomicBool> {
     signal
 }
 
-// An insert-only lock-free Option<Box<T>>
+// An insert-only lock-free Option<Box<T>>. Values that lose a race to set
+// it are freed immediately, and the contents are freed with the box.
 pub(super) struct AtomicBox<T>(AtomicPtr<T>);
 
 impl<T> Default for AtomicBox<T> {
@@ -44,9 +52,19 @@ impl<T> AtomicBox<T> {
     }
 
     pub(super) fn get(&self) -> Option<&T> {
-        let ptr = self.0.load(Ordering::Relaxed);
+        // Acquire, to see the contents written by whichever thread set it.
+        let ptr = self.0.load(Ordering::Acquire);
         unsafe { ptr.as_ref() }
     }
+
+    pub(super) fn take(&mut self) -> Option<Box<T>> {
+        let ptr = std::mem::replace(self.0.get_mut(), std::ptr::null_mut());
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { Box::from_raw(ptr) })
+        }
+    }
 }
 
 impl<T> Drop for AtomicBox<T> {
@@ -66,10 +84,166 @@ fn test_atomic_box() {
     assert_eq!(Some(&3), b.get());
     b.try_set(Box::new(4));
     assert_eq!(Some(&3), b.get());
+    let mut b = b;
+    assert_eq!(Some(Box::new(3)), b.take());
+    assert_eq!(None, b.get());
+
+    // Both the value that lost the race and the one in the box are freed.
+    let value = Arc::new(());
+    let b = AtomicBox::default();
+    b.try_set(Box::new(value.clone()));
+    b.try_set(Box::new(value.clone()));
+    assert_eq!(Arc::strong_count(&value), 2);
+    drop(b);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+// An append-only lock-free arena, addressing its values by u32 index.
+// Values live in chunks of doubling size, so they never move, and are all
+// freed together when the arena is dropped.
+pub(super) struct Arena<T> {
+    chunks: [AtomicPtr<T>; ARENA_CHUNKS],
+    len: AtomicU32,
+}
+
+const ARENA_FIRST_CHUNK: u32 = 64;
+// Enough chunks to hold any u32 index.
+const ARENA_CHUNKS: usize = 27;
+
+// Values are only shared by reference, and only written before their index
+// is handed out.
+unsafe impl<T: Send> Send for Arena<T> {}
+unsafe impl<T: Send + Sync> Sync for Arena<T> {}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self { chunks: std::array::from_fn(|_| AtomicPtr::default()), len: AtomicU32::new(0) }
+    }
+}
+
+impl<T> Arena<T> {
+    // The chunk holding an index, and the offset into it.
+    fn locate(index: u32) -> (usize, usize) {
+        let chunk = (index / ARENA_FIRST_CHUNK + 1).ilog2();
+        (chunk as usize, (index - ARENA_FIRST_CHUNK * ((1 << chunk) - 1)) as usize)
+    }
+
+    fn chunk_len(chunk: usize) -> usize {
+        (ARENA_FIRST_CHUNK as usize) << chunk
+    }
+
+    // Adds a value, and returns its index.
+    pub(super) fn alloc(&self, value: T) -> u32 {
+        let index = self.len.fetch_add(1, Ordering::Relaxed);
+        assert!(index < u32::MAX, "arena is full");
+        let (chunk, offset) = Self::locate(index);
+        let mut ptr = self.chunks[chunk].load(Ordering::Acquire);
+        if ptr.is_null() {
+            let mut new = Vec::<T>::with_capacity(Self::chunk_len(chunk));
+            let new_ptr = new.as_mut_ptr();
+            std::mem::forget(new);
+            ptr = match self.chunks[chunk].compare_exchange(
+                std::ptr::null_mut(),
+                new_ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new_ptr,
+                Err(existing) => {
+                    // Someone else allocated this chunk first.
+                    unsafe { drop(Vec::from_raw_parts(new_ptr, 0, Self::chunk_len(chunk))) };
+                    existing
+                }
+            };
+        }
+        unsafe { ptr.add(offset).write(value) };
+        index
+    }
+
+    // The value at an index returned by alloc. Callers must have received
+    // the index through a Release/Acquire pair, so the value is visible.
+    pub(super) fn get(&self, index: u32) -> &T {
+        debug_assert!(index < self.len.load(Ordering::Relaxed));
+        let (chunk, offset) = Self::locate(index);
+        unsafe { &*self.chunks[chunk].load(Ordering::Acquire).add(offset) }
+    }
+
+    pub(super) fn get_mut(&mut self, index: u32) -> &mut T {
+        assert!(index < *self.len.get_mut());
+        let (chunk, offset) = Self::locate(index);
+        unsafe { &mut *self.chunks[chunk].get_mut().add(offset) }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed) as usize
+    }
+
+    // Bytes allocated for values, including unused space in the last chunk.
+    pub(super) fn capacity_bytes(&self) -> usize {
+        (0..ARENA_CHUNKS)
+            .filter(|&chunk| !self.chunks[chunk].load(Ordering::Relaxed).is_null())
+            .map(|chunk| Self::chunk_len(chunk) * std::mem::size_of::<T>())
+            .sum()
+    }
+}
+
+impl<T> Drop for Arena<T> {
+    fn drop(&mut self) {
+        if std::mem::needs_drop::<T>() {
+            for index in 0..*self.len.get_mut() {
+                let (chunk, offset) = Self::locate(index);
+                unsafe { std::ptr::drop_in_place(self.chunks[chunk].get_mut().add(offset)) };
+            }
+        }
+        for (chunk, ptr) in self.chunks.iter_mut().enumerate() {
+            if !ptr.get_mut().is_null() {
+                unsafe { drop(Vec::from_raw_parts(*ptr.get_mut(), 0, Self::chunk_len(chunk))) };
+            }
+        }
+    }
+}
+
+#[test]
+fn test_arena() {
+    assert_eq!(Arena::<u8>::locate(0), (0, 0));
+    assert_eq!(Arena::<u8>::locate(63), (0, 63));
+    assert_eq!(Arena::<u8>::locate(64), (1, 0));
+    assert_eq!(Arena::<u8>::locate(191), (1, 127));
+    assert_eq!(Arena::<u8>::locate(192), (2, 0));
+    assert_eq!(Arena::<u8>::locate(u32::MAX - 1).0, ARENA_CHUNKS - 1);
+
+    let arena = Arena::<Box<u32>>::default();
+    let counter = AtomicU32::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..250 {
+                    let value = counter.fetch_add(1, Ordering::Relaxed);
+                    let index = arena.alloc(Box::new(value));
+                    assert_eq!(**arena.get(index), value);
+                }
+            });
+        }
+    });
+    assert_eq!(arena.len(), 1000);
+    let mut values = (0..1000).map(|i| **arena.get(i)).collect::<Vec<_>>();
+    values.sort();
+    assert_eq!(values, (0..1000).collect::<Vec<_>>());
+
+    // Dropping the arena drops every value in it.
+    let value = Arc::new(());
+    let arena = Arena::default();
+    for _ in 0..100 {
+        arena.alloc(value.clone());
+    }
+    assert_eq!(Arc::strong_count(&value), 101);
+    drop(arena);
+    assert_eq!(Arc::strong_count(&value), 1);
 }
 
 // Safe API around lockfree threadlocals for rayon threadpools.
 // Only safe when used from a single threadpool, as this crate does.
+#[cfg(not(target_arch = "wasm32"))]
 pub(super) struct ThreadLocal<T> {
     // Our owned reference to all the locals.
     locals: Vec<T>,
@@ -78,9 +252,12 @@ pub(super) struct ThreadLocal<T> {
 }
 
 // Values are only accessed from their individual threads and references do not leak.
+#[cfg(not(target_arch = "wasm32"))]
 unsafe impl<T: Send> Send for ThreadLocal<T> {}
+#[cfg(not(target_arch = "wasm32"))]
 unsafe impl<T> Sync for ThreadLocal<T> {}
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<T: Send> ThreadLocal<T> {
     pub(super) fn new<F: Fn() -> T>(f: F, pool: &rayon::ThreadPool) -> Self {
         let n = pool.current_num_threads();
@@ -104,6 +281,7 @@ impl<T: Send> ThreadLocal<T> {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 #[test]
 fn test_threadlocal() {
     use rayon::prelude::*;
@@ -123,17 +301,20 @@ fn test_threadlocal() {
 }
 
 // 64-bytes is a common cache line size.
+#[cfg(not(target_arch = "wasm32"))]
 #[repr(align(64))]
 pub(super) struct CachePadded<T> {
     value: T,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<T: Default> Default for CachePadded<T> {
     fn default() -> Self {
         Self { value: T::default() }
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<T> Deref for CachePadded<T> {
     type Target = T;
 
@@ -142,6 +323,7 @@ impl<T> Deref for CachePadded<T> {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<T> DerefMut for CachePadded<T> {
     fn deref_mut(&mut self) -> &mut T {
         &mut self.value
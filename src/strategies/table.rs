@@ -1,7 +1,8 @@
 use super::util::{move_to_front, unclamp_value};
 use crate::interface::*;
-use std::cmp::{max, min};
-use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::cmp::{max, min, Reverse};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 
 // Common transposition table stuff.
@@ -26,8 +27,21 @@ pub(super) struct Entry<M> {
 
 #[test]
 fn test_entry_size() {
-    assert!(std::mem::size_of::<Entry<[u16; 2]>>() <= 16);
-    assert!(std::mem::size_of::<ConcurrentEntry<[u8; 6]>>() <= 16);
+    // Wider evaluations take the next size up.
+    let size = if std::mem::size_of::<Evaluation>() == 2 { 16 } else { 32 };
+    assert!(std::mem::size_of::<Entry<[u16; 2]>>() <= size);
+    assert!(std::mem::size_of::<ConcurrentEntry<[u8; 6]>>() <= size);
+    assert_eq!(std::mem::size_of::<PackedEntry>(), 16);
+}
+
+// Memory held by a table of `capacity` entries of type T.
+pub(super) fn table_usage<T>(entries: usize, capacity: usize) -> MemoryUsage {
+    MemoryUsage {
+        bytes: capacity * size_of::<T>(),
+        table_entries: entries,
+        table_capacity: capacity,
+        ..MemoryUsage::default()
+    }
 }
 
 pub(super) fn high_bits(hash: u64) -> u32 {
@@ -60,6 +74,7 @@ pub(super) trait Table<M: Copy> {
     fn lookup(&self, hash: u64) -> Option<Entry<M>>;
     fn store(&mut self, hash: u64, value: Evaluation, depth: u8, flag: EntryFlag, best_move: M);
     fn advance_generation(&mut self);
+    fn memory_usage(&self) -> MemoryUsage;
 
     // Check and update negamax state based on any transposition table hit.
     // Returns Some(value) on an exact match.
@@ -106,10 +121,12 @@ pub(super) trait Table<M: Copy> {
     }
 
     // After finishing a search, populate the principal variation as deep as
-    // the table remembers it.
+    // the table remembers it, stopping at any entry that a hash collision
+    // left without a legal move.
     fn populate_pv<G: Game<M = M>>(&self, pv: &mut Vec<M>, state: &G::S)
     where
         G::S: Clone,
+        M: PartialEq,
     {
         pv.clear();
         let mut hash_history = Vec::new();
@@ -124,7 +141,9 @@ pub(super) trait Table<M: Copy> {
             // value to be exact, and we can't guarantee that the table entry
             // will remain in the table between the searches that find
             // equivalent upper and lower bounds.
-            let m = entry.best_move.unwrap();
+            let Some(m) = entry.best_move.filter(|&m| G::is_legal(&state, m)) else {
+                break;
+            };
             pv.push(m);
             if let Some(new_state) = G::apply(&mut state, m) {
                 state = new_state;
@@ -171,6 +190,9 @@ impl<M: Copy, T: Table<M> + ConcurrentTable<M>> Table<M> for Arc<T> {
     fn advance_generation(&mut self) {
         self.concurrent_advance_generation()
     }
+    fn memory_usage(&self) -> MemoryUsage {
+        (**self).memory_usage()
+    }
 }
 
 // A concurrent table that doesn't bother to use atomic operations to access its entries.
@@ -218,6 +240,10 @@ impl<M: Copy> Table<M> for RacyTable<M> {
     fn advance_generation(&mut self) {
         self.concurrent_advance_generation()
     }
+    fn memory_usage(&self) -> MemoryUsage {
+        let entries = self.table.iter().filter(|entry| entry.best_move.is_some()).count();
+        table_usage::<Entry<M>>(entries, self.table.len())
+    }
 }
 
 impl<M: Copy> ConcurrentTable<M> for RacyTable<M> {
@@ -295,6 +321,11 @@ impl<M: Copy> Table<M> for LockfreeTable<M> {
     fn advance_generation(&mut self) {
         self.concurrent_advance_generation()
     }
+    fn memory_usage(&self) -> MemoryUsage {
+        // Racy, but only an estimate.
+        let entries = self.table.iter().filter(|entry| entry.best_move.is_some()).count();
+        table_usage::<ConcurrentEntry<M>>(entries, self.table.len())
+    }
 }
 
 #[allow(dead_code)]
@@ -378,24 +409,295 @@ impl<M: Copy> ConcurrentTable<M> for LockfreeTable<M> {
     }
 }
 
+// A concurrent table whose entries are two atomic words: the entry packed into
+// 64 bits, and the full hash XORed with the packed entry. A reader that sees
+// halves of two different writes gets a mismatched hash, and treats it as a
+// miss, so no locks are taken and torn entries are never returned.
+//
+// Moves are packed with the game's `Game::move_bits` encoding.
+pub(super) struct PackedTable<M> {
+    table: Vec<PackedEntry>,
+    mask: usize,
+    generation: AtomicU8,
+    bits: MoveBits<M>,
+}
+
+#[repr(align(16))]
+struct PackedEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl<M: Copy> PackedTable<M> {
+    pub(super) fn fits() -> bool {
+        size_of::<Evaluation>() == 2
+    }
+
+    pub(super) fn new(table_byte_size: usize, bits: MoveBits<M>) -> Self {
+        assert!(Self::fits());
+        let size = (table_byte_size / size_of::<PackedEntry>()).next_power_of_two();
+        let mut table = Vec::with_capacity(size);
+        for _ in 0..size {
+            table.push(PackedEntry { key: AtomicU64::new(0), data: AtomicU64::new(0) });
+        }
+        Self { table, mask: size - 1, generation: AtomicU8::new(0), bits }
+    }
+
+    // Bits 0-15: value, 16-23: depth, 24-28: generation (modulo 32), 29-30:
+    // flag (0 for an empty entry), 31: whether there is a move, 32-63: move.
+    fn pack(
+        &self, value: Evaluation, depth: u8, flag: EntryFlag, generation: u8, best_move: Option<M>,
+    ) -> u64 {
+        let flag = match flag {
+            EntryFlag::Exact => 1,
+            EntryFlag::Upperbound => 2,
+            EntryFlag::Lowerbound => 3,
+        };
+        let mut bits = value as u16 as u64
+            | (depth as u64) << 16
+            | ((generation & 31) as u64) << 24
+            | flag << 29;
+        if let Some(m) = best_move {
+            bits |= 1 << 31 | ((self.bits.0)(m) as u64) << 32;
+        }
+        bits
+    }
+
+    fn unpack(&self, hash: u64, bits: u64) -> Option<Entry<M>> {
+        let flag = match (bits >> 29) & 3 {
+            1 => EntryFlag::Exact,
+            2 => EntryFlag::Upperbound,
+            3 => EntryFlag::Lowerbound,
+            _ => return None,
+        };
+        // Only bits packed from a real move get here, as the key matched.
+        let best_move = (bits & 1 << 31 != 0).then(|| (self.bits.1)((bits >> 32) as u32));
+        Some(Entry {
+            high_hash: high_bits(hash),
+            value: bits as u16 as i16 as Evaluation,
+            depth: (bits >> 16) as u8,
+            flag,
+            generation: (bits >> 24) as u8 & 31,
+            best_move,
+        })
+    }
+}
+
+impl<M: Copy> Table<M> for PackedTable<M> {
+    fn lookup(&self, hash: u64) -> Option<Entry<M>> {
+        let entry = &self.table[(hash as usize) & self.mask];
+        let key = entry.key.load(Ordering::Relaxed);
+        let data = entry.data.load(Ordering::Relaxed);
+        if key ^ data != hash {
+            return None;
+        }
+        self.unpack(hash, data)
+    }
+
+    fn store(&mut self, hash: u64, value: Evaluation, depth: u8, flag: EntryFlag, best_move: M) {
+        self.concurrent_store(hash, value, depth, flag, best_move)
+    }
+    fn advance_generation(&mut self) {
+        self.concurrent_advance_generation()
+    }
+    fn memory_usage(&self) -> MemoryUsage {
+        let entries =
+            self.table.iter().filter(|entry| entry.data.load(Ordering::Relaxed) != 0).count();
+        table_usage::<PackedEntry>(entries, self.table.len())
+    }
+}
+
+impl<M: Copy> ConcurrentTable<M> for PackedTable<M> {
+    fn concurrent_store(
+        &self, hash: u64, value: Evaluation, depth: u8, flag: EntryFlag, best_move: M,
+    ) {
+        let table_gen = self.generation.load(Ordering::Relaxed) & 31;
+        let entry = &self.table[(hash as usize) & self.mask];
+        // A torn read here only makes for a worse replacement decision.
+        let old = entry.data.load(Ordering::Relaxed);
+        let (old_depth, old_gen) = ((old >> 16) as u8, (old >> 24) as u8 & 31);
+        if old_gen != table_gen || old_depth <= depth {
+            let data = self.pack(value, depth, flag, table_gen, Some(best_move));
+            entry.data.store(data, Ordering::Relaxed);
+            entry.key.store(hash ^ data, Ordering::Relaxed);
+        }
+    }
+
+    fn concurrent_advance_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// The table shared by parallel search threads: packed if the game encodes its
+// moves in 32 bits, otherwise with a lock bit in each entry.
+pub(super) enum SharedTable<M> {
+    Packed(PackedTable<M>),
+    Lockfree(LockfreeTable<M>),
+}
+
+impl<M: Copy> SharedTable<M> {
+    pub(super) fn new(table_byte_size: usize, bits: Option<MoveBits<M>>) -> Self {
+        match bits {
+            Some(bits) if PackedTable::<M>::fits() => {
+                SharedTable::Packed(PackedTable::new(table_byte_size, bits))
+            }
+            _ => SharedTable::Lockfree(LockfreeTable::new(table_byte_size)),
+        }
+    }
+}
+
+impl<M: Copy> Table<M> for SharedTable<M> {
+    fn lookup(&self, hash: u64) -> Option<Entry<M>> {
+        match self {
+            SharedTable::Packed(table) => table.lookup(hash),
+            SharedTable::Lockfree(table) => table.lookup(hash),
+        }
+    }
+
+    fn store(&mut self, hash: u64, value: Evaluation, depth: u8, flag: EntryFlag, best_move: M) {
+        self.concurrent_store(hash, value, depth, flag, best_move)
+    }
+    fn advance_generation(&mut self) {
+        self.concurrent_advance_generation()
+    }
+    fn memory_usage(&self) -> MemoryUsage {
+        match self {
+            SharedTable::Packed(table) => table.memory_usage(),
+            SharedTable::Lockfree(table) => table.memory_usage(),
+        }
+    }
+}
+
+impl<M: Copy> ConcurrentTable<M> for SharedTable<M> {
+    fn concurrent_store(
+        &self, hash: u64, value: Evaluation, depth: u8, flag: EntryFlag, best_move: M,
+    ) {
+        match self {
+            SharedTable::Packed(table) => {
+                table.concurrent_store(hash, value, depth, flag, best_move)
+            }
+            SharedTable::Lockfree(table) => {
+                table.concurrent_store(hash, value, depth, flag, best_move)
+            }
+        }
+    }
+
+    fn concurrent_advance_generation(&self) {
+        match self {
+            SharedTable::Packed(table) => table.concurrent_advance_generation(),
+            SharedTable::Lockfree(table) => table.concurrent_advance_generation(),
+        }
+    }
+}
+
+#[test]
+#[cfg(not(any(feature = "eval-i32", feature = "eval-i64")))]
+fn test_packed_table() {
+    let bits: MoveBits<[u8; 3]> = (
+        |m| u32::from_le_bytes([m[0], m[1], m[2], 0]),
+        |bits| {
+            let bytes = bits.to_le_bytes();
+            [bytes[0], bytes[1], bytes[2]]
+        },
+    );
+    let mut table = PackedTable::new(1 << 10, bits);
+    let hash = 0x1234_5678_9abc_def0;
+    assert!(table.lookup(hash).is_none());
+    table.store(hash, -300, 7, EntryFlag::Lowerbound, [1, 2, 3]);
+    let entry = table.lookup(hash).unwrap();
+    assert_eq!(entry.value, -300);
+    assert_eq!(entry.depth, 7);
+    assert_eq!(entry.flag, EntryFlag::Lowerbound);
+    assert_eq!(entry.best_move, Some([1, 2, 3]));
+    // Same slot, different hash.
+    assert!(table.lookup(hash ^ (1 << 40)).is_none());
+
+    // Half of another write is detected as a miss.
+    let index = (hash as usize) & table.mask;
+    let other = table.pack(5, 9, EntryFlag::Exact, 0, Some([4, 5, 6]));
+    table.table[index].data.store(other, Ordering::Relaxed);
+    assert!(table.lookup(hash).is_none());
+}
+
+/// Tuning for the countermove history table, enabled with
+/// `IterativeOptions::with_countermove_history`.
+///
+/// A move that causes a beta cutoff gets a bonus of its remaining depth
+/// squared, and the moves searched before it in the node get the same
+/// penalty. Updates follow "history gravity": each pulls a score toward the
+/// maximum in proportion to how far it is from it, so scores saturate
+/// smoothly instead of growing without bound.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryOptions {
+    pub(super) max_score: i32,
+    pub(super) max_bonus: i32,
+    pub(super) aging_divisor: i32,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        Self { max_score: 16384, max_bonus: 1024, aging_divisor: 2 }
+    }
+}
+
+impl HistoryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The largest magnitude of a history score. Defaults to 16384.
+    pub fn with_max_score(mut self, max_score: i32) -> Self {
+        self.max_score = max_score.max(1);
+        self
+    }
+
+    /// Cap each bonus or penalty at this, however deep the cutoff. Defaults
+    /// to 1024.
+    pub fn with_max_bonus(mut self, max_bonus: i32) -> Self {
+        self.max_bonus = max_bonus.max(0);
+        self
+    }
+
+    /// Divide every score by this between searches, so that stale ordering
+    /// from earlier in the game doesn't dominate. Defaults to 2.
+    pub fn with_aging_divisor(mut self, divisor: i32) -> Self {
+        self.aging_divisor = divisor.max(1);
+        self
+    }
+
+    // The new score after a bonus (or penalty, if negative), with gravity.
+    pub(super) fn apply(&self, score: i32, bonus: i32) -> i32 {
+        let bonus = bonus.clamp(-self.max_bonus, self.max_bonus);
+        let gravity = score as i64 * bonus.abs() as i64 / self.max_score as i64;
+        (score as i64 + bonus as i64 - gravity).clamp(-self.max_score as i64, self.max_score as i64)
+            as i32
+    }
+}
+
 // A single-threaded utility to find moves that have done well in other branches.
 pub(super) struct CounterMoves<G: Game> {
     countermove_enabled: bool,
     history_enabled: bool,
+    history: HistoryOptions,
     // For a given move index, which followup most recently led to a beta cutoff?
     countermove_table: Vec<G::M>,
-    // For each move index, how many beta cutoffs has it produced?
-    history_table: Vec<u32>,
+    // For each move index, how often has it produced beta cutoffs, compared
+    // to being searched without one?
+    history_table: Vec<i32>,
 }
 
 impl<G: Game> CounterMoves<G>
 where
     G::M: Eq + Copy,
 {
-    pub(super) fn new(countermove_enabled: bool, history_enabled: bool) -> Self {
+    pub(super) fn new(
+        countermove_enabled: bool, history_enabled: bool, history: HistoryOptions,
+    ) -> Self {
         Self {
             countermove_enabled,
             history_enabled,
+            history,
             countermove_table: Vec::new(),
             history_table: Vec::new(),
         }
@@ -404,7 +706,7 @@ where
     pub(super) fn reorder(&self, prev: Option<G::M>, moves: &mut [G::M]) {
         if !self.history_table.is_empty() {
             // Stable sort to preserve previous orderings.
-            moves.sort_by_key(|&m| !self.history_table[G::table_index(m) as usize]);
+            moves.sort_by_key(|&m| Reverse(self.history_table[G::table_index(m) as usize]));
         }
         if let Some(prev) = prev {
             if let Some(response) = self.countermove_table.get(G::table_index(prev) as usize) {
@@ -413,15 +715,24 @@ where
         }
     }
 
-    pub(super) fn update(&mut self, prev: Option<G::M>, m: G::M) {
+    // Move `m` caused a beta cutoff at `depth`, after searching the moves in
+    // `searched` before it.
+    pub(super) fn update(&mut self, prev: Option<G::M>, m: G::M, depth: u8, searched: &[G::M]) {
         if let Some(prev) = prev {
             if let Some(entry) = self.countermove_table.get_mut(G::table_index(prev) as usize) {
                 *entry = m;
             }
         }
-        if let Some(entry) = self.history_table.get_mut(G::table_index(m) as usize) {
-            *entry = 1u32.saturating_add(*entry);
+        if self.history_table.is_empty() {
+            return;
+        }
+        let bonus = depth as i32 * depth as i32;
+        for &other in searched.iter().filter(|&&other| other != m) {
+            let entry = &mut self.history_table[G::table_index(other) as usize];
+            *entry = self.history.apply(*entry, -bonus);
         }
+        let entry = &mut self.history_table[G::table_index(m) as usize];
+        *entry = self.history.apply(*entry, bonus);
     }
 
     pub(super) fn advance_generation(&mut self, null_move: Option<G::M>) {
@@ -435,7 +746,50 @@ where
             self.history_table = vec![0; G::max_table_index() as usize + 1];
         }
 
-        // Partially degrade old values, to bias towards new data.
-        self.history_table.iter_mut().for_each(|n| *n >>= 1);
+        // Age old values, to bias towards new data.
+        let divisor = self.history.aging_divisor;
+        self.history_table.iter_mut().for_each(|n| *n /= divisor);
+    }
+}
+
+#[test]
+fn test_history_gravity() {
+    struct Moves;
+    impl Game for Moves {
+        type S = ();
+        type M = u8;
+        fn generate_moves(_: &(), moves: &mut Vec<u8>) {
+            moves.extend(0..4);
+        }
+        fn apply(_: &mut (), _: u8) -> Option<()> {
+            None
+        }
+        fn get_winner(_: &()) -> Option<Winner> {
+            None
+        }
+        fn table_index(m: u8) -> u16 {
+            m as u16
+        }
+        fn max_table_index() -> u16 {
+            3
+        }
+    }
+
+    let history = HistoryOptions::new().with_max_score(1000).with_aging_divisor(4);
+    let mut cm = CounterMoves::<Moves>::new(false, true, history);
+    cm.advance_generation(None);
+    // Cutoffs saturate at the maximum score.
+    for _ in 0..1000 {
+        cm.update(None, 2, 30, &[0, 1]);
     }
+    assert!(cm.history_table[2] > 900 && cm.history_table[2] <= 1000);
+    assert!(cm.history_table[0] < -900 && cm.history_table[0] >= -1000);
+    assert_eq!(cm.history_table[3], 0);
+    let mut moves = [0, 1, 2, 3];
+    cm.reorder(None, &mut moves);
+    assert_eq!(moves, [2, 3, 0, 1]);
+
+    let score = cm.history_table[2];
+    cm.advance_generation(None);
+    assert_eq!(cm.history_table[2], score / 4);
 }
@@ -0,0 +1,127 @@
+//! Tracing why an alpha-beta search cut off the nodes along one line of
+//! play, to debug unexpected moves.
+
+use crate::interface::Evaluation;
+
+use std::fmt::{Debug, Display, Formatter, Result};
+
+/// What the search did at a node on the traced line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TraceKind<M> {
+    /// The transposition table had a value deep enough to return without
+    /// searching.
+    TableHit { value: Evaluation },
+    /// The node was at the search horizon, so it was evaluated, after any
+    /// quiescence search.
+    Leaf { value: Evaluation },
+    /// The node was still at least beta after passing the turn, so it was
+    /// pruned by null move pruning.
+    NullMoveCutoff { value: Evaluation },
+    /// Enough of the first moves were at least beta in a reduced depth
+    /// search, so the node was pruned by multi-cut pruning.
+    MultiCut { value: Evaluation },
+    /// A null-window probe of this move came back inside the window, so the
+    /// move was searched again with the full window.
+    ReSearch { m: M, probe: Evaluation },
+    /// This move was at least beta, so the search stopped after `searched`
+    /// moves of the node.
+    BetaCutoff { m: M, value: Evaluation, searched: usize },
+    /// Every move of the node was searched.
+    Searched { best_move: M, value: Evaluation },
+}
+
+/// An event at one node on the traced line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent<M> {
+    /// The node is reached by the first `ply` moves of the line.
+    pub ply: u8,
+    /// The remaining depth of the search at the node.
+    pub depth: u8,
+    /// The window the node was searched with.
+    pub alpha: Evaluation,
+    pub beta: Evaluation,
+    pub kind: TraceKind<M>,
+}
+
+/// The events of one search at the nodes along a line of play, in the order
+/// they happened. Nodes are usually visited many times in a search, once per
+/// iteration and again for any re-searches.
+#[derive(Clone, Debug, Default)]
+pub struct SearchTrace<M> {
+    /// The moves from the root of the search.
+    pub line: Vec<M>,
+    pub events: Vec<TraceEvent<M>>,
+}
+
+impl<M: Debug> Display for SearchTrace<M> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for event in self.events.iter() {
+            let ply = event.ply as usize;
+            write!(f, "{:indent$}", "", indent = 2 * ply)?;
+            match ply.checked_sub(1).and_then(|i| self.line.get(i)) {
+                Some(m) => write!(f, "{:?}", m)?,
+                None => write!(f, "root")?,
+            }
+            write!(f, " depth={} window=[{}, {}]: ", event.depth, event.alpha, event.beta)?;
+            match &event.kind {
+                TraceKind::TableHit { value } => writeln!(f, "table hit, value {}", value)?,
+                TraceKind::Leaf { value } => writeln!(f, "leaf, value {}", value)?,
+                TraceKind::NullMoveCutoff { value } => {
+                    writeln!(f, "null move cutoff, value {}", value)?
+                }
+                TraceKind::MultiCut { value } => writeln!(f, "multi-cut, value {}", value)?,
+                TraceKind::ReSearch { m, probe } => {
+                    writeln!(f, "re-search {:?} after null-window probe {}", m, probe)?
+                }
+                TraceKind::BetaCutoff { m, value, searched } => writeln!(
+                    f,
+                    "beta cutoff by {:?} with value {} after {} moves",
+                    m, value, searched
+                )?,
+                TraceKind::Searched { best_move, value } => {
+                    writeln!(f, "searched, best {:?} with value {}", best_move, value)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Follows the search to record the events on the traced line.
+pub(super) struct Tracer<M> {
+    pub(super) trace: SearchTrace<M>,
+    // The moves to the node being searched, with None for null moves.
+    path: Vec<Option<M>>,
+}
+
+impl<M: Copy + Eq> Tracer<M> {
+    pub(super) fn new(line: Vec<M>) -> Self {
+        Self { trace: SearchTrace { line, events: Vec::new() }, path: Vec::new() }
+    }
+
+    // Start a new search.
+    pub(super) fn reset(&mut self) {
+        self.trace.events.clear();
+        self.path.clear();
+    }
+
+    // Enter a node at `ply`, reached by `prev_move`, returning whether it is
+    // on the traced line.
+    pub(super) fn enter(&mut self, ply: u8, prev_move: Option<M>) -> bool {
+        let ply = ply as usize;
+        self.path.truncate(ply.saturating_sub(1));
+        if ply > 0 {
+            self.path.push(prev_move);
+        }
+        ply <= self.trace.line.len()
+            && self.path.iter().zip(self.trace.line.iter()).all(|(p, m)| *p == Some(*m))
+    }
+
+    pub(super) fn record(
+        &mut self, ply: u8, depth: u8, window: (Evaluation, Evaluation), kind: TraceKind<M>,
+    ) {
+        let (alpha, beta) = window;
+        self.trace.events.push(TraceEvent { ply, depth, alpha, beta, kind });
+    }
+}
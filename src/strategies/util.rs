@@ -1,20 +1,47 @@
+// Without the standard library, only Negamax and Random use these helpers.
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
 use super::super::interface::*;
+#[cfg(feature = "std")]
+use super::super::time::Instant;
+use super::super::util::AppliedMove;
 
-use rand::Rng;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::time::Duration;
 
 // For values near winning and losing values, push them slightly closer to zero.
 // A win in 3 moves (BEST-3) will be chosen over a win in 5 moves (BEST-5).
 // A loss in 5 moves (WORST+5) will be chosen over a loss in 3 moves (WORST+3).
 pub(super) fn clamp_value(value: Evaluation) -> Evaluation {
     if value > BEST_EVAL - 100 {
-        value - 1
+        value.saturating_sub(1)
     } else if value < WORST_EVAL + 100 {
-        value + 1
+        value.saturating_add(1)
     } else {
         value
     }
 }
 
+// The value for a node to return: as is when failing soft, or within the
+// window it was searched with when failing hard. Proven wins and losses are
+// never clamped, as that would lose their distance to the end of the game.
+pub(super) fn fail(value: Evaluation, window: (Evaluation, Evaluation), soft: bool) -> Evaluation {
+    let proven = |value| !(WORST_EVAL + 100..=BEST_EVAL - 100).contains(&value);
+    let hard = value.max(window.0).min(window.1);
+    if soft || proven(value) || proven(hard) {
+        value
+    } else {
+        hard
+    }
+}
+
 // Undo any value clamping.
 pub(super) fn unclamp_value(value: Evaluation) -> Evaluation {
     if value > BEST_EVAL - 100 {
@@ -26,6 +53,215 @@ pub(super) fn unclamp_value(value: Evaluation) -> Evaluation {
     }
 }
 
+// The chance of winning with a reported value, if the strategy was given a
+// scale. Without the standard library there's no `exp`, so no chance.
+pub(super) fn scaled_win_probability(value: Option<Evaluation>, scale: Option<f32>) -> Option<f32> {
+    #[cfg(feature = "std")]
+    return value.zip(scale).map(|(value, scale)| win_probability(value, scale));
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = (value, scale);
+        None
+    }
+}
+
+pub(super) type SharedTablebase<G> = Arc<dyn Tablebase<G = G> + Send + Sync>;
+
+// Whether the move by `player` that reached `child` passed the turn.
+pub(super) fn passes_turn<G: Game>(player: Option<u8>, child: &G::S) -> bool {
+    player.is_none() || G::player_to_move(child) != player
+}
+
+// Whether a move passed the turn to the opponent, so the values of the
+// position after it are for the other side. Without `Game::player_to_move`
+// every move does.
+#[derive(Clone, Copy)]
+pub(super) struct Turn {
+    passed: bool,
+}
+
+impl Turn {
+    // The turn after a move by `player`, the player to move before it.
+    pub(super) fn after<G: Game>(player: Option<u8>, child: &G::S) -> Self {
+        Turn { passed: passes_turn::<G>(player, child) }
+    }
+
+    // The window to search the position after the move with.
+    pub(super) fn window(self, alpha: Evaluation, beta: Evaluation) -> (Evaluation, Evaluation) {
+        if self.passed {
+            (-beta, -alpha)
+        } else {
+            (alpha, beta)
+        }
+    }
+
+    // The value of the move, from the value of the position after it.
+    pub(super) fn value(self, value: Evaluation) -> Evaluation {
+        if self.passed {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+// Exact value of a position for the player to move, if the tablebase has it.
+pub(super) fn probe_tablebase<G: Game>(
+    tablebase: &Option<SharedTablebase<G>>, s: &G::S,
+) -> Option<Evaluation> {
+    tablebase.as_ref()?.probe(s).map(|result| result.evaluate())
+}
+
+// If the result after every move from the root is known, return the best
+// move and its value without searching.
+pub(super) fn tablebase_root<G: Game>(
+    tablebase: &Option<SharedTablebase<G>>, s: &mut G::S,
+) -> Option<(G::M, Evaluation)>
+where
+    G::M: Copy,
+{
+    tablebase.as_ref()?;
+    let mut moves = Vec::new();
+    G::generate_moves(s, &mut moves);
+    let player = G::player_to_move(s);
+    let mut best = None;
+    for m in moves {
+        let child = AppliedMove::<G>::new(s, m);
+        let value = match G::get_winner(&child) {
+            Some(winner) => winner.evaluate(),
+            None => probe_tablebase(tablebase, &child)?,
+        };
+        let value = Turn::after::<G>(player, &child).value(value);
+        if best.is_none_or(|(_, best_value)| value > best_value) {
+            best = Some((m, value));
+        }
+    }
+    best
+}
+
+// Evaluate a position from the perspective of the player to move, including
+// any komi the game awards to that player. `ply` and `depth` are passed on
+// to the evaluator as its EvalContext, and `root_ply` is the number of moves
+// played before the search's root.
+pub(super) fn evaluate<E: Evaluator>(
+    eval: &E, s: &<E::G as Game>::S, root_ply: u32, ply: u8, depth: u8,
+) -> Evaluation {
+    let value = eval.evaluate_in_context(s, &EvalContext { ply, depth });
+    let value = for_player_to_move(eval, s, root_ply + ply as u32, value);
+    with_komi(value, E::G::komi(s))
+}
+
+// Add komi to a heuristic evaluation. Komi never moves a value into or out of
+// the range of proven wins and losses, so a large lead plus komi is still
+// only a lead.
+fn with_komi(value: Evaluation, komi: Evaluation) -> Evaluation {
+    let heuristic = WORST_EVAL + 100..=BEST_EVAL - 100;
+    if komi == 0 || !heuristic.contains(&value) {
+        return value.max(WORST_EVAL);
+    }
+    value.saturating_add(komi).clamp(*heuristic.start(), *heuristic.end())
+}
+
+// Turn an evaluation from an absolute evaluator into one for the player to
+// move. Games that don't say who is to move strictly alternate, so the
+// parity of the state's ply tells, or else of `ply`, the moves the search
+// counted to reach it.
+fn for_player_to_move<E: Evaluator>(
+    eval: &E, s: &<E::G as Game>::S, ply: u32, value: Evaluation,
+) -> Evaluation {
+    if !eval.is_absolute() {
+        return value;
+    }
+    let first_to_move = match E::G::player_to_move(s) {
+        Some(player) => player == 0,
+        None => E::G::ply(s).unwrap_or(ply) % 2 == 0,
+    };
+    if first_to_move {
+        value
+    } else {
+        value.saturating_neg()
+    }
+}
+
+// Collects the leaves below a node to evaluate them in one batch.
+pub(super) struct LeafBatch<S> {
+    states: Vec<S>,
+    evals: Vec<Evaluation>,
+    // For each child, whether it passed the turn and either its value, or the
+    // index of its state in the batch.
+    children: Vec<(Turn, Result<Evaluation, usize>)>,
+}
+
+impl<S> Default for LeafBatch<S> {
+    fn default() -> Self {
+        Self { states: Vec::new(), evals: Vec::new(), children: Vec::new() }
+    }
+}
+
+impl<S: Clone> LeafBatch<S> {
+    // Search the children of `s`, reached after `ply` moves, to depth zero,
+    // returning the best value and the index of its move. There's no
+    // pruning, as every leaf is evaluated by the same call.
+    pub(super) fn search<E: Evaluator>(
+        &mut self, eval: &E, tablebase: &Option<SharedTablebase<E::G>>, s: &mut S, ply: u32,
+        moves: &[<E::G as Game>::M],
+    ) -> (Evaluation, usize)
+    where
+        E::G: Game<S = S>,
+        <E::G as Game>::M: Copy,
+    {
+        self.states.clear();
+        self.evals.clear();
+        self.children.clear();
+        let player = E::G::player_to_move(s);
+        for &m in moves.iter() {
+            let child = AppliedMove::<E::G>::new(s, m);
+            let turn = Turn::after::<E::G>(player, &child);
+            let value = match E::G::get_winner(&child) {
+                Some(winner) => Ok(winner.evaluate()),
+                None => match probe_tablebase(tablebase, &child) {
+                    Some(value) => Ok(value),
+                    None => {
+                        self.states.push((*child).clone());
+                        Err(self.states.len() - 1)
+                    }
+                },
+            };
+            self.children.push((turn, value));
+        }
+        if !self.states.is_empty() {
+            eval.evaluate_batch(&self.states, &mut self.evals);
+        }
+
+        let mut best = (WORST_EVAL, 0);
+        for (i, &(turn, child)) in self.children.iter().enumerate() {
+            let value = match child {
+                Ok(value) => value,
+                Err(j) => {
+                    let state = &self.states[j];
+                    let value = for_player_to_move(eval, state, ply + 1, self.evals[j]);
+                    with_komi(value, E::G::komi(state))
+                }
+            };
+            let value = turn.value(value);
+            if value > best.0 || i == 0 {
+                best = (value, i);
+            }
+        }
+        best
+    }
+}
+
+// A randomly seeded rng. Without the standard library there is no source of
+// entropy, so every strategy starts from the same seed unless given one
+// with `set_rng_seed`.
+pub(super) fn new_rng() -> StdRng {
+    #[cfg(feature = "std")]
+    return StdRng::from_entropy();
+    #[cfg(not(feature = "std"))]
+    StdRng::seed_from_u64(0)
+}
+
 // Return a unique id for humans for this move.
 pub(super) fn move_id<G: Game>(s: &<G as Game>::S, m: Option<<G as Game>::M>) -> String {
     if let Some(mov) = m {
@@ -54,6 +290,135 @@ where
     out
 }
 
+// Record a finished search through the metrics facade, labeled with the
+// strategy's name. MCTS has no table, so no hit rate.
+#[cfg(feature = "metrics")]
+pub(super) fn record_search_metrics(
+    strategy: &'static str, depth: u8, nodes: u64, elapsed: Duration, table_hit_rate: Option<f64>,
+) {
+    metrics::counter!("minimax_searches_total", "strategy" => strategy).increment(1);
+    metrics::histogram!("minimax_search_depth", "strategy" => strategy).record(depth as f64);
+    metrics::histogram!("minimax_search_seconds", "strategy" => strategy)
+        .record(elapsed.as_secs_f64());
+    if elapsed > Duration::ZERO {
+        metrics::histogram!("minimax_nodes_per_second", "strategy" => strategy)
+            .record(nodes as f64 / elapsed.as_secs_f64());
+    }
+    if let Some(rate) = table_hit_rate {
+        metrics::histogram!("minimax_table_hit_rate", "strategy" => strategy).record(rate);
+    }
+}
+
+// Writes periodic snapshots of a search, e.g. to diagnose a search that
+// hangs or misbehaves.
+#[cfg(feature = "std")]
+pub(super) struct Snapshots {
+    writer: Box<dyn Write + Send>,
+    interval: Duration,
+    last: Instant,
+}
+
+#[cfg(feature = "std")]
+impl Snapshots {
+    pub(super) fn new(writer: Box<dyn Write + Send>, interval: Duration) -> Self {
+        Self { writer, interval, last: Instant::now() }
+    }
+
+    // Start the interval over, at the start of a search.
+    pub(super) fn reset(&mut self) {
+        self.last = Instant::now();
+    }
+
+    // Whether a snapshot is due, restarting the interval if so.
+    pub(super) fn due(&mut self) -> bool {
+        if self.last.elapsed() < self.interval {
+            return false;
+        }
+        self.last = Instant::now();
+        true
+    }
+
+    // Errors are ignored, as the search shouldn't fail on account of them.
+    pub(super) fn write(&mut self, snapshot: &str) {
+        let _ = self.writer.write_all(snapshot.as_bytes());
+        let _ = self.writer.flush();
+    }
+}
+
+// Streams the events of searches as JSON lines, remembering the last best
+// move and principal variation to report when they change.
+#[cfg(feature = "std")]
+pub(super) struct SearchLog {
+    writer: Box<dyn Write + Send>,
+    best_move: String,
+    pv: String,
+}
+
+#[cfg(feature = "std")]
+impl SearchLog {
+    pub(super) fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self { writer, best_move: String::new(), pv: String::new() }
+    }
+
+    // Forget the last search, at the start of a new one.
+    pub(super) fn reset(&mut self) {
+        self.best_move.clear();
+        self.pv.clear();
+    }
+
+    // Write an event, given as a JSON object. Errors are ignored, as the
+    // search shouldn't fail on account of them.
+    pub(super) fn write(&mut self, event: &str) {
+        let _ = writeln!(self.writer, "{}", event);
+        let _ = self.writer.flush();
+    }
+
+    // Whether this best move, as a JSON string, differs from the last one.
+    pub(super) fn best_move_changed(&mut self, best_move: &str) -> bool {
+        let changed = self.best_move != best_move;
+        if changed {
+            self.best_move = best_move.to_string();
+        }
+        changed
+    }
+
+    // Whether this principal variation, as a JSON array, differs from the
+    // last one.
+    pub(super) fn pv_changed(&mut self, pv: &str) -> bool {
+        let changed = self.pv != pv;
+        if changed {
+            self.pv = pv.to_string();
+        }
+        changed
+    }
+}
+
+// Generate the moves of `s`, highest priority first if the game attaches
+// priorities, and otherwise in the order generated. `priorities` is scratch
+// space, and is left empty.
+pub(super) fn generate_prioritized_moves<G: Game>(
+    s: &G::S, moves: &mut Vec<G::M>, priorities: &mut Vec<i16>,
+) {
+    let start = moves.len();
+    G::generate_scored_moves(s, moves, priorities);
+    if !priorities.is_empty() {
+        debug_assert_eq!(priorities.len(), moves.len() - start, "one priority per generated move");
+        // A stable insertion sort, as move lists are short and often
+        // nearly sorted already. Mismatched priorities are ignored.
+        let moves = &mut moves[start..];
+        let sorted = if priorities.len() == moves.len() { moves.len() } else { 0 };
+        for i in 1..sorted {
+            let mut j = i;
+            while j > 0 && priorities[j - 1] < priorities[j] {
+                priorities.swap(j - 1, j);
+                moves.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        priorities.clear();
+    }
+}
+
 pub(super) fn move_to_front<M: Eq>(m: M, moves: &mut [M]) {
     for i in 0..moves.len() {
         if moves[i] == m {
@@ -63,6 +428,87 @@ pub(super) fn move_to_front<M: Eq>(m: M, moves: &mut [M]) {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MoveStage {
+    TableMove,
+    Noisy,
+    Quiet,
+    Done,
+}
+
+// Yields the moves of a node one at a time. With staged generation, that's
+// the transposition table move, then the noisy moves, then the quiet moves,
+// generating each stage only when the previous one runs out, so that a
+// cutoff can skip generating the rest. Otherwise, it yields moves that were
+// generated up front.
+pub(super) struct StagedMoves<M> {
+    moves: Vec<M>,
+    next: usize,
+    stage: MoveStage,
+}
+
+impl<M: Copy + Eq> StagedMoves<M> {
+    // Yield the already generated `moves`.
+    pub(super) fn generated(moves: Vec<M>) -> Self {
+        Self { moves, next: 0, stage: MoveStage::Done }
+    }
+
+    // Generate moves in stages, starting with `table_move` if it's legal in
+    // `s`, as a hash collision can suggest any move. `moves` must be empty.
+    pub(super) fn staged<G: Game<M = M>>(
+        mut moves: Vec<M>, table_move: Option<M>, s: &G::S,
+    ) -> Self {
+        moves.extend(table_move.filter(|&m| G::is_legal(s, m)));
+        Self { moves, next: 0, stage: MoveStage::TableMove }
+    }
+
+    // The next move to search, if any. `reorder` sorts the moves of each
+    // newly generated stage.
+    pub(super) fn next<E: Evaluator>(
+        &mut self, eval: &E, s: &<E::G as Game>::S, mut reorder: impl FnMut(&mut [M]),
+    ) -> Option<M>
+    where
+        E::G: Game<M = M>,
+    {
+        while self.next == self.moves.len() {
+            let start = self.moves.len();
+            self.stage = match self.stage {
+                MoveStage::TableMove => {
+                    eval.generate_noisy_moves(s, &mut self.moves);
+                    MoveStage::Noisy
+                }
+                MoveStage::Noisy => {
+                    eval.generate_quiet_moves(s, &mut self.moves);
+                    MoveStage::Quiet
+                }
+                MoveStage::Quiet | MoveStage::Done => return None,
+            };
+            // Skip moves yielded by earlier stages.
+            let mut i = start;
+            while i < self.moves.len() {
+                if self.moves[..start].contains(&self.moves[i]) {
+                    self.moves.swap_remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+            reorder(&mut self.moves[start..]);
+        }
+        self.next += 1;
+        Some(self.moves[self.next - 1])
+    }
+
+    // The moves yielded so far.
+    pub(super) fn searched(&self) -> &[M] {
+        &self.moves[..self.next]
+    }
+
+    // The moves generated so far, to return to the move pool.
+    pub(super) fn into_inner(self) -> Vec<M> {
+        self.moves
+    }
+}
+
 // This exists to be wrapped in a mutex, because it didn't work when I tried a tuple.
 pub(super) struct ValueMove<M> {
     pub(super) value: Evaluation,
@@ -101,14 +547,16 @@ static PRIMES: [usize; 16] = [
 //       bias towards the one that scans first.
 //   - Don't shuffle the input or allocate a new array for shuffling.
 //   - Optimized for sets with <10k values.
-pub(super) fn random_best<T, F: Fn(&T) -> f32>(set: &[T], score_fn: F) -> Option<&T> {
+pub(super) fn random_best<'a, T, F: Fn(&T) -> f32, R: Rng + ?Sized>(
+    set: &'a [T], score_fn: F, rng: &mut R,
+) -> Option<&'a T> {
     // To make the choice more uniformly random among the best moves,
     // start at a random offset and stride by a random amount.
     // The stride must be coprime with n, so pick from a set of 5 digit primes.
 
     let n = set.len();
     // Combine both random numbers into a single rng call.
-    let r = rand::thread_rng().gen_range(0..n * PRIMES.len());
+    let r = rng.gen_range(0..n * PRIMES.len());
     let mut i = r / PRIMES.len();
     let stride = PRIMES[r % PRIMES.len()];
 
@@ -8,6 +8,7 @@
 extern crate rayon;
 
 use super::super::interface::*;
+use super::super::time::Instant;
 use super::super::util::*;
 use super::iterative::{IterativeOptions, Stats};
 use super::sync_util::{timeout_signal, CachePadded, ThreadLocal};
@@ -16,21 +17,35 @@ use super::util::*;
 
 use rayon::prelude::*;
 use std::cmp::max;
-use std::sync::atomic::{AtomicBool, AtomicI16, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+#[cfg(not(any(feature = "eval-i32", feature = "eval-i64")))]
+type AtomicEvaluation = std::sync::atomic::AtomicI16;
+#[cfg(all(feature = "eval-i32", not(feature = "eval-i64")))]
+type AtomicEvaluation = std::sync::atomic::AtomicI32;
+#[cfg(feature = "eval-i64")]
+type AtomicEvaluation = std::sync::atomic::AtomicI64;
 
 /// Options to use for the parallel search engine.
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParallelOptions {
     pub num_threads: Option<usize>,
     serial_cutoff_depth: u8,
     pub background_pondering: bool,
+    root_split: bool,
 }
 
 impl ParallelOptions {
     pub fn new() -> Self {
-        ParallelOptions { num_threads: None, serial_cutoff_depth: 1, background_pondering: false }
+        ParallelOptions {
+            num_threads: None,
+            serial_cutoff_depth: 1,
+            background_pondering: false,
+            root_split: false,
+        }
     }
 }
 
@@ -59,21 +74,48 @@ impl ParallelOptions {
         self
     }
 
+    /// Instead of splitting the tree wherever it can, search each root move
+    /// on its own thread with a full window, serially to full depth, and
+    /// merge the values in move order. This is less efficient, since no root
+    /// move can prune another, but the values and the chosen move don't
+    /// depend on which thread finishes first, which helps to debug
+    /// discrepancies between the serial and parallel searches.
+    pub fn with_root_split(mut self) -> Self {
+        self.root_split = true;
+        self
+    }
+
     pub fn num_threads(self) -> usize {
         self.num_threads.unwrap_or_else(num_cpus::get)
     }
 }
 
 struct ParallelNegamaxer<E: Evaluator> {
-    table: Arc<LockfreeTable<<E::G as Game>::M>>,
+    table: Arc<SharedTable<<E::G as Game>::M>>,
     eval: E,
     opts: IterativeOptions,
     par_opts: ParallelOptions,
     timeout: Arc<AtomicBool>,
+    stop: StopSignal,
     stats: ThreadLocal<CachePadded<Stats>>,
     move_pool: ThreadLocal<MovePool<<E::G as Game>::M>>,
+    priorities: ThreadLocal<Vec<i16>>,
+    leaf_batch: ThreadLocal<LeafBatch<<E::G as Game>::S>>,
     countermoves: ThreadLocal<CounterMoves<E::G>>,
     pv: Mutex<Vec<<E::G as Game>::M>>,
+    tablebase: Option<SharedTablebase<E::G>>,
+    // Don't start another depth after this, if nonzero.
+    soft_time: Duration,
+    // Stop after all threads together searched this many nodes, if nonzero.
+    max_nodes: u64,
+    nodes: AtomicU64,
+    // Moves played before the root.
+    root_ply: u32,
+    // The number of root moves to find exact values for, and the best of
+    // them from the last completed depth, if more than one.
+    multi_pv: usize,
+    lines: Mutex<Vec<(Evaluation, <E::G as Game>::M)>>,
+    win_scale: Option<f32>,
 }
 
 impl<E: Evaluator> ParallelNegamaxer<E>
@@ -84,7 +126,7 @@ where
 {
     fn new(
         opts: IterativeOptions, par_opts: ParallelOptions, eval: E,
-        table: Arc<LockfreeTable<<E::G as Game>::M>>, timeout: Arc<AtomicBool>,
+        table: Arc<SharedTable<<E::G as Game>::M>>, timeout: Arc<AtomicBool>, stop: StopSignal,
         thread_pool: &rayon::ThreadPool,
     ) -> Self {
         Self {
@@ -93,13 +135,30 @@ where
             opts,
             par_opts,
             timeout,
+            stop,
             stats: ThreadLocal::new(CachePadded::default, thread_pool),
             move_pool: ThreadLocal::new(MovePool::default, thread_pool),
+            priorities: ThreadLocal::new(Vec::new, thread_pool),
+            leaf_batch: ThreadLocal::new(LeafBatch::default, thread_pool),
             countermoves: ThreadLocal::new(
-                || CounterMoves::new(opts.countermove_table, opts.countermove_history_table),
+                || {
+                    CounterMoves::new(
+                        opts.countermove_table,
+                        opts.countermove_history_table,
+                        opts.history,
+                    )
+                },
                 thread_pool,
             ),
             pv: Mutex::new(Vec::new()),
+            tablebase: None,
+            soft_time: Duration::ZERO,
+            max_nodes: 0,
+            root_ply: 0,
+            multi_pv: 1,
+            lines: Mutex::new(Vec::new()),
+            nodes: AtomicU64::new(0),
+            win_scale: None,
         }
     }
 
@@ -107,8 +166,22 @@ where
         self.pv.lock().unwrap().clone()
     }
 
+    fn stopped(&self) -> bool {
+        self.timeout.load(Ordering::Relaxed)
+            || self.stop.is_stopped()
+            || (self.max_nodes != 0 && self.nodes.load(Ordering::Relaxed) >= self.max_nodes)
+    }
+
+    // Count a node against the node limit, returning whether to stop.
+    fn enter_node(&self) -> bool {
+        if self.max_nodes != 0 {
+            self.nodes.fetch_add(1, Ordering::Relaxed);
+        }
+        self.stopped()
+    }
+
     fn null_move_check(
-        &self, s: &mut <E::G as Game>::S, depth: u8, beta: Evaluation,
+        &self, s: &mut <E::G as Game>::S, hash: u64, ply: u8, depth: u8, beta: Evaluation,
     ) -> Option<Evaluation> {
         if let (Some(depth_reduction), Some(null_move)) =
             (self.opts.null_move_depth, E::G::null_move(s))
@@ -116,12 +189,20 @@ where
             // Default to a minimum of depth=1 after null moving.
             if depth > depth_reduction &&
 	    // If the position already seems pretty awesome.
-	      self.eval.evaluate(s) >= beta
+	      evaluate(&self.eval, s, self.root_ply, ply, depth) >= beta
             {
                 // If we just pass and let the opponent play this position (at reduced depth),
+                let nulled_hash = E::G::zobrist_hash_after(hash, s, null_move);
                 let mut nulled = AppliedMove::<E::G>::new(s, null_move);
-                let value =
-                    -self.negamax(&mut nulled, None, depth - depth_reduction, -beta, -beta + 1)?;
+                let value = -self.negamax(
+                    &mut nulled,
+                    nulled_hash,
+                    None,
+                    ply.saturating_add(1),
+                    depth - depth_reduction,
+                    -beta,
+                    -beta + 1,
+                )?;
                 // is the result still so good that we shouldn't bother with a full search?
                 if value >= beta {
                     return Some(value);
@@ -132,18 +213,95 @@ where
         Some(WORST_EVAL)
     }
 
-    // Negamax only among noisy moves.
+    #[allow(clippy::too_many_arguments)]
+    fn multi_cut_check(
+        &self, s: &mut <E::G as Game>::S, hash: u64, ply: u8, depth: u8, alpha: Evaluation,
+        beta: Evaluation, moves: &[<E::G as Game>::M],
+    ) -> Option<Evaluation> {
+        if let Some(cut) = self.opts.multi_cut {
+            // Only expect a cutoff at null-window nodes, and default to a
+            // minimum of depth=1 after the reduction.
+            if alpha + 1 == beta && depth > cut.depth_reduction && cut.cuts > 0 {
+                let player = E::G::player_to_move(s);
+                let mut cuts = 0;
+                for (i, &m) in moves.iter().take(cut.moves as usize).enumerate() {
+                    // Give up once too few moves are left to reach the cuts.
+                    if cut.moves as usize - i < (cut.cuts - cuts) as usize {
+                        break;
+                    }
+                    let new_hash = E::G::zobrist_hash_after(hash, s, m);
+                    let mut new = AppliedMove::<E::G>::new(s, m);
+                    let turn = Turn::after::<E::G>(player, &new);
+                    let (a, b) = turn.window(alpha, beta);
+                    let value = turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - cut.depth_reduction,
+                        a,
+                        b,
+                    )?);
+                    if value >= beta {
+                        cuts += 1;
+                        if cuts == cut.cuts {
+                            return Some(beta);
+                        }
+                    }
+                }
+            }
+        }
+        // If we didn't cut, return a low value that won't trigger beta cutoff.
+        Some(WORST_EVAL)
+    }
+
+    // Negamax only among noisy moves, or among all moves for up to
+    // `extension` plies while the position is unstable.
     fn noisy_negamax(
-        &self, s: &mut <E::G as Game>::S, depth: u8, mut alpha: Evaluation, beta: Evaluation,
+        &self, s: &mut <E::G as Game>::S, ply: u8, depth: u8, extension: u8, mut alpha: Evaluation,
+        beta: Evaluation,
     ) -> Option<Evaluation> {
-        if self.timeout.load(Ordering::Relaxed) {
+        if self.enter_node() {
             return None;
         }
         if let Some(winner) = E::G::get_winner(s) {
             return Some(winner.evaluate());
         }
+        if let Some(value) = probe_tablebase(&self.tablebase, s) {
+            return Some(value);
+        }
+        let player = E::G::player_to_move(s);
+        if extension > 0 && self.eval.is_unstable(s) {
+            let mut moves = Vec::new();
+            self.move_pool.local_do(|pool| moves = pool.alloc());
+            E::G::generate_moves(s, &mut moves);
+            let mut best = WORST_EVAL;
+            for &m in moves.iter() {
+                let mut new = AppliedMove::<E::G>::new(s, m);
+                let turn = Turn::after::<E::G>(player, &new);
+                let (a, b) = turn.window(alpha, beta);
+                let value = turn.value(self.noisy_negamax(
+                    &mut new,
+                    ply.saturating_add(1),
+                    depth,
+                    extension - 1,
+                    a,
+                    b,
+                )?);
+                best = max(best, value);
+                alpha = max(alpha, value);
+                if alpha >= beta {
+                    break;
+                }
+            }
+            let stuck = moves.is_empty();
+            self.move_pool.local_do(|pool| pool.free(moves));
+            if !stuck {
+                return Some(best);
+            }
+        }
         if depth == 0 {
-            return Some(self.eval.evaluate(s));
+            return Some(evaluate(&self.eval, s, self.root_ply, ply, depth));
         }
 
         let mut moves = Vec::new();
@@ -151,13 +309,36 @@ where
         self.eval.generate_noisy_moves(s, &mut moves);
         if moves.is_empty() {
             self.move_pool.local_do(|pool| pool.free(moves));
-            return Some(self.eval.evaluate(s));
+            return Some(evaluate(&self.eval, s, self.root_ply, ply, depth));
         }
 
+        // With delta pruning, skip moves that can't raise alpha, even with
+        // the margin.
+        let delta_base = self.opts.delta_margin.map(|margin| {
+            evaluate(&self.eval, s, self.root_ply, ply, depth).saturating_add(margin)
+        });
         let mut best = WORST_EVAL;
         for &m in moves.iter() {
+            if let Some(bound) = delta_base
+                .zip(self.eval.noisy_move_gain(s, m))
+                .map(|(base, gain)| base.saturating_add(gain))
+            {
+                if bound <= alpha {
+                    best = max(best, bound);
+                    continue;
+                }
+            }
             let mut new = AppliedMove::<E::G>::new(s, m);
-            let value = -self.noisy_negamax(&mut new, depth - 1, -beta, -alpha)?;
+            let turn = Turn::after::<E::G>(player, &new);
+            let (a, b) = turn.window(alpha, beta);
+            let value = turn.value(self.noisy_negamax(
+                &mut new,
+                ply.saturating_add(1),
+                depth - 1,
+                extension,
+                a,
+                b,
+            )?);
             best = max(best, value);
             alpha = max(alpha, value);
             if alpha >= beta {
@@ -169,44 +350,63 @@ where
     }
 
     // Recursively compute negamax on the game state. Returns None if it hits the timeout.
+    #[allow(clippy::too_many_arguments)]
     fn negamax(
-        &self, s: &mut <E::G as Game>::S, prev_move: Option<<E::G as Game>::M>, depth: u8,
-        mut alpha: Evaluation, mut beta: Evaluation,
+        &self, s: &mut <E::G as Game>::S, hash: Option<u64>, prev_move: Option<<E::G as Game>::M>,
+        ply: u8, depth: u8, mut alpha: Evaluation, mut beta: Evaluation,
     ) -> Option<Evaluation>
     where
         <E::G as Game>::S: Clone + Send + Sync,
         <E::G as Game>::M: Copy + Eq + Send + Sync,
         E: Sync,
     {
-        if self.timeout.load(Ordering::Relaxed) {
+        if self.enter_node() {
             return None;
         }
 
         self.stats.local_do(|stats| stats.explore_node());
+        let window = (alpha, beta);
 
         if depth == 0 {
             // Evaluate quiescence search on leaf nodes.
             // Will just return the node's evaluation if quiescence search is disabled.
-            return self.noisy_negamax(s, self.opts.max_quiescence_depth, alpha, beta);
+            let value = self.noisy_negamax(
+                s,
+                ply,
+                self.opts.max_quiescence_depth,
+                self.opts.unstable_extension,
+                alpha,
+                beta,
+            )?;
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
         if let Some(winner) = E::G::get_winner(s) {
-            return Some(winner.evaluate());
+            return Some(fail(winner.evaluate(), window, self.opts.fails_soft()));
+        }
+        if let Some(value) = probe_tablebase(&self.tablebase, s) {
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
 
         let alpha_orig = alpha;
-        let hash = E::G::zobrist_hash(s);
+        let hash = hash.unwrap_or_else(|| E::G::zobrist_hash(s));
         let mut good_move = None;
-        if let Some(value) = self.table.check(hash, depth, &mut good_move, &mut alpha, &mut beta) {
-            return Some(value);
+        let table_value = self.table.check(hash, depth, &mut good_move, &mut alpha, &mut beta);
+        self.stats
+            .local_do(|stats| stats.probe_table(table_value.is_some() || good_move.is_some()));
+        if let Some(value) = table_value {
+            return Some(fail(value, window, self.opts.fails_soft()));
         }
 
-        if self.null_move_check(s, depth, beta)? >= beta {
-            return Some(beta);
+        let null_value = self.null_move_check(s, hash, ply, depth, beta)?;
+        if null_value >= beta {
+            return Some(fail(null_value, window, self.opts.fails_soft()));
         }
 
         let mut moves = Vec::new();
         self.move_pool.local_do(|pool| moves = pool.alloc());
-        E::G::generate_moves(s, &mut moves);
+        self.priorities.local_do(|priorities| {
+            self.opts.generate_moves::<E::G>(s, ply, &mut moves, priorities)
+        });
         self.stats.local_do(|stats| stats.generate_moves(moves.len()));
         if moves.is_empty() {
             self.move_pool.local_do(|pool| pool.free(moves));
@@ -222,34 +422,106 @@ where
             move_to_front(good, &mut moves);
         }
 
+        let cut_value = self.multi_cut_check(s, hash, ply, depth, alpha, beta, &moves)?;
+        if cut_value >= beta {
+            self.move_pool.local_do(|pool| pool.free(moves));
+            return Some(fail(cut_value, window, self.opts.fails_soft()));
+        }
+
+        if depth == 1 && self.opts.batches_leaves() {
+            let mut result = (WORST_EVAL, 0);
+            self.leaf_batch.local_do(|batch| {
+                result = batch.search(
+                    &self.eval,
+                    &self.tablebase,
+                    s,
+                    self.root_ply + ply as u32,
+                    &moves,
+                );
+            });
+            let (best, i) = result;
+            self.stats.local_do(|stats| {
+                for _ in 0..moves.len() {
+                    stats.explore_node();
+                }
+            });
+            if best >= beta {
+                self.countermoves.local_do(|cm| cm.update(prev_move, moves[i], depth, &moves[..i]));
+            }
+            self.table.concurrent_update(hash, alpha_orig, beta, depth, best, moves[i]);
+            self.move_pool.local_do(|pool| pool.free(moves));
+            return Some(fail(clamp_value(best), window, self.opts.fails_soft()));
+        }
+
         let first_move = moves[0];
+        let player = E::G::player_to_move(s);
 
         // Evaluate first move serially.
         let initial_value = {
+            let new_hash = E::G::zobrist_hash_after(hash, s, first_move);
             let mut new = AppliedMove::<E::G>::new(s, first_move);
-            -self.negamax(&mut new, Some(first_move), depth - 1, -beta, -alpha)?
+            let turn = Turn::after::<E::G>(player, &new);
+            let (a, b) = turn.window(alpha, beta);
+            turn.value(self.negamax(
+                &mut new,
+                new_hash,
+                Some(first_move),
+                ply.saturating_add(1),
+                depth - 1,
+                a,
+                b,
+            )?)
         };
         alpha = max(alpha, initial_value);
         let (best, best_move) = if alpha >= beta {
             // Skip search
             (initial_value, first_move)
-        } else if self.par_opts.serial_cutoff_depth >= depth {
+        } else if self.par_opts.serial_cutoff_depth >= depth || self.par_opts.root_split {
             // Serial search
             let mut best = initial_value;
             let mut best_move = first_move;
             let mut null_window = false;
-            for &m in moves[1..].iter() {
+            for (i, &m) in moves.iter().enumerate().skip(1) {
+                let new_hash = E::G::zobrist_hash_after(hash, s, m);
                 let mut new = AppliedMove::<E::G>::new(s, m);
+                let turn = Turn::after::<E::G>(player, &new);
                 let value = if null_window {
-                    let probe = -self.negamax(&mut new, Some(m), depth - 1, -alpha - 1, -alpha)?;
+                    let (a, b) = turn.window(alpha, alpha + 1);
+                    let probe = turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - 1,
+                        a,
+                        b,
+                    )?);
                     if probe > alpha && probe < beta {
                         // Full search fallback.
-                        -self.negamax(&mut new, Some(m), depth - 1, -beta, -probe)?
+                        let (a, b) = turn.window(probe, beta);
+                        turn.value(self.negamax(
+                            &mut new,
+                            new_hash,
+                            Some(m),
+                            ply.saturating_add(1),
+                            depth - 1,
+                            a,
+                            b,
+                        )?)
                     } else {
                         probe
                     }
                 } else {
-                    -self.negamax(&mut new, Some(m), depth - 1, -beta, -alpha)?
+                    let (a, b) = turn.window(alpha, beta);
+                    turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - 1,
+                        a,
+                        b,
+                    )?)
                 };
                 if value > best {
                     best = value;
@@ -262,13 +534,13 @@ where
                     null_window = self.opts.null_window_search;
                 }
                 if alpha >= beta {
-                    self.countermoves.local_do(|cm| cm.update(prev_move, m));
+                    self.countermoves.local_do(|cm| cm.update(prev_move, m, depth, &moves[..i]));
                     break;
                 }
             }
             (best, best_move)
         } else {
-            let alpha = AtomicI16::new(alpha);
+            let alpha = AtomicEvaluation::new(alpha);
             let best_move = Mutex::new(ValueMove::new(initial_value, first_move));
             // Parallel search
             let result = moves[1..].par_iter().with_max_len(1).try_for_each(|&m| -> Option<()> {
@@ -279,28 +551,51 @@ where
                 }
 
                 let mut state = s.clone();
+                let new_hash = E::G::zobrist_hash_after(hash, &state, m);
                 let mut new = AppliedMove::<E::G>::new(&mut state, m);
+                let turn = Turn::after::<E::G>(player, &new);
                 let value = if self.opts.null_window_search && initial_alpha > alpha_orig {
                     // TODO: send reference to alpha as neg_beta to children.
-                    let probe = -self.negamax(
+                    let (a, b) = turn.window(initial_alpha, initial_alpha + 1);
+                    let probe = turn.value(self.negamax(
                         &mut new,
+                        new_hash,
                         Some(m),
+                        ply.saturating_add(1),
                         depth - 1,
-                        -initial_alpha - 1,
-                        -initial_alpha,
-                    )?;
+                        a,
+                        b,
+                    )?);
                     if probe > initial_alpha && probe < beta {
                         // Check again that we're not cancelled.
                         if alpha.load(Ordering::SeqCst) >= beta {
                             return None;
                         }
                         // Full search fallback.
-                        -self.negamax(&mut new, Some(m), depth - 1, -beta, -probe)?
+                        let (a, b) = turn.window(probe, beta);
+                        turn.value(self.negamax(
+                            &mut new,
+                            new_hash,
+                            Some(m),
+                            ply.saturating_add(1),
+                            depth - 1,
+                            a,
+                            b,
+                        )?)
                     } else {
                         probe
                     }
                 } else {
-                    -self.negamax(&mut new, Some(m), depth - 1, -beta, -initial_alpha)?
+                    let (a, b) = turn.window(initial_alpha, beta);
+                    turn.value(self.negamax(
+                        &mut new,
+                        new_hash,
+                        Some(m),
+                        ply.saturating_add(1),
+                        depth - 1,
+                        a,
+                        b,
+                    )?)
                 };
 
                 alpha.fetch_max(value, Ordering::SeqCst);
@@ -310,7 +605,7 @@ where
             });
             if result.is_none() {
                 // Check for timeout.
-                if self.timeout.load(Ordering::Relaxed) {
+                if self.stopped() {
                     return None;
                 }
             }
@@ -319,16 +614,56 @@ where
 
         self.table.concurrent_update(hash, alpha_orig, beta, depth, best, best_move);
         self.move_pool.local_do(|pool| pool.free(moves));
-        Some(clamp_value(best))
+        Some(fail(clamp_value(best), window, self.opts.fails_soft()))
+    }
+
+    // Search each root move with a full window on its own thread, and store
+    // the first of the best moves in the table, and the `multi_pv` best in
+    // `lines`.
+    fn root_split(&self, s: &<E::G as Game>::S, hash: u64, depth: u8) -> Option<Evaluation> {
+        let mut moves = Vec::new();
+        E::G::generate_moves(s, &mut moves);
+        let player = E::G::player_to_move(s);
+        let values = moves
+            .par_iter()
+            .with_max_len(1)
+            .map(|&m| {
+                let mut state = s.clone();
+                let new_hash = E::G::zobrist_hash_after(hash, &state, m);
+                let mut new = AppliedMove::<E::G>::new(&mut state, m);
+                let turn = Turn::after::<E::G>(player, &new);
+                let value =
+                    self.negamax(&mut new, new_hash, Some(m), 1, depth - 1, WORST_EVAL, BEST_EVAL);
+                value.map(|value| turn.value(value))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let mut best = 0;
+        for (i, &value) in values.iter().enumerate() {
+            if value > values[best] {
+                best = i;
+            }
+        }
+        let value = *values.get(best)?;
+        self.table.concurrent_update(hash, WORST_EVAL, BEST_EVAL, depth, value, moves[best]);
+        if self.multi_pv > 1 {
+            let mut lines = values.into_iter().zip(moves).collect::<Vec<_>>();
+            lines.sort_by_key(|&(value, _)| -value);
+            lines.truncate(self.multi_pv);
+            *self.lines.lock().unwrap() = lines;
+        }
+        Some(value)
     }
 
     fn iterative_search(
         &self, mut state: <E::G as Game>::S, max_depth: u8, background: bool,
-    ) -> Option<(<E::G as Game>::M, Evaluation)> {
+        mut progress: Option<&mut Box<dyn SearchProgress<<E::G as Game>::M>>>,
+    ) -> Option<(<E::G as Game>::M, Evaluation, u8)> {
+        let start_time = Instant::now();
         self.table.concurrent_advance_generation();
         let root_hash = E::G::zobrist_hash(&state);
         let mut best_move = None;
         let mut best_value = 0;
+        let mut completed_depth = 0;
         let mut interval_start;
         let mut pv = String::new();
 
@@ -338,7 +673,13 @@ where
         }
         while depth <= max_depth {
             interval_start = Instant::now();
-            if self.negamax(&mut state, None, depth, WORST_EVAL, BEST_EVAL).is_none() {
+            // Only a split root gives every root move an exact value.
+            let value = if self.par_opts.root_split || self.multi_pv > 1 {
+                self.root_split(&state, root_hash, depth)
+            } else {
+                self.negamax(&mut state, Some(root_hash), None, 0, depth, WORST_EVAL, BEST_EVAL)
+            };
+            if value.is_none() {
                 // Timeout. Return the best move from the previous depth.
                 break;
             }
@@ -356,6 +697,7 @@ where
 
             best_move = entry.best_move;
             best_value = entry.value;
+            completed_depth = depth;
 
             if self.opts.verbose && !background {
                 let interval = Instant::now() - interval_start;
@@ -374,14 +716,45 @@ where
             self.table.populate_pv::<E::G>(&mut pv_moves, &state);
             self.pv.lock().unwrap().clone_from(&pv_moves);
             pv = pv_string::<E::G>(&pv_moves[..], &state);
-            if unclamp_value(entry.value).abs() == BEST_EVAL {
+            if let Some(progress) = progress.as_mut() {
+                // Node counts are thread-local until the search finishes.
+                let value = Some(unclamp_value(best_value));
+                progress.report(&SearchInfo {
+                    best_move,
+                    value,
+                    win_probability: scaled_win_probability(value, self.win_scale),
+                    principal_variation: pv_moves,
+                    depth: completed_depth,
+                    elapsed: start_time.elapsed(),
+                    ..SearchInfo::default()
+                });
+            }
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                depth = completed_depth,
+                value = unclamp_value(best_value),
+                background,
+                elapsed_ms = start_time.elapsed().as_millis() as u64,
+                "completed depth"
+            );
+            // When analyzing several lines, keep going until all of them are
+            // decided, not just the best.
+            let decided = if self.multi_pv > 1 {
+                let lines = self.lines.lock().unwrap();
+                lines.iter().all(|&(value, _)| unclamp_value(value).abs() == BEST_EVAL)
+            } else {
+                unclamp_value(entry.value).abs() == BEST_EVAL
+            };
+            if decided
+                || (self.soft_time > Duration::ZERO && start_time.elapsed() >= self.soft_time)
+            {
                 break;
             }
         }
         if self.opts.verbose && !background {
             eprintln!("Principal variation: {}", pv);
         }
-        best_move.map(|m| (m, best_value))
+        best_move.map(|m| (m, best_value, completed_depth))
     }
 }
 
@@ -398,13 +771,31 @@ fn pretty_stats(stats: &Stats, start: Instant) -> String {
 pub struct ParallelSearch<E: Evaluator> {
     max_depth: u8,
     max_time: Duration,
+    max_nodes: u64,
+    // The clock to budget each move from, instead of a fixed timeout.
+    time_left: Option<TimeLeft>,
+    // Moves played in the game, as told by notify_move.
+    ply: u32,
 
     background_cancel: Arc<AtomicBool>,
-    table: Arc<LockfreeTable<<E::G as Game>::M>>,
+    stop: StopSignal,
+    table: Arc<SharedTable<<E::G as Game>::M>>,
     prev_value: Evaluation,
     principal_variation: Vec<<E::G as Game>::M>,
+    // The number of root moves to find exact values for, and the best of
+    // them from the last search, if more than one.
+    multi_pv: usize,
+    lines: Vec<(Evaluation, <E::G as Game>::M)>,
     eval: E,
 
+    // Runtime stats for the last move generated.
+    actual_depth: u8,
+    nodes_explored: u64,
+    wall_time: Duration,
+    progress: Option<Box<dyn SearchProgress<<E::G as Game>::M>>>,
+    tablebase: Option<SharedTablebase<E::G>>,
+    win_scale: Option<f32>,
+
     thread_pool: rayon::ThreadPool,
 
     opts: IterativeOptions,
@@ -413,20 +804,32 @@ pub struct ParallelSearch<E: Evaluator> {
 
 impl<E: Evaluator> ParallelSearch<E> {
     pub fn new(eval: E, opts: IterativeOptions, par_opts: ParallelOptions) -> ParallelSearch<E> {
-        let table = Arc::new(LockfreeTable::new(opts.table_byte_size));
+        let table = Arc::new(SharedTable::new(opts.table_byte_size, E::G::move_bits()));
         let num_threads = par_opts.num_threads();
         let pool_builder = rayon::ThreadPoolBuilder::new().num_threads(num_threads);
         ParallelSearch {
             max_depth: 99,
             max_time: Duration::from_secs(5),
+            max_nodes: 0,
+            time_left: None,
+            ply: 0,
             background_cancel: Arc::new(AtomicBool::new(false)),
+            stop: StopSignal::new(),
             table,
             prev_value: 0,
             principal_variation: Vec::new(),
+            multi_pv: 1,
+            lines: Vec::new(),
             thread_pool: pool_builder.build().unwrap(),
             opts,
             par_opts,
             eval,
+            actual_depth: 0,
+            nodes_explored: 0,
+            wall_time: Duration::default(),
+            progress: None,
+            tablebase: None,
+            win_scale: None,
         }
     }
 
@@ -436,6 +839,53 @@ impl<E: Evaluator> ParallelSearch<E> {
     }
 }
 
+/// Copies have the same settings, tablebase, and evaluator, and their own
+/// empty transposition table and threads. Games played at the same time
+/// share the machine's cores, so consider fewer threads for each with
+/// `ParallelOptions::with_num_threads`.
+impl<E: Evaluator + Clone> FreshCopy for ParallelSearch<E> {
+    fn fresh_copy(&self) -> Self {
+        let mut search = ParallelSearch::new(self.eval.clone(), self.opts, self.par_opts);
+        search.max_depth = self.max_depth;
+        search.max_time = self.max_time;
+        search.max_nodes = self.max_nodes;
+        search.time_left = self.time_left;
+        search.tablebase = self.tablebase.clone();
+        search.win_scale = self.win_scale;
+        search
+    }
+}
+
+impl<E: Evaluator> ParallelSearch<E>
+where
+    <E::G as Game>::S: Clone + Send + Sync,
+    <E::G as Game>::M: Copy + Eq + Send + Sync,
+    E: Clone + Sync + Send + 'static,
+{
+    // Search until cancelled to fill the shared table, which the next
+    // choose_move can reuse. `root_ply` moves were played before `state`.
+    fn search_in_background(&mut self, state: <E::G as Game>::S, root_ply: u32) {
+        self.background_cancel = Arc::new(AtomicBool::new(false));
+        // Create a separate negamaxer to have a dedicated cancel
+        // signal, and to allow the negamaxer to outlive this scope.
+        let mut negamaxer = ParallelNegamaxer::new(
+            self.opts,
+            self.par_opts,
+            self.eval.clone(),
+            self.table.clone(),
+            self.background_cancel.clone(),
+            StopSignal::new(),
+            &self.thread_pool,
+        );
+        negamaxer.tablebase = self.tablebase.clone();
+        negamaxer.root_ply = root_ply;
+        // Launch in threadpool asynchronously.
+        self.thread_pool.spawn(move || {
+            negamaxer.iterative_search(state, 99, true, None);
+        });
+    }
+}
+
 impl<E: Evaluator> Strategy<E::G> for ParallelSearch<E>
 where
     <E::G as Game>::S: Clone + Send + Sync,
@@ -443,79 +893,263 @@ where
     E: Clone + Sync + Send + 'static,
 {
     fn choose_move(&mut self, s: &<E::G as Game>::S) -> Option<<E::G as Game>::M> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "parallel_search",
+            max_depth = self.max_depth,
+            threads = self.par_opts.num_threads()
+        )
+        .entered();
         if E::G::get_winner(s).is_some() {
             return None;
         }
         // Cancel any ongoing background processing.
         self.background_cancel.store(true, Ordering::Relaxed);
+        let ply = E::G::ply(s).unwrap_or(self.ply);
+        let budget = self.time_left.map(|time| {
+            self.opts.time_allocator.budget_at_ply(&time, E::G::max_moves_remaining(s), Some(ply))
+        });
+        let max_time = budget.map_or(self.max_time, |budget| budget.hard);
         // Start timer if configured.
-        let timeout = if self.max_time == Duration::new(0, 0) {
+        let timeout = if max_time == Duration::new(0, 0) {
             Arc::new(AtomicBool::new(false))
         } else {
-            timeout_signal(self.max_time)
+            timeout_signal(max_time)
         };
 
-        let (best_move, value) = {
-            let start_time = Instant::now();
+        let start_time = Instant::now();
+        self.lines.clear();
+        if let Some((m, value)) = tablebase_root(&self.tablebase, &mut s.clone()) {
+            self.prev_value = value;
+            self.principal_variation = vec![m];
+            self.actual_depth = 0;
+            self.wall_time = start_time.elapsed();
+            self.stop.reset();
+            return Some(m);
+        }
+        let value_move = {
             let mut negamaxer = ParallelNegamaxer::new(
                 self.opts,
                 self.par_opts,
                 self.eval.clone(),
                 self.table.clone(),
                 timeout,
+                self.stop.clone(),
                 &self.thread_pool,
             );
+            negamaxer.tablebase = self.tablebase.clone();
+            negamaxer.soft_time = budget.map_or(Duration::ZERO, |budget| budget.soft);
+            negamaxer.max_nodes = self.max_nodes;
+            negamaxer.root_ply = ply;
+            negamaxer.multi_pv = self.multi_pv;
+            negamaxer.win_scale = self.win_scale;
             // Launch in threadpool and wait for result.
+            let progress = self.progress.as_mut();
             let value_move = self
                 .thread_pool
-                .install(|| negamaxer.iterative_search(s.clone(), self.max_depth, false));
+                .install(|| negamaxer.iterative_search(s.clone(), self.max_depth, false, progress));
             self.principal_variation = negamaxer.principal_variation();
+            self.lines = negamaxer.lines.into_inner().unwrap();
             let mut stats = Stats::default();
             negamaxer.stats.do_all(|local| stats.add(local));
+            self.nodes_explored = stats.nodes_explored;
             if self.opts.verbose {
                 eprintln!("{}", pretty_stats(&stats, start_time));
             }
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                depth = value_move.map_or(0, |(_, _, depth)| depth),
+                nodes = stats.nodes_explored,
+                table_hit_rate = stats.table_hit_rate(),
+                elapsed_ms = start_time.elapsed().as_millis() as u64,
+                "finished search"
+            );
+            #[cfg(feature = "metrics")]
+            record_search_metrics(
+                "parallel",
+                value_move.map_or(0, |(_, _, depth)| depth),
+                stats.nodes_explored,
+                start_time.elapsed(),
+                Some(stats.table_hit_rate()),
+            );
             value_move
-        }?;
+        };
+        self.stop.reset();
+        self.wall_time = start_time.elapsed();
+        let (best_move, value, depth) = match value_move {
+            Some(value_move) => value_move,
+            None => {
+                // Stopped before finishing the first iteration, any move will do.
+                let mut moves = Vec::new();
+                E::G::generate_moves(s, &mut moves);
+                (*moves.first()?, self.prev_value, 0)
+            }
+        };
         self.prev_value = value;
+        self.actual_depth = depth;
 
         if self.par_opts.background_pondering {
-            self.background_cancel = Arc::new(AtomicBool::new(false));
-            // Create a separate negamaxer to have a dedicated cancel
-            // signal, and to allow the negamaxer to outlive this scope.
-            let negamaxer = ParallelNegamaxer::new(
-                self.opts,
-                self.par_opts,
-                self.eval.clone(),
-                self.table.clone(),
-                self.background_cancel.clone(),
-                &self.thread_pool,
-            );
             let mut state = s.clone();
             if let Some(new_state) = E::G::apply(&mut state, best_move) {
                 state = new_state;
             }
-            // Launch in threadpool asynchronously.
-            self.thread_pool.spawn(move || {
-                negamaxer.iterative_search(state, 99, true);
-            });
+            self.search_in_background(state, ply + 1);
         }
         Some(best_move)
     }
 
+    fn choose_move_with_info(&mut self, s: &<E::G as Game>::S) -> SearchInfo<<E::G as Game>::M> {
+        self.actual_depth = 0;
+        self.nodes_explored = 0;
+        let best_move = self.choose_move(s);
+        let value = if self.actual_depth > 0 { Some(self.root_value()) } else { None };
+        SearchInfo {
+            best_move,
+            value,
+            win_probability: scaled_win_probability(value, self.win_scale),
+            principal_variation: self.principal_variation.clone(),
+            nodes: self.nodes_explored,
+            depth: self.actual_depth,
+            elapsed: self.wall_time,
+            ..SearchInfo::default()
+        }
+    }
+
     fn set_timeout(&mut self, max_time: Duration) {
         self.max_time = max_time;
+        self.time_left = None;
         self.max_depth = 99;
+        self.max_nodes = 0;
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.win_scale = Some(scale);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.max_nodes = nodes;
+        self.max_time = Duration::new(0, 0);
+        self.time_left = None;
+    }
+
+    fn notify_move(&mut self, _state: &<E::G as Game>::S, _m: <E::G as Game>::M) {
+        self.ply += 1;
+    }
+
+    fn new_game(&mut self) {
+        // Any background search keeps the old table to itself.
+        self.background_cancel.store(true, Ordering::Relaxed);
+        self.table = Arc::new(SharedTable::new(self.opts.table_byte_size, E::G::move_bits()));
+        self.ply = 0;
+        self.prev_value = 0;
+        self.principal_variation.clear();
+    }
+
+    fn set_time_left(&mut self, time: &TimeLeft) {
+        self.set_timeout(time.move_budget());
+        self.time_left = Some(*time);
     }
 
     fn set_max_depth(&mut self, depth: u8) {
         self.max_depth = depth;
         self.max_time = Duration::new(0, 0);
+        self.time_left = None;
+    }
+
+    fn set_stop_signal(&mut self, signal: StopSignal) {
+        self.stop = signal;
+    }
+
+    fn set_progress(&mut self, progress: Box<dyn SearchProgress<<E::G as Game>::M>>) {
+        self.progress = Some(progress);
+    }
+
+    fn set_tablebase(&mut self, tablebase: SharedTablebase<E::G>) {
+        self.tablebase = Some(tablebase);
     }
 
+    // Nothing to seed: the search makes no random choices, and only varies
+    // with the timing of its threads.
+    fn set_rng_seed(&mut self, _seed: u64) {}
+
     fn principal_variation(&self) -> Vec<<E::G as Game>::M> {
         self.principal_variation.clone()
     }
+
+    fn analyze(&mut self, s: &<E::G as Game>::S, k: usize) -> Vec<MoveAnalysis<<E::G as Game>::M>> {
+        if k == 0 {
+            return Vec::new();
+        }
+        self.multi_pv = k;
+        let info = self.choose_move_with_info(s);
+        self.multi_pv = 1;
+        // Don't let a background search change the table under the lines.
+        self.background_cancel.store(true, Ordering::Relaxed);
+        if self.lines.is_empty() {
+            // Only the best move is known, e.g. from a tablebase.
+            return MoveAnalysis::from_search(info).into_iter().collect();
+        }
+        let lines = std::mem::take(&mut self.lines);
+        lines
+            .into_iter()
+            .map(|(value, m)| {
+                let value = Some(unclamp_value(value));
+                let principal_variation = if self.principal_variation.first() == Some(&m) {
+                    self.principal_variation.clone()
+                } else {
+                    // The rest of the line is what the table remembers after
+                    // the move.
+                    let mut state = s.clone();
+                    if let Some(new_state) = E::G::apply(&mut state, m) {
+                        state = new_state;
+                    }
+                    let mut pv = Vec::new();
+                    self.table.populate_pv::<E::G>(&mut pv, &state);
+                    pv.insert(0, m);
+                    pv
+                };
+                MoveAnalysis {
+                    m,
+                    value,
+                    win_probability: scaled_win_probability(value, self.win_scale),
+                    principal_variation,
+                }
+            })
+            .collect()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        self.table.memory_usage()
+    }
+}
+
+/// The ponder search fills the shared transposition table, so the search after
+/// a ponder hit finishes deeper iterations quickly.
+impl<E: Evaluator> Ponder<E::G> for ParallelSearch<E>
+where
+    <E::G as Game>::S: Clone + Send + Sync,
+    <E::G as Game>::M: Copy + Eq + Send + Sync,
+    E: Clone + Sync + Send + 'static,
+{
+    fn ponder(&mut self, s: &<E::G as Game>::S, predicted_move: <E::G as Game>::M) {
+        self.stop_ponder();
+        if !E::G::is_legal(s, predicted_move) {
+            return;
+        }
+        let mut state = s.clone();
+        if let Some(new_state) = E::G::apply(&mut state, predicted_move) {
+            state = new_state;
+        }
+        if E::G::get_winner(&state).is_none() {
+            self.search_in_background(state, E::G::ply(s).unwrap_or(self.ply) + 1);
+        }
+    }
+
+    fn ponder_hit(&mut self) {}
+
+    fn stop_ponder(&mut self) {
+        self.background_cancel.store(true, Ordering::Relaxed);
+    }
 }
 
 impl<E: Evaluator> Drop for ParallelSearch<E> {
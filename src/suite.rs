@@ -0,0 +1,149 @@
+//! Test suites of positions with known answers, for tracking the strength
+//! of a strategy, e.g. on tactical puzzles.
+//!
+//! Suites are written one position per line, with fields separated by
+//! semicolons. The first field describes the position, and is parsed by a
+//! function for the game. The other fields are any of:
+//!
+//! * `bm <move>...`: the best move is one of these
+//! * `am <move>...`: avoid all of these moves
+//! * `win`, `loss` or `draw`: the value of the position for the player to
+//!   move
+//! * `id <name>`: a name for the position
+//!
+//! Moves are written as for `util::parse_move`. Blank lines and lines
+//! starting with `#` are ignored.
+
+use super::interface::*;
+use super::util::{move_notation, parse_move};
+
+use std::time::Duration;
+
+// Values at least this close to the extremes are known wins or losses.
+const DECIDED: Evaluation = 100;
+
+/// What a strategy should find in a test position.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expectation<M> {
+    /// Choose one of these moves.
+    BestMove(Vec<M>),
+    /// Choose none of these moves.
+    AvoidMove(Vec<M>),
+    /// Find that the player to move wins, loses, or draws.
+    Value(Winner),
+}
+
+/// A position and what a strategy should find in it.
+pub struct TestPosition<G: Game> {
+    pub id: String,
+    pub state: G::S,
+    pub expectations: Vec<Expectation<G::M>>,
+}
+
+impl<G: Game> TestPosition<G> {
+    /// Whether a search result meets every expectation.
+    pub fn check(&self, info: &SearchInfo<G::M>) -> bool
+    where
+        G::M: Eq,
+    {
+        self.expectations.iter().all(|expectation| match expectation {
+            Expectation::BestMove(moves) => info.best_move.is_some_and(|m| moves.contains(&m)),
+            Expectation::AvoidMove(moves) => info.best_move.is_some_and(|m| !moves.contains(&m)),
+            Expectation::Value(winner) => info.value.is_some_and(|value| match winner {
+                Winner::PlayerToMove => value >= BEST_EVAL - DECIDED,
+                Winner::PlayerJustMoved => value <= WORST_EVAL + DECIDED,
+                Winner::Draw => value == 0,
+            }),
+        })
+    }
+}
+
+/// Parse a suite in the text format, with `parse_position` parsing the
+/// position of each line. Returns an error naming the first bad line.
+pub fn parse_suite<G, F>(text: &str, parse_position: F) -> Result<Vec<TestPosition<G>>, String>
+where
+    G: Game,
+    G::M: Copy,
+    F: Fn(&str) -> Option<G::S>,
+{
+    let mut suite = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let mut fields = line.split(';').map(str::trim);
+        let description = fields.next().unwrap_or("");
+        let state = parse_position(description).ok_or_else(|| error("bad position"))?;
+        let mut position =
+            TestPosition { id: (number + 1).to_string(), state, expectations: Vec::new() };
+        for field in fields.filter(|field| !field.is_empty()) {
+            let mut words = field.split_whitespace();
+            let op = words.next().unwrap_or("");
+            let mut moves = || -> Result<Vec<G::M>, String> {
+                words
+                    .by_ref()
+                    .map(|word| {
+                        parse_move::<G>(&position.state, word)
+                            .ok_or_else(|| error(&format!("illegal move {}", word)))
+                    })
+                    .collect()
+            };
+            let expectation = match op {
+                "bm" => Expectation::BestMove(moves()?),
+                "am" => Expectation::AvoidMove(moves()?),
+                "win" => Expectation::Value(Winner::PlayerToMove),
+                "loss" => Expectation::Value(Winner::PlayerJustMoved),
+                "draw" => Expectation::Value(Winner::Draw),
+                "id" => {
+                    position.id = words.collect::<Vec<_>>().join(" ");
+                    continue;
+                }
+                _ => return Err(error(&format!("unknown field {}", op))),
+            };
+            position.expectations.push(expectation);
+        }
+        suite.push(position);
+    }
+    Ok(suite)
+}
+
+/// The outcome of one test position.
+#[derive(Clone, Debug)]
+pub struct TestResult<M> {
+    pub id: String,
+    pub passed: bool,
+    pub info: SearchInfo<M>,
+}
+
+/// Run the strategy on each position of the suite, with whatever budget it
+/// has been given. Prints whether each position passed with the move chosen
+/// and the time taken, followed by a summary, and returns the results.
+pub fn run_suite<G, S>(suite: &[TestPosition<G>], strategy: &mut S) -> Vec<TestResult<G::M>>
+where
+    G: Game,
+    G::M: Copy + Eq,
+    S: Strategy<G>,
+{
+    let mut results = Vec::with_capacity(suite.len());
+    for position in suite {
+        let info = strategy.choose_move_with_info(&position.state);
+        let passed = position.check(&info);
+        let m =
+            info.best_move.map_or("none".to_string(), |m| move_notation::<G>(&position.state, m));
+        let time = format!("{:.1?}", info.elapsed);
+        println!(
+            "{:<16} {:<4} {:>11} {:>11}",
+            position.id,
+            if passed { "pass" } else { "FAIL" },
+            m,
+            time
+        );
+        results.push(TestResult { id: position.id.clone(), passed, info });
+    }
+    let passed = results.iter().filter(|result| result.passed).count();
+    let elapsed = results.iter().map(|result| result.info.elapsed).sum::<Duration>();
+    println!("passed {}/{} in {:.1?}", passed, results.len(), elapsed);
+    results
+}
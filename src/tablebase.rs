@@ -0,0 +1,183 @@
+//! Tablebases built by retrograde analysis, for games small enough to
+//! enumerate every reachable position.
+//!
+//! Every position reachable from a start is generated, and results are
+//! propagated backwards from the finished games: a position is won if some
+//! move leads to a position lost for the opponent, and lost if every move
+//! leads to a position won for the opponent. Whatever is left over is drawn.
+//!
+//! Positions are identified by `zobrist_hash`, so the game must implement it,
+//! and the hash should be collision-free over the positions in the table.
+
+use super::interface::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+const MAGIC: &[u8; 4] = b"MMTB";
+
+/// Exact results with distances for every position reachable from a start.
+pub struct RetrogradeTablebase<G: Game> {
+    entries: HashMap<u64, TablebaseResult>,
+    game_type: PhantomData<fn() -> G>,
+}
+
+impl<G: Game> RetrogradeTablebase<G> {
+    /// Solve every position reachable from `start`, or fail if there are
+    /// more than `max_states` of them.
+    ///
+    /// Wins have the distance of the fastest win, and losses of the slowest
+    /// loss. Drawn positions from which the game can't end have no distance.
+    pub fn build(start: &G::S, max_states: usize) -> Result<Self, String>
+    where
+        G::S: Clone,
+    {
+        // Enumerate the positions and the moves between them.
+        let mut index = HashMap::new();
+        let mut states = vec![start.clone()];
+        let mut successors: Vec<Vec<usize>> = Vec::new();
+        index.insert(G::zobrist_hash(start), 0);
+        let mut moves = Vec::new();
+        while successors.len() < states.len() {
+            let mut state = states[successors.len()].clone();
+            let mut children = Vec::new();
+            if G::get_winner(&state).is_none() {
+                moves.clear();
+                G::generate_moves(&state, &mut moves);
+                for &m in moves.iter() {
+                    let child = match G::apply(&mut state, m) {
+                        Some(child) => child,
+                        None => {
+                            let child = state.clone();
+                            G::undo(&mut state, m);
+                            child
+                        }
+                    };
+                    let next = index.len();
+                    let i = *index.entry(G::zobrist_hash(&child)).or_insert(next);
+                    if i == next {
+                        if states.len() == max_states {
+                            return Err(format!("more than {} positions", max_states));
+                        }
+                        states.push(child);
+                    }
+                    children.push(i);
+                }
+            }
+            successors.push(children);
+        }
+
+        let n = states.len();
+        let mut predecessors = vec![Vec::new(); n];
+        for (i, children) in successors.iter().enumerate() {
+            for &child in children.iter() {
+                predecessors[child].push(i);
+            }
+        }
+
+        // Moves not yet known to lose, for positions not yet solved.
+        let mut unresolved: Vec<usize> = successors.iter().map(Vec::len).collect();
+        let mut results: Vec<Option<TablebaseResult>> = vec![None; n];
+        let mut queue = VecDeque::new();
+        for (i, state) in states.iter().enumerate() {
+            if let Some(winner) = G::get_winner(state) {
+                results[i] = Some(TablebaseResult { winner, distance: Some(0) });
+                if winner != Winner::Draw {
+                    queue.push_back(i);
+                }
+            }
+        }
+        // Positions are solved in order of distance, so each win is found at
+        // its shortest distance and each loss at its longest.
+        while let Some(i) = queue.pop_front() {
+            let result = results[i].unwrap();
+            let distance = result.distance.map(|d| d.saturating_add(1).min(u8::MAX - 1));
+            for &parent in predecessors[i].iter() {
+                if results[parent].is_some() {
+                    continue;
+                }
+                if result.winner == Winner::PlayerJustMoved {
+                    results[parent] =
+                        Some(TablebaseResult { winner: Winner::PlayerToMove, distance });
+                    queue.push_back(parent);
+                } else {
+                    unresolved[parent] -= 1;
+                    if unresolved[parent] == 0 {
+                        results[parent] =
+                            Some(TablebaseResult { winner: Winner::PlayerJustMoved, distance });
+                        queue.push_back(parent);
+                    }
+                }
+            }
+        }
+
+        // Anything unsolved can avoid losing but can't force a win.
+        let draw = TablebaseResult { winner: Winner::Draw, distance: None };
+        let entries =
+            index.into_iter().map(|(hash, i)| (hash, results[i].unwrap_or(draw))).collect();
+        Ok(Self { entries, game_type: PhantomData })
+    }
+
+    /// Number of positions in the table.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the table in a compact binary format.
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (hash, result) in self.entries.iter() {
+            let winner = match result.winner {
+                Winner::PlayerJustMoved => 0u8,
+                Winner::PlayerToMove => 1,
+                Winner::Draw => 2,
+            };
+            writer.write_all(&hash.to_le_bytes())?;
+            // A distance of 255 is saved as unknown.
+            writer.write_all(&[winner, result.distance.unwrap_or(u8::MAX)])?;
+        }
+        writer.flush()
+    }
+
+    /// Read a table written by `save`.
+    pub fn load<R: Read>(mut reader: R) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a tablebase"));
+        }
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len) as usize;
+        let mut entries = HashMap::with_capacity(len.min(1 << 20));
+        let mut entry = [0; 10];
+        for _ in 0..len {
+            reader.read_exact(&mut entry)?;
+            let hash = u64::from_le_bytes(entry[..8].try_into().unwrap());
+            let winner = match entry[8] {
+                0 => Winner::PlayerJustMoved,
+                1 => Winner::PlayerToMove,
+                2 => Winner::Draw,
+                _ => return Err(invalid("bad result")),
+            };
+            let distance = Some(entry[9]).filter(|&d| d != u8::MAX);
+            entries.insert(hash, TablebaseResult { winner, distance });
+        }
+        Ok(Self { entries, game_type: PhantomData })
+    }
+}
+
+impl<G: Game> Tablebase for RetrogradeTablebase<G> {
+    type G = G;
+
+    fn probe(&self, state: &G::S) -> Option<TablebaseResult> {
+        self.entries.get(&G::zobrist_hash(state)).copied()
+    }
+}
@@ -0,0 +1,97 @@
+//! The clock behind time limits.
+//!
+//! Every strategy and match reads the time through a global `TimeSource`.
+//! With the standard library, the default source is the system's monotonic
+//! clock, which on `wasm32-unknown-unknown` is the browser's
+//! `performance.now()`. Environments without either, such as `no_std`
+//! targets or WebAssembly hosts without JavaScript, can supply their own with
+//! `set_time_source`. Without the standard library and a source, time stands
+//! still: deadlines never pass, and nothing takes any time.
+//!
+//! On native targets, the timeouts of `IterativeSearch`, `ParallelSearch`,
+//! and `MonteCarloTreeSearch` are raised by a timer thread sleeping on the
+//! system clock instead.
+
+use alloc::boxed::Box;
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::time::Duration;
+
+/// A monotonic clock.
+pub trait TimeSource: Send + Sync {
+    /// The time since some fixed point, such as when the program started.
+    fn now(&self) -> Duration;
+}
+
+impl<F: Fn() -> Duration + Send + Sync> TimeSource for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+/// The system's monotonic clock, which is the default time source.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdTimeSource;
+
+#[cfg(feature = "std")]
+impl TimeSource for StdTimeSource {
+    fn now(&self) -> Duration {
+        static START: std::sync::OnceLock<instant::Instant> = std::sync::OnceLock::new();
+        START.get_or_init(instant::Instant::now).elapsed()
+    }
+}
+
+static SOURCE: AtomicPtr<Box<dyn TimeSource>> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Read the time from `source` from now on, in every thread.
+///
+/// This is meant to be called once at startup: the source is never freed,
+/// and searches already running may mix times from the old and new sources.
+pub fn set_time_source<T: TimeSource + 'static>(source: T) {
+    let source: Box<Box<dyn TimeSource>> = Box::new(Box::new(source));
+    SOURCE.store(Box::into_raw(source), Ordering::Release);
+}
+
+fn now() -> Duration {
+    let source = SOURCE.load(Ordering::Acquire);
+    if !source.is_null() {
+        // Sources are leaked when set, so this is never dangling.
+        return unsafe { (*source).now() };
+    }
+    #[cfg(feature = "std")]
+    return StdTimeSource.now();
+    #[cfg(not(feature = "std"))]
+    Duration::ZERO
+}
+
+// A point in time from the time source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Instant(Duration);
+
+impl Instant {
+    pub(crate) fn now() -> Self {
+        Instant(now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Self::now() - *self
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant(self.0 + duration)
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    // Saturates, in case the time source is replaced by one that is behind.
+    fn sub(self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+}
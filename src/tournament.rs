@@ -0,0 +1,852 @@
+//! Tournaments between strategies, for measuring their relative strength.
+//!
+//! By default every pair of players meets the same number of times,
+//! alternating who moves first, and the results are summarized as a
+//! win/draw/loss matrix and Elo estimates with error bars. To vary the
+//! games, each pairing can be played from every one of a set of openings.
+//!
+//! For day-to-day testing of a change, a gauntlet plays one candidate
+//! against a pool of reference players, and a knockout eliminates the loser
+//! of each pairing until one player is left.
+//!
+//! For A/B tests of a change, a sequential probability ratio test (SPRT) can
+//! end each pairing as soon as the result is statistically significant.
+//!
+//! Long tournaments can be checkpointed to a file after every game, so that
+//! an interrupted run can be resumed and partial results read while it is
+//! still going.
+
+use super::interface::*;
+use super::json;
+use super::match_play::{Match, MoveLog, Notation, TimeControl};
+use super::record::{GameRecord, Outcome};
+use super::util::move_notation;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::Duration;
+
+const CHECKPOINT_HEADER: &str = "minimax tournament checkpoint";
+
+// Finished games from a checkpoint, keyed by the players' indices in
+// pairing order and the game's index within the pairing.
+struct Checkpoint {
+    names: Vec<String>,
+    games_per_pairing: u32,
+    format: TournamentFormat,
+    games: HashMap<(usize, usize, u32), Outcome>,
+    // The players left after each finished round of a knockout.
+    rounds: Vec<Vec<usize>>,
+}
+
+impl Checkpoint {
+    // The checkpoint is a line-based text file: a header, the number of
+    // games per pairing, the format unless it's a round robin, a line per
+    // player, and then a line per finished game, such as "game 0 1 3 1-0",
+    // with the result for the player who moved first. A knockout also has a
+    // line per finished round, such as "round 0 3", with the players left.
+    fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("bad checkpoint line: {}", line))
+        };
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        if header != CHECKPOINT_HEADER {
+            return Err(invalid(&header));
+        }
+        let mut checkpoint = Checkpoint {
+            names: Vec::new(),
+            games_per_pairing: 0,
+            format: TournamentFormat::RoundRobin,
+            games: HashMap::new(),
+            rounds: Vec::new(),
+        };
+        for line in lines {
+            let line = line?;
+            if let Some(name) = line.strip_prefix("player ") {
+                checkpoint.names.push(name.to_string());
+            } else if let Some(games) = line.strip_prefix("games_per_pairing ") {
+                checkpoint.games_per_pairing = games.parse().map_err(|_| invalid(&line))?;
+            } else if let Some(format) = line.strip_prefix("format ") {
+                checkpoint.format = match format {
+                    "gauntlet" => TournamentFormat::Gauntlet,
+                    "knockout" => TournamentFormat::Knockout,
+                    _ => return Err(invalid(&line)),
+                };
+            } else if let Some(round) = line.strip_prefix("round ") {
+                let players = round.split(' ').map(|player| player.parse().ok()).collect();
+                // Like a game, a partly written round is written again.
+                if let Some(players) = players {
+                    checkpoint.rounds.push(players);
+                }
+            } else if let Some(game) = line.strip_prefix("game ") {
+                // A partly written last line is left for the game to be
+                // replayed.
+                if let Some((key, outcome)) = Self::parse_game(game) {
+                    checkpoint.games.insert(key, outcome);
+                }
+            } else if !line.is_empty() {
+                return Err(invalid(&line));
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    fn parse_game(game: &str) -> Option<((usize, usize, u32), Outcome)> {
+        let fields: Vec<&str> = game.split(' ').collect();
+        let [i, j, index, result] = fields[..] else {
+            return None;
+        };
+        let outcome = match result {
+            "1-0" => Outcome::Win(0),
+            "0-1" => Outcome::Win(1),
+            "draw" => Outcome::Draw,
+            _ => return None,
+        };
+        Some(((i.parse().ok()?, j.parse().ok()?, index.parse().ok()?), outcome))
+    }
+
+    fn create(
+        path: &Path, names: &[String], games_per_pairing: u32, format: TournamentFormat,
+    ) -> io::Result<File> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", CHECKPOINT_HEADER)?;
+        writeln!(file, "games_per_pairing {}", games_per_pairing)?;
+        match format {
+            TournamentFormat::RoundRobin => {}
+            TournamentFormat::Gauntlet => writeln!(file, "format gauntlet")?,
+            TournamentFormat::Knockout => writeln!(file, "format knockout")?,
+        }
+        for name in names {
+            writeln!(file, "player {}", name)?;
+        }
+        file.flush()?;
+        Ok(file)
+    }
+
+    fn write_game(
+        file: &mut File, i: usize, j: usize, index: u32, outcome: Option<Outcome>,
+    ) -> io::Result<()> {
+        let result = match outcome {
+            Some(Outcome::Win(0)) => "1-0",
+            Some(Outcome::Win(_)) => "0-1",
+            _ => "draw",
+        };
+        writeln!(file, "game {} {} {} {}", i, j, index, result)?;
+        file.flush()
+    }
+
+    fn write_round(file: &mut File, players: &[usize]) -> io::Result<()> {
+        let players: Vec<String> = players.iter().map(|player| player.to_string()).collect();
+        writeln!(file, "round {}", players.join(" "))?;
+        file.flush()
+    }
+}
+
+/// Who plays whom in a tournament.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TournamentFormat {
+    /// Every player plays every other player.
+    #[default]
+    RoundRobin,
+    /// The first player added, the candidate, plays each of the others, a
+    /// pool of references that don't play each other.
+    Gauntlet,
+    /// Single elimination, seeded in the order the players were added. Each
+    /// round, the best remaining seed plays the worst, the second best the
+    /// second worst, and so on, with the middle seed resting when there's
+    /// an odd number. The player with the higher score goes through, or the
+    /// better seed on a tie.
+    Knockout,
+}
+
+struct Player<G: Game> {
+    name: String,
+    strategy: Box<dyn Strategy<G>>,
+    time_odds: f64,
+}
+
+/// Plays every player against every other player.
+pub struct Tournament<G: Game> {
+    players: Vec<Player<G>>,
+    games_per_pairing: u32,
+    openings: Vec<G::S>,
+    opening_lines: Vec<Vec<G::M>>,
+    time_control: Option<TimeControl>,
+    max_moves: Option<u32>,
+    sprt: Option<Sprt>,
+    format: TournamentFormat,
+    json_output: Option<Box<dyn Write + Send>>,
+    move_log: Option<(MoveLog, Notation<G>)>,
+    records: Vec<GameRecord<G>>,
+}
+
+impl<G: Game> Tournament<G> {
+    pub fn new() -> Self {
+        Self {
+            players: Vec::new(),
+            games_per_pairing: 2,
+            openings: Vec::new(),
+            opening_lines: Vec::new(),
+            time_control: None,
+            max_moves: None,
+            sprt: None,
+            format: TournamentFormat::RoundRobin,
+            json_output: None,
+            move_log: None,
+            records: Vec::new(),
+        }
+    }
+
+    /// Add a player, identified by `name` in the results.
+    pub fn with_player(mut self, name: &str, strategy: Box<dyn Strategy<G>>) -> Self {
+        self.players.push(Player { name: name.to_string(), strategy, time_odds: 1.0 });
+        self
+    }
+
+    /// Play this many games between each pair of players, from each opening
+    /// if there are any, half with each moving first. Defaults to 2.
+    pub fn with_games_per_pairing(mut self, games: u32) -> Self {
+        self.games_per_pairing = games;
+        self
+    }
+
+    /// Play the games per pairing from each of these positions, instead of
+    /// from the start position given to `run`. With an even number of games
+    /// per pairing, both players move first equally often from each.
+    pub fn with_openings(mut self, openings: Vec<G::S>) -> Self {
+        self.openings = openings;
+        self
+    }
+
+    /// Play the games per pairing from each of the positions reached by
+    /// playing one of these lines of moves from the start position, like
+    /// `with_openings`. The lines' moves are not part of the game records.
+    pub fn with_opening_lines(mut self, lines: Vec<Vec<G::M>>) -> Self {
+        self.opening_lines = lines;
+        self
+    }
+
+    // Games each pair of players plays, over all the openings.
+    fn games_per_pair(&self) -> u32 {
+        let openings = self.openings.len() + self.opening_lines.len();
+        self.games_per_pairing * openings.max(1) as u32
+    }
+
+    /// Give each player this much thinking time for each game. Each move
+    /// gets a share of the time left, and a player that runs out of time
+    /// loses. By default, the strategies' own limits apply.
+    pub fn with_time_per_game(self, time: Duration) -> Self {
+        self.with_time_control(TimeControl::new(time))
+    }
+
+    /// Play every game with this time control for both players.
+    pub fn with_time_control(mut self, time_control: TimeControl) -> Self {
+        self.time_control = Some(time_control);
+        self
+    }
+
+    /// Multiply the time control of the player called `name` by `factor`,
+    /// so that, for example, a factor of 2 measures what a doubling of
+    /// thinking time is worth against the other players. Only applies with
+    /// a time control; other budgets, such as depths or rollouts, can be
+    /// given to each player's strategy directly.
+    ///
+    /// # Panics
+    ///
+    /// If there is no player called `name`.
+    pub fn with_time_odds(mut self, name: &str, factor: f64) -> Self {
+        let player = self.players.iter_mut().find(|player| player.name == name);
+        player.unwrap_or_else(|| panic!("no player called {}", name)).time_odds = factor;
+        self
+    }
+
+    /// Declare a game drawn after this many moves, for games that can go on
+    /// forever.
+    pub fn with_max_moves(mut self, moves: u32) -> Self {
+        self.max_moves = Some(moves);
+        self
+    }
+
+    /// Stop each pairing once `sprt` reaches a decision about the player
+    /// added first, playing at most the games per pairing. The test is
+    /// checked after each pair of games, so both players have moved first
+    /// equally often.
+    pub fn with_sprt(mut self, sprt: Sprt) -> Self {
+        self.sprt = Some(sprt);
+        self
+    }
+
+    /// Choose who plays whom. Defaults to a round robin.
+    pub fn with_format(mut self, format: TournamentFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Write each game to `output` as a line of JSON when it finishes, as
+    /// from `GameRecord::to_json`, followed by a line with the results, as
+    /// from `TournamentResults::to_json`.
+    pub fn with_json_output(mut self, output: Box<dyn Write + Send>) -> Self {
+        self.json_output = Some(output);
+        self
+    }
+
+    /// Write each move to `log` as soon as it is played, so that a strategy
+    /// that crashes loses at most the move it was choosing, and the games
+    /// up to the crash can be replayed with `MoveLog::read`. Games from
+    /// openings are logged with the index of their opening.
+    pub fn with_move_log(mut self, log: MoveLog) -> Self
+    where
+        G::M: Eq,
+    {
+        self.move_log = Some((log, move_notation::<G>));
+        self
+    }
+
+    /// Play all the games from `start`.
+    pub fn run(&mut self, start: &G::S) -> TournamentResults
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        // Only writing a checkpoint can fail.
+        self.play_all(start, &HashMap::new(), 0, None).unwrap()
+    }
+
+    /// Play all the games from `start`, recording each result in the
+    /// checkpoint file at `path` as it finishes.
+    ///
+    /// If the file already exists, the games it records are not played
+    /// again, so an interrupted tournament can be resumed by running it
+    /// again with the same players and games per pairing. Only the games
+    /// played by this run are kept in `records`. Partial results can be read
+    /// with `TournamentResults::from_checkpoint`.
+    pub fn run_with_checkpoint(
+        &mut self, start: &G::S, path: &Path,
+    ) -> io::Result<TournamentResults>
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        let names: Vec<String> = self.players.iter().map(|player| player.name.clone()).collect();
+        let (done, rounds, mut file) = if path.exists() {
+            let checkpoint = Checkpoint::read(path)?;
+            if checkpoint.names != names
+                || checkpoint.games_per_pairing != self.games_per_pair()
+                || checkpoint.format != self.format
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "checkpoint is from a different tournament",
+                ));
+            }
+            // Start a new line, in case the last one was cut off.
+            let mut file = OpenOptions::new().append(true).open(path)?;
+            writeln!(file)?;
+            (checkpoint.games, checkpoint.rounds.len(), file)
+        } else {
+            let file = Checkpoint::create(path, &names, self.games_per_pair(), self.format)?;
+            (HashMap::new(), 0, file)
+        };
+        self.play_all(start, &done, rounds, Some(&mut file))
+    }
+
+    // Play the games not already `done`, and the knockout rounds after the
+    // first `rounds_done`.
+    fn play_all(
+        &mut self, start: &G::S, done: &HashMap<(usize, usize, u32), Outcome>, rounds_done: usize,
+        mut checkpoint: Option<&mut File>,
+    ) -> io::Result<TournamentResults>
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        self.records.clear();
+        let mut starts = self.openings.clone();
+        for line in self.opening_lines.iter() {
+            let mut state = start.clone();
+            for &m in line.iter() {
+                if let Some(new_state) = G::apply(&mut state, m) {
+                    state = new_state;
+                }
+            }
+            starts.push(state);
+        }
+        if starts.is_empty() {
+            starts.push(start.clone());
+        }
+        let n = self.players.len();
+        let mut results = TournamentResults {
+            names: self.players.iter().map(|player| player.name.clone()).collect(),
+            wins: vec![vec![0; n]; n],
+            draws: vec![vec![0; n]; n],
+            sprt: if self.sprt.is_some() { vec![vec![None; n]; n] } else { Vec::new() },
+            format: self.format,
+            rounds: Vec::new(),
+        };
+        match self.format {
+            TournamentFormat::RoundRobin => {
+                for i in 0..n {
+                    for j in i + 1..n {
+                        let file = checkpoint.as_deref_mut();
+                        self.play_pairing(i, j, &starts, done, file, &mut results)?;
+                    }
+                }
+            }
+            TournamentFormat::Gauntlet => {
+                for j in 1..n {
+                    let file = checkpoint.as_deref_mut();
+                    self.play_pairing(0, j, &starts, done, file, &mut results)?;
+                }
+            }
+            TournamentFormat::Knockout => {
+                let mut left: Vec<usize> = (0..n).collect();
+                while left.len() > 1 {
+                    let pairings = left.len() / 2;
+                    let mut next = Vec::new();
+                    if left.len() % 2 == 1 {
+                        next.push(left[pairings]);
+                    }
+                    for k in 0..pairings {
+                        let (i, j) = (left[k], left[left.len() - 1 - k]);
+                        let file = checkpoint.as_deref_mut();
+                        self.play_pairing(i, j, &starts, done, file, &mut results)?;
+                        next.push(if results.score(j, i) > results.score(i, j) { j } else { i });
+                    }
+                    next.sort_unstable();
+                    if results.rounds.len() >= rounds_done {
+                        if let Some(file) = checkpoint.as_deref_mut() {
+                            Checkpoint::write_round(file, &next)?;
+                        }
+                    }
+                    results.rounds.push(next.clone());
+                    left = next;
+                }
+            }
+        }
+        self.write_json(|| results.to_json());
+        Ok(results)
+    }
+
+    // Play the games between players `i < j`, counting those already `done`
+    // instead of playing them again.
+    fn play_pairing(
+        &mut self, i: usize, j: usize, starts: &[G::S],
+        done: &HashMap<(usize, usize, u32), Outcome>, mut checkpoint: Option<&mut File>,
+        results: &mut TournamentResults,
+    ) -> io::Result<()>
+    where
+        G::S: Clone,
+        G::M: Copy + Eq,
+    {
+        for game in 0..self.games_per_pair() {
+            let (first, second) = if game % 2 == 0 { (i, j) } else { (j, i) };
+            if let Some(&outcome) = done.get(&(i, j, game)) {
+                results.add_game(first, second, Some(outcome));
+            } else {
+                let opening = (game / self.games_per_pairing) as usize;
+                let record = self.play_game(starts, opening, first, second);
+                results.add_game(first, second, record.result);
+                if let Some(file) = checkpoint.as_deref_mut() {
+                    Checkpoint::write_game(file, i, j, game, record.result)?;
+                }
+                self.write_json(|| record.to_json());
+                self.records.push(record);
+            }
+            if let Some(sprt) = self.sprt.filter(|_| game % 2 == 1) {
+                let decision =
+                    sprt.decision(results.wins[i][j], results.draws[i][j], results.losses(i, j));
+                if decision.is_some() {
+                    results.sprt[i][j] = decision;
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Errors are ignored, so that a broken log doesn't stop the tournament.
+    fn write_json<F: FnOnce() -> String>(&mut self, line: F) {
+        if let Some(output) = self.json_output.as_mut() {
+            let _ = writeln!(output, "{}", line());
+            let _ = output.flush();
+        }
+    }
+
+    /// The games played by the last run, in the order they were played.
+    pub fn records(&self) -> &[GameRecord<G>] {
+        &self.records
+    }
+
+    // Play a game from `starts[opening]`.
+    fn play_game(
+        &mut self, starts: &[G::S], opening: usize, first: usize, second: usize,
+    ) -> GameRecord<G>
+    where
+        G::S: Clone,
+    {
+        let (low, high) = self.players.split_at_mut(first.max(second));
+        let (a, b) = (&mut low[first.min(second)], &mut high[0]);
+        let players: [&mut Player<G>; 2] = if first < second { [a, b] } else { [b, a] };
+        let mut game = match self.time_control {
+            Some(time_control) => Match::untimed().with_time_controls(
+                time_control.scaled(players[0].time_odds),
+                time_control.scaled(players[1].time_odds),
+            ),
+            None => Match::untimed(),
+        };
+        if let Some(max_moves) = self.max_moves {
+            game = game.with_max_moves(max_moves);
+        }
+        if !self.openings.is_empty() || !self.opening_lines.is_empty() {
+            game.opening = Some(opening);
+        }
+        let [a, b] = players;
+        let log = self.move_log.as_ref().map(|(log, notation)| (log, *notation));
+        game.with_players(&a.name, &b.name).play_with_log(
+            &starts[opening],
+            a.strategy.as_mut(),
+            b.strategy.as_mut(),
+            log,
+        )
+    }
+}
+
+impl<G: Game> Default for Tournament<G> {
+    fn default() -> Self {
+        Tournament::new()
+    }
+}
+
+/// A sequential probability ratio test of whether a player is stronger
+/// than its opponent by `elo1` (hypothesis H1) rather than `elo0` (H0).
+///
+/// `alpha` is the chance of accepting H1 when H0 holds, and `beta` the
+/// chance of accepting H0 when H1 holds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// The conclusion of an SPRT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SprtDecision {
+    /// The player is no stronger than `elo0`.
+    AcceptH0,
+    /// The player is at least `elo1` stronger.
+    AcceptH1,
+}
+
+impl Sprt {
+    /// Test `elo0` against `elo1`, with 5% error rates.
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Self { elo0, elo1, alpha: 0.05, beta: 0.05 }
+    }
+
+    pub fn with_error_rates(mut self, alpha: f64, beta: f64) -> Self {
+        self.alpha = alpha;
+        self.beta = beta;
+        self
+    }
+
+    /// The log-likelihood ratio of H1 to H0 given a player's results,
+    /// using the normal approximation of the score.
+    pub fn llr(&self, wins: u32, draws: u32, losses: u32) -> f64 {
+        // Add half a win and half a loss, so that the variance isn't zero
+        // when every game ends the same way.
+        let (wins, draws, losses) = (wins as f64 + 0.5, draws as f64, losses as f64 + 0.5);
+        let games = wins + draws + losses;
+        let (score, variance) = score_variance(wins, draws, losses);
+        let score0 = expected_score(self.elo0);
+        let score1 = expected_score(self.elo1);
+        games * (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance)
+    }
+
+    /// The LLR below which H0 is accepted, and above which H1 is.
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// The decision given a player's results, or None to keep playing.
+    pub fn decision(&self, wins: u32, draws: u32, losses: u32) -> Option<SprtDecision> {
+        let llr = self.llr(wins, draws, losses);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            Some(SprtDecision::AcceptH1)
+        } else if llr <= lower {
+            Some(SprtDecision::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+// The mean score per game and its variance, for at least one game.
+pub(crate) fn score_variance(wins: f64, draws: f64, losses: f64) -> (f64, f64) {
+    let games = wins + draws + losses;
+    let score = (wins + 0.5 * draws) / games;
+    let variance =
+        (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2))
+            / games;
+    (score, variance)
+}
+
+// The expected score against an opponent this much weaker.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// An Elo rating relative to the average opponent, and the margin of error
+/// of its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EloEstimate {
+    pub elo: f64,
+    pub error: f64,
+}
+
+/// The outcome of a tournament.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TournamentResults {
+    /// Player names, in the order they were added.
+    pub names: Vec<String>,
+    /// `wins[i][j]` is the number of games player `i` won against player `j`.
+    pub wins: Vec<Vec<u32>>,
+    /// `draws[i][j]` is the number of draws between players `i` and `j`.
+    pub draws: Vec<Vec<u32>>,
+    /// With an SPRT, `sprt[i][j]` for `i < j` is its decision about player
+    /// `i` against player `j`, if the pairing ended early. Empty otherwise.
+    pub sprt: Vec<Vec<Option<SprtDecision>>>,
+    /// Who played whom.
+    pub format: TournamentFormat,
+    /// In a knockout, the players left after each round, by index. Empty
+    /// otherwise.
+    pub rounds: Vec<Vec<usize>>,
+}
+
+impl TournamentResults {
+    /// The results of the games recorded so far in a checkpoint file written
+    /// by `Tournament::run_with_checkpoint`, which may still be running.
+    pub fn from_checkpoint(path: &Path) -> io::Result<Self> {
+        let checkpoint = Checkpoint::read(path)?;
+        let n = checkpoint.names.len();
+        let mut results = TournamentResults {
+            names: checkpoint.names,
+            wins: vec![vec![0; n]; n],
+            draws: vec![vec![0; n]; n],
+            sprt: Vec::new(),
+            format: checkpoint.format,
+            rounds: checkpoint.rounds,
+        };
+        for (&(i, j, game), &outcome) in checkpoint.games.iter() {
+            if i >= n || j >= n {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown player"));
+            }
+            let (first, second) = if game % 2 == 0 { (i, j) } else { (j, i) };
+            results.add_game(first, second, Some(outcome));
+        }
+        Ok(results)
+    }
+
+    fn add_game(&mut self, first: usize, second: usize, result: Option<Outcome>) {
+        match result {
+            Some(Outcome::Win(0)) => self.wins[first][second] += 1,
+            Some(Outcome::Win(_)) => self.wins[second][first] += 1,
+            _ => {
+                self.draws[first][second] += 1;
+                self.draws[second][first] += 1;
+            }
+        }
+    }
+
+    /// The results as a line of JSON, with the player names, the wins and
+    /// draws matrices, and the Elo estimates, and for a knockout, the
+    /// players left after each round.
+    pub fn to_json(&self) -> String {
+        let matrix = |rows: &Vec<Vec<u32>>| {
+            json::array(
+                rows.iter().map(|row| json::array(row.iter().map(|count| count.to_string()))),
+            )
+        };
+        let elo = self.elo().into_iter().map(|estimate| {
+            json::Object::new().number("elo", estimate.elo).number("error", estimate.error).finish()
+        });
+        let mut object = json::Object::new()
+            .raw("names", &json::array(self.names.iter().map(|name| json::string(name))))
+            .raw("wins", &matrix(&self.wins))
+            .raw("draws", &matrix(&self.draws))
+            .raw("elo", &json::array(elo));
+        if self.format == TournamentFormat::Knockout {
+            let rounds = self
+                .rounds
+                .iter()
+                .map(|left| json::array(left.iter().map(|&i| json::string(&self.names[i]))));
+            object = object.raw("rounds", &json::array(rounds));
+        }
+        object.finish()
+    }
+
+    /// The number of games player `i` lost against player `j`.
+    pub fn losses(&self, i: usize, j: usize) -> u32 {
+        self.wins[j][i]
+    }
+
+    // Player `i`'s points against player `j`, counting draws as one and
+    // wins as two.
+    fn score(&self, i: usize, j: usize) -> u32 {
+        2 * self.wins[i][j] + self.draws[i][j]
+    }
+
+    /// The winner of a knockout, once it's over.
+    pub fn winner(&self) -> Option<usize> {
+        match self.rounds.last() {
+            Some(left) if left.len() == 1 => Some(left[0]),
+            _ => None,
+        }
+    }
+
+    /// Total wins, draws, and losses of player `i`.
+    pub fn record(&self, i: usize) -> (u32, u32, u32) {
+        let wins = self.wins[i].iter().sum();
+        let draws = self.draws[i].iter().sum();
+        let losses = (0..self.names.len()).map(|j| self.losses(i, j)).sum();
+        (wins, draws, losses)
+    }
+
+    /// Elo estimates for each player, from their score against the rest of
+    /// the field. A player that won or lost every game has an infinite
+    /// rating.
+    ///
+    /// In a gauntlet, the references only played the candidate, so they are
+    /// rated against it, and the candidate by its performance against all
+    /// of them together. There, a perfect or zero score counts as one draw
+    /// short of it, so that every rating and error is finite.
+    pub fn elo(&self) -> Vec<EloEstimate> {
+        if self.format == TournamentFormat::Gauntlet && !self.names.is_empty() {
+            return self.gauntlet_elo();
+        }
+        (0..self.names.len())
+            .map(|i| {
+                let (wins, draws, losses) = self.record(i);
+                estimate_elo(wins, draws, losses)
+            })
+            .collect()
+    }
+
+    /// The Elo estimate of player `i` relative to player `j`, from only the
+    /// games between them.
+    pub fn elo_between(&self, i: usize, j: usize) -> EloEstimate {
+        estimate_elo(self.wins[i][j], self.draws[i][j], self.losses(i, j))
+    }
+
+    fn gauntlet_elo(&self) -> Vec<EloEstimate> {
+        let (wins, draws, losses) = self.record(0);
+        let candidate = finite_elo(wins, draws, losses);
+        let references = (1..self.names.len()).map(|j| {
+            let estimate = finite_elo(self.wins[0][j], self.draws[0][j], self.losses(0, j));
+            EloEstimate { elo: -estimate.elo, error: estimate.error }
+        });
+        std::iter::once(candidate).chain(references).collect()
+    }
+}
+
+// Like `estimate_elo`, but with a perfect or zero score counted as one draw
+// short of it, and the confidence interval kept clear of either, so that the
+// rating and its error are finite.
+fn finite_elo(wins: u32, draws: u32, losses: u32) -> EloEstimate {
+    let (wins, draws, losses) = match (wins, draws, losses) {
+        (0, 0, 0) => return estimate_elo(0, 0, 0),
+        (wins, 0, 0) => (wins - 1, 1, 0),
+        (0, 0, losses) => (0, 1, losses - 1),
+        record => record,
+    };
+    let games = (wins + draws + losses) as f64;
+    let (score, variance) = score_variance(wins as f64, draws as f64, losses as f64);
+    let margin = 1.96 * (variance / games).sqrt();
+    let bound = 0.5 / (games + 1.0);
+    let low = elo_difference((score - margin).max(bound));
+    let high = elo_difference((score + margin).min(1.0 - bound));
+    EloEstimate { elo: elo_difference(score), error: (high - low) / 2.0 }
+}
+
+// The Elo estimate for a player's results, relative to its opponents.
+pub(crate) fn estimate_elo(wins: u32, draws: u32, losses: u32) -> EloEstimate {
+    let games = (wins + draws + losses) as f64;
+    if games == 0.0 {
+        return EloEstimate { elo: 0.0, error: f64::INFINITY };
+    }
+    let (score, variance) = score_variance(wins as f64, draws as f64, losses as f64);
+    let margin = 1.96 * (variance / games).sqrt();
+    let low = elo_difference(score - margin);
+    let high = elo_difference(score + margin);
+    // Infinite ratings have unbounded error.
+    let error = if score == 0.0 || score == 1.0 { f64::INFINITY } else { (high - low) / 2.0 };
+    EloEstimate { elo: elo_difference(score), error }
+}
+
+// The rating difference that predicts this expected score.
+fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score.clamp(0.0, 1.0) - 1.0).log10()
+}
+
+impl fmt::Display for TournamentResults {
+    /// Shows the wins, draws, and losses of each player against each other
+    /// player, followed by the Elo estimates.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.names.iter().map(|name| name.len()).max().unwrap_or(0).max(8);
+        write!(f, "{:width$}", "")?;
+        for name in self.names.iter() {
+            write!(f, " {:>width$}", name)?;
+        }
+        writeln!(f)?;
+        for (i, name) in self.names.iter().enumerate() {
+            write!(f, "{:width$}", name)?;
+            for j in 0..self.names.len() {
+                // In a gauntlet or knockout, most players never meet.
+                let unplayed = self.format != TournamentFormat::RoundRobin
+                    && self.score(i, j) + self.score(j, i) == 0;
+                if i == j || unplayed {
+                    write!(f, " {:>width$}", "-")?;
+                } else {
+                    let wdl =
+                        format!("{}-{}-{}", self.wins[i][j], self.draws[i][j], self.losses(i, j));
+                    write!(f, " {:>width$}", wdl)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        for (name, estimate) in self.names.iter().zip(self.elo()) {
+            writeln!(f, "{:width$} {:>8.1} +/- {:.1}", name, estimate.elo, estimate.error)?;
+        }
+        for (round, left) in self.rounds.iter().enumerate() {
+            let names: Vec<&str> = left.iter().map(|&i| self.names[i].as_str()).collect();
+            writeln!(f, "After round {}: {}", round + 1, names.join(", "))?;
+        }
+        for (i, decisions) in self.sprt.iter().enumerate() {
+            for (j, decision) in decisions.iter().enumerate().skip(i + 1) {
+                if let Some(decision) = decision {
+                    let hypothesis = match decision {
+                        SprtDecision::AcceptH0 => "H0",
+                        SprtDecision::AcceptH1 => "H1",
+                    };
+                    writeln!(
+                        f,
+                        "SPRT {} vs {}: {} accepted",
+                        self.names[i], self.names[j], hypothesis
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
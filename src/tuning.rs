@@ -0,0 +1,146 @@
+//! Tuning numeric parameters of strategies and evaluators by self-play,
+//! using simultaneous perturbation stochastic approximation (SPSA).
+//!
+//! Each iteration perturbs every parameter up or down at random, plays a
+//! few games between the two perturbed versions, and moves the parameters
+//! towards the version that scored better. Step sizes shrink over the run
+//! following the usual SPSA schedule.
+
+use super::interface::*;
+use super::match_play::Match;
+use super::record::Outcome;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Standard SPSA schedule exponents.
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+
+/// A parameter to tune.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    /// Current value, starting from the initial guess.
+    pub value: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Size of the perturbation at the end of the run.
+    pub c_end: f64,
+    /// Learning rate at the end of the run.
+    pub r_end: f64,
+}
+
+impl Parameter {
+    /// A parameter starting at `value`, kept within `min..=max`. By default,
+    /// the final perturbation is a twentieth of the range.
+    pub fn new(name: &str, value: f64, min: f64, max: f64) -> Self {
+        Self { name: name.to_string(), value, min, max, c_end: (max - min) / 20.0, r_end: 0.002 }
+    }
+
+    pub fn with_c_end(mut self, c_end: f64) -> Self {
+        self.c_end = c_end;
+        self
+    }
+
+    pub fn with_r_end(mut self, r_end: f64) -> Self {
+        self.r_end = r_end;
+        self
+    }
+}
+
+/// Tunes parameters by SPSA.
+pub struct Spsa {
+    params: Vec<Parameter>,
+    iterations: u32,
+    games_per_iteration: u32,
+    game: Match,
+    rng: StdRng,
+}
+
+impl Spsa {
+    /// Tune `params` over this many iterations.
+    pub fn new(params: Vec<Parameter>, iterations: u32) -> Self {
+        Self {
+            params,
+            iterations,
+            games_per_iteration: 2,
+            game: Match::untimed(),
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Play this many games per iteration, alternating who moves first.
+    /// Defaults to 2.
+    pub fn with_games_per_iteration(mut self, games: u32) -> Self {
+        self.games_per_iteration = games;
+        self
+    }
+
+    /// Play the games with these settings, e.g. a time control.
+    pub fn with_match(mut self, game: Match) -> Self {
+        self.game = game;
+        self
+    }
+
+    /// Seed the perturbations, for reproducible runs.
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// The parameters, with their current values.
+    pub fn params(&self) -> &[Parameter] {
+        &self.params
+    }
+
+    /// Run the tuning from `start`, building a strategy from parameter
+    /// values with `build`. Returns the tuned values.
+    pub fn run<G, F>(&mut self, start: &G::S, mut build: F) -> Vec<f64>
+    where
+        G: Game,
+        G::S: Clone,
+        F: FnMut(&[f64]) -> Box<dyn Strategy<G>>,
+    {
+        let n = self.iterations as f64;
+        // The stability constant, conventionally a tenth of the iterations.
+        let big_a = n / 10.0;
+        for k in 0..self.iterations {
+            let k = k as f64;
+            let mut plus = Vec::with_capacity(self.params.len());
+            let mut minus = Vec::with_capacity(self.params.len());
+            let mut steps = Vec::with_capacity(self.params.len());
+            for param in self.params.iter() {
+                let c = param.c_end * n.powf(GAMMA);
+                let a = param.r_end * param.c_end.powi(2) * (big_a + n).powf(ALPHA);
+                let c_k = c / (k + 1.0).powf(GAMMA);
+                let a_k = a / (big_a + k + 1.0).powf(ALPHA);
+                let delta = if self.rng.gen::<bool>() { 1.0 } else { -1.0 };
+                plus.push((param.value + c_k * delta).clamp(param.min, param.max));
+                minus.push((param.value - c_k * delta).clamp(param.min, param.max));
+                steps.push(a_k / c_k * delta);
+            }
+
+            let mut plus_strategy = build(&plus);
+            let mut minus_strategy = build(&minus);
+            // Games won by the plus side minus games won by the minus side.
+            let mut score = 0.0;
+            for game in 0..self.games_per_iteration {
+                let plus_first = game % 2 == 0;
+                let record = if plus_first {
+                    self.game.play(start, plus_strategy.as_mut(), minus_strategy.as_mut())
+                } else {
+                    self.game.play(start, minus_strategy.as_mut(), plus_strategy.as_mut())
+                };
+                if let Some(Outcome::Win(winner)) = record.result {
+                    score += if (winner == 0) == plus_first { 1.0 } else { -1.0 };
+                }
+            }
+
+            for (param, step) in self.params.iter_mut().zip(steps) {
+                param.value = (param.value + step * score).clamp(param.min, param.max);
+            }
+        }
+        self.params.iter().map(|param| param.value).collect()
+    }
+}
@@ -0,0 +1,292 @@
+//! A line-based engine protocol modeled on the Universal Chess Interface,
+//! for driving any strategy from external GUIs and match managers.
+//!
+//! Moves are written with `Game::notation`, or as their 1-based index in the
+//! generated moves for games without one. The supported commands are:
+//!
+//! * `uci`, answered by `id name ...` and `uciok`
+//! * `isready`, answered by `readyok`
+//! * `ucinewgame`
+//! * `position startpos [moves ...]` or
+//!   `position fen <description> [moves ...]`, where the description is
+//!   parsed by the function passed to `with_position_parser`
+//! * `go` with any of `wtime`, `btime`, `winc`, `binc`, `movestogo` (in
+//!   milliseconds, where white is the player who moved first), `movetime`,
+//!   `depth`, or `infinite`, answered by `info` lines during the search and
+//!   `bestmove` at the end. The limits apply to that search only, and a
+//!   search without any lasts until `stop`, like `go infinite`.
+//! * `stop`, which ends the search early
+//! * `quit`
+//!
+//! Unknown commands are ignored. At the end of the input, any search is
+//! allowed to finish.
+
+use super::interface::*;
+use super::util::{move_notation, parse_move};
+
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Time limit for `go infinite`, which lasts until `stop`.
+const INFINITE: Duration = Duration::from_secs(365 * 24 * 3600);
+// Assumed number of moves left in the game if `movestogo` isn't given.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// Depth limit for searches limited by time, as the searches themselves use.
+const UNLIMITED_DEPTH: u8 = 99;
+
+type Output = Arc<Mutex<Box<dyn Write + Send>>>;
+type PositionParser<S> = Box<dyn Fn(&str) -> Option<S>>;
+
+/// Runs a strategy as an engine speaking the protocol.
+pub struct UciEngine<G: Game, S> {
+    name: String,
+    strategy: Arc<Mutex<S>>,
+    stop: StopSignal,
+    parse_position: Option<PositionParser<G::S>>,
+    // The starting position of the last `position` command, and the moves
+    // played from it, which the strategy has been told about.
+    start: String,
+    moves: Vec<G::M>,
+    state: G::S,
+    search: Option<thread::JoinHandle<()>>,
+}
+
+impl<G, S> UciEngine<G, S>
+where
+    G: Game,
+    G::S: Clone + Default + Send + 'static,
+    G::M: Copy + Eq + Send + 'static,
+    S: Strategy<G> + Send + 'static,
+{
+    /// Serve `strategy` under `name`, from the default starting position.
+    pub fn new(name: &str, mut strategy: S) -> Self {
+        let stop = StopSignal::new();
+        strategy.set_stop_signal(stop.clone());
+        Self {
+            name: name.to_string(),
+            strategy: Arc::new(Mutex::new(strategy)),
+            stop,
+            parse_position: None,
+            start: "startpos".to_string(),
+            moves: Vec::new(),
+            state: G::S::default(),
+            search: None,
+        }
+    }
+
+    /// Accept `position fen` commands, parsing the rest of the line up to
+    /// any `moves` with `parse`. The parsed position is assumed to have the
+    /// first player to move.
+    pub fn with_position_parser<F>(mut self, parse: F) -> Self
+    where
+        F: Fn(&str) -> Option<G::S> + 'static,
+    {
+        self.parse_position = Some(Box::new(parse));
+        self
+    }
+
+    /// Serve commands from stdin, replying on stdout.
+    pub fn run_stdio(&mut self) -> io::Result<()> {
+        self.run(io::stdin().lock(), Box::new(io::stdout()))
+    }
+
+    /// Serve commands from `input` until `quit` or the end of the input.
+    pub fn run(&mut self, input: impl BufRead, output: Box<dyn Write + Send>) -> io::Result<()> {
+        let output: Output = Arc::new(Mutex::new(output));
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.first().copied() {
+                Some("uci") => {
+                    let mut out = output.lock().unwrap();
+                    writeln!(out, "id name {}", self.name)?;
+                    writeln!(out, "uciok")?;
+                    out.flush()?;
+                }
+                Some("isready") => {
+                    let mut out = output.lock().unwrap();
+                    writeln!(out, "readyok")?;
+                    out.flush()?;
+                }
+                Some("ucinewgame") => {
+                    self.stop_search();
+                    self.strategy.lock().unwrap().new_game();
+                    self.start = "startpos".to_string();
+                    self.moves.clear();
+                    self.state = G::S::default();
+                }
+                Some("position") => {
+                    self.stop_search();
+                    self.set_position(&words[1..]);
+                }
+                Some("go") => {
+                    self.stop_search();
+                    self.go(&words[1..], output.clone());
+                }
+                Some("stop") => self.stop_search(),
+                Some("quit") => {
+                    self.stop_search();
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        if let Some(search) = self.search.take() {
+            let _ = search.join();
+        }
+        Ok(())
+    }
+
+    fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.stop.stop();
+            let _ = search.join();
+            self.stop.reset();
+        }
+    }
+
+    fn set_position(&mut self, words: &[&str]) {
+        let moves_at = words.iter().position(|&word| word == "moves").unwrap_or(words.len());
+        let state = match words.first().copied() {
+            Some("startpos") => G::S::default(),
+            Some("fen") => {
+                let parsed = self
+                    .parse_position
+                    .as_ref()
+                    .and_then(|parse| parse(&words[1..moves_at].join(" ")));
+                match parsed {
+                    Some(state) => state,
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+        let start = words[..moves_at].join(" ");
+        let mut moves = Vec::new();
+        let mut end = state.clone();
+        for word in words.iter().skip(moves_at + 1) {
+            // Stop at the first illegal move.
+            let Some(m) = parse_move::<G>(&end, word) else {
+                break;
+            };
+            if let Some(new_state) = G::apply(&mut end, m) {
+                end = new_state;
+            }
+            moves.push(m);
+        }
+        // GUIs send the whole game every time, so only tell the strategy
+        // about the moves since the last position, or start a new game if
+        // this one doesn't continue it.
+        let mut strategy = self.strategy.lock().unwrap();
+        let seen = if start == self.start && moves.starts_with(&self.moves) {
+            self.moves.len()
+        } else {
+            strategy.new_game();
+            self.state = state;
+            0
+        };
+        for &m in moves[seen..].iter() {
+            strategy.notify_move(&self.state, m);
+            if let Some(new_state) = G::apply(&mut self.state, m) {
+                self.state = new_state;
+            }
+        }
+        self.start = start;
+        self.moves = moves;
+    }
+
+    fn go(&mut self, words: &[&str], output: Output) {
+        let arg = |name: &str| -> Option<u64> {
+            let i = words.iter().position(|&word| word == name)?;
+            words.get(i + 1)?.parse().ok()
+        };
+        // Games with extra turns don't alternate, so ask the game first.
+        let first_to_move = match G::player_to_move(&self.state) {
+            Some(player) => player == 0,
+            None => self.moves.len().is_multiple_of(2),
+        };
+        let (time, inc) =
+            if first_to_move { (arg("wtime"), arg("winc")) } else { (arg("btime"), arg("binc")) };
+        let depth = arg("depth").map(|depth| depth.min(u8::MAX as u64) as u8);
+        let timeout = if words.contains(&"infinite") {
+            Some(INFINITE)
+        } else if let Some(movetime) = arg("movetime") {
+            Some(Duration::from_millis(movetime))
+        } else if let Some(time) = time {
+            let moves_to_go = arg("movestogo").unwrap_or(DEFAULT_MOVES_TO_GO as u64).max(1);
+            let inc = inc.unwrap_or(0);
+            // Leave some time in hand for the increment to build up.
+            let budget = (time / moves_to_go + inc * 3 / 4).min(time / 2).max(1);
+            Some(Duration::from_millis(budget))
+        } else if depth.is_some() {
+            None
+        } else {
+            Some(INFINITE)
+        };
+        // Replace both limits, so that none carries over from the last search.
+        let mut strategy = self.strategy.lock().unwrap();
+        match timeout {
+            Some(timeout) => {
+                strategy.set_max_depth(UNLIMITED_DEPTH);
+                strategy.set_timeout(timeout);
+            }
+            None => strategy.set_max_depth(depth.unwrap_or(UNLIMITED_DEPTH)),
+        }
+
+        let state = self.state.clone();
+        let info_state = self.state.clone();
+        let info_output = output.clone();
+        strategy.set_progress(Box::new(move |info: &SearchInfo<G::M>| {
+            let line = info_line::<G>(&info_state, info);
+            let mut out = info_output.lock().unwrap();
+            let _ = writeln!(out, "{}", line);
+            let _ = out.flush();
+        }));
+        drop(strategy);
+
+        let strategy = self.strategy.clone();
+        self.search = Some(thread::spawn(move || {
+            let m = strategy.lock().unwrap().choose_move(&state);
+            let name = m.map_or("(none)".to_string(), |m| move_notation::<G>(&state, m));
+            let mut out = output.lock().unwrap();
+            let _ = writeln!(out, "bestmove {}", name);
+            let _ = out.flush();
+        }));
+    }
+}
+
+fn info_line<G: Game>(state: &G::S, info: &SearchInfo<G::M>) -> String
+where
+    G::S: Clone,
+    G::M: Copy + Eq,
+{
+    let mut line = format!("info depth {}", info.depth);
+    if let Some(value) = info.value {
+        // Values near the extremes are wins or losses in about that many
+        // plies, though strategies may round them to the extremes.
+        let plies = BEST_EVAL.abs_diff(value.abs()) as i64;
+        if plies < 100 {
+            let moves = ((plies + 1) / 2).max(1);
+            line.push_str(&format!(" score mate {}", if value > 0 { moves } else { -moves }));
+        } else {
+            line.push_str(&format!(" score cp {}", value));
+        }
+    }
+    let millis = info.elapsed.as_millis().max(1) as u64;
+    line.push_str(&format!(" nodes {} time {}", info.nodes, millis));
+    line.push_str(&format!(" nps {}", info.nodes * 1000 / millis));
+    if !info.principal_variation.is_empty() {
+        line.push_str(" pv");
+        let mut state = state.clone();
+        for &m in info.principal_variation.iter() {
+            line.push(' ');
+            line.push_str(&move_notation::<G>(&state, m));
+            if let Some(new_state) = G::apply(&mut state, m) {
+                state = new_state;
+            }
+        }
+    }
+    line
+}
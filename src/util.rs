@@ -1,15 +1,20 @@
 //! Utility functions for testing, and tests.
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 extern crate rayon;
 
 use super::interface;
 use super::interface::Game;
+#[cfg(feature = "std")]
+use super::time::Instant;
 
-#[cfg(not(target_arch = "wasm32"))]
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::default::Default;
+use core::marker::PhantomData;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 use rayon::prelude::*;
-use std::default::Default;
-use std::time::Instant;
 
 pub(crate) struct AppliedMove<'a, G: Game> {
     old: &'a mut <G as Game>::S,
@@ -17,14 +22,14 @@ pub(crate) struct AppliedMove<'a, G: Game> {
     m: <G as Game>::M,
 }
 
-impl<'a, G: Game> std::ops::Deref for AppliedMove<'a, G> {
+impl<'a, G: Game> core::ops::Deref for AppliedMove<'a, G> {
     type Target = <G as Game>::S;
     fn deref(&self) -> &<G as Game>::S {
         self.new.as_ref().unwrap_or(self.old)
     }
 }
 
-impl<'a, G: Game> std::ops::DerefMut for AppliedMove<'a, G> {
+impl<'a, G: Game> core::ops::DerefMut for AppliedMove<'a, G> {
     fn deref_mut(&mut self) -> &mut <G as Game>::S {
         self.new.as_mut().unwrap_or(self.old)
     }
@@ -54,8 +59,26 @@ where
     S1: interface::Strategy<G>,
     S2: interface::Strategy<G>,
 {
-    let mut state = G::S::default();
+    battle_royale_from(G::S::default(), s1, s2)
+}
+
+/// Play a complete game from the given starting position, e.g. a handicap
+/// position, with players using the two provided strategies.
+///
+/// The first strategy moves first from `state`, and both are told of a new
+/// game before it starts. Returns `None` if the game
+/// ends in a draw, or `Some(0)`, `Some(1)` if the first or second strategy
+/// won, respectively.
+pub fn battle_royale_from<G, S1, S2>(mut state: G::S, s1: &mut S1, s2: &mut S2) -> Option<usize>
+where
+    G: interface::Game,
+    S1: interface::Strategy<G>,
+    S2: interface::Strategy<G>,
+{
     let mut strategies: [&mut dyn interface::Strategy<G>; 2] = [s1, s2];
+    for strategy in strategies.iter_mut() {
+        strategy.new_game();
+    }
     let mut s = 0;
     loop {
         if let Some(winner) = G::get_winner(&state) {
@@ -65,16 +88,22 @@ where
                 interface::Winner::PlayerToMove => Some(s),
             };
         }
-        let strategy = &mut strategies[s];
-        match strategy.choose_move(&state) {
+        let to_move = G::player_to_move(&state);
+        match strategies[s].choose_move(&state) {
             Some(m) => {
+                for strategy in strategies.iter_mut() {
+                    strategy.notify_move(&state, m);
+                }
                 if let Some(new_state) = G::apply(&mut state, m) {
                     state = new_state;
                 }
             }
             None => return None,
         }
-        s = 1 - s;
+        // The same player may move again.
+        if to_move.is_none() || G::player_to_move(&state) != to_move {
+            s = 1 - s;
+        }
     }
 }
 
@@ -99,7 +128,7 @@ impl<M> MovePool<M> {
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 fn perft_recurse<G: Game>(
     pool: &mut MovePool<G::M>, state: &mut G::S, depth: u8, single_thread_cutoff: u8,
 ) -> u64
@@ -145,7 +174,7 @@ where
     n
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
 fn perft_recurse<G: Game>(
     pool: &mut MovePool<G::M>, state: &mut G::S, depth: u8, single_thread_cutoff: u8,
 ) -> u64
@@ -177,6 +206,7 @@ where
     n
 }
 
+#[cfg(feature = "std")]
 pub fn perft<G: Game>(state: &mut <G as Game>::S, max_depth: u8, multi_threaded: bool) -> Vec<u64>
 where
     <G as Game>::S: Clone + Sync,
@@ -197,3 +227,560 @@ where
     }
     counts
 }
+
+/// Like perft, but counts the positions after each move from the root
+/// separately, to narrow down a discrepancy with known counts to a single
+/// move.
+///
+/// Prints and returns the count under each root move at the given depth.
+#[cfg(feature = "std")]
+pub fn perft_divide<G: Game>(
+    state: &mut <G as Game>::S, depth: u8, multi_threaded: bool,
+) -> Vec<(<G as Game>::M, u64)>
+where
+    <G as Game>::S: Clone + Sync,
+    <G as Game>::M: Copy + Sync,
+{
+    let mut pool = MovePool::<G::M>::default();
+    let single_thread_cutoff = if multi_threaded { 3 } else { depth };
+    let mut moves = Vec::new();
+    if depth > 0 && G::get_winner(state).is_none() {
+        G::generate_moves(state, &mut moves);
+    }
+    let mut counts = Vec::new();
+    for m in moves {
+        let label = notation::<G>(state, m);
+        let mut new = AppliedMove::<G>::new(state, m);
+        let count = perft_recurse::<G>(&mut pool, &mut new, depth - 1, single_thread_cutoff);
+        println!("{:>10} {:>15}", label, count);
+        counts.push((m, count));
+    }
+    println!("{:>10} {:>15}", "total", counts.iter().map(|(_, count)| count).sum::<u64>());
+    counts
+}
+
+/// Benchmark a strategy on a fixed set of positions, e.g. to catch
+/// performance regressions in a search or a game implementation.
+///
+/// Each position is searched by a fresh strategy from `new_strategy`, which
+/// should set the search budget, seeded with `seed` so that runs are
+/// comparable. Prints the move, depth reached, nodes (or simulations) and
+/// speed for each position, and returns the details of each search.
+#[cfg(feature = "std")]
+pub fn benchmark<G, S, F>(
+    positions: &[<G as Game>::S], seed: u64, mut new_strategy: F,
+) -> Vec<interface::SearchInfo<<G as Game>::M>>
+where
+    G: Game,
+    G::M: Copy,
+    S: interface::Strategy<G>,
+    F: FnMut() -> S,
+{
+    println!("position        move  depth           nodes        time        kn/s");
+    let mut infos = Vec::with_capacity(positions.len());
+    for (i, state) in positions.iter().enumerate() {
+        let mut strategy = new_strategy();
+        strategy.set_rng_seed(seed);
+        let start = Instant::now();
+        let mut info = strategy.choose_move_with_info(state);
+        if info.elapsed == std::time::Duration::default() {
+            info.elapsed = start.elapsed();
+        }
+        let rate = info.nodes as f64 / info.elapsed.as_secs_f64() / 1000.0;
+        let m = info.best_move.map_or("none".to_string(), |m| notation::<G>(state, m));
+        let time = format!("{:.1?}", info.elapsed);
+        println!(
+            "{:>8} {:>11} {:>6} {:>15} {:>11} {:>11.1}",
+            i, m, info.depth, info.nodes, time, rate
+        );
+        infos.push(info);
+    }
+    let nodes = infos.iter().map(|info| info.nodes).sum::<u64>();
+    let elapsed = infos.iter().map(|info| info.elapsed).sum::<std::time::Duration>();
+    let rate = nodes as f64 / elapsed.as_secs_f64() / 1000.0;
+    let time = format!("{:.1?}", elapsed);
+    println!("{:>8} {:>11} {:>6} {:>15} {:>11} {:>11.1}", "total", "", "", nodes, time, rate);
+    infos
+}
+
+/// Notation for a move in text protocols and files: `Game::notation`, or
+/// its 1-based index in the generated moves.
+pub fn move_notation<G: Game>(state: &G::S, m: G::M) -> String
+where
+    G::M: Eq,
+{
+    G::notation(state, m).unwrap_or_else(|| {
+        let mut moves = Vec::new();
+        G::generate_moves(state, &mut moves);
+        let index = moves.iter().position(|&other| other == m);
+        index.map_or("(none)".to_string(), |index| (index + 1).to_string())
+    })
+}
+
+/// Find the legal move with this notation, or this 1-based index.
+pub fn parse_move<G: Game>(state: &G::S, text: &str) -> Option<G::M>
+where
+    G::M: Copy,
+{
+    let mut moves = Vec::new();
+    G::generate_moves(state, &mut moves);
+    if let Some(&m) =
+        moves.iter().find(|&&m| G::notation(state, m).is_some_and(|name| name == text))
+    {
+        return Some(m);
+    }
+    let index = text.parse::<usize>().ok()?;
+    if G::notation(state, *moves.first()?).is_none() && index >= 1 {
+        moves.get(index - 1).copied()
+    } else {
+        None
+    }
+}
+
+/// A field of `Game::describe` that differs between two states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The name of the field, as given by `Game::describe`.
+    pub field: String,
+    /// The field in the expected state, or None if it has no such field.
+    pub expected: Option<String>,
+    /// The field in the actual state, or None if it has no such field.
+    pub actual: Option<String>,
+}
+
+impl core::fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let missing = "(missing)".to_string();
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field,
+            self.expected.as_ref().unwrap_or(&missing),
+            self.actual.as_ref().unwrap_or(&missing)
+        )
+    }
+}
+
+/// Compare two states that should be equal, e.g. after undoing a move, or
+/// the engine's state and one rebuilt from a game server's record, and
+/// return the fields of `Game::describe` that differ. The fields of
+/// `expected` come first, in order, then any only `actual` has. Returns None
+/// if neither state is described, so that an empty diff always means the
+/// states look the same.
+pub fn diff_states<G: Game>(expected: &G::S, actual: &G::S) -> Option<Vec<StateDiff>> {
+    let expected = G::describe(expected);
+    let actual = G::describe(actual);
+    if expected.is_empty() && actual.is_empty() {
+        return None;
+    }
+    let find = |fields: &[(String, String)], field: &str| {
+        fields.iter().find(|(name, _)| name == field).map(|(_, value)| value.clone())
+    };
+    let mut diffs = Vec::new();
+    for (field, value) in expected.iter() {
+        let other = find(&actual, field);
+        if other.as_ref() != Some(value) {
+            diffs.push(StateDiff {
+                field: field.clone(),
+                expected: Some(value.clone()),
+                actual: other,
+            });
+        }
+    }
+    for (field, value) in actual.iter() {
+        if find(&expected, field).is_none() {
+            diffs.push(StateDiff {
+                field: field.clone(),
+                expected: None,
+                actual: Some(value.clone()),
+            });
+        }
+    }
+    Some(diffs)
+}
+
+// The differences between two states for an error message, if the game
+// describes them.
+fn diff_message<G: Game>(expected: &G::S, actual: &G::S) -> String {
+    let diffs = match diff_states::<G>(expected, actual) {
+        Some(diffs) if !diffs.is_empty() => diffs,
+        _ => return String::new(),
+    };
+    let diffs: Vec<_> = diffs.iter().map(|diff| diff.to_string()).collect();
+    format!(" ({})", diffs.join("; "))
+}
+
+fn notation<G: Game>(state: &G::S, m: G::M) -> String {
+    G::notation(state, m).unwrap_or_else(|| "(no notation impl)".to_string())
+}
+
+/// Check that a Game implementation upholds the invariants the strategies
+/// rely on at the given state.
+///
+/// Verifies that terminal states generate no moves, that the generated moves
+/// are distinct, pass `Game::is_legal`, and are decoded back to themselves by
+/// any `Game::move_bits` encoding, and that applying and then undoing each
+/// move restores the original state. Returns a description of the first
+/// violation found.
+pub fn validate<G: Game>(state: &G::S) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    validate_impl::<G>(state, false)
+}
+
+/// Like `validate`, but also checks that `zobrist_hash` is consistent: it is
+/// restored by undoing a move, `zobrist_hash_after` agrees with it, and equal
+/// states have equal hashes.
+pub fn validate_with_hash<G: Game>(state: &G::S) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    validate_impl::<G>(state, true)
+}
+
+fn validate_impl<G: Game>(state: &G::S, check_hash: bool) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    let mut moves = Vec::new();
+    G::generate_moves(state, &mut moves);
+    if let Some(winner) = G::get_winner(state) {
+        if !moves.is_empty() {
+            return Err(format!(
+                "terminal state ({:?}) generated {} moves, including {}",
+                winner,
+                moves.len(),
+                notation::<G>(state, moves[0])
+            ));
+        }
+        return Ok(());
+    }
+
+    for (i, &m) in moves.iter().enumerate() {
+        if moves[..i].contains(&m) {
+            return Err(format!("move {} was generated more than once", notation::<G>(state, m)));
+        }
+        if !G::is_legal(state, m) {
+            return Err(format!("generated move {} is not legal", notation::<G>(state, m)));
+        }
+        if let Some((to_bits, from_bits)) = G::move_bits() {
+            if from_bits(to_bits(m)) != m {
+                return Err(format!("move {} does not survive move_bits", notation::<G>(state, m)));
+            }
+        }
+    }
+
+    let hash = if check_hash { G::zobrist_hash(state) } else { 0 };
+    let mut children = Vec::new();
+    for &m in moves.iter() {
+        let mut s = state.clone();
+        let child = match G::apply(&mut s, m) {
+            Some(new_state) => new_state,
+            None => s.clone(),
+        };
+        G::undo(&mut s, m);
+        if s != *state {
+            return Err(format!(
+                "applying and undoing move {} did not restore the state{}",
+                notation::<G>(state, m),
+                diff_message::<G>(state, &s)
+            ));
+        }
+        if check_hash {
+            if G::zobrist_hash(&s) != hash {
+                return Err(format!(
+                    "applying and undoing move {} did not restore the hash",
+                    notation::<G>(state, m)
+                ));
+            }
+            let child_hash = G::zobrist_hash(&child);
+            if G::zobrist_hash_after(hash, state, m).is_some_and(|after| after != child_hash) {
+                return Err(format!(
+                    "zobrist_hash_after for move {} differs from the hash of the new state",
+                    notation::<G>(state, m)
+                ));
+            }
+            children.push((m, child_hash, child));
+        }
+    }
+
+    for (i, (m, hash, child)) in children.iter().enumerate() {
+        for (other_m, other_hash, other_child) in children[..i].iter() {
+            if child == other_child && hash != other_hash {
+                return Err(format!(
+                    "moves {} and {} reach equal states with different hashes",
+                    notation::<G>(state, *other_m),
+                    notation::<G>(state, *m)
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check a Game implementation over many random games from `start`, seeded
+/// with `seed`.
+///
+/// Runs `validate` at every state reached before the game ends, checks that
+/// generating moves twice gives the same list, and at the end of each game undoes its moves
+/// one by one, checking that each undo restores the exact earlier state and
+/// its moves. Returns a description of the first violation found, along with
+/// the moves leading to it.
+pub fn fuzz<G: Game>(start: &G::S, games: u32, seed: u64) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    fuzz_impl::<G>(start, games, seed, false)
+}
+
+/// Like `fuzz`, but also checks that `zobrist_hash` is consistent, as in
+/// `validate_with_hash`.
+pub fn fuzz_with_hash<G: Game>(start: &G::S, games: u32, seed: u64) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    fuzz_impl::<G>(start, games, seed, true)
+}
+
+// Stop games that go on this long, in case the game never ends.
+const FUZZ_MAX_MOVES: usize = 1000;
+
+// A move played during fuzzing, and what to check when it is undone.
+struct FuzzedMove<G: Game> {
+    before: G::S,
+    moves: Vec<G::M>,
+    hash: u64,
+    m: G::M,
+    // Whether apply updated the state in place, rather than returning a
+    // new one.
+    in_place: bool,
+}
+
+fn fuzz_impl<G: Game>(start: &G::S, games: u32, seed: u64, check_hash: bool) -> Result<(), String>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let hash = |state: &G::S| if check_hash { G::zobrist_hash(state) } else { 0 };
+    let fail = |history: &[FuzzedMove<G>], message: String| -> Result<(), String> {
+        let line: Vec<_> =
+            history.iter().map(|played| move_notation::<G>(&played.before, played.m)).collect();
+        Err(format!("after moves [{}]: {}", line.join(" "), message))
+    };
+    for _ in 0..games {
+        let mut state = start.clone();
+        let mut history: Vec<FuzzedMove<G>> = Vec::new();
+        loop {
+            // Games may still generate moves once they are over, as searches
+            // never ask for them.
+            if G::get_winner(&state).is_some() || history.len() >= FUZZ_MAX_MOVES {
+                break;
+            }
+            if let Err(message) = validate_impl::<G>(&state, check_hash) {
+                return fail(&history, message);
+            }
+            let mut moves = Vec::new();
+            G::generate_moves(&state, &mut moves);
+            let mut again = Vec::new();
+            G::generate_moves(&state, &mut again);
+            if again != moves {
+                return fail(&history, "generating moves twice gave different lists".to_string());
+            }
+            let Some(&m) = moves.choose(&mut rng) else {
+                break;
+            };
+            let before = state.clone();
+            let hash_before = hash(&state);
+            let in_place = match G::apply(&mut state, m) {
+                Some(new_state) => {
+                    state = new_state;
+                    false
+                }
+                None => true,
+            };
+            history.push(FuzzedMove { before, moves, hash: hash_before, m, in_place });
+        }
+
+        // Unwind the game the way a search does.
+        while let Some(played) = history.pop() {
+            if played.in_place {
+                G::undo(&mut state, played.m);
+            } else {
+                state = played.before.clone();
+            }
+            let name = move_notation::<G>(&played.before, played.m);
+            let message = if state != played.before {
+                Some(format!(
+                    "undoing move {} did not restore the state{}",
+                    name,
+                    diff_message::<G>(&played.before, &state)
+                ))
+            } else if hash(&state) != played.hash {
+                Some(format!("undoing move {} did not restore the hash", name))
+            } else {
+                let mut moves = Vec::new();
+                G::generate_moves(&state, &mut moves);
+                (moves != played.moves)
+                    .then(|| format!("undoing move {} changed the generated moves", name))
+            };
+            if let Some(message) = message {
+                history.push(played);
+                return fail(&history, message);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A Strategy wrapper that validates the Game implementation at every state
+/// it is asked to choose a move from or analyze, and checks that the moves
+/// it returns were generated from that state.
+///
+/// Panics with a description of the problem, instead of letting a
+/// misimplemented game fail silently or deep inside the search.
+pub struct ValidatingStrategy<G, S> {
+    inner: S,
+    check_hash: bool,
+    game_type: PhantomData<G>,
+}
+
+impl<G: Game, S: interface::Strategy<G>> ValidatingStrategy<G, S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, check_hash: false, game_type: PhantomData }
+    }
+
+    /// Also check the consistency of `zobrist_hash`.
+    pub fn with_hash_checks(mut self) -> Self {
+        self.check_hash = true;
+        self
+    }
+
+    /// Return the wrapped strategy.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<G: Game, S> ValidatingStrategy<G, S>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    fn check_state(&self, state: &G::S) {
+        if let Err(err) = validate_impl::<G>(state, self.check_hash) {
+            panic!("invalid Game implementation: {}", err);
+        }
+    }
+
+    fn check_move(&self, state: &G::S, m: Option<G::M>) {
+        if let Some(m) = m {
+            let mut moves = Vec::new();
+            G::generate_moves(state, &mut moves);
+            assert!(
+                moves.contains(&m),
+                "strategy chose ungenerated move {}",
+                notation::<G>(state, m)
+            );
+        }
+    }
+}
+
+impl<G, S: interface::FreshCopy> interface::FreshCopy for ValidatingStrategy<G, S> {
+    fn fresh_copy(&self) -> Self {
+        Self { inner: self.inner.fresh_copy(), check_hash: self.check_hash, game_type: PhantomData }
+    }
+}
+
+impl<G: Game, S: interface::Strategy<G>> interface::Strategy<G> for ValidatingStrategy<G, S>
+where
+    G::S: Clone + PartialEq,
+    G::M: Eq,
+{
+    fn choose_move(&mut self, state: &G::S) -> Option<G::M> {
+        self.check_state(state);
+        let m = self.inner.choose_move(state);
+        self.check_move(state, m);
+        m
+    }
+
+    fn choose_move_with_info(&mut self, state: &G::S) -> interface::SearchInfo<G::M> {
+        self.check_state(state);
+        let info = self.inner.choose_move_with_info(state);
+        self.check_move(state, info.best_move);
+        info
+    }
+
+    fn set_timeout(&mut self, timeout: core::time::Duration) {
+        self.inner.set_timeout(timeout);
+    }
+
+    fn notify_move(&mut self, state: &G::S, m: G::M) {
+        self.inner.notify_move(state, m);
+    }
+
+    fn new_game(&mut self) {
+        self.inner.new_game();
+    }
+
+    fn set_win_probability_scale(&mut self, scale: f32) {
+        self.inner.set_win_probability_scale(scale);
+    }
+
+    fn set_max_nodes(&mut self, nodes: u64) {
+        self.inner.set_max_nodes(nodes);
+    }
+
+    fn set_max_depth(&mut self, depth: u8) {
+        self.inner.set_max_depth(depth);
+    }
+
+    fn set_stop_signal(&mut self, signal: interface::StopSignal) {
+        self.inner.set_stop_signal(signal);
+    }
+
+    fn set_progress(&mut self, progress: alloc::boxed::Box<dyn interface::SearchProgress<G::M>>) {
+        self.inner.set_progress(progress);
+    }
+
+    fn set_tablebase(
+        &mut self, tablebase: alloc::sync::Arc<dyn interface::Tablebase<G = G> + Send + Sync>,
+    ) {
+        self.inner.set_tablebase(tablebase);
+    }
+
+    fn set_rng_seed(&mut self, seed: u64) {
+        self.inner.set_rng_seed(seed);
+    }
+
+    fn set_time_left(&mut self, time: &interface::TimeLeft) {
+        self.inner.set_time_left(time);
+    }
+
+    fn principal_variation(&self) -> Vec<G::M> {
+        self.inner.principal_variation()
+    }
+
+    fn analyze(&mut self, state: &G::S, k: usize) -> Vec<interface::MoveAnalysis<G::M>> {
+        self.check_state(state);
+        let analysis = self.inner.analyze(state, k);
+        for line in analysis.iter() {
+            self.check_move(state, Some(line.m));
+        }
+        analysis
+    }
+
+    fn memory_usage(&self) -> interface::MemoryUsage {
+        self.inner.memory_usage()
+    }
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "async")]
+
+extern crate minimax;
+use minimax::games::connect4;
+
+use minimax::*;
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread;
+use std::time::Duration;
+
+struct ThreadWaker(thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Minimal executor for a single future.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn test_async_choose_move() {
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_max_depth(4);
+    let strategy = AsyncStrategy::new(iterative);
+    assert!(block_on(strategy.choose_move(&connect4::Board::default())).is_some());
+}
+
+#[test]
+fn test_async_cancel() {
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_timeout(Duration::from_secs(3600));
+    let strategy = AsyncStrategy::new(iterative);
+    let future = strategy.choose_move(&connect4::Board::default());
+    thread::sleep(Duration::from_millis(50));
+    future.cancel();
+    assert!(block_on(future).is_some());
+}
+
+#[test]
+fn test_async_cancel_only_own_search() {
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_timeout(Duration::from_millis(300));
+    let strategy = AsyncStrategy::new(iterative);
+    let start = std::time::Instant::now();
+    let running = strategy.choose_move(&connect4::Board::default());
+    thread::sleep(Duration::from_millis(50));
+    // Dropping a queued search leaves the running one alone.
+    drop(strategy.choose_move(&connect4::Board::default()));
+    assert!(block_on(running).is_some());
+    assert!(start.elapsed() >= Duration::from_millis(250));
+}
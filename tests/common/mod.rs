@@ -0,0 +1,26 @@
+//! Fixtures shared by the integration tests.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// A writer whose output can be read back while a strategy, engine, or
+/// tournament writes to a clone of it.
+#[derive(Clone, Default)]
+pub struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl SharedOutput {
+    /// Everything written so far.
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+extern crate minimax;
+
+use minimax::games::{nim, ttt};
+use minimax::util::battle_royale;
+use minimax::*;
+
+#[test]
+fn test_registry() {
+    let registry = Registry::new().with_reference_games();
+    assert_eq!(registry.games().collect::<Vec<_>>(), ["ttt", "connect4", "nim", "othello"]);
+    assert!(registry.new_engine("chess", "mcts").is_none());
+    assert!(registry.new_engine("ttt", "magic").is_none());
+
+    let mut engine = registry.new_engine("ttt", "iterative").unwrap();
+    engine.set_max_depth(9);
+    // X in the top corners and O in the middle row, with X to win in the
+    // top middle.
+    for m in ["@0", "@3", "@2", "@4"] {
+        assert!(engine.play(m), "{}", m);
+    }
+    assert!(!engine.play("@0"));
+    assert_eq!(engine.moves(), ["@1", "@5", "@6", "@7", "@8"]);
+    let info = engine.choose_move();
+    assert_eq!(info.best_move.as_deref(), Some("@1"));
+    assert_eq!(info.principal_variation.first().map(String::as_str), Some("@1"));
+    assert!(info.value.unwrap() > 0);
+    let lines = engine.analyze(2);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].m, "@1");
+    assert!(engine.play("@1"));
+    assert_eq!(engine.winner(), Some(Winner::PlayerJustMoved));
+    assert_eq!(engine.describe()[0], ("row 1".to_string(), "XXX".to_string()));
+
+    engine.reset();
+    assert_eq!(engine.winner(), None);
+    assert_eq!(engine.moves().len(), 9);
+}
+
+#[test]
+fn test_custom_strategies() {
+    // A game registered with its own strategies and starting position.
+    let registry = Registry::new().with_strategies::<nim::Game, _>(
+        "nim",
+        nim::Board::new(&[1, 2]),
+        |name: &str| -> Option<BoxedStrategy<nim::Game>> {
+            match name {
+                "perfect" => Some(Box::new(Negamax::new(nim::Evaluator, 3))),
+                _ => None,
+            }
+        },
+    );
+    assert!(registry.new_engine("nim", "random").is_none());
+    let mut engine = registry.new_engine("nim", "perfect").unwrap();
+    // Evening the heaps leaves a nim-sum of zero.
+    assert_eq!(engine.choose_move().best_move.as_deref(), Some("1-1"));
+}
+
+#[test]
+fn test_boxed_strategy() {
+    // Boxed strategies can play wherever a strategy is expected.
+    let choose = |perfect: bool| -> BoxedStrategy<ttt::Game> {
+        if perfect {
+            Box::new(Negamax::new(ttt::Evaluator, 9))
+        } else {
+            Box::new(Random::new())
+        }
+    };
+    let mut first = choose(true);
+    let mut second = choose(true);
+    assert_eq!(battle_royale(&mut first, &mut second), None);
+}
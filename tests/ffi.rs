@@ -0,0 +1,67 @@
+#![cfg(feature = "ffi")]
+
+extern crate minimax;
+use minimax::ffi::*;
+use std::ffi::{c_char, CStr};
+
+fn c(s: &str) -> Vec<c_char> {
+    s.bytes().chain([0]).map(|b| b as c_char).collect()
+}
+
+#[test]
+fn test_engine() {
+    unsafe {
+        assert!(minimax_engine_new(c("chess").as_ptr(), c("mcts").as_ptr()).is_null());
+        assert!(minimax_engine_new(c("ttt").as_ptr(), c("magic").as_ptr()).is_null());
+
+        let engine = minimax_engine_new(c("ttt").as_ptr(), c("iterative").as_ptr());
+        assert!(!engine.is_null());
+        assert!(minimax_engine_set_option(engine, c("depth").as_ptr(), c("9").as_ptr()));
+        assert!(!minimax_engine_set_option(engine, c("depth").as_ptr(), c("deep").as_ptr()));
+        assert!(!minimax_engine_set_option(engine, c("color").as_ptr(), c("1").as_ptr()));
+
+        // X in the top corners and O in the middle row, with X to win in the
+        // top middle.
+        for m in ["@0", "@3", "@2", "@4"] {
+            assert!(minimax_engine_play(engine, c(m).as_ptr()), "{m}");
+        }
+        assert!(!minimax_engine_play(engine, c("@0").as_ptr()));
+        let mut buf = [0 as c_char; 8];
+        assert_eq!(minimax_engine_choose_move(engine, buf.as_mut_ptr(), buf.len()), 2);
+        assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str(), Ok("@1"));
+        assert_eq!(minimax_engine_choose_move(engine, buf.as_mut_ptr(), 2), -1);
+
+        let mut stats = MinimaxStats::default();
+        assert!(minimax_engine_stats(engine, &mut stats));
+        assert!(stats.nodes > 0 && stats.has_value && stats.value > 0);
+
+        assert!(minimax_engine_reset(engine));
+        assert!(minimax_engine_play(engine, c("@0").as_ptr()));
+        minimax_engine_free(engine);
+    }
+}
+
+// An evaluator that always panics, standing in for a buggy game.
+#[derive(Clone)]
+struct PanickingEvaluator;
+
+impl minimax::Evaluator for PanickingEvaluator {
+    type G = minimax::games::ttt::Game;
+    fn evaluate(&self, _: &minimax::games::ttt::Board) -> minimax::Evaluation {
+        panic!("evaluator failed");
+    }
+}
+
+#[test]
+fn test_engine_panic() {
+    register_game("panicking", PanickingEvaluator);
+    unsafe {
+        let engine = minimax_engine_new(c("panicking").as_ptr(), c("iterative").as_ptr());
+        assert!(!engine.is_null());
+        assert!(minimax_engine_set_option(engine, c("depth").as_ptr(), c("2").as_ptr()));
+        // The panic is reported as an error, not unwound into the caller.
+        let mut buf = [0 as c_char; 8];
+        assert_eq!(minimax_engine_choose_move(engine, buf.as_mut_ptr(), buf.len()), -1);
+        minimax_engine_free(engine);
+    }
+}
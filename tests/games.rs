@@ -0,0 +1,535 @@
+extern crate minimax;
+
+use minimax::games::{connect4, nim, othello, ttt};
+use minimax::util::{fuzz, fuzz_with_hash, perft_divide};
+use minimax::{
+    perft, Evaluation, Evaluator, Game, IterativeOptions, IterativeSearch, Negamax,
+    ParallelOptions, ParallelSearch, Strategy,
+};
+use std::marker::PhantomData;
+
+// Knows nothing, so searches have to solve the position.
+struct Zero<G>(PhantomData<G>);
+
+impl<G> Zero<G> {
+    fn new() -> Self {
+        Zero(PhantomData)
+    }
+}
+
+impl<G> Clone for Zero<G> {
+    fn clone(&self) -> Self {
+        Zero::new()
+    }
+}
+
+impl<G: Game> Evaluator for Zero<G> {
+    type G = G;
+    fn evaluate(&self, _: &G::S) -> Evaluation {
+        0
+    }
+}
+
+// Changes to the rules of nim, each of which defaults to nim's own.
+trait NimRules {
+    fn generate_plausible_moves(b: &nim::Board, moves: &mut Vec<nim::Take>) {
+        nim::Game::generate_plausible_moves(b, moves)
+    }
+
+    fn generate_scored_moves(
+        b: &nim::Board, moves: &mut Vec<nim::Take>, priorities: &mut Vec<i16>,
+    ) {
+        nim::Game::generate_scored_moves(b, moves, priorities)
+    }
+
+    fn undo(b: &mut nim::Board, m: nim::Take) {
+        nim::Game::undo(b, m)
+    }
+
+    fn is_legal(b: &nim::Board, m: nim::Take) -> bool {
+        nim::Game::is_legal(b, m)
+    }
+
+    fn zobrist_hash(b: &nim::Board) -> u64 {
+        nim::Game::zobrist_hash(b)
+    }
+
+    fn komi(b: &nim::Board) -> Evaluation {
+        nim::Game::komi(b)
+    }
+}
+
+// Nim, with the changes to its rules in `R`.
+struct NimWith<R>(PhantomData<R>);
+
+impl<R: NimRules> Game for NimWith<R> {
+    type S = nim::Board;
+    type M = nim::Take;
+
+    fn generate_moves(b: &nim::Board, moves: &mut Vec<nim::Take>) {
+        nim::Game::generate_moves(b, moves)
+    }
+
+    fn generate_plausible_moves(b: &nim::Board, moves: &mut Vec<nim::Take>) {
+        R::generate_plausible_moves(b, moves)
+    }
+
+    fn generate_scored_moves(
+        b: &nim::Board, moves: &mut Vec<nim::Take>, priorities: &mut Vec<i16>,
+    ) {
+        R::generate_scored_moves(b, moves, priorities)
+    }
+
+    fn get_winner(b: &nim::Board) -> Option<minimax::Winner> {
+        nim::Game::get_winner(b)
+    }
+
+    fn apply(b: &mut nim::Board, m: nim::Take) -> Option<nim::Board> {
+        nim::Game::apply(b, m)
+    }
+
+    fn undo(b: &mut nim::Board, m: nim::Take) {
+        R::undo(b, m)
+    }
+
+    fn is_legal(b: &nim::Board, m: nim::Take) -> bool {
+        R::is_legal(b, m)
+    }
+
+    fn zobrist_hash(b: &nim::Board) -> u64 {
+        R::zobrist_hash(b)
+    }
+
+    fn komi(b: &nim::Board) -> Evaluation {
+        R::komi(b)
+    }
+
+    fn notation(b: &nim::Board, m: nim::Take) -> Option<String> {
+        nim::Game::notation(b, m)
+    }
+
+    fn describe(b: &nim::Board) -> Vec<(String, String)> {
+        nim::Game::describe(b)
+    }
+}
+
+#[test]
+fn test_othello_perft() {
+    // Known counts from the starting position.
+    let mut b = othello::Board::default();
+    assert_eq!(perft::<othello::Game>(&mut b, 6, false), vec![1, 4, 12, 56, 244, 1396, 8200]);
+    let divide = perft_divide::<othello::Game>(&mut b, 3, false);
+    assert!(divide.iter().all(|&(_, count)| count == 14));
+    let names: Vec<_> =
+        divide.iter().map(|&(m, _)| othello::Game::notation(&b, m).unwrap()).collect();
+    assert_eq!(names, vec!["d3", "c4", "f5", "e6"]);
+}
+
+#[test]
+fn test_othello_search() {
+    let mut b = othello::Board::default();
+    let mut s1 = IterativeSearch::new(othello::Evaluator, IterativeOptions::new());
+    s1.set_max_depth(3);
+    let mut s2 = Negamax::new(othello::Evaluator, 2);
+    let (mut moves, mut placed) = (0, 0);
+    while othello::Game::get_winner(&b).is_none() {
+        let m = if moves % 2 == 0 { s1.choose_move(&b) } else { s2.choose_move(&b) };
+        if m != Some(othello::Move::Pass) {
+            placed += 1;
+        }
+        b = othello::Game::apply(&mut b, m.unwrap()).unwrap();
+        moves += 1;
+    }
+    // Every placement adds a piece to the four in the middle, and flips
+    // never overlap the two colors.
+    assert_eq!((b.pieces_to_move | b.pieces_just_moved).count_ones(), 4 + placed);
+    assert_eq!(b.pieces_to_move & b.pieces_just_moved, 0);
+}
+
+#[test]
+fn test_nim_negamax_matches_nim_sum() {
+    for heaps in [[1, 2, 3], [1, 3, 4], [2, 2, 3], [1, 1, 4]] {
+        let b = nim::Board::new(&heaps);
+        let mut s = Negamax::new(Zero::<nim::Game>::new(), 10);
+        let m = s.choose_move(&b).unwrap();
+        let mut after = b.clone();
+        nim::Game::apply(&mut after, m);
+        if b.nim_sum() != 0 {
+            assert_eq!(after.nim_sum(), 0, "{} from {:?}", m, heaps);
+        }
+    }
+}
+
+// Nim, with komi for the player to move.
+struct Komi;
+
+impl NimRules for Komi {
+    fn komi(_: &nim::Board) -> Evaluation {
+        7
+    }
+}
+
+type KomiNim = NimWith<Komi>;
+// Scores every position the same.
+struct ConstantEvaluator(Evaluation);
+
+impl Evaluator for ConstantEvaluator {
+    type G = KomiNim;
+    fn evaluate(&self, _: &nim::Board) -> Evaluation {
+        self.0
+    }
+}
+
+#[test]
+fn test_komi() {
+    use minimax::util::battle_royale_from;
+    use minimax::{Random, Winner, BEST_EVAL, WORST_EVAL};
+
+    // Games scored by points are decided net of komi.
+    assert_eq!(Winner::from_margin(3, 0), Winner::PlayerJustMoved);
+    assert_eq!(Winner::from_margin(3, 5), Winner::PlayerToMove);
+    assert_eq!(Winner::from_margin(5, 5), Winner::Draw);
+
+    // Every leaf is worth the komi to the opponent.
+    let b = nim::Board::new(&[1, 2, 3]);
+    let mut s = Negamax::new(ConstantEvaluator(0), 1);
+    s.choose_move(&b).unwrap();
+    assert_eq!(s.root_value(), -7);
+    // A large lead plus komi is still not a proven result.
+    let mut s = Negamax::new(ConstantEvaluator(BEST_EVAL - 101), 1);
+    s.choose_move(&b).unwrap();
+    assert_eq!(s.root_value(), WORST_EVAL + 100);
+
+    // From a handicap position, the first player can only lose.
+    let start = nim::Board::new(&[1, 1]);
+    let result =
+        battle_royale_from::<nim::Game, _, _>(start, &mut Random::new(), &mut Random::new());
+    assert_eq!(result, Some(1));
+}
+
+#[test]
+fn test_games_fuzz() {
+    assert_eq!(fuzz_with_hash::<ttt::Game>(&ttt::Board::default(), 200, 1), Ok(()));
+    assert_eq!(fuzz_with_hash::<nim::Game>(&nim::Board::default(), 200, 2), Ok(()));
+    assert_eq!(fuzz_with_hash::<connect4::Game>(&connect4::Board::default(), 100, 3), Ok(()));
+    assert_eq!(fuzz_with_hash::<othello::Game>(&othello::Board::default(), 20, 4), Ok(()));
+}
+
+// Nim, except that undo forgets to put back a whole heap.
+struct Leaky;
+
+impl NimRules for Leaky {
+    fn undo(b: &mut nim::Board, m: nim::Take) {
+        if b.heaps[m.heap as usize] != 0 {
+            nim::Game::undo(b, m)
+        }
+    }
+}
+
+type LeakyNim = NimWith<Leaky>;
+#[test]
+fn test_fuzz_reports_moves() {
+    let err = fuzz::<LeakyNim>(&nim::Board::new(&[2, 2]), 10, 0).unwrap_err();
+    assert!(err.starts_with("after moves ["), "{}", err);
+    assert!(err.contains("did not restore the state"), "{}", err);
+    // The heap that wasn't put back.
+    assert!(err.contains(": expected 2, got 0)"), "{}", err);
+}
+
+#[test]
+fn test_diff_states() {
+    use minimax::util::{diff_states, StateDiff};
+    let b = ttt::Board::default();
+    assert_eq!(diff_states::<ttt::Game>(&b, &b), Some(vec![]));
+    let mut after = b.clone();
+    let mut moves = Vec::new();
+    ttt::Game::generate_moves(&b, &mut moves);
+    if let Some(new) = ttt::Game::apply(&mut after, moves[4]) {
+        after = new;
+    }
+    let diffs = diff_states::<ttt::Game>(&b, &after).unwrap();
+    let fields: Vec<_> = diffs.iter().map(|diff| diff.field.as_str()).collect();
+    assert_eq!(fields, ["row 2", "to move"]);
+    assert_eq!(diffs[0].to_string(), "row 2: expected ..., got .X.");
+
+    // Fields only one state has.
+    let diffs =
+        diff_states::<nim::Game>(&nim::Board::new(&[1, 2]), &nim::Board::new(&[1])).unwrap();
+    assert_eq!(
+        diffs,
+        vec![StateDiff {
+            field: "heap 1".to_string(),
+            expected: Some("2".to_string()),
+            actual: None
+        }]
+    );
+    assert_eq!(diffs[0].to_string(), "heap 1: expected 2, got (missing)");
+
+    // Games that don't describe their states can't be compared.
+    let b = othello::Board::default();
+    assert_eq!(diff_states::<othello::Game>(&b, &b), None);
+}
+
+// Tic-tac-toe, except that hashes are updated incorrectly.
+struct MishashedTtt;
+
+impl Game for MishashedTtt {
+    type S = ttt::Board;
+    type M = ttt::Place;
+
+    fn generate_moves(b: &ttt::Board, moves: &mut Vec<ttt::Place>) {
+        ttt::Game::generate_moves(b, moves)
+    }
+
+    fn get_winner(b: &ttt::Board) -> Option<minimax::Winner> {
+        ttt::Game::get_winner(b)
+    }
+
+    fn apply(b: &mut ttt::Board, m: ttt::Place) -> Option<ttt::Board> {
+        ttt::Game::apply(b, m)
+    }
+
+    fn undo(b: &mut ttt::Board, m: ttt::Place) {
+        ttt::Game::undo(b, m)
+    }
+
+    fn zobrist_hash(b: &ttt::Board) -> u64 {
+        ttt::Game::zobrist_hash(b)
+    }
+
+    fn zobrist_hash_after(hash: u64, _: &ttt::Board, _: ttt::Place) -> Option<u64> {
+        Some(hash + 1)
+    }
+}
+
+#[test]
+fn test_validate_hash_after() {
+    use minimax::util::validate_with_hash;
+    let err = validate_with_hash::<MishashedTtt>(&ttt::Board::default()).unwrap_err();
+    assert!(err.contains("zobrist_hash_after"), "{}", err);
+}
+
+// Nim, except that the hash ignores the first heap, so positions that differ
+// only there collide in the transposition table.
+struct Colliding;
+
+impl NimRules for Colliding {
+    fn zobrist_hash(b: &nim::Board) -> u64 {
+        nim::Game::zobrist_hash(&nim::Board::new(&b.heaps[1..]))
+    }
+}
+
+type CollidingNim = NimWith<Colliding>;
+#[test]
+fn test_staged_table_move_collision() {
+    // The table suggests moves from the first heap that were legal where
+    // they were found, but not in the smaller heaps that collide with it.
+    let opts = IterativeOptions::new().with_staged_move_generation();
+    let mut search = IterativeSearch::new(Zero::<CollidingNim>::new(), opts);
+    search.set_max_depth(6);
+    assert!(search.choose_move(&nim::Board::new(&[5, 3])).is_some());
+}
+
+// Nim, except that is_legal forbids taking a whole heap, which it generates.
+struct Strict;
+
+impl NimRules for Strict {
+    fn is_legal(b: &nim::Board, m: nim::Take) -> bool {
+        m.count < b.heaps[m.heap as usize]
+    }
+}
+
+type StrictNim = NimWith<Strict>;
+#[test]
+fn test_is_legal() {
+    let b = nim::Board::new(&[1, 3]);
+    assert!(nim::Game::is_legal(&b, nim::Take { heap: 1, count: 3 }));
+    assert!(!nim::Game::is_legal(&b, nim::Take { heap: 0, count: 2 }));
+    assert!(!nim::Game::is_legal(&b, nim::Take { heap: 2, count: 1 }));
+
+    let err = minimax::util::validate::<StrictNim>(&b).unwrap_err();
+    assert!(err.contains("is not legal"), "{}", err);
+}
+
+// Nim, where taking one object is the only plausible move.
+struct Plausible;
+
+impl NimRules for Plausible {
+    fn generate_plausible_moves(b: &nim::Board, moves: &mut Vec<nim::Take>) {
+        nim::Game::generate_moves(b, moves);
+        moves.retain(|m| m.count == 1);
+    }
+}
+
+type PlausibleNim = NimWith<Plausible>;
+#[test]
+fn test_plausible_moves() {
+    // A lost position, unless the opponent only ever takes one object, when
+    // taking a whole heap leaves an even number of them.
+    let b = nim::Board::new(&[2, 2]);
+    let opts = IterativeOptions::new().with_table_byte_size(64000);
+    let mut full = IterativeSearch::new(Zero::<PlausibleNim>::new(), opts);
+    full.set_max_depth(4);
+    full.choose_move(&b).unwrap();
+    assert!(full.root_value() < 0);
+
+    let opts = opts.with_plausible_moves(1);
+    let mut pruned = IterativeSearch::new(Zero::<PlausibleNim>::new(), opts);
+    pruned.set_max_depth(4);
+    assert_eq!(pruned.choose_move(&b).unwrap().count, 2);
+    assert!(pruned.root_value() > 0);
+
+    let mut parallel =
+        ParallelSearch::new(Zero::<PlausibleNim>::new(), opts, ParallelOptions::new());
+    parallel.set_max_depth(4);
+    parallel.choose_move(&b).unwrap();
+    assert_eq!(parallel.root_value(), pruned.root_value());
+}
+
+// Nim whose generator knows the winning moves, and puts them first.
+struct Prioritized;
+
+impl NimRules for Prioritized {
+    fn generate_scored_moves(
+        b: &nim::Board, moves: &mut Vec<nim::Take>, priorities: &mut Vec<i16>,
+    ) {
+        nim::Game::generate_moves(b, moves);
+        let sum = b.nim_sum();
+        for m in moves.iter() {
+            let heap = b.heaps[m.heap as usize];
+            priorities.push(((heap - m.count) ^ heap ^ sum == 0) as i16);
+        }
+    }
+}
+
+type PrioritizedNim = NimWith<Prioritized>;
+#[test]
+fn test_scored_moves() {
+    let b = nim::Board::new(&[1, 2, 4]);
+    let opts = IterativeOptions::new().with_table_byte_size(64000);
+    let mut plain = IterativeSearch::new(Zero::<nim::Game>::new(), opts);
+    plain.set_max_depth(7);
+    plain.set_rng_seed(1);
+    let plain_nodes = plain.choose_move_with_info(&b).nodes;
+
+    let mut prioritized = IterativeSearch::new(Zero::<PrioritizedNim>::new(), opts);
+    prioritized.set_max_depth(7);
+    prioritized.set_rng_seed(1);
+    let info = prioritized.choose_move_with_info(&b);
+    // The only winning move is searched first, and refutations are found
+    // sooner all the way down.
+    let m = info.best_move.unwrap();
+    assert_eq!((m.heap, m.count), (2, 1));
+    assert!(info.nodes < plain_nodes, "{} >= {}", info.nodes, plain_nodes);
+
+    let mut parallel =
+        ParallelSearch::new(Zero::<PrioritizedNim>::new(), opts, ParallelOptions::new());
+    parallel.set_max_depth(7);
+    let m = parallel.choose_move(&b).unwrap();
+    assert_eq!((m.heap, m.count), (2, 1));
+}
+
+#[test]
+fn test_retrograde_tablebase() {
+    use minimax::{RetrogradeTablebase, Tablebase, Winner};
+    use std::sync::Arc;
+
+    // Every reachable tic-tac-toe position, and the start is a draw.
+    let table = RetrogradeTablebase::<ttt::Game>::build(&ttt::Board::default(), 10000).unwrap();
+    assert_eq!(table.len(), 5478);
+    let start = table.probe(&ttt::Board::default()).unwrap();
+    assert_eq!(start.winner, Winner::Draw);
+    assert!(RetrogradeTablebase::<ttt::Game>::build(&ttt::Board::default(), 1000).is_err());
+
+    // Nim positions are won exactly when the nim-sum is nonzero, and the
+    // winner empties the heaps as slowly as the loser can manage.
+    let start = nim::Board::new(&[1, 2, 3]);
+    let table = RetrogradeTablebase::<nim::Game>::build(&start, 1000).unwrap();
+    assert_eq!(table.len(), 2 * 3 * 4);
+    for a in 0..=1 {
+        for b in 0..=2 {
+            for c in 0..=3 {
+                let board = nim::Board::new(&[a, b, c]);
+                let result = table.probe(&board).unwrap();
+                let expected = if board.nim_sum() == 0 {
+                    Winner::PlayerJustMoved
+                } else {
+                    Winner::PlayerToMove
+                };
+                assert_eq!(result.winner, expected, "{:?}", board);
+            }
+        }
+    }
+    let single = table.probe(&nim::Board::new(&[0, 0, 3])).unwrap();
+    assert_eq!(single.distance, Some(1));
+    let pairs = table.probe(&nim::Board::new(&[0, 2, 2])).unwrap();
+    assert_eq!(pairs.distance, Some(4));
+
+    // It survives a round trip to disk.
+    let mut bytes = Vec::new();
+    table.save(&mut bytes).unwrap();
+    let loaded = RetrogradeTablebase::<nim::Game>::load(&bytes[..]).unwrap();
+    assert_eq!(loaded.len(), table.len());
+    assert_eq!(loaded.probe(&start), table.probe(&start));
+    assert!(RetrogradeTablebase::<nim::Game>::load(&bytes[1..]).is_err());
+
+    // A search that knows nothing plays perfectly with it.
+    let mut negamax = Negamax::new(Zero::<nim::Game>::new(), 2);
+    negamax.set_tablebase(Arc::new(loaded));
+    let winning = nim::Board::new(&[1, 2, 2]);
+    let m = negamax.choose_move(&winning).unwrap();
+    let mut after = winning.clone();
+    nim::Game::apply(&mut after, m);
+    assert_eq!(after.nim_sum(), 0);
+}
+
+#[test]
+fn test_seat_results() {
+    use minimax::balance::seat_results;
+    use minimax::{Match, Random};
+
+    // With perfect play, the nim-sum decides who wins.
+    let perfect = Negamax::new(nim::Evaluator, 10);
+    let game = Match::untimed();
+    let results = seat_results(&game, &nim::Board::new(&[1, 2]), &perfect, 10);
+    assert_eq!((results.wins, results.draws), ([10, 0], 0));
+    assert_eq!(results.first_player_score(), 1.0);
+    // Ten wins don't prove the first player always wins.
+    let (low, high) = results.first_player_score_interval();
+    assert!(0.5 < low && low < 1.0 && high == 1.0);
+    assert_eq!(results.first_player_elo().elo, f64::INFINITY);
+    let results = seat_results(&game, &nim::Board::new(&[1, 2, 3]), &perfect, 10);
+    assert_eq!((results.wins, results.draws), ([0, 10], 0));
+
+    // Moving first is an advantage in tic-tac-toe, even at random.
+    let results = seat_results(&game, &ttt::Board::default(), &Random::<ttt::Game>::new(), 400);
+    assert_eq!(results.games(), 400);
+    assert!(results.win_rate(0) > results.win_rate(1));
+    for seat in 0..2 {
+        let (low, high) = results.win_rate_interval(seat);
+        assert!(low < results.win_rate(seat) && results.win_rate(seat) < high);
+    }
+    let (low, high) = results.first_player_score_interval();
+    assert!(0.5 < low && low < results.first_player_score() && results.first_player_score() < high);
+    assert!(results.first_player_elo().elo > 0.0);
+    assert!(results.to_string().contains("first player"));
+}
+
+#[test]
+fn test_corpus_without_notation() {
+    use minimax::corpus::CorpusStats;
+    use minimax::{Match, Random};
+
+    // Connect four has no move notation, so only lengths and results count.
+    let mut first = Random::<connect4::Game>::new();
+    let record = Match::untimed().play(&connect4::Board::default(), &mut first, &mut Random::new());
+    let stats = CorpusStats::from_records([&record]);
+    assert_eq!(stats.lengths.get(&record.moves.len()), Some(&1));
+    assert!(stats.moves.is_empty());
+
+    // The text export names moves by their index, which reads back.
+    let text = record.to_text();
+    let first = text.lines().next().unwrap().split(' ').nth(1).unwrap();
+    let parsed = minimax::util::parse_move::<connect4::Game>(&record.start, first);
+    assert!(parsed == Some(record.moves[0].m));
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "metrics")]
+
+extern crate metrics;
+extern crate minimax;
+
+use metrics::{
+    Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use minimax::games::connect4;
+use minimax::*;
+use std::sync::{Arc, Mutex};
+
+// Minimal recorder that collects every update as "name{strategy} value".
+#[derive(Clone, Default)]
+struct Collector {
+    updates: Arc<Mutex<Vec<String>>>,
+}
+
+struct Metric {
+    name: String,
+    updates: Arc<Mutex<Vec<String>>>,
+}
+
+impl CounterFn for Metric {
+    fn increment(&self, value: u64) {
+        self.updates.lock().unwrap().push(format!("{} {}", self.name, value));
+    }
+
+    fn absolute(&self, value: u64) {
+        self.increment(value);
+    }
+}
+
+impl HistogramFn for Metric {
+    fn record(&self, value: f64) {
+        self.updates.lock().unwrap().push(format!("{} {}", self.name, value));
+    }
+}
+
+impl Collector {
+    fn metric(&self, key: &Key) -> Arc<Metric> {
+        let labels = key.labels().map(|label| label.value().to_string()).collect::<Vec<_>>();
+        let name = format!("{}{{{}}}", key.name(), labels.join(","));
+        Arc::new(Metric { name, updates: self.updates.clone() })
+    }
+}
+
+impl Recorder for Collector {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.metric(key))
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.metric(key))
+    }
+}
+
+#[test]
+fn test_search_metrics() {
+    let collector = Collector::default();
+    metrics::with_local_recorder(&collector, || {
+        let b = connect4::Board::default();
+        let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+        iterative.set_max_depth(4);
+        iterative.choose_move(&b);
+        let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default());
+        mcts.set_max_rollouts(100);
+        mcts.choose_move(&b);
+    });
+    let updates = collector.updates.lock().unwrap();
+    assert!(updates.contains(&"minimax_searches_total{iterative} 1".to_string()));
+    assert!(updates.contains(&"minimax_search_depth{iterative} 4".to_string()));
+    assert!(updates.iter().any(|update| update.starts_with("minimax_table_hit_rate{iterative}")));
+    assert!(updates.contains(&"minimax_searches_total{mcts} 1".to_string()));
+    assert!(updates.iter().any(|update| update.starts_with("minimax_search_seconds{mcts}")));
+    assert!(!updates.iter().any(|update| update.starts_with("minimax_table_hit_rate{mcts}")));
+}
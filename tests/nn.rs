@@ -0,0 +1,133 @@
+#![cfg(feature = "nn")]
+
+extern crate minimax;
+
+use minimax::games::connect4;
+use minimax::nn::*;
+use minimax::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const COLUMNS: usize = 7;
+
+// A network that always prefers the leftmost column, and expects a draw.
+#[derive(Default)]
+struct LeftNetwork {
+    runs: AtomicUsize,
+    on_gpu: bool,
+}
+
+impl NeuralNetwork for LeftNetwork {
+    fn run(&self, input: &[f32], batch: usize, policy: &mut [f32], value: &mut [f32]) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(input.len(), batch);
+        for logits in policy.chunks_mut(COLUMNS) {
+            logits[0] = 10.0;
+        }
+        value.fill(0.0);
+    }
+
+    fn max_batch_size(&self) -> usize {
+        4
+    }
+
+    fn set_device(&mut self, device: Device) -> bool {
+        // Only one GPU.
+        if device == Device::Gpu(1) {
+            return false;
+        }
+        self.on_gpu = device == Device::Gpu(0);
+        true
+    }
+}
+
+struct Connect4Encoder;
+
+impl Encoder for Connect4Encoder {
+    type G = connect4::Game;
+
+    fn input_size(&self) -> usize {
+        1
+    }
+
+    fn policy_size(&self) -> usize {
+        COLUMNS
+    }
+
+    fn encode(&self, _: &connect4::Board, input: &mut [f32]) {
+        input[0] = 1.0;
+    }
+
+    fn move_index(&self, _: &connect4::Board, m: connect4::Place) -> usize {
+        m.col as usize
+    }
+}
+
+#[test]
+fn test_neural_evaluator() {
+    let mut evaluator = NeuralEvaluator::new(LeftNetwork::default(), Connect4Encoder);
+    assert!(evaluator.set_device(Device::Gpu(0)));
+    assert!(!evaluator.set_device(Device::Gpu(1)));
+    assert!(evaluator.network().on_gpu);
+
+    let states = vec![connect4::Board::default(); 5];
+    let mut results = Vec::new();
+    evaluator.evaluate_batch(&states, &mut results);
+    assert_eq!(results.len(), 5);
+    assert_eq!(evaluator.network().runs.load(Ordering::Relaxed), 2);
+    let evaluator = evaluator.with_max_batch_size(1);
+    evaluator.evaluate_batch(&states, &mut results);
+    assert_eq!(evaluator.network().runs.load(Ordering::Relaxed), 7);
+
+    let result = &results[0];
+    assert_eq!(result.policy.len(), COLUMNS);
+    assert!(result.policy[0] > 0.99);
+    assert!((result.policy.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    assert_eq!(result.value, 0.0);
+}
+
+#[test]
+fn test_mcts_with_evaluator() {
+    let evaluator = NeuralEvaluator::new(LeftNetwork::default(), Connect4Encoder);
+    let mut mcts = MonteCarloTreeSearch::new_with_evaluator(
+        MCTSOptions::default().with_num_threads(1),
+        Box::new(evaluator),
+    );
+    mcts.set_rng_seed(1);
+    mcts.set_max_rollouts(200);
+    // The priors lead the search to the leftmost column.
+    let b = connect4::Board::default();
+    assert_eq!(mcts.choose_move(&b), Some(connect4::Place { col: 0 }));
+}
+
+// Records the largest batch it is asked to evaluate.
+struct BatchRecorder {
+    inner: NeuralEvaluator<LeftNetwork, Connect4Encoder>,
+    largest: Arc<AtomicUsize>,
+}
+
+impl PolicyValueEvaluator for BatchRecorder {
+    type G = connect4::Game;
+
+    fn evaluate_batch(&self, states: &[connect4::Board], results: &mut Vec<PolicyValue>) {
+        self.largest.fetch_max(states.len(), Ordering::Relaxed);
+        self.inner.evaluate_batch(states, results);
+    }
+}
+
+#[test]
+fn test_mcts_batches_children() {
+    let largest = Arc::new(AtomicUsize::new(0));
+    let evaluator = BatchRecorder {
+        inner: NeuralEvaluator::new(LeftNetwork::default(), Connect4Encoder),
+        largest: largest.clone(),
+    };
+    let mut mcts = MonteCarloTreeSearch::new_with_evaluator(
+        MCTSOptions::default().with_num_threads(1),
+        Box::new(evaluator),
+    );
+    mcts.set_max_rollouts(50);
+    assert!(mcts.choose_move(&connect4::Board::default()).is_some());
+    // Expanding the root evaluates it along with all seven of its children.
+    assert_eq!(largest.load(Ordering::Relaxed), 8);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "serde")]
+
+extern crate minimax;
+extern crate serde_json;
+use minimax::games::ttt;
+use minimax::*;
+use std::time::Duration;
+
+// Serializing the deserialized value gives the same JSON.
+fn round_trip<T: serde::Serialize + serde::de::DeserializeOwned>(value: &T) -> T {
+    let json = serde_json::to_string(value).unwrap();
+    let back: T = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_string(&back).unwrap(), json);
+    back
+}
+
+#[test]
+fn test_options() {
+    let opts = IterativeOptions::new().with_table_byte_size(1 << 12).with_mtdf();
+    assert_eq!(round_trip(&opts).table_byte_size, 1 << 12);
+    let opts = ParallelOptions::new().with_num_threads(3);
+    assert_eq!(round_trip(&opts).num_threads, Some(3));
+    let opts = MCTSOptions::default().verbose().with_max_tree_nodes(1000);
+    assert!(round_trip(&opts).verbose);
+    let time_control = TimeControl::new(Duration::from_secs(60));
+    assert_eq!(round_trip(&time_control), time_control);
+}
+
+#[test]
+fn test_search_and_record() {
+    let start = ttt::Board::default();
+    let mut strategy = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    strategy.set_max_depth(4);
+    let info = strategy.choose_move_with_info(&start);
+    let back = round_trip(&info);
+    assert_eq!(back.value, info.value);
+    assert_eq!(back.nodes, info.nodes);
+
+    let mut record = GameRecord::<ttt::Game>::new(start).with_players("a", "b");
+    record.add_search(&info);
+    record.result = Some(Outcome::Draw);
+    let back = round_trip(&record);
+    assert_eq!(back.players, record.players);
+    assert!(back.moves == record.moves);
+    assert_eq!(back.result, Some(Outcome::Draw));
+}
+
+#[test]
+fn test_corpus() {
+    use minimax::corpus::CorpusStats;
+
+    let mut first = Random::<ttt::Game>::new();
+    let record = Match::untimed().play(&ttt::Board::default(), &mut first, &mut Random::new());
+    let stats = CorpusStats::from_records([&record]);
+    assert_eq!(round_trip(&stats), stats);
+}
@@ -6,8 +6,10 @@
 
 extern crate minimax;
 extern crate rand;
-#[path = "../examples/connect4.rs"]
-mod connect4;
+mod common;
+
+use common::SharedOutput;
+use minimax::games::{connect4, nim};
 
 use minimax::*;
 use rand::seq::SliceRandom;
@@ -25,7 +27,7 @@ pub struct PlainNegamax<E: Evaluator> {
 
 impl<E: Evaluator> PlainNegamax<E> {
     pub fn new(eval: E, depth: u8) -> PlainNegamax<E> {
-        PlainNegamax { depth: depth, root_value: 0, best_moves: Vec::new(), eval }
+        PlainNegamax { depth, root_value: 0, best_moves: Vec::new(), eval }
     }
 
     fn negamax(&self, s: &mut <E::G as Game>::S, depth: u8) -> Evaluation
@@ -74,19 +76,13 @@ where
             }
         }
         self.root_value = best_value;
-        self.best_moves.first().map(|m| *m)
+        self.best_moves.first().copied()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 struct RandomEvaluator;
 
-impl Default for RandomEvaluator {
-    fn default() -> Self {
-        Self {}
-    }
-}
-
 impl minimax::Evaluator for RandomEvaluator {
     type G = connect4::Game;
     fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
@@ -133,11 +129,10 @@ fn test_winning_position() {
     assert_eq!(None, Negamax::new(RandomEvaluator, 4).choose_move(&b));
 
     let opt = IterativeOptions::new();
-    assert_eq!(None, IterativeSearch::new(RandomEvaluator::default(), opt).choose_move(&b));
+    assert_eq!(None, IterativeSearch::new(RandomEvaluator, opt).choose_move(&b));
     assert_eq!(
         None,
-        ParallelSearch::new(RandomEvaluator::default(), opt, ParallelOptions::default())
-            .choose_move(&b)
+        ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default()).choose_move(&b)
     );
 }
 
@@ -147,7 +142,7 @@ fn compare_plain_negamax() {
         for max_depth in 1..6 {
             let b = generate_random_state(10);
 
-            let mut plain_negamax = PlainNegamax::new(RandomEvaluator::default(), max_depth);
+            let mut plain_negamax = PlainNegamax::new(RandomEvaluator, max_depth);
             plain_negamax.choose_move(&b);
             let value = plain_negamax.root_value;
 
@@ -175,14 +170,15 @@ fn compare_plain_negamax() {
                     .with_replacement_strategy(Replacement::TwoTier)
                     .with_aspiration_window(5),
                 IterativeOptions::new().with_replacement_strategy(Replacement::TwoTier).with_mtdf(),
+                IterativeOptions::new().with_batched_leaves(),
+                IterativeOptions::new().with_staged_move_generation(),
+                IterativeOptions::new().with_aspiration_window(5).with_fail_soft(false),
             ]
             .drain(..)
             .enumerate()
             {
-                let mut iterative = IterativeSearch::new(
-                    RandomEvaluator::default(),
-                    opt.with_table_byte_size(64000),
-                );
+                let mut iterative =
+                    IterativeSearch::new(RandomEvaluator, opt.with_table_byte_size(64000));
                 iterative.set_max_depth(max_depth);
                 let iterative_move = iterative.choose_move(&b).unwrap();
                 let iterative_value = iterative.root_value();
@@ -202,7 +198,7 @@ fn compare_plain_negamax() {
 
             let opt = IterativeOptions::new().with_table_byte_size(64000);
             let mut parallel =
-                ParallelSearch::new(RandomEvaluator::default(), opt, ParallelOptions::default());
+                ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
             parallel.set_max_depth(max_depth);
             let par_move = parallel.choose_move(&b).unwrap();
             let par_value = parallel.root_value();
@@ -214,10 +210,42 @@ fn compare_plain_negamax() {
                 max_depth,
                 b
             );
+
+            let mut root_split = ParallelSearch::new(
+                RandomEvaluator,
+                opt,
+                ParallelOptions::default().with_root_split(),
+            );
+            root_split.set_max_depth(max_depth);
+            let split_move = root_split.choose_move(&b).unwrap();
+            assert_eq!(value, root_split.root_value(), "search depth={}\n{}", max_depth, b);
+            assert!(
+                plain_negamax.best_moves.contains(&split_move),
+                "bad move={:?}\nsearch depth={}\n{}",
+                split_move,
+                max_depth,
+                b
+            );
         }
     }
 }
 
+#[test]
+fn test_history_options() {
+    // Tuning the history table only changes the move order, not the value.
+    let history = HistoryOptions::new().with_max_bonus(16).with_aging_divisor(4);
+    let opt = IterativeOptions::new().with_countermove_history().with_history_options(history);
+    for max_depth in 1..7 {
+        let b = generate_random_state(10);
+        let mut negamax = Negamax::new(RandomEvaluator, max_depth);
+        negamax.choose_move(&b).unwrap();
+        let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+        iterative.set_max_depth(max_depth);
+        iterative.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), iterative.root_value(), "depth={}\n{}", max_depth, b);
+    }
+}
+
 // The same test, but a deeper tree (without plain negamax) to try to expose
 // more parallelism in the parallel strategies.
 #[test]
@@ -234,25 +262,1549 @@ fn compare_deep_negamax() {
             negamax.choose_move(&b).unwrap();
             let value = negamax.root_value();
 
-            let mut iterative = IterativeSearch::new(RandomEvaluator::default(), opt);
+            let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
             iterative.set_max_depth(max_depth);
             iterative.choose_move(&b).unwrap();
             let iterative_value = iterative.root_value();
             assert_eq!(value, iterative_value, "search depth={}\n{}", max_depth, b);
 
-            let mut mtdf =
-                IterativeSearch::new(RandomEvaluator::default(), opt.clone().with_mtdf());
+            let mut staged =
+                IterativeSearch::new(RandomEvaluator, opt.with_staged_move_generation());
+            staged.set_max_depth(max_depth);
+            staged.choose_move(&b).unwrap();
+            let staged_value = staged.root_value();
+            assert_eq!(value, staged_value, "search depth={}\n{}", max_depth, b);
+
+            let mut mtdf = IterativeSearch::new(RandomEvaluator, opt.with_mtdf());
             mtdf.set_max_depth(max_depth);
             mtdf.choose_move(&b).unwrap();
             let mtdf_value = mtdf.root_value();
             assert_eq!(value, mtdf_value, "search depth={}\n{}", max_depth, b);
 
             let mut parallel =
-                ParallelSearch::new(RandomEvaluator::default(), opt, ParallelOptions::default());
+                ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
             parallel.set_max_depth(max_depth);
             parallel.choose_move(&b).unwrap();
             let parallel_value = parallel.root_value();
             assert_eq!(value, parallel_value, "search iter={} depth={}\n{}", iter, max_depth, b);
+
+            let mut fail_hard = ParallelSearch::new(
+                RandomEvaluator,
+                opt.with_fail_soft(false),
+                ParallelOptions::default(),
+            );
+            fail_hard.set_max_depth(max_depth);
+            fail_hard.choose_move(&b).unwrap();
+            let fail_hard_value = fail_hard.root_value();
+            assert_eq!(value, fail_hard_value, "search iter={} depth={}\n{}", iter, max_depth, b);
+        }
+    }
+}
+
+// Calls the center columns noisy, to split move generation into stages.
+#[derive(Clone, Default)]
+struct CenterNoisyEvaluator;
+
+impl minimax::Evaluator for CenterNoisyEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
+        RandomEvaluator.evaluate(b)
+    }
+
+    fn generate_noisy_moves(&self, b: &connect4::Board, moves: &mut Vec<connect4::Place>) {
+        let mut all = Vec::new();
+        connect4::Game::generate_moves(b, &mut all);
+        moves.extend(all.into_iter().filter(|m| (2..5).contains(&m.col)));
+    }
+}
+
+#[test]
+fn test_staged_move_generation() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000).with_staged_move_generation();
+    for max_depth in 1..7 {
+        let b = generate_random_state(10);
+        let mut negamax = Negamax::new(RandomEvaluator, max_depth);
+        negamax.choose_move(&b).unwrap();
+
+        let mut staged = IterativeSearch::new(CenterNoisyEvaluator, opt.with_countermoves());
+        staged.set_max_depth(max_depth);
+        staged.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), staged.root_value(), "\n{}", b);
+    }
+}
+
+// Bounds the gain of each noisy move that doesn't win.
+#[derive(Clone)]
+struct GainEvaluator(Evaluation);
+
+impl minimax::Evaluator for GainEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
+        RandomEvaluator.evaluate(b)
+    }
+
+    fn generate_noisy_moves(&self, b: &connect4::Board, moves: &mut Vec<connect4::Place>) {
+        CenterNoisyEvaluator.generate_noisy_moves(b, moves);
+    }
+
+    fn noisy_move_gain(&self, b: &connect4::Board, m: connect4::Place) -> Option<Evaluation> {
+        let mut b = b.clone();
+        let after = connect4::Game::apply(&mut b, m).unwrap();
+        connect4::Game::get_winner(&after).is_none().then_some(self.0)
+    }
+}
+
+#[test]
+fn test_delta_pruning() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000).with_quiescence_search_depth(2);
+    for max_depth in 1..5 {
+        let b = generate_random_state(10);
+        let mut plain = IterativeSearch::new(GainEvaluator(0), opt);
+        plain.set_max_depth(max_depth);
+        plain.choose_move(&b).unwrap();
+
+        // Random evaluations keep all but 9 bits of an Evaluation, so they
+        // span less than this, and no move can gain more.
+        let span = 1 << (Evaluation::BITS - 9);
+        let mut pruned = IterativeSearch::new(GainEvaluator(span), opt.with_delta_pruning(0));
+        pruned.set_max_depth(max_depth);
+        pruned.choose_move(&b).unwrap();
+        assert_eq!(plain.root_value(), pruned.root_value(), "\n{}", b);
+
+        let mut parallel = ParallelSearch::new(
+            GainEvaluator(span),
+            opt.with_delta_pruning(0),
+            ParallelOptions::default(),
+        );
+        parallel.set_max_depth(max_depth);
+        parallel.choose_move(&b).unwrap();
+        assert_eq!(plain.root_value(), parallel.root_value(), "\n{}", b);
+
+        // Pruning everything still finds a move.
+        let mut aggressive = IterativeSearch::new(GainEvaluator(-200), opt.with_delta_pruning(0));
+        aggressive.set_max_depth(max_depth);
+        assert!(aggressive.choose_move(&b).is_some());
+    }
+}
+
+#[test]
+fn test_multi_cut() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    for max_depth in 1..7 {
+        let b = generate_random_state(10);
+        let mut plain = IterativeSearch::new(RandomEvaluator, opt);
+        plain.set_max_depth(max_depth);
+        plain.choose_move(&b).unwrap();
+
+        // Needing more cuts than moves searched never prunes.
+        let mut never = IterativeSearch::new(RandomEvaluator, opt.with_multi_cut(2, 3, 2));
+        never.set_max_depth(max_depth);
+        never.choose_move(&b).unwrap();
+        assert_eq!(plain.root_value(), never.root_value(), "\n{}", b);
+
+        let mut parallel = ParallelSearch::new(
+            RandomEvaluator,
+            opt.with_multi_cut(2, 3, 2),
+            ParallelOptions::default(),
+        );
+        parallel.set_max_depth(max_depth);
+        parallel.choose_move(&b).unwrap();
+        assert_eq!(plain.root_value(), parallel.root_value(), "\n{}", b);
+    }
+
+    let b = connect4::Board::default();
+    let search = |opt: IterativeOptions| {
+        let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, opt);
+        iterative.set_rng_seed(17);
+        iterative.set_max_depth(8);
+        let info = iterative.choose_move_with_info(&b);
+        assert!(info.best_move.is_some());
+        info.nodes
+    };
+    let plain_nodes = search(opt);
+    let cut_nodes = search(opt.with_multi_cut(3, 2, 2));
+    assert!(cut_nodes < plain_nodes, "{} >= {}", cut_nodes, plain_nodes);
+}
+
+// Calls every move noisy, or every position unstable.
+#[derive(Clone)]
+struct UnstableEvaluator {
+    noisy: bool,
+}
+
+impl minimax::Evaluator for UnstableEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
+        RandomEvaluator.evaluate(b)
+    }
+
+    fn generate_noisy_moves(&self, b: &connect4::Board, moves: &mut Vec<connect4::Place>) {
+        if self.noisy {
+            connect4::Game::generate_moves(b, moves);
+        }
+    }
+
+    fn is_unstable(&self, _: &connect4::Board) -> bool {
+        !self.noisy
+    }
+}
+
+#[test]
+fn test_unstable_extension() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    for max_depth in 1..5 {
+        let b = generate_random_state(10);
+        // Extending every position searches the same tree as quiescence
+        // search with every move noisy.
+        let mut noisy = IterativeSearch::new(
+            UnstableEvaluator { noisy: true },
+            opt.with_quiescence_search_depth(2),
+        );
+        noisy.set_max_depth(max_depth);
+        noisy.choose_move(&b).unwrap();
+
+        let mut unstable = IterativeSearch::new(
+            UnstableEvaluator { noisy: false },
+            opt.with_unstable_extension(2),
+        );
+        unstable.set_max_depth(max_depth);
+        unstable.choose_move(&b).unwrap();
+        assert_eq!(noisy.root_value(), unstable.root_value(), "\n{}", b);
+
+        let mut parallel = ParallelSearch::new(
+            UnstableEvaluator { noisy: false },
+            opt.with_unstable_extension(2),
+            ParallelOptions::default(),
+        );
+        parallel.set_max_depth(max_depth);
+        parallel.choose_move(&b).unwrap();
+        assert_eq!(noisy.root_value(), parallel.root_value(), "\n{}", b);
+
+        // Without the option, unstable positions are evaluated as usual.
+        let mut plain = IterativeSearch::new(RandomEvaluator, opt);
+        plain.set_max_depth(max_depth);
+        plain.choose_move(&b).unwrap();
+        let mut ignored = IterativeSearch::new(UnstableEvaluator { noisy: false }, opt);
+        ignored.set_max_depth(max_depth);
+        ignored.choose_move(&b).unwrap();
+        assert_eq!(plain.root_value(), ignored.root_value(), "\n{}", b);
+    }
+}
+
+// Counts the batches it's asked to evaluate.
+#[derive(Clone, Default)]
+struct BatchEvaluator {
+    batches: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl minimax::Evaluator for BatchEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
+        RandomEvaluator.evaluate(b)
+    }
+
+    fn evaluate_batch(&self, boards: &[connect4::Board], evals: &mut Vec<Evaluation>) {
+        self.batches.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        evals.extend(boards.iter().map(|b| RandomEvaluator.evaluate(b)));
+    }
+}
+
+#[test]
+fn test_batched_leaves() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000).with_batched_leaves();
+    let eval = BatchEvaluator::default();
+    let par_eval = BatchEvaluator::default();
+    for _ in 0..10 {
+        let b = generate_random_state(10);
+        let mut negamax = Negamax::new(RandomEvaluator, 5);
+        negamax.choose_move(&b).unwrap();
+
+        let mut iterative = IterativeSearch::new(eval.clone(), opt);
+        iterative.set_max_depth(5);
+        iterative.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), iterative.root_value(), "\n{}", b);
+
+        let mut parallel = ParallelSearch::new(par_eval.clone(), opt, ParallelOptions::default());
+        parallel.set_max_depth(5);
+        parallel.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), parallel.root_value(), "\n{}", b);
+    }
+    assert!(eval.batches.load(std::sync::atomic::Ordering::Relaxed) > 0);
+    assert!(par_eval.batches.load(std::sync::atomic::Ordering::Relaxed) > 0);
+}
+
+// Scores positions for red, the first player, from RandomEvaluator.
+#[derive(Clone)]
+struct RedEvaluator;
+
+impl minimax::Evaluator for RedEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, b: &connect4::Board) -> minimax::Evaluation {
+        let value = RandomEvaluator.evaluate(b);
+        if b.reds_move() {
+            value
+        } else {
+            -value
+        }
+    }
+
+    fn is_absolute(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_absolute_evaluator() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    for max_depth in 1..5 {
+        let b = generate_random_state(10);
+        let mut negamax = Negamax::new(RandomEvaluator, max_depth);
+        negamax.choose_move(&b).unwrap();
+
+        let mut absolute = Negamax::new(RedEvaluator, max_depth);
+        absolute.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), absolute.root_value(), "\n{}", b);
+
+        let mut iterative = IterativeSearch::new(RedEvaluator, opt);
+        iterative.set_max_depth(max_depth);
+        iterative.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), iterative.root_value(), "\n{}", b);
+
+        let mut batched = ParallelSearch::new(
+            RedEvaluator,
+            opt.with_batched_leaves(),
+            ParallelOptions::default(),
+        );
+        batched.set_max_depth(max_depth);
+        batched.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), batched.root_value(), "\n{}", b);
+    }
+}
+
+// Favors the first player in nim, which doesn't count plies. With heaps of
+// one, the objects taken so far tell whose turn it is.
+#[derive(Clone)]
+struct FirstPlayerNim;
+
+impl minimax::Evaluator for FirstPlayerNim {
+    type G = nim::Game;
+    fn evaluate(&self, _: &nim::Board) -> minimax::Evaluation {
+        5
+    }
+
+    fn is_absolute(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_absolute_evaluator_counts_moves() {
+    let b = nim::Board::new(&[1; 6]);
+    for max_depth in 1..4 {
+        let mut negamax = Negamax::new(FirstPlayerNim, max_depth);
+        let mut iterative = IterativeSearch::new(FirstPlayerNim, IterativeOptions::new());
+        iterative.set_max_depth(max_depth);
+        negamax.choose_move(&b).unwrap();
+        iterative.choose_move(&b).unwrap();
+        assert_eq!(negamax.root_value(), 5);
+        assert_eq!(iterative.root_value(), 5);
+
+        // After a move, the second player is to move.
+        let m = nim::Take { heap: 0, count: 1 };
+        negamax.notify_move(&b, m);
+        iterative.notify_move(&b, m);
+        let after = nim::Board::new(&[0, 1, 1, 1, 1, 1]);
+        negamax.choose_move(&after).unwrap();
+        iterative.choose_move(&after).unwrap();
+        assert_eq!(negamax.root_value(), -5);
+        assert_eq!(iterative.root_value(), -5);
+    }
+}
+
+// Records the context of every evaluation.
+#[derive(Clone, Default)]
+struct ContextEvaluator {
+    contexts: std::sync::Arc<std::sync::Mutex<Vec<EvalContext>>>,
+}
+
+impl minimax::Evaluator for ContextEvaluator {
+    type G = connect4::Game;
+    fn evaluate(&self, _: &connect4::Board) -> minimax::Evaluation {
+        unreachable!()
+    }
+
+    fn evaluate_in_context(
+        &self, b: &connect4::Board, context: &EvalContext,
+    ) -> minimax::Evaluation {
+        self.contexts.lock().unwrap().push(*context);
+        RandomEvaluator.evaluate(b)
+    }
+}
+
+#[test]
+fn test_eval_context() {
+    let b = generate_random_state(10);
+    let eval = ContextEvaluator::default();
+    let mut negamax = Negamax::new(eval.clone(), 4);
+    negamax.choose_move(&b).unwrap();
+    let contexts = eval.contexts.lock().unwrap().clone();
+    assert!(!contexts.is_empty());
+    assert!(contexts.iter().all(|c| *c == EvalContext { ply: 4, depth: 0 }));
+
+    let eval = ContextEvaluator::default();
+    let mut iterative = IterativeSearch::new(eval.clone(), IterativeOptions::new());
+    iterative.set_max_depth(4);
+    iterative.choose_move(&b).unwrap();
+    let contexts = eval.contexts.lock().unwrap().clone();
+    assert!(contexts.iter().all(|c| c.depth == 0 && (1..=4).contains(&c.ply)));
+    assert!(!contexts.is_empty());
+
+    let eval = ContextEvaluator::default();
+    let mut parallel = ParallelSearch::new(
+        eval.clone(),
+        IterativeOptions::new().with_quiescence_search_depth(2),
+        ParallelOptions::new(),
+    );
+    parallel.set_max_depth(3);
+    parallel.choose_move(&b).unwrap();
+    let contexts = eval.contexts.lock().unwrap().clone();
+    // Connect four has no noisy moves, so quiescence search stops right away.
+    assert!(contexts.iter().all(|c| c.depth == 2 && (1..=3).contains(&c.ply)));
+}
+
+#[test]
+fn test_search_trace() {
+    let b = connect4::Board::default();
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(5);
+    iterative.choose_move(&b).unwrap();
+    assert!(iterative.trace().is_none());
+    let line = iterative.principal_variation()[..2].to_vec();
+
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(5);
+    iterative.set_trace_line(line.clone());
+    iterative.choose_move(&b).unwrap();
+    let trace = iterative.trace().unwrap();
+    assert_eq!(trace.line, line);
+    assert!(trace.events.iter().all(|event| event.ply <= 2));
+    // The root is traced once per iteration.
+    let root = trace.events.iter().filter(|event| event.ply == 0).collect::<Vec<_>>();
+    assert_eq!(root.len(), 5);
+    assert!(matches!(root[4].kind, TraceKind::Searched { best_move, .. } if best_move == line[0]));
+    // The principal variation is searched in full on every iteration.
+    assert!(trace.events.iter().any(|event| event.ply == 2 && event.depth == 3));
+    let text = trace.to_string();
+    assert_eq!(text.lines().count(), trace.events.len());
+    assert!(text.contains("depth="));
+}
+
+// A single line of play, where each move counts down by one, and taking the
+// count to zero wins.
+struct Countdown;
+
+impl Game for Countdown {
+    type S = u16;
+    type M = ();
+
+    fn generate_moves(count: &u16, moves: &mut Vec<()>) {
+        if *count > 0 {
+            moves.push(());
+        }
+    }
+
+    fn get_winner(count: &u16) -> Option<Winner> {
+        (*count == 0).then_some(Winner::PlayerJustMoved)
+    }
+
+    fn apply(count: &mut u16, _: ()) -> Option<u16> {
+        *count -= 1;
+        None
+    }
+
+    fn undo(count: &mut u16, _: ()) {
+        *count += 1;
+    }
+
+    fn zobrist_hash(count: &u16) -> u64 {
+        (*count as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+    }
+}
+
+// Every move is noisy, so quiescence search follows the line to the end.
+#[derive(Clone)]
+struct CountdownEvaluator;
+
+impl minimax::Evaluator for CountdownEvaluator {
+    type G = Countdown;
+    fn evaluate(&self, _: &u16) -> minimax::Evaluation {
+        0
+    }
+
+    fn generate_noisy_moves(&self, count: &u16, moves: &mut Vec<()>) {
+        Countdown::generate_moves(count, moves);
+    }
+}
+
+#[test]
+fn test_deep_plies() {
+    // Quiescence search past the deepest ply doesn't overflow it.
+    let opts = IterativeOptions::new().with_quiescence_search_depth(20).with_singular_extension();
+    let mut iterative = IterativeSearch::new(CountdownEvaluator, opts);
+    iterative.set_max_depth(250);
+    assert_eq!(iterative.choose_move(&265), Some(()));
+    let mut parallel = ParallelSearch::new(CountdownEvaluator, opts, ParallelOptions::new());
+    parallel.set_max_depth(250);
+    assert_eq!(parallel.choose_move(&265), Some(()));
+    let mut negamax = Negamax::new(CountdownEvaluator, 255);
+    assert_eq!(negamax.choose_move(&300), Some(()));
+}
+
+#[test]
+fn test_stop_signal() {
+    let b = connect4::Board::default();
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut strategies: Vec<Box<dyn Strategy<connect4::Game>>> = vec![
+        Box::new(Negamax::new(connect4::BasicEvaluator, 40)),
+        Box::new(IterativeSearch::new(connect4::BasicEvaluator, opt)),
+        Box::new(ParallelSearch::new(connect4::BasicEvaluator, opt, ParallelOptions::default())),
+        Box::new(MonteCarloTreeSearch::new(MCTSOptions::default())),
+    ];
+    for strategy in strategies.iter_mut() {
+        strategy.set_timeout(std::time::Duration::from_secs(3600));
+        let signal = StopSignal::new();
+        strategy.set_stop_signal(signal.clone());
+        let start = std::time::Instant::now();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            signal.stop();
+        });
+        assert!(strategy.choose_move(&b).is_some());
+        assert!(start.elapsed() < std::time::Duration::from_secs(60));
+        stopper.join().unwrap();
+    }
+
+    // The signal is lowered even when there was nothing to search.
+    let signal = StopSignal::new();
+    let mut negamax = Negamax::new(connect4::BasicEvaluator, 0);
+    negamax.set_stop_signal(signal.clone());
+    signal.stop();
+    assert_eq!(negamax.choose_move(&b), None);
+    assert!(!signal.is_stopped());
+}
+
+#[test]
+fn test_negamax_timeout() {
+    let b = connect4::Board::default();
+    let mut negamax = Negamax::new(connect4::BasicEvaluator, 40);
+    negamax.set_timeout(std::time::Duration::from_millis(50));
+    let start = std::time::Instant::now();
+    let info = negamax.choose_move_with_info(&b);
+    assert!(start.elapsed() < std::time::Duration::from_secs(60));
+    // The best move found before time ran out, from an unfinished search.
+    assert!(info.best_move.is_some());
+    assert_eq!(info.depth, 0);
+}
+
+#[test]
+fn test_max_nodes() {
+    let b = connect4::Board::default();
+    let search = |max_nodes: u64| {
+        let opt = IterativeOptions::new().with_table_byte_size(64000);
+        let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, opt);
+        iterative.set_rng_seed(17);
+        iterative.set_max_nodes(max_nodes);
+        let info = iterative.choose_move_with_info(&b);
+        (info.best_move, info.value, info.depth, info.nodes, info.principal_variation)
+    };
+    // The same budget gives the same search, however fast the machine is.
+    let first = search(5000);
+    assert!(first.0.is_some());
+    assert!(first.3 <= 5000);
+    assert!(first.2 < 99);
+    assert_eq!(first, search(5000));
+
+    let mut negamax = Negamax::new(connect4::BasicEvaluator, 40);
+    negamax.set_max_nodes(5000);
+    let info = negamax.choose_move_with_info(&b);
+    assert!(info.best_move.is_some());
+    assert!(info.nodes <= 5000);
+
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut parallel =
+        ParallelSearch::new(connect4::BasicEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_nodes(5000);
+    let start = std::time::Instant::now();
+    assert!(parallel.choose_move(&b).is_some());
+    assert!(start.elapsed() < std::time::Duration::from_secs(60));
+}
+
+#[test]
+fn test_search_info() {
+    let b = generate_random_state(10);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut negamax = Negamax::new(RandomEvaluator, 4);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    let mut parallel = ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_depth(4);
+
+    let infos = [
+        negamax.choose_move_with_info(&b),
+        iterative.choose_move_with_info(&b),
+        parallel.choose_move_with_info(&b),
+    ];
+    for info in infos.iter() {
+        assert!(info.best_move.is_some());
+        // Iterative searches stop deepening once they find a forced result.
+        if info.value.unwrap().abs() < BEST_EVAL {
+            assert_eq!(info.depth, 4);
+        }
+        assert_eq!(info.value, Some(negamax.root_value()));
+        assert!(info.nodes > 0);
+    }
+}
+
+#[test]
+fn test_win_probability() {
+    let b = generate_random_state(10);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    assert_eq!(iterative.choose_move_with_info(&b).win_probability, None);
+
+    let mut strategies: Vec<Box<dyn Strategy<connect4::Game>>> = vec![
+        Box::new(Negamax::new(RandomEvaluator, 4)),
+        Box::new(iterative),
+        Box::new(ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default())),
+    ];
+    for strategy in strategies.iter_mut() {
+        strategy.set_max_depth(4);
+        strategy.set_win_probability_scale(50.0);
+        let info = strategy.choose_move_with_info(&b);
+        let expected = minimax::win_probability(info.value.unwrap(), 50.0);
+        assert_eq!(info.win_probability, Some(expected));
+    }
+    assert_eq!(minimax::win_probability(0, 50.0), 0.5);
+    assert!(minimax::win_probability(BEST_EVAL, 50.0) > 0.999);
+    assert!(minimax::win_probability(WORST_EVAL, 50.0) < 0.001);
+
+    // Monte Carlo searches report their win rate.
+    let mut mcts =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    mcts.set_max_rollouts(200);
+    let chance = mcts.choose_move_with_info(&b).win_probability.unwrap();
+    assert!((0.0..=1.0).contains(&chance));
+}
+
+#[test]
+fn test_search_progress() {
+    let b = generate_random_state(10);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    let mut parallel = ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_depth(4);
+    let mut mcts = MonteCarloTreeSearch::new(MCTSOptions::default().with_num_threads(2));
+    mcts.set_max_rollouts(1000);
+    let strategies: [&mut dyn Strategy<connect4::Game>; 3] =
+        [&mut iterative, &mut parallel, &mut mcts];
+
+    for strategy in strategies {
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        strategy.set_progress(Box::new(move |info: &SearchInfo<_>| {
+            sink.lock().unwrap().push(info.clone());
+        }));
+        assert!(strategy.choose_move(&b).is_some());
+        let reports = reports.lock().unwrap();
+        let last = reports.last().unwrap();
+        assert!(last.best_move.is_some());
+        assert!(!last.principal_variation.is_empty());
+    }
+}
+
+#[test]
+fn test_opening_book() {
+    use connect4::Place;
+    let start = connect4::Board::default();
+    let games =
+        vec![vec![Place { col: 3 }, Place { col: 3 }], vec![Place { col: 3 }, Place { col: 2 }]];
+    let book = OpeningBook::from_games::<connect4::Game>(&start, &games, 2);
+    assert_eq!(book.len(), 2);
+    assert_eq!(book.probe::<connect4::Game>(&start), Some(&[(Place { col: 3 }, 2)][..]));
+
+    let mut strategy = BookStrategy::new(book, Negamax::new(RandomEvaluator, 1));
+    assert_eq!(strategy.choose_move(&start), Some(Place { col: 3 }));
+    // Out of book, falls back to the search.
+    let off_book = connect4::Game::apply(&mut start.clone(), Place { col: 0 }).unwrap();
+    assert!(strategy.choose_move(&off_book).is_some());
+
+    let mut iterative = IterativeSearch::new(RandomEvaluator, IterativeOptions::new());
+    iterative.set_max_depth(2);
+    let book = OpeningBook::from_self_play::<connect4::Game, _>(&start, &mut iterative, 3, 4);
+    assert!(book.probe::<connect4::Game>(&start).is_some());
+}
+
+#[test]
+fn test_book_file() {
+    use connect4::Place;
+    let start = connect4::Board::default();
+    let games = vec![
+        vec![Place { col: 3 }, Place { col: 3 }, Place { col: 4 }],
+        vec![Place { col: 3 }, Place { col: 2 }],
+    ];
+    let mut book = OpeningBook::from_games::<connect4::Game>(&start, &games, 3);
+    let other = OpeningBook::from_games::<connect4::Game>(&start, &[vec![Place { col: 1 }]], 3);
+    book.merge(&other);
+    assert_eq!(
+        book.probe::<connect4::Game>(&start),
+        Some(&[(Place { col: 3 }, 2), (Place { col: 1 }, 1)][..])
+    );
+
+    let mut bytes = Vec::new();
+    book.save(&mut bytes, |m| m.col as u32).unwrap();
+    let decode = |code| Place { col: code as u8 };
+    let file = BookFile::new(bytes.clone(), decode).unwrap();
+    assert_eq!(file.len(), book.len());
+    let mut state = start.clone();
+    for &m in games[0].iter() {
+        assert_eq!(
+            file.probe::<connect4::Game>(&state).as_deref(),
+            book.probe::<connect4::Game>(&state)
+        );
+        if let Some(new_state) = connect4::Game::apply(&mut state, m) {
+            state = new_state;
+        }
+    }
+    assert_eq!(file.probe::<connect4::Game>(&state), None);
+    let reloaded = file.to_book();
+    assert_eq!(reloaded.len(), book.len());
+    assert_eq!(reloaded.probe::<connect4::Game>(&start), book.probe::<connect4::Game>(&start));
+
+    let mut strategy = BookStrategy::new(file, Negamax::new(RandomEvaluator, 1));
+    let m = strategy.choose_move(&start).unwrap();
+    assert!(m == Place { col: 3 } || m == Place { col: 1 });
+
+    assert!(BookFile::new(bytes[..bytes.len() - 1].to_vec(), decode).is_err());
+    assert!(BookFile::new(b"MMTB".to_vec(), decode).is_err());
+    let mut future = bytes.clone();
+    future[4] = 2;
+    assert!(BookFile::new(future, decode).is_err());
+    // The first position's moves run past the end.
+    let mut overrun = bytes.clone();
+    overrun[24 + 12..24 + 16].copy_from_slice(&100u32.to_le_bytes());
+    assert!(BookFile::new(overrun, decode).is_err());
+    // The first two positions are swapped.
+    let mut unsorted = bytes;
+    let first = unsorted[24..24 + 16].to_vec();
+    unsorted.copy_within(40..56, 24);
+    unsorted[40..56].copy_from_slice(&first);
+    assert!(BookFile::new(unsorted, decode).is_err());
+}
+
+#[test]
+fn test_root_split() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let par_opt = ParallelOptions::default().with_num_threads(4).with_root_split();
+    for _ in 0..10 {
+        let b = generate_random_state(10);
+        let search = || {
+            let mut parallel = ParallelSearch::new(RandomEvaluator, opt, par_opt);
+            parallel.set_max_depth(5);
+            (parallel.choose_move(&b), parallel.root_value())
+        };
+        // The first of the best root moves wins, whichever thread finishes first.
+        assert_eq!(search(), search(), "\n{}", b);
+    }
+}
+
+#[test]
+fn test_ponder() {
+    let b = generate_random_state(10);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_timeout(std::time::Duration::from_millis(50));
+    let mut parallel = ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_depth(4);
+    let mut mcts = MonteCarloTreeSearch::new(MCTSOptions::default().with_num_threads(2));
+    mcts.set_max_rollouts(1000);
+    let strategies: [&mut dyn Ponder<connect4::Game>; 3] =
+        [&mut iterative, &mut parallel, &mut mcts];
+
+    for strategy in strategies {
+        let mut moves = Vec::new();
+        connect4::Game::generate_moves(&b, &mut moves);
+        // Predict a move that doesn't end the game, so there's a reply.
+        let (predicted, after) = moves
+            .iter()
+            .map(|&m| (m, connect4::Game::apply(&mut b.clone(), m).unwrap()))
+            .find(|(_, after)| connect4::Game::get_winner(after).is_none())
+            .unwrap();
+        let mut after_moves = Vec::new();
+        connect4::Game::generate_moves(&after, &mut after_moves);
+
+        // Hit.
+        strategy.ponder(&b, predicted);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        strategy.ponder_hit();
+        let m = strategy.choose_move(&after).unwrap();
+        assert!(after_moves.contains(&m));
+
+        // Miss.
+        strategy.ponder(&b, predicted);
+        strategy.stop_ponder();
+        let m = strategy.choose_move(&b).unwrap();
+        assert!(moves.contains(&m));
+    }
+}
+
+#[test]
+fn test_anytime() {
+    let b = generate_random_state(10);
+    let mut moves = Vec::new();
+    connect4::Game::generate_moves(&b, &mut moves);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    let mut mcts = MonteCarloTreeSearch::new(MCTSOptions::default().with_num_threads(2));
+    let strategies: [&mut dyn AnytimeStrategy<connect4::Game>; 2] = [&mut iterative, &mut mcts];
+
+    for strategy in strategies {
+        strategy.start(&b);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while strategy.best_so_far().is_none() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(moves.contains(&strategy.best_so_far().unwrap()));
+        let m = strategy.stop().unwrap();
+        assert!(moves.contains(&m));
+        assert_eq!(None, strategy.best_so_far());
+
+        // Still usable as a normal strategy afterwards.
+        strategy.set_max_depth(2);
+        strategy.set_timeout(std::time::Duration::from_millis(20));
+        assert!(moves.contains(&strategy.choose_move(&b).unwrap()));
+    }
+}
+
+#[test]
+fn test_ensemble() {
+    let b = generate_random_state(10);
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    let mut expected = Negamax::new(RandomEvaluator, 3);
+    let expected_value = {
+        expected.choose_move(&b);
+        expected.root_value()
+    };
+
+    for voting in [Voting::Majority, Voting::ScoreWeighted { scale: 100.0 }] {
+        let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+        iterative.set_max_depth(3);
+        let mut ensemble = Ensemble::new(voting)
+            .with_strategy(Box::new(Negamax::new(RandomEvaluator, 3)), 0.5)
+            .with_strategy(Box::new(iterative), 0.5);
+        let info = ensemble.choose_move_with_info(&b);
+        assert!(info.best_move.is_some());
+        // Both members agree on the value, whichever move wins the vote.
+        assert_eq!(info.value, Some(expected_value));
+        assert!(info.nodes > 0);
+    }
+}
+
+#[test]
+fn test_fallback() {
+    let b = generate_random_state(10);
+    let mut expected = Negamax::new(RandomEvaluator, 3);
+    expected.choose_move(&b);
+
+    // A person who has walked away passes to the search.
+    let human = HumanStrategy::with_io(Box::new(std::io::empty()), Box::new(std::io::sink()));
+    let mut fallback = Fallback::new()
+        .with_strategy(Box::new(human))
+        .with_strategy(Box::new(Negamax::new(RandomEvaluator, 3)))
+        .with_strategy(Box::new(Random::new()));
+    let info = fallback.choose_move_with_info(&b);
+    assert_eq!(fallback.chosen(), Some(1));
+    assert_eq!(info.value, Some(expected.root_value()));
+    assert_eq!(fallback.principal_variation(), info.principal_variation);
+
+    // Nobody can move after the game is over.
+    let mut b = connect4::Board::default();
+    for m in [0, 1, 0, 1, 0, 1, 0] {
+        b = connect4::Game::apply(&mut b, connect4::Place { col: m }).unwrap();
+    }
+    assert_eq!(fallback.choose_move(&b), None);
+    assert_eq!(fallback.chosen(), None);
+}
+
+#[test]
+fn test_rng_seed() {
+    fn play(mut strategy: Box<dyn Strategy<connect4::Game>>) -> Vec<connect4::Place> {
+        strategy.set_rng_seed(17);
+        let mut b = connect4::Board::default();
+        let mut moves = Vec::new();
+        for _ in 0..6 {
+            let m = strategy.choose_move(&b).unwrap();
+            moves.push(m);
+            b = connect4::Game::apply(&mut b, m).unwrap();
+        }
+        moves
+    }
+
+    // Fresh strategies with the same seed play the same moves.
+    let strategies = || -> [Box<dyn Strategy<connect4::Game>>; 4] {
+        let opt = IterativeOptions::new().with_table_byte_size(64000);
+        let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, opt);
+        iterative.set_max_depth(3);
+        let mut mcts = MonteCarloTreeSearch::new(MCTSOptions::default().with_num_threads(1));
+        mcts.set_max_rollouts(200);
+        [
+            Box::new(Random::new()),
+            Box::new(Negamax::new(connect4::BasicEvaluator, 2)),
+            Box::new(iterative),
+            Box::new(mcts),
+        ]
+    };
+    for (s1, s2) in strategies().into_iter().zip(strategies()) {
+        assert_eq!(play(s1), play(s2));
+    }
+}
+
+#[test]
+fn test_snapshots() {
+    use std::time::Duration;
+
+    let b = connect4::Board::default();
+    let output = SharedOutput::default();
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_max_depth(4);
+    iterative.set_snapshots(Box::new(output.clone()), Duration::ZERO);
+    iterative.choose_move(&b);
+    // One after each depth, and one at the end.
+    let snapshots = output.text();
+    assert_eq!(snapshots.matches("snapshot ").count(), 5);
+    assert!(snapshots.contains("depth 4 nodes "));
+    assert_eq!(snapshots.matches("pv: ").count(), 5);
+
+    let output = SharedOutput::default();
+    let mut mcts =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    mcts.set_max_rollouts(500);
+    mcts.set_snapshots(Box::new(output.clone()), Duration::from_secs(3600));
+    mcts.choose_move(&b);
+    let snapshots = output.text();
+    assert_eq!(snapshots.matches("snapshot ").count(), 1);
+    assert!(snapshots.contains("simulations 500\n"));
+    assert_eq!(snapshots.matches(" visits ").count(), 7);
+}
+
+#[test]
+fn test_search_log() {
+    let b = connect4::Board::default();
+    let output = SharedOutput::default();
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_max_depth(4);
+    iterative.set_search_log(Box::new(output.clone()));
+    let m = iterative.choose_move(&b).unwrap();
+
+    let text = output.text();
+    let events = text
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    let count = |event: &str| events.iter().filter(|e| e["event"] == event).count();
+    assert_eq!(events[0]["event"], "start");
+    assert_eq!(events[0]["max_depth"], 4);
+    assert!(events[0]["max_time"].is_null());
+    assert_eq!(count("depth"), 4);
+    assert!(count("best_move") >= 1);
+    assert!(count("pv") >= 1);
+    let depth = events.iter().rfind(|e| e["event"] == "depth").unwrap();
+    assert_eq!(depth["depth"], 4);
+    assert!(depth["nodes"].as_f64().unwrap() > 0.0);
+    assert!(depth["table_hit_rate"].is_number());
+    let finish = events.last().unwrap();
+    assert_eq!(finish["event"], "finish");
+    assert_eq!(finish["move"], util::move_notation::<connect4::Game>(&b, m));
+}
+
+#[test]
+fn test_memory_usage() {
+    let b = connect4::Board::default();
+    let opt = IterativeOptions::new().with_table_byte_size(1 << 16);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    let usage = iterative.memory_usage();
+    assert_eq!(usage.bytes, 1 << 16);
+    assert_eq!(usage.table_entries, 0);
+    iterative.choose_move(&b);
+    let usage = iterative.memory_usage();
+    assert!(usage.table_entries > 0 && usage.table_entries <= usage.table_capacity);
+    let table_capacity = usage.table_capacity;
+
+    let mut parallel = ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_depth(4);
+    parallel.choose_move(&b);
+    let usage = parallel.memory_usage();
+    assert_eq!(usage.bytes, 1 << 16);
+    assert!(usage.table_entries > 0 && usage.table_entries <= usage.table_capacity);
+
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_rollouts_before_expanding(0),
+    );
+    mcts.set_max_rollouts(100);
+    assert_eq!(mcts.memory_usage(), MemoryUsage::default());
+    mcts.choose_move(&b);
+    let usage = mcts.memory_usage();
+    assert!(usage.tree_nodes > 100);
+    assert!(usage.bytes >= usage.tree_nodes * 64);
+
+    let ensemble = Ensemble::new(Voting::Majority)
+        .with_strategy(Box::new(iterative), 0.5)
+        .with_strategy(Box::new(mcts), 0.5);
+    assert_eq!(ensemble.memory_usage().tree_nodes, usage.tree_nodes);
+    assert_eq!(ensemble.memory_usage().table_capacity, table_capacity);
+}
+
+#[test]
+fn test_new_game() {
+    // Starting a new game forgets the tables and trees of the last one, but
+    // keeps the configuration.
+    let b = connect4::Board::default();
+    let opt = IterativeOptions::new().with_table_byte_size(1 << 16);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    iterative.choose_move(&b);
+    iterative.new_game();
+    let usage = iterative.memory_usage();
+    assert_eq!((usage.bytes, usage.table_entries), (1 << 16, 0));
+    iterative.choose_move(&b);
+    assert_eq!(iterative.principal_variation().len(), 4);
+
+    let mut parallel = ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default());
+    parallel.set_max_depth(4);
+    parallel.choose_move(&b);
+    parallel.new_game();
+    let usage = parallel.memory_usage();
+    assert_eq!((usage.bytes, usage.table_entries), (1 << 16, 0));
+
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_rollouts_before_expanding(0),
+    );
+    mcts.set_max_rollouts(100);
+    mcts.choose_move(&b);
+    mcts.new_game();
+    assert_eq!(mcts.memory_usage(), MemoryUsage::default());
+    assert!(mcts.principal_variation().is_empty());
+}
+
+#[test]
+fn test_fresh_copies() {
+    use minimax::util::ValidatingStrategy;
+    use std::sync::Arc;
+
+    // Copies keep the settings and shared resources, but not the tables.
+    let b = connect4::Board::default();
+    let opt = IterativeOptions::new().with_table_byte_size(1 << 16);
+    let mut iterative = IterativeSearch::new(RandomEvaluator, opt);
+    iterative.set_max_depth(4);
+    iterative.choose_move(&b);
+    let mut copy = iterative.fresh_copy();
+    let usage = copy.memory_usage();
+    assert_eq!((usage.bytes, usage.table_entries), (1 << 16, 0));
+    copy.choose_move(&b);
+    assert_eq!(copy.principal_variation().len(), 4);
+
+    let mut moves = Vec::new();
+    connect4::Game::generate_moves(&b, &mut moves);
+    let mut book = OpeningBook::new();
+    book.add::<connect4::Game>(&b, moves[3], 1);
+    let book = Arc::new(book);
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_rollouts_before_expanding(0),
+    );
+    mcts.set_max_rollouts(100);
+    let mut parallel =
+        ParallelSearch::new(RandomEvaluator, opt, ParallelOptions::default().with_num_threads(1));
+    parallel.set_max_depth(2);
+    let strategies: Vec<Box<dyn ClonableStrategy<connect4::Game>>> = vec![
+        Box::new(Negamax::new(RandomEvaluator, 2)),
+        Box::new(iterative),
+        Box::new(parallel),
+        Box::new(FlowStrategy::new(mcts, FlowOptions::new())),
+        Box::new(BookStrategy::new(book.clone(), Random::new())),
+        Box::new(ValidatingStrategy::new(Random::new())),
+    ];
+    // Each strategy plays against a copy of itself on its own thread.
+    let b = &b;
+    let records = std::thread::scope(|scope| {
+        let games = strategies
+            .iter()
+            .map(|strategy| {
+                let mut first = strategy.fresh_copy();
+                let mut second = strategy.fresh_copy();
+                scope.spawn(move || {
+                    Match::untimed().with_max_moves(8).play(b, &mut first, &mut second)
+                })
+            })
+            .collect::<Vec<_>>();
+        games.into_iter().map(|game| game.join().unwrap()).collect::<Vec<_>>()
+    });
+    for record in records.iter() {
+        // Random players may win before the move limit.
+        assert!(record.moves.len() == 8 || record.result.is_some());
+        assert!(record.moves.len() <= 8);
+    }
+    assert!(records[4].moves[0].m == moves[3]);
+    // The copies all shared the one book.
+    assert_eq!(Arc::strong_count(&book), 2);
+}
+
+#[test]
+fn test_tree_gc() {
+    use minimax::strategies::mcts::{EvictDeepest, EvictLeastVisited, EvictOldest, GcPolicy};
+    use std::sync::Arc;
+
+    let b = connect4::Board::default();
+    let policies: [Arc<dyn GcPolicy>; 3] =
+        [Arc::new(EvictLeastVisited), Arc::new(EvictOldest), Arc::new(EvictDeepest)];
+    for policy in policies {
+        let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+            MCTSOptions::default()
+                .with_num_threads(1)
+                .with_max_tree_nodes(100)
+                .with_gc_policy(policy),
+        );
+        mcts.set_max_rollouts(1000);
+        let info = mcts.choose_move_with_info(&b);
+        assert!(info.best_move.is_some());
+        assert_eq!(info.nodes, 1000);
+        let nodes = mcts.memory_usage().tree_nodes;
+        // The last simulation can overshoot the limit by a node or two.
+        assert!(nodes > 0 && nodes <= 102, "{nodes} nodes");
+    }
+
+    // Too small a limit still lets the search make progress.
+    for limit in [0, 1] {
+        let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+            MCTSOptions::default().with_num_threads(1).with_max_tree_nodes(limit),
+        );
+        mcts.set_max_rollouts(100);
+        assert!(mcts.choose_move(&b).is_some());
+    }
+}
+
+#[test]
+fn test_tree_policies() {
+    use minimax::strategies::mcts::{Puct, TreePolicy, Ucb1Tuned, Uct};
+    use std::sync::Arc;
+
+    let b = connect4::Board::default();
+    let policies: [Arc<dyn TreePolicy>; 3] =
+        [Arc::new(Uct::default()), Arc::new(Puct::default()), Arc::new(Ucb1Tuned::default())];
+    for policy in policies {
+        let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+            MCTSOptions::default().with_num_threads(1).with_tree_policy(policy).with_tree_shape(),
+        );
+        mcts.set_max_rollouts(1000);
+        let info = mcts.choose_move_with_info(&b);
+        assert!(info.best_move.is_some());
+        assert_eq!(info.nodes, 1000);
+        let shape = mcts.tree_shape().unwrap();
+        assert_eq!(shape.nodes_per_depth.iter().sum::<usize>(), mcts.memory_usage().tree_nodes);
+        assert!(shape.max_depth() >= info.principal_variation.len());
+    }
+}
+
+#[test]
+fn test_exploration_schedule() {
+    use minimax::strategies::mcts::ExplorationSchedule;
+
+    // The most visits any root move gets on this schedule.
+    let most_visits = |schedule| {
+        let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+            MCTSOptions::default().with_num_threads(1).with_exploration_schedule(schedule),
+        );
+        mcts.set_rng_seed(1);
+        mcts.set_max_rollouts(1000);
+        let info = mcts.choose_move_with_info(&connect4::Board::default());
+        assert_eq!(info.nodes, 1000);
+        mcts.root_stats().iter().map(|stats| stats.visits).max().unwrap()
+    };
+    // A huge constant spreads the visits about evenly over the seven moves.
+    let wide = most_visits(ExplorationSchedule { start: 10.0, end: 10.0, horizon: 200 });
+    assert!(wide < 200, "{wide}");
+    // Annealed to no exploration, the later visits follow the best move.
+    let annealed = most_visits(ExplorationSchedule { start: 10.0, end: 0.0, horizon: 200 });
+    assert!(annealed > 2 * wide, "{annealed} vs {wide}");
+}
+
+#[test]
+fn test_progressive_unpruning() {
+    use minimax::strategies::mcts::ProgressiveUnpruning;
+
+    // Never admits a second move from the root.
+    let unpruning = ProgressiveUnpruning { initial: 1, visits: 1_000_000, growth: 2.0 };
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_progressive_unpruning(unpruning),
+    );
+    mcts.set_max_rollouts(100);
+    mcts.choose_move(&connect4::Board::default());
+    let visited = mcts.root_stats().iter().filter(|stats| stats.visits > 0).count();
+    assert_eq!(visited, 1);
+}
+
+#[test]
+fn test_rollouts_per_leaf() {
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_rollouts_per_leaf(4),
+    );
+    mcts.set_max_rollouts(100);
+    let info = mcts.choose_move_with_info(&connect4::Board::default());
+    assert!(info.best_move.is_some());
+    // Each simulation backs up four rollouts, as none end the game so soon,
+    // and the budget counts rollouts.
+    assert_eq!(info.nodes, 100);
+    assert_eq!(mcts.root_stats().iter().map(|stats| stats.visits).sum::<u32>(), 100);
+    // Reaching a quarter as many leaves grows a smaller tree.
+    let mut single =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    single.set_max_rollouts(100);
+    assert_eq!(single.choose_move_with_info(&connect4::Board::default()).nodes, 100);
+    assert!(mcts.memory_usage().tree_nodes < single.memory_usage().tree_nodes);
+}
+
+#[test]
+fn test_rollout_cycle_detection() {
+    use minimax::strategies::mcts::RolloutPolicy;
+    use rand::rngs::StdRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Counts around a cycle of three positions forever.
+    struct Cycle;
+    impl Game for Cycle {
+        type S = u8;
+        type M = u8;
+        fn generate_moves(_: &u8, moves: &mut Vec<u8>) {
+            moves.push(1);
+        }
+        fn apply(s: &mut u8, m: u8) -> Option<u8> {
+            Some((*s + m) % 3)
+        }
+        fn get_winner(_: &u8) -> Option<Winner> {
+            None
+        }
+        fn zobrist_hash(s: &u8) -> u64 {
+            *s as u64
+        }
+    }
+
+    struct Counting(Arc<AtomicUsize>);
+    impl RolloutPolicy for Counting {
+        type G = Cycle;
+        fn random_move(&self, _: &mut u8, _: &mut Vec<u8>, _: &mut StdRng) -> u8 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            1
+        }
+    }
+
+    let rollout_moves = |options: MCTSOptions| {
+        let moves = Arc::new(AtomicUsize::new(0));
+        let mut mcts = MonteCarloTreeSearch::new_with_policy(
+            options.with_num_threads(1),
+            Box::new(Counting(moves.clone())),
+        );
+        mcts.set_max_rollouts(10);
+        assert_eq!(mcts.choose_move(&0), Some(1));
+        moves.load(Ordering::Relaxed)
+    };
+    assert_eq!(rollout_moves(MCTSOptions::default()), 1000);
+    // Each rollout stops at the first repetition.
+    assert_eq!(rollout_moves(MCTSOptions::default().with_rollout_cycle_detection()), 30);
+}
+
+#[test]
+fn test_warm_start() {
+    let b = connect4::Board::default();
+    let mut mcts =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    mcts.set_max_rollouts(1000);
+    mcts.choose_move(&b);
+    let stats = mcts.root_stats().to_vec();
+    assert_eq!(stats.len(), 7);
+    assert_eq!(stats.iter().map(|stats| stats.visits).sum::<u32>(), 1000);
+
+    // A new search starts from half of those visits.
+    let mut warm =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    warm.warm_start(&stats, 0.5);
+    warm.set_max_rollouts(100);
+    let info = warm.choose_move_with_info(&b);
+    assert_eq!(info.nodes, 100);
+    for (before, after) in stats.iter().zip(warm.root_stats()) {
+        assert_eq!(before.m, after.m);
+        assert!(after.visits >= (before.visits as f32 * 0.5).round() as u32);
+    }
+
+    // Only the next search is warm-started.
+    warm.choose_move(&b);
+    assert_eq!(warm.root_stats().iter().map(|stats| stats.visits).sum::<u32>(), 100);
+
+    // A new game forgets a warm start that wasn't used.
+    warm.warm_start(&stats, 0.5);
+    warm.new_game();
+    assert!(warm.root_stats().is_empty());
+    warm.choose_move(&b);
+    assert_eq!(warm.root_stats().iter().map(|stats| stats.visits).sum::<u32>(), 100);
+}
+
+#[test]
+fn test_forced_playouts() {
+    let b = connect4::Board::default();
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_forced_playouts(2.0),
+    );
+    mcts.set_max_rollouts(1000);
+    mcts.choose_move(&b);
+    // Every move gets about sqrt(2 / 7 * 1000) visits.
+    assert!(mcts.root_stats().iter().all(|stats| stats.visits >= 15));
+    let target = mcts.policy_target();
+    assert_eq!(target.len(), 7);
+    assert!((target.iter().map(|&(_, p)| p).sum::<f32>() - 1.0).abs() < 1e-5);
+    // The most visited move only gains from the pruning.
+    let best = mcts.root_stats().iter().max_by_key(|stats| stats.visits).unwrap();
+    let (_, p) = target.iter().find(|&&(m, _)| m == best.m).unwrap();
+    assert!(*p >= best.visits as f32 / 1000.0);
+}
+
+#[test]
+fn test_max_expansion_depth() {
+    let b = connect4::Board::default();
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_num_threads(1).with_max_expansion_depth(1).with_tree_shape(),
+    );
+    mcts.set_max_rollouts(1000);
+    let info = mcts.choose_move_with_info(&b);
+    assert!(info.best_move.is_some());
+    assert_eq!(info.nodes, 1000);
+    // Only the root and its children.
+    assert_eq!(mcts.memory_usage().tree_nodes, 8);
+    assert_eq!(info.principal_variation.len(), 1);
+    let shape = mcts.tree_shape().unwrap();
+    assert_eq!(shape.nodes_per_depth, vec![1, 7]);
+    assert_eq!(shape.max_depth(), 1);
+    assert_eq!(shape.mean_branching, 7.0);
+}
+
+#[test]
+fn test_decided_rollouts() {
+    use minimax::strategies::mcts::RolloutPolicy;
+    use rand::rngs::StdRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Calls every rollout a draw before it starts.
+    struct Drawn(Arc<AtomicUsize>);
+    impl RolloutPolicy for Drawn {
+        type G = connect4::Game;
+        fn random_move(
+            &self, state: &mut connect4::Board, moves: &mut Vec<connect4::Place>, rng: &mut StdRng,
+        ) -> connect4::Place {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            connect4::Game::generate_moves(state, moves);
+            *moves.choose(rng).unwrap()
+        }
+
+        fn decided(&self, _: &connect4::Board) -> Option<Winner> {
+            Some(Winner::Draw)
+        }
+    }
+
+    let moves = Arc::new(AtomicUsize::new(0));
+    let mut mcts = MonteCarloTreeSearch::new_with_policy(
+        MCTSOptions::default().with_num_threads(1),
+        Box::new(Drawn(moves.clone())),
+    );
+    mcts.set_max_rollouts(100);
+    assert!(mcts.choose_move(&connect4::Board::default()).is_some());
+    assert_eq!(moves.load(Ordering::Relaxed), 0);
+    assert!(mcts.root_stats().iter().all(|stats| stats.score == 0));
+}
+
+#[test]
+fn test_compare_moves() {
+    use connect4::Place;
+    use minimax::strategies::mcts::MoveStats;
+
+    // Red can win in the first column, or lose by letting yellow win in the
+    // second.
+    let mut b = connect4::Board::default();
+    for col in [0, 1, 0, 1, 0, 1] {
+        b = connect4::Game::apply(&mut b, Place { col }).unwrap();
+    }
+    let mut mcts =
+        MonteCarloTreeSearch::<connect4::Game>::new(MCTSOptions::default().with_num_threads(1));
+    mcts.set_max_rollouts(1000);
+    let results = mcts.compare_moves(&b, &[Place { col: 0 }, Place { col: 6 }]);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].m, Place { col: 0 });
+    assert_eq!(results[0].win_ratio, 1.0);
+    assert_eq!(results[0].simulations, 0);
+    assert_eq!(results[1].m, Place { col: 6 });
+    // Proven lost once yellow's win is found, which ends the search.
+    assert_eq!(results[1].win_ratio, 0.0);
+    assert!(results[1].simulations > 0 && results[1].simulations <= 500);
+    assert_eq!(&results[1].principal_variation[..2], &[Place { col: 6 }, Place { col: 1 }]);
+
+    // A warm start seeds every candidate's search, not just the first.
+    let b = connect4::Board::default();
+    let stats =
+        (0..7).map(|col| MoveStats { m: Place { col }, visits: 100, score: 0 }).collect::<Vec<_>>();
+    mcts.warm_start(&stats, 1.0);
+    mcts.set_max_rollouts(200);
+    let results = mcts.compare_moves(&b, &[Place { col: 0 }, Place { col: 6 }]);
+    assert!(results.iter().all(|result| result.simulations == 100));
+    assert_eq!(mcts.root_stats().iter().map(|stats| stats.visits).sum::<u32>(), 800);
+    mcts.choose_move(&b);
+    assert_eq!(mcts.root_stats().iter().map(|stats| stats.visits).sum::<u32>(), 200);
+}
+
+#[test]
+fn test_stepped_search() {
+    let b = connect4::Board::default();
+
+    let mut mcts = MonteCarloTreeSearch::<connect4::Game>::new(
+        MCTSOptions::default().with_max_tree_nodes(100),
+    );
+    mcts.set_max_rollouts(1000);
+    mcts.start_stepping(&b);
+    let mut steps = 1;
+    while !mcts.step(100) {
+        steps += 1;
+    }
+    assert_eq!(steps, 10);
+    assert!(mcts.step(100));
+    assert!(mcts.finish_stepping().is_some());
+    assert!(mcts.memory_usage().tree_nodes <= 100);
+    assert_eq!(mcts.finish_stepping(), None);
+    assert!(mcts.step(100));
+
+    // One step per depth, ending with the same result as choose_move.
+    let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    iterative.set_max_depth(4);
+    iterative.start_stepping(&b);
+    let mut steps = 1;
+    while !iterative.step(1) {
+        steps += 1;
+    }
+    assert_eq!(steps, 4);
+    let m = iterative.finish_stepping();
+    let value = iterative.root_value();
+    let mut other = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+    other.set_max_depth(4);
+    let info = other.choose_move_with_info(&b);
+    assert!(m.is_some());
+    assert_eq!(info.value, Some(value));
+}
+
+// Take 1, 2, or 3 stones from a pile, and whoever takes the last stone wins,
+// but taking 3 stones earns another turn.
+struct ExtraTurns;
+
+#[derive(Clone, Debug)]
+struct Pile {
+    stones: u8,
+    player: u8,
+    // Whether the last move earned another turn.
+    extra: bool,
+}
+
+impl Game for ExtraTurns {
+    type S = Pile;
+    type M = u8;
+
+    fn generate_moves(pile: &Pile, moves: &mut Vec<u8>) {
+        moves.extend((1..=3).filter(|&take| take <= pile.stones));
+    }
+
+    fn get_winner(pile: &Pile) -> Option<Winner> {
+        // Winners are relative to the player to move, who may have taken
+        // the last stone themselves.
+        (pile.stones == 0).then_some(if pile.extra {
+            Winner::PlayerToMove
+        } else {
+            Winner::PlayerJustMoved
+        })
+    }
+
+    fn apply(pile: &mut Pile, take: u8) -> Option<Pile> {
+        let extra = take == 3;
+        let player = if extra { pile.player } else { 1 - pile.player };
+        Some(Pile { stones: pile.stones - take, player, extra })
+    }
+
+    fn zobrist_hash(pile: &Pile) -> u64 {
+        let key = pile.stones as u64 | (pile.player as u64) << 8 | (pile.extra as u64) << 9;
+        // Spread the keys over the table, away from the empty hash of 0.
+        (key + 1).wrapping_mul(0x9e3779b97f4a7c15)
+    }
+
+    fn player_to_move(pile: &Pile) -> Option<u8> {
+        Some(pile.player)
+    }
+}
+
+#[derive(Clone)]
+struct ExtraTurnsEvaluator;
+
+impl Evaluator for ExtraTurnsEvaluator {
+    type G = ExtraTurns;
+    fn evaluate(&self, _: &Pile) -> Evaluation {
+        0
+    }
+}
+
+// Whether taking `take` of `stones` wins for the player taking them.
+fn extra_turns_wins(stones: u8, take: u8) -> bool {
+    let left = stones - take;
+    let to_move_wins = (1..=3.min(left)).any(|next| extra_turns_wins(left, next));
+    left == 0 || if take == 3 { to_move_wins } else { !to_move_wins }
+}
+
+#[test]
+fn test_extra_turns() {
+    let opt = IterativeOptions::new().with_table_byte_size(64000);
+    for stones in 1..=12 {
+        let pile = Pile { stones, player: 0, extra: false };
+        let wins = (1..=3.min(stones)).any(|take| extra_turns_wins(stones, take));
+
+        let mut negamax = Negamax::new(ExtraTurnsEvaluator, stones);
+        let m = negamax.choose_move(&pile).unwrap();
+        assert_eq!(negamax.root_value() > 0, wins, "{} stones", stones);
+        assert_eq!(extra_turns_wins(stones, m), wins, "{} stones", stones);
+
+        let mut iterative = IterativeSearch::new(ExtraTurnsEvaluator, opt);
+        iterative.set_max_depth(stones);
+        let m = iterative.choose_move(&pile).unwrap();
+        assert_eq!(iterative.root_value(), negamax.root_value(), "{} stones", stones);
+        assert_eq!(extra_turns_wins(stones, m), wins, "{} stones", stones);
+
+        let mut parallel = ParallelSearch::new(ExtraTurnsEvaluator, opt, ParallelOptions::new());
+        parallel.set_max_depth(stones);
+        let m = parallel.choose_move(&pile).unwrap();
+        assert_eq!(parallel.root_value(), negamax.root_value(), "{} stones", stones);
+        assert_eq!(extra_turns_wins(stones, m), wins, "{} stones", stones);
+
+        if wins {
+            let mut mcts =
+                MonteCarloTreeSearch::<ExtraTurns>::new(MCTSOptions::default().with_num_threads(1));
+            mcts.set_rng_seed(1);
+            mcts.set_max_rollouts(2000);
+            let m = mcts.choose_move(&pile).unwrap();
+            assert!(extra_turns_wins(stones, m), "{} stones", stones);
         }
     }
 }
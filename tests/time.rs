@@ -0,0 +1,36 @@
+// In its own test binary, as the time source is global.
+
+extern crate minimax;
+
+use minimax::games::connect4;
+use minimax::{set_time_source, Match, Negamax, Strategy, TimeControl};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[test]
+fn test_time_source() {
+    // Each reading of this clock is 10ms after the last.
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+    set_time_source(|| Duration::from_millis(10 * TICKS.fetch_add(1, Ordering::Relaxed)));
+
+    // Far too deep to finish, but the deadline passes after a hundred
+    // readings of the clock.
+    let mut negamax = Negamax::new(connect4::BasicEvaluator, 20);
+    negamax.set_timeout(Duration::from_secs(1));
+    let before = TICKS.load(Ordering::Relaxed);
+    let info = negamax.choose_move_with_info(&connect4::Board::default());
+    assert!(info.best_move.is_some());
+    assert_eq!(info.depth, 0);
+    assert!(info.elapsed >= Duration::from_secs(1));
+    assert!(TICKS.load(Ordering::Relaxed) - before < 200);
+
+    // Match clocks run on the same time.
+    let mut first = Negamax::new(connect4::BasicEvaluator, 1);
+    let mut second = Negamax::new(connect4::BasicEvaluator, 1);
+    let game = Match::new(TimeControl::new(Duration::from_secs(60)));
+    let record = game.play(&connect4::Board::default(), &mut first, &mut second);
+    for recorded in record.moves.iter() {
+        assert_eq!(recorded.elapsed.as_millis() % 10, 0);
+        assert!(recorded.elapsed > Duration::ZERO);
+    }
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "tracing")]
+
+extern crate minimax;
+extern crate tracing;
+
+use minimax::games::connect4;
+use minimax::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// Minimal subscriber that collects the messages of events.
+#[derive(Default)]
+struct Collector {
+    messages: Mutex<Vec<String>>,
+    next_span: AtomicU64,
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            *self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = String::new();
+        event.record(&mut MessageVisitor(&mut message));
+        self.messages.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+fn test_tracing_events() {
+    let collector = Arc::new(Collector::default());
+    tracing::subscriber::with_default(collector.clone(), || {
+        let mut iterative = IterativeSearch::new(connect4::BasicEvaluator, IterativeOptions::new());
+        iterative.set_max_depth(4);
+        iterative.choose_move(&connect4::Board::default());
+    });
+    let messages = collector.messages.lock().unwrap();
+    assert_eq!(messages.iter().filter(|m| *m == "completed depth").count(), 4);
+    assert_eq!(messages.last().map(String::as_str), Some("finished search"));
+}
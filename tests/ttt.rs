@@ -1,16 +1,22 @@
 extern crate minimax;
+mod common;
 
-#[path = "../examples/ttt.rs"]
-mod ttt;
+use common::SharedOutput;
+use minimax::games::ttt;
 
 use minimax::util::battle_royale;
-use minimax::{MCTSOptions, MonteCarloTreeSearch, Negamax, Random};
+use minimax::{
+    BookStrategy, EloEstimate, Ensemble, Fallback, Game, GameRecord, IterativeOptions,
+    IterativeSearch, MCTSOptions, MonteCarloTreeSearch, Negamax, OpeningBook, Outcome,
+    ParallelOptions, ParallelSearch, Random, Strategy, Tournament, TournamentResults, Voting,
+};
+use std::sync::Arc;
 
 // Ensure that two players using negamax always results in a draw.
 #[test]
 fn test_ttt_negamax_always_draws() {
-    let mut s1 = Negamax::new(ttt::Evaluator::default(), 10);
-    let mut s2 = Negamax::new(ttt::Evaluator::default(), 10);
+    let mut s1 = Negamax::new(ttt::Evaluator, 10);
+    let mut s2 = Negamax::new(ttt::Evaluator, 10);
     for _ in 0..100 {
         assert_eq!(battle_royale(&mut s1, &mut s2), None);
     }
@@ -20,19 +26,1054 @@ fn test_ttt_negamax_always_draws() {
 // either a draw or a win for the former player.
 #[test]
 fn test_ttt_negamax_vs_random_always_wins_or_draws() {
-    let mut s1 = Negamax::new(ttt::Evaluator::default(), 10);
+    let mut s1 = Negamax::new(ttt::Evaluator, 10);
     let mut s2 = Random::new();
     for _ in 0..100 {
         assert_ne!(battle_royale(&mut s1, &mut s2), Some(1));
     }
 }
 
+// Positions' hashes are spread out enough for transposition tables.
+#[test]
+fn test_ttt_iterative_always_draws() {
+    let mut s1 = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    let mut s2 = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    s1.set_max_depth(9);
+    s2.set_max_depth(9);
+    for _ in 0..10 {
+        assert_eq!(battle_royale(&mut s1, &mut s2), None);
+    }
+}
+
 #[test]
 fn test_ttt_mcts_vs_random_always_wins_or_draws() {
     let mut s1 = MonteCarloTreeSearch::new(MCTSOptions::default().with_num_threads(1));
     s1.set_max_rollouts(100);
     let mut s2 = Random::new();
+    // A few rollouts can miss the only defense, so fix the games played.
+    s1.set_rng_seed(1);
+    s2.set_rng_seed(2);
     for _ in 0..50 {
         assert_ne!(battle_royale::<ttt::Game, _, _>(&mut s1, &mut s2), Some(1));
     }
 }
+
+#[test]
+fn test_ttt_validates() {
+    use minimax::util::{validate, ValidatingStrategy};
+    use minimax::Game;
+
+    let mut b = ttt::Board::default();
+    while ttt::Game::get_winner(&b).is_none() {
+        assert_eq!(validate::<ttt::Game>(&b), Ok(()));
+        let mut moves = Vec::new();
+        ttt::Game::generate_moves(&b, &mut moves);
+        ttt::Game::apply(&mut b, moves[moves.len() / 2]);
+    }
+
+    let mut s1 = ValidatingStrategy::new(Negamax::new(ttt::Evaluator, 3));
+    let mut s2 = ValidatingStrategy::new(Random::new());
+    battle_royale(&mut s1, &mut s2);
+
+    // Analysis is validated too.
+    let mut b = ttt::Board::default();
+    let mut moves = Vec::new();
+    ttt::Game::generate_moves(&b, &mut moves);
+    ttt::Game::apply(&mut b, moves[4]);
+    let mut valid = ValidatingStrategy::new(Negamax::new(ttt::Evaluator, 3));
+    assert_eq!(valid.analyze(&b, 3).len(), 3);
+    let mut stale = ValidatingStrategy::new(StaleAnalysis(Negamax::new(ttt::Evaluator, 3)));
+    let analysis = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| stale.analyze(&b, 9)));
+    assert!(analysis.is_err());
+}
+
+// Analyzes the empty board, whatever position it's given.
+struct StaleAnalysis(Negamax<ttt::Evaluator>);
+
+impl Strategy<ttt::Game> for StaleAnalysis {
+    fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+        self.0.choose_move(b)
+    }
+
+    fn analyze(&mut self, _: &ttt::Board, k: usize) -> Vec<minimax::MoveAnalysis<ttt::Place>> {
+        self.0.analyze(&ttt::Board::default(), k)
+    }
+}
+
+// Solves positions with few enough empty squares by brute force.
+struct Solver {
+    max_empty: usize,
+}
+
+impl Solver {
+    // Value for the player to move: 1 for a win, -1 for a loss.
+    fn solve(b: &mut ttt::Board) -> i8 {
+        use minimax::Game;
+        if let Some(winner) = ttt::Game::get_winner(b) {
+            return winner.evaluate().signum() as i8;
+        }
+        let mut moves = Vec::new();
+        ttt::Game::generate_moves(b, &mut moves);
+        let mut best = -1;
+        for m in moves {
+            ttt::Game::apply(b, m);
+            best = best.max(-Self::solve(b));
+            ttt::Game::undo(b, m);
+        }
+        best
+    }
+}
+
+impl minimax::Tablebase for Solver {
+    type G = ttt::Game;
+
+    fn probe(&self, b: &ttt::Board) -> Option<minimax::TablebaseResult> {
+        use minimax::Game;
+        let mut moves = Vec::new();
+        ttt::Game::generate_moves(b, &mut moves);
+        if moves.len() > self.max_empty {
+            return None;
+        }
+        let winner = match Self::solve(&mut b.clone()) {
+            1 => minimax::Winner::PlayerToMove,
+            -1 => minimax::Winner::PlayerJustMoved,
+            _ => minimax::Winner::Draw,
+        };
+        Some(minimax::TablebaseResult { winner, distance: None })
+    }
+}
+
+#[test]
+fn test_ttt_tablebase() {
+    // Chooses every move from the tablebase at the root.
+    let mut s1 = Negamax::new(ttt::Evaluator, 1);
+    s1.set_tablebase(Arc::new(Solver { max_empty: 9 }));
+    let mut s2 = Random::new();
+    for _ in 0..20 {
+        assert_ne!(battle_royale(&mut s1, &mut s2), Some(1));
+    }
+
+    // Cuts off the search at interior nodes.
+    let mut s1 = Negamax::new(ttt::Evaluator, 3);
+    s1.set_tablebase(Arc::new(Solver { max_empty: 6 }));
+    let mut s2 = Negamax::new(ttt::Evaluator, 10);
+    for _ in 0..20 {
+        assert_eq!(battle_royale(&mut s1, &mut s2), None);
+    }
+}
+
+#[test]
+fn test_ttt_human() {
+    use minimax::{Game, HumanStrategy};
+
+    let b = ttt::Board::default();
+    let mut moves = Vec::new();
+    ttt::Game::generate_moves(&b, &mut moves);
+
+    // Skips unrecognized input, then accepts the move number.
+    let input = std::io::Cursor::new("0\nxyz\n2\n@4\n");
+    let mut human = HumanStrategy::<ttt::Game>::with_io(Box::new(input), Box::new(Vec::new()));
+    assert!(human.choose_move(&b) == Some(moves[1]));
+    // Accepts the move notation.
+    assert!(human.choose_move(&b) == Some(moves[4]));
+    // Out of input.
+    assert!(human.choose_move(&b).is_none());
+}
+
+#[test]
+fn test_ttt_random() {
+    use minimax::Game;
+
+    let mut random = Random::<ttt::Game>::new();
+    let mut b = ttt::Board::default();
+    while ttt::Game::get_winner(&b).is_none() {
+        let mut moves = Vec::new();
+        ttt::Game::generate_moves(&b, &mut moves);
+        let m = random.choose_move(&b).unwrap();
+        assert!(moves.contains(&m));
+        ttt::Game::apply(&mut b, m);
+    }
+    assert!(random.choose_move(&b).is_none());
+}
+
+#[test]
+fn test_ttt_tournament() {
+    let mut random = Random::new();
+    random.set_rng_seed(3);
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("negamax", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("random", Box::new(random))
+        .with_games_per_pairing(10)
+        .with_time_per_game(std::time::Duration::from_secs(10));
+    let results = tournament.run(&ttt::Board::default());
+
+    // Perfect players always draw each other, and never lose.
+    assert_eq!(results.draws[0][1], 10);
+    assert_eq!(results.losses(0, 2), 0);
+    assert_eq!(results.losses(1, 2), 0);
+    let (wins, draws, losses) = results.record(2);
+    assert_eq!(wins + draws + losses, 20);
+    assert_eq!(wins, 0);
+
+    let elo = results.elo();
+    assert!(elo[0].elo > elo[2].elo);
+    assert!(elo[1].elo > elo[2].elo);
+    assert!(results.to_string().contains("random"));
+
+    let records = tournament.records();
+    assert_eq!(records.len(), 30);
+    for record in records {
+        let end = record.positions().pop().unwrap();
+        assert!(ttt::Game::get_winner(&end).is_some());
+        assert!(record.result.is_some());
+    }
+}
+
+#[test]
+fn test_ttt_gauntlet_and_knockout() {
+    use minimax::TournamentFormat;
+
+    let players = |format| {
+        let mut random = Random::new();
+        random.set_rng_seed(5);
+        Tournament::<ttt::Game>::new()
+            .with_player("perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+            .with_player("random", Box::new(random))
+            .with_player("also perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+            .with_games_per_pairing(4)
+            .with_format(format)
+    };
+
+    // The candidate plays each reference, which don't play each other.
+    let mut gauntlet = players(TournamentFormat::Gauntlet);
+    let results = gauntlet.run(&ttt::Board::default());
+    assert_eq!(gauntlet.records().len(), 8);
+    assert_eq!(results.record(1).0 + results.record(1).1 + results.record(1).2, 4);
+    assert_eq!(results.draws[1][2] + results.wins[1][2] + results.wins[2][1], 0);
+    assert_eq!(results.draws[0][2], 4);
+    let elo = results.elo();
+    assert_eq!(elo[2], EloEstimate { elo: 0.0, error: 0.0 });
+    // Rated against both references together, which is better than even
+    // but short of the perfect score against random alone.
+    let (wins, draws, losses) = results.record(0);
+    assert_eq!(wins + draws + losses, 8);
+    assert!(elo[0].elo > 0.0 && elo[0].elo <= -elo[1].elo);
+    // Even perfect scores have finite ratings.
+    assert!(elo.iter().all(|estimate| estimate.elo.is_finite() && estimate.error.is_finite()));
+    assert!(results.winner().is_none());
+
+    // The perfect players draw, and the better seed goes on to beat random.
+    let path = std::env::temp_dir().join(format!("minimax-knockout-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut knockout = players(TournamentFormat::Knockout);
+    let results = knockout.run_with_checkpoint(&ttt::Board::default(), &path).unwrap();
+    assert_eq!(knockout.records().len(), 8);
+    assert_eq!(results.draws[0][2], 4);
+    assert_eq!(results.rounds, [vec![0, 1], vec![0]]);
+    assert_eq!(results.winner(), Some(0));
+    assert!(results.to_string().contains("After round 2: perfect\n"));
+    assert_eq!(TournamentResults::from_checkpoint(&path).unwrap(), results);
+
+    // Resuming a finished knockout plays nothing and repeats no rounds.
+    let mut resumed = players(TournamentFormat::Knockout);
+    assert_eq!(resumed.run_with_checkpoint(&ttt::Board::default(), &path).unwrap(), results);
+    assert!(resumed.records().is_empty());
+    assert_eq!(TournamentResults::from_checkpoint(&path).unwrap(), results);
+    // Nor can a different format.
+    let mut other = players(TournamentFormat::RoundRobin);
+    assert!(other.run_with_checkpoint(&ttt::Board::default(), &path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_ttt_game_record() {
+    let mut record = GameRecord::<ttt::Game>::new(ttt::Board::default()).with_players("x", "o");
+    let mut s1 = Negamax::new(ttt::Evaluator, 10);
+    let mut state = ttt::Board::default();
+    for _ in 0..2 {
+        let info = s1.choose_move_with_info(&state);
+        record.add_search(&info);
+        ttt::Game::apply(&mut state, info.best_move.unwrap());
+    }
+    assert_eq!(record.moves.len(), 2);
+    assert_eq!(record.positions().len(), 3);
+    let text = record.to_text();
+    assert!(text.starts_with("players: x vs o\n1. "));
+    assert!(text.ends_with("result: unfinished\n"));
+    record.set_result(Outcome::Draw);
+    assert!(record.to_text().ends_with("result: draw\n"));
+}
+
+#[test]
+fn test_ttt_perft() {
+    let mut b = ttt::Board::default();
+    assert_eq!(minimax::perft::<ttt::Game>(&mut b, 3, false), vec![1, 9, 72, 504]);
+    let divide = minimax::perft_divide::<ttt::Game>(&mut b, 3, true);
+    assert_eq!(divide.len(), 9);
+    assert!(divide.iter().all(|&(_, count)| count == 56));
+}
+
+#[test]
+fn test_ttt_benchmark() {
+    let mut positions = vec![ttt::Board::default()];
+    let mut b = ttt::Board::default();
+    let mut moves = Vec::new();
+    ttt::Game::generate_moves(&b, &mut moves);
+    ttt::Game::apply(&mut b, moves[4]);
+    positions.push(b);
+
+    let run = || {
+        minimax::util::benchmark::<ttt::Game, _, _>(&positions, 5, || {
+            Negamax::new(ttt::Evaluator, 4)
+        })
+    };
+    let infos = run();
+    assert_eq!(infos.len(), 2);
+    assert!(infos.iter().all(|info| info.nodes > 0 && info.best_move.is_some()));
+    // Fixed seeds choose the same moves every time.
+    let moves = infos.iter().map(|info| info.best_move).collect::<Vec<_>>();
+    assert!(moves == run().iter().map(|info| info.best_move).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_ttt_uci() {
+    use minimax::UciEngine;
+
+    let output = SharedOutput::default();
+    let input = "uci\nisready\nposition startpos moves @4 @0\nbogus\ngo depth 9\n";
+    let mut engine = UciEngine::new("negamax", Negamax::new(ttt::Evaluator, 10));
+    engine.run(std::io::Cursor::new(input), Box::new(output.clone())).unwrap();
+
+    let text = output.text();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(&lines[..3], &["id name negamax", "uciok", "readyok"]);
+    assert!(lines.iter().any(|line| line.starts_with("info depth 9 score ")));
+    let best = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+    assert!(!["@4", "@0", "(none)"].contains(&best));
+
+    // The depth limit of one search doesn't carry over to the next.
+    let output = SharedOutput::default();
+    let input = "position startpos\ngo depth 1\ngo movetime 60000\n";
+    let mut engine = UciEngine::new("negamax", Negamax::new(ttt::Evaluator, 10));
+    engine.run(std::io::Cursor::new(input), Box::new(output.clone())).unwrap();
+    let text = output.text();
+    let info = text.lines().rfind(|line| line.starts_with("info")).unwrap();
+    assert!(info.starts_with("info depth 99 "), "{}", info);
+}
+
+#[test]
+fn test_ttt_gtp() {
+    use minimax::gtp::GtpEngine;
+
+    let input = "1 name\nboardsize 19\nplay b B2 # center\ngenmove w\nplay w B2\n\
+                 undo\nundo\nundo\nundo\nknown_command genmove\nquit\nname\n";
+    let mut engine = GtpEngine::<ttt::Game, _>::new("negamax", Negamax::new(ttt::Evaluator, 10), 3);
+    let mut output = Vec::new();
+    engine.run(std::io::Cursor::new(input), &mut output).unwrap();
+    let text = String::from_utf8(output).unwrap();
+    let responses = text.split("\n\n").collect::<Vec<_>>();
+
+    assert_eq!(responses[0], "=1 negamax");
+    assert_eq!(responses[1], "? unacceptable size");
+    assert_eq!(responses[2], "= ");
+    // O answers the center with a corner.
+    assert!(["= A1", "= A3", "= C1", "= C3"].contains(&responses[3]));
+    // Tic-tac-toe has no passes.
+    assert_eq!(responses[4], "? wrong color to move");
+    assert_eq!(&responses[5..9], &["= ", "= ", "? cannot undo", "? cannot undo"]);
+    assert_eq!(responses[9], "= true");
+    // Nothing after quit.
+    assert_eq!(&responses[10..], &["= ", ""]);
+    assert!(engine.state() == &ttt::Board::default());
+}
+
+#[test]
+fn test_ttt_match_clocks() {
+    use minimax::{Clock, Match, Termination, TimeControl};
+    use std::time::Duration;
+
+    // Byo-yomi periods are only used up by moves that overrun them.
+    let control =
+        TimeControl::new(Duration::from_millis(100)).with_byo_yomi(Duration::from_millis(50), 2);
+    let mut clock = Clock::new(control);
+    assert!(clock.punch(Duration::from_millis(120)));
+    assert_eq!((clock.remaining(), clock.periods()), (Duration::ZERO, 2));
+    assert!(clock.punch(Duration::from_millis(70)));
+    assert_eq!(clock.periods(), 1);
+    assert!(clock.punch(Duration::from_millis(50)));
+    assert!(!clock.punch(Duration::from_millis(51)));
+    assert!(clock.is_flagged());
+
+    let mut clock = Clock::new(
+        TimeControl::new(Duration::from_secs(1)).with_increment(Duration::from_millis(10)),
+    );
+    assert!(clock.punch(Duration::from_millis(100)));
+    assert_eq!(clock.remaining(), Duration::from_millis(910));
+    assert_eq!(clock.time_left().increment, Duration::from_millis(10));
+
+    // A player that ignores its clock loses on time.
+    struct Slow(Random<ttt::Game>);
+    impl Strategy<ttt::Game> for Slow {
+        fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+            std::thread::sleep(Duration::from_millis(30));
+            self.0.choose_move(b)
+        }
+    }
+    let game = Match::new(TimeControl::new(Duration::from_millis(50)));
+    let mut negamax = Negamax::new(ttt::Evaluator, 10);
+    let record = game.play(&ttt::Board::default(), &mut Slow(Random::new()), &mut negamax);
+    assert_eq!(record.result, Some(Outcome::Win(1)));
+    assert_eq!(record.termination, Some(Termination::TimeForfeit));
+    assert_eq!(record.moves.len(), 2);
+    assert!(record.moves[0].clock.is_some());
+    assert!(record.to_text().ends_with("second player wins on time\n"));
+}
+
+// Plays randomly, always reporting the same chance of winning.
+struct Confident(Random<ttt::Game>, f32);
+
+impl Strategy<ttt::Game> for Confident {
+    fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+        self.0.choose_move(b)
+    }
+
+    fn choose_move_with_info(&mut self, b: &ttt::Board) -> minimax::SearchInfo<ttt::Place> {
+        minimax::SearchInfo {
+            best_move: self.0.choose_move(b),
+            win_probability: Some(self.1),
+            ..Default::default()
+        }
+    }
+}
+
+#[test]
+fn test_ttt_match_flow() {
+    use minimax::{FlowOptions, FlowStrategy, Match, Recommendation, Termination};
+
+    // A player resigns on its second hopeless search.
+    let resigning = FlowOptions::new().with_resignation(0.1, 2);
+    let mut loser = FlowStrategy::new(Confident(Random::new(), 0.05), resigning);
+    let mut winner = FlowStrategy::new(Confident(Random::new(), 0.95), resigning);
+    let record = Match::untimed().play(&ttt::Board::default(), &mut loser, &mut winner);
+    assert_eq!(record.result, Some(Outcome::Win(1)));
+    assert_eq!(record.termination, Some(Termination::Resignation));
+    assert_eq!(record.moves.len(), 2);
+    assert!(record.to_text().ends_with("second player wins by resignation\n"));
+
+    // Draws are offered when even, and accepted when not winning.
+    let drawing = FlowOptions::new().with_draw_offers(0.05, 1);
+    let mut even = FlowStrategy::new(Confident(Random::new(), 0.5), drawing);
+    assert_eq!(
+        even.choose_move_with_info(&ttt::Board::default()).recommendation,
+        Some(Recommendation::OfferDraw)
+    );
+    let mut behind = FlowStrategy::new(Confident(Random::new(), 0.3), drawing);
+    assert_eq!(
+        behind.choose_move_with_info(&ttt::Board::default()).recommendation,
+        Some(Recommendation::AcceptDraw)
+    );
+    let mut ahead = FlowStrategy::new(Confident(Random::new(), 0.7), drawing);
+    assert_eq!(ahead.choose_move_with_info(&ttt::Board::default()).recommendation, None);
+
+    // The first offer comes before the second player has judged the
+    // position, so the second offer is the one accepted.
+    let record = Match::untimed().play(&ttt::Board::default(), &mut even, &mut behind);
+    assert_eq!(record.result, Some(Outcome::Draw));
+    assert_eq!(record.termination, Some(Termination::DrawAgreed));
+    assert_eq!(record.moves.len(), 2);
+}
+
+#[test]
+fn test_ttt_time_allocator() {
+    use minimax::{IterativeOptions, IterativeSearch, TimeAllocator, TimeLeft};
+    use std::time::Duration;
+
+    let time = TimeLeft {
+        remaining: Duration::from_secs(60),
+        increment: Duration::from_secs(1),
+        ..TimeLeft::default()
+    };
+    let allocator = TimeAllocator::new();
+    let budget = allocator.budget(&time, None);
+    assert_eq!(budget.soft, Duration::from_millis(2750));
+    assert_eq!(budget.hard, Duration::from_millis(8250));
+    // With 9 squares left, we have at most 5 more moves.
+    let budget = allocator.budget(&time, ttt::Game::max_moves_remaining(&ttt::Board::default()));
+    assert_eq!(budget.soft, Duration::from_millis(12750));
+    // Never more than half the main time.
+    assert_eq!(budget.hard, Duration::from_secs(30));
+    let budget = allocator.with_safety_margin(Duration::from_secs(59)).budget(&time, Some(1));
+    assert_eq!(budget.soft, Duration::from_millis(500));
+    assert_eq!(budget.soft, budget.hard);
+    // Later in a game of known length, each move gets more of the time left,
+    // down to a quarter of the default moves to go.
+    let allocator = allocator.with_game_length(40);
+    assert_eq!(allocator.budget(&time, None).soft, Duration::from_millis(2750));
+    assert_eq!(allocator.budget_at_ply(&time, None, Some(0)).soft, Duration::from_millis(3750));
+    assert_eq!(allocator.budget_at_ply(&time, None, Some(38)).soft, Duration::from_millis(8250));
+    // Byo-yomi is left for after the main time.
+    let time = TimeLeft { byo_yomi: Duration::from_secs(10), ..TimeLeft::default() };
+    assert_eq!(allocator.budget(&time, None).soft, Duration::from_secs(9));
+
+    // A search told its clock budgets its own time.
+    let mut search = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    search.set_time_left(&TimeLeft { remaining: Duration::from_secs(10), ..TimeLeft::default() });
+    assert!(search.choose_move(&ttt::Board::default()).is_some());
+}
+
+#[test]
+fn test_ttt_spsa() {
+    use minimax::{Parameter, Spsa};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Plays perfectly with probability `skill`, and randomly otherwise.
+    struct Sometimes {
+        skill: f64,
+        perfect: Negamax<ttt::Evaluator>,
+        random: Random<ttt::Game>,
+        rng: StdRng,
+    }
+    impl Strategy<ttt::Game> for Sometimes {
+        fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+            if self.rng.gen::<f64>() < self.skill {
+                self.perfect.choose_move(b)
+            } else {
+                self.random.choose_move(b)
+            }
+        }
+    }
+
+    let mut seed = 0;
+    let mut spsa = Spsa::new(vec![Parameter::new("skill", 0.2, 0.0, 1.0).with_r_end(2.0)], 40)
+        .with_games_per_iteration(4)
+        .with_rng_seed(1);
+    let values = spsa.run::<ttt::Game, _>(&ttt::Board::default(), |values| {
+        seed += 1;
+        let mut perfect = Negamax::new(ttt::Evaluator, 10);
+        perfect.set_rng_seed(seed);
+        let mut random = Random::new();
+        random.set_rng_seed(seed);
+        let rng = StdRng::seed_from_u64(seed);
+        Box::new(Sometimes { skill: values[0], perfect, random, rng })
+    });
+    assert!(values[0] > 0.5);
+    assert_eq!(spsa.params()[0].value, values[0]);
+}
+
+#[test]
+fn test_ttt_suite() {
+    use minimax::suite::{parse_suite, run_suite};
+    use minimax::util::parse_move;
+
+    // Positions are written as the moves from the start.
+    let parse_position = |text: &str| {
+        let mut b = ttt::Board::default();
+        for word in text.split_whitespace() {
+            let m = parse_move::<ttt::Game>(&b, word)?;
+            ttt::Game::apply(&mut b, m);
+        }
+        Some(b)
+    };
+    let text = "# X to move wins at once.\n\
+                @0 @3 @1 @4 ; bm @2 ; win ; id row\n\
+                \n\
+                @4 ; am @1 @3 @5 @7 ; draw ; id edges\n\
+                @4 ; bm @1 ; id wrong\n";
+    let suite = parse_suite::<ttt::Game, _>(text, parse_position).unwrap();
+    assert_eq!(suite.len(), 3);
+    assert_eq!(suite[0].id, "row");
+
+    let mut negamax = Negamax::new(ttt::Evaluator, 10);
+    let results = run_suite(&suite, &mut negamax);
+    let passed = results.iter().map(|result| result.passed).collect::<Vec<_>>();
+    assert_eq!(passed, vec![true, true, false]);
+
+    assert_eq!(
+        parse_suite::<ttt::Game, _>("@4 ; bm @4", parse_position).err().unwrap(),
+        "line 1: illegal move @4"
+    );
+}
+
+// Counts the moves it's told about.
+struct Follower {
+    inner: Random<ttt::Game>,
+    plies: u32,
+}
+
+impl Strategy<ttt::Game> for Follower {
+    fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+        assert_eq!(ttt::Game::ply(b), Some(self.plies));
+        self.inner.choose_move(b)
+    }
+
+    fn notify_move(&mut self, _: &ttt::Board, _: ttt::Place) {
+        self.plies += 1;
+    }
+
+    fn new_game(&mut self) {
+        self.plies = 0;
+    }
+}
+
+#[test]
+fn test_ttt_notify_move() {
+    use minimax::Match;
+
+    let mut s1 = Follower { inner: Random::new(), plies: 0 };
+    let mut s2 = Follower { inner: Random::new(), plies: 0 };
+    let record = Match::untimed().play(&ttt::Board::default(), &mut s1, &mut s2);
+    assert_eq!(s1.plies as usize, record.moves.len());
+    assert_eq!(s2.plies as usize, record.moves.len());
+    // The same strategies play another game, from its start.
+    let record = Match::untimed().play(&ttt::Board::default(), &mut s2, &mut s1);
+    assert_eq!(s1.plies as usize, record.moves.len());
+
+    let mut s1 = Follower { inner: Random::new(), plies: 0 };
+    let mut s2 = Follower { inner: Random::new(), plies: 0 };
+    minimax::util::battle_royale(&mut s1, &mut s2);
+    assert!(s1.plies >= 5);
+    assert_eq!(s1.plies, s2.plies);
+    minimax::util::battle_royale(&mut s2, &mut s1);
+    assert_eq!(s1.plies, s2.plies);
+
+    // The UCI engine tells it the new moves of each position, and starts a
+    // new game for a position that doesn't continue the last one.
+    let output = SharedOutput::default();
+    let input = "position startpos moves @4\ngo depth 1\nposition startpos moves @4 @0 @8\n\
+                 go depth 1\nposition startpos moves @0\ngo depth 1\nucinewgame\n\
+                 position startpos moves @0\ngo depth 1\n";
+    let follower = Follower { inner: Random::new(), plies: 0 };
+    let mut engine = minimax::UciEngine::new("follower", follower);
+    engine.run(std::io::Cursor::new(input), Box::new(output.clone())).unwrap();
+    assert_eq!(output.text().matches("bestmove @").count(), 4);
+}
+
+#[test]
+fn test_ttt_analyze() {
+    use minimax::analysis::{analyze, annotated_text};
+    use minimax::Match;
+
+    let mut s1 = Random::new();
+    s1.set_rng_seed(3);
+    let mut s2 = Random::new();
+    s2.set_rng_seed(4);
+    let record = Match::untimed().play(&ttt::Board::default(), &mut s1, &mut s2);
+
+    let mut negamax = Negamax::new(ttt::Evaluator, 9);
+    let annotations = analyze(&record, &mut negamax);
+    assert_eq!(annotations.len(), record.moves.len());
+    for annotation in annotations.iter() {
+        // Nothing is better than perfect play.
+        assert!(annotation.best_value.unwrap() >= annotation.played_value.unwrap());
+        if annotation.agrees() {
+            assert_eq!(annotation.loss(), Some(0));
+        }
+    }
+    let text = annotated_text(&record, &annotations);
+    assert_eq!(text.lines().count(), record.moves.len());
+    assert!(text.starts_with("1. @"));
+}
+
+#[test]
+fn test_ttt_multi_pv() {
+    use minimax::util::parse_move;
+
+    // X in the top corners and O in the middle row, with X to win in the
+    // top middle.
+    let mut b = ttt::Board::default();
+    for word in ["@0", "@3", "@2", "@4"] {
+        let m = parse_move::<ttt::Game>(&b, word).unwrap();
+        ttt::Game::apply(&mut b, m);
+    }
+    let win = parse_move::<ttt::Game>(&b, "@1").unwrap();
+    let check = |lines: &[minimax::MoveAnalysis<ttt::Place>], k: usize| {
+        assert_eq!(lines.len(), k);
+        assert!(lines[0].m == win);
+        for pair in lines.windows(2) {
+            assert!(pair[0].m != pair[1].m);
+        }
+        for line in lines {
+            assert!(line.principal_variation.first() == Some(&line.m));
+            // Every line can be played out.
+            let mut state = b.clone();
+            for &m in line.principal_variation.iter() {
+                assert!(ttt::Game::is_legal(&state, m));
+                ttt::Game::apply(&mut state, m);
+            }
+        }
+    };
+
+    let mut negamax = Negamax::new(ttt::Evaluator, 9);
+    let lines = negamax.analyze(&b, 3);
+    check(&lines, 3);
+    assert!(lines[0].value.unwrap() > 0);
+    // Lines that don't win at once go on until the game is decided.
+    assert!(lines[1..].iter().all(|line| line.principal_variation.len() > 1));
+    assert!(lines.windows(2).all(|pair| pair[0].value >= pair[1].value));
+    // Asking for more lines than there are moves gives them all.
+    let all = negamax.analyze(&b, 10);
+    check(&all, 5);
+    assert!(all.iter().zip(&lines).all(|(a, b)| a.value == b.value));
+    // Back to normal searches afterwards.
+    assert!(negamax.choose_move(&b) == Some(win));
+
+    let mut iterative = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    iterative.set_max_depth(9);
+    let deep = iterative.analyze(&b, 3);
+    check(&deep, 3);
+    assert_eq!(
+        deep.iter().map(|line| line.value).collect::<Vec<_>>(),
+        lines.iter().map(|line| line.value).collect::<Vec<_>>()
+    );
+
+    let values = |lines: &[minimax::MoveAnalysis<ttt::Place>]| {
+        lines.iter().map(|line| line.value).collect::<Vec<_>>()
+    };
+    let mut parallel =
+        ParallelSearch::new(ttt::Evaluator, IterativeOptions::new(), ParallelOptions::new());
+    parallel.set_max_depth(9);
+    let split = parallel.analyze(&b, 3);
+    check(&split, 3);
+    assert_eq!(values(&split), values(&lines));
+
+    // Wrappers pass analysis on to the strategies they wrap.
+    let book = BookStrategy::new(OpeningBook::new(), Negamax::new(ttt::Evaluator, 9));
+    let mut fallback = Fallback::new().with_strategy(Box::new(book));
+    let wrapped = fallback.analyze(&b, 3);
+    check(&wrapped, 3);
+    assert_eq!(values(&wrapped), values(&lines));
+    assert_eq!(fallback.chosen(), Some(0));
+    let mut ensemble = Ensemble::new(Voting::Majority)
+        .with_strategy(Box::new(Negamax::new(ttt::Evaluator, 9)), 1.0);
+    assert_eq!(values(&ensemble.analyze(&b, 3)), values(&lines));
+
+    let mut mcts = MonteCarloTreeSearch::<ttt::Game>::new(MCTSOptions::default());
+    mcts.set_max_rollouts(2000);
+    mcts.set_rng_seed(5);
+    let lines = mcts.analyze(&b, 2);
+    check(&lines, 2);
+
+    assert_eq!(Random::<ttt::Game>::new().analyze(&b, 3).len(), 1);
+    assert!(negamax.analyze(&b, 0).is_empty());
+}
+
+#[test]
+fn test_ttt_sprt() {
+    use minimax::{Sprt, SprtDecision};
+
+    let sprt = Sprt::new(0.0, 50.0);
+    assert_eq!(sprt.decision(0, 0, 0), None);
+    assert_eq!(sprt.decision(60, 20, 20), Some(SprtDecision::AcceptH1));
+    assert_eq!(sprt.decision(20, 20, 60), Some(SprtDecision::AcceptH0));
+    let (lower, upper) = sprt.bounds();
+    assert!(lower < 0.0 && upper > 0.0);
+
+    // A perfect player is quickly found to be stronger than a random one.
+    let mut random = Random::new();
+    random.set_rng_seed(5);
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("random", Box::new(random))
+        .with_games_per_pairing(1000)
+        .with_sprt(sprt);
+    let results = tournament.run(&ttt::Board::default());
+    assert_eq!(results.sprt[0][1], Some(SprtDecision::AcceptH1));
+    assert!(tournament.records().len() < 100);
+    assert!(tournament.records().len().is_multiple_of(2));
+    assert!(results.to_string().contains("SPRT perfect vs random: H1 accepted"));
+}
+
+#[test]
+fn test_ttt_json() {
+    use minimax::analysis::{analyze, annotated_json};
+
+    let output = SharedOutput::default();
+    let mut random = Random::new();
+    random.set_rng_seed(6);
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("perfect \"one\"", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("random", Box::new(random))
+        .with_games_per_pairing(4)
+        .with_json_output(Box::new(output.clone()));
+    let results = tournament.run(&ttt::Board::default());
+    let text = output.text();
+    let lines: Vec<&str> = text.lines().collect();
+
+    // One line per game, then the results.
+    assert_eq!(lines.len(), 5);
+    for (line, record) in lines.iter().zip(tournament.records()) {
+        assert!(
+            line.starts_with("{\"players\":[\"perfect \\\"one\\\"\",\"random\"]")
+                || line.starts_with("{\"players\":[\"random\",\"perfect \\\"one\\\"\"]")
+        );
+        assert_eq!(line.matches("\"depth\":").count(), record.moves.len());
+        assert!(line.contains("\"nodes\":") && line.contains("\"pv\":["));
+        assert!(
+            !line.contains("\"result\":\"0-1\"") || line.starts_with("{\"players\":[\"random\"")
+        );
+    }
+    assert_eq!(lines[4], results.to_json());
+    assert!(lines[4].starts_with("{\"names\":[\"perfect \\\"one\\\"\",\"random\"],\"wins\":[[0,"));
+    assert!(lines[4].contains("\"elo\":[{\"elo\":"));
+
+    let record = &tournament.records()[0];
+    let annotations = analyze(record, &mut Negamax::new(ttt::Evaluator, 9));
+    let json = annotated_json(record, &annotations);
+    assert!(json.starts_with("[{\"played\":\"@"));
+    assert_eq!(json.matches("\"best_value\":").count(), record.moves.len());
+}
+
+#[test]
+fn test_ttt_checkpoint() {
+    let path = std::env::temp_dir().join(format!("minimax-checkpoint-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let tournament = || {
+        let mut random = Random::new();
+        random.set_rng_seed(7);
+        Tournament::<ttt::Game>::new()
+            .with_player("perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+            .with_player("random", Box::new(random))
+            .with_games_per_pairing(6)
+    };
+
+    let mut first = tournament();
+    let results = first.run_with_checkpoint(&ttt::Board::default(), &path).unwrap();
+    assert_eq!(first.records().len(), 6);
+    assert_eq!(TournamentResults::from_checkpoint(&path).unwrap(), results);
+
+    // Interrupt it after two games, partway through writing the third.
+    let text = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4 + 6);
+    std::fs::write(&path, format!("{}\ngame 0 1", lines[..6].join("\n"))).unwrap();
+    let partial = TournamentResults::from_checkpoint(&path).unwrap();
+    assert_eq!(partial.record(0).0 + partial.record(0).1 + partial.record(0).2, 2);
+
+    let mut resumed = tournament();
+    let results = resumed.run_with_checkpoint(&ttt::Board::default(), &path).unwrap();
+    assert_eq!(resumed.records().len(), 4);
+    let (wins, draws, losses) = results.record(1);
+    assert_eq!(wins + draws + losses, 6);
+    assert_eq!(wins, 0);
+    assert_eq!(TournamentResults::from_checkpoint(&path).unwrap(), results);
+
+    // A different tournament can't resume it.
+    let mut other = tournament().with_games_per_pairing(8);
+    assert!(other.run_with_checkpoint(&ttt::Board::default(), &path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Panics instead of choosing the sixth move of a game.
+struct Crasher(Negamax<ttt::Evaluator>);
+
+impl Strategy<ttt::Game> for Crasher {
+    fn choose_move(&mut self, b: &ttt::Board) -> Option<ttt::Place> {
+        assert_ne!(ttt::Game::ply(b), Some(5), "crashed");
+        self.0.choose_move(b)
+    }
+}
+
+#[test]
+fn test_ttt_move_log() {
+    use minimax::{Match, MoveLog};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let path = std::env::temp_dir().join(format!("minimax-move-log-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let start = ttt::Board::default();
+    let starts = [start.clone()];
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("perfect", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("crasher", Box::new(Crasher(Negamax::new(ttt::Evaluator, 10))))
+        .with_move_log(MoveLog::append(&path).unwrap());
+    let crash = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| tournament.run(&start)));
+    assert!(crash.is_err());
+
+    // Only the move being chosen is lost.
+    let read = || MoveLog::read::<ttt::Game>(BufReader::new(File::open(&path).unwrap()), &starts);
+    let games = read().unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].players, ["perfect", "crasher"]);
+    assert_eq!(games[0].moves.len(), 5);
+    assert!(games[0].result.is_none());
+    // The crash can be reproduced from the last position.
+    let position = games[0].positions().pop().unwrap();
+    let mut crasher = Crasher(Negamax::new(ttt::Evaluator, 10));
+    let crash = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        crasher.choose_move(&position);
+    }));
+    assert!(crash.is_err());
+
+    // Games played later are numbered after the crashed one, and after one
+    // whose first line was cut off. Cut off lines are skipped.
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    std::io::Write::write_all(&mut file, b"move 0 6 0.001 - @\ngame 1 perfect v").unwrap();
+    let log = MoveLog::append(&path).unwrap();
+    let game = Match::untimed().with_players("x", "o");
+    let first =
+        game.play_logged(&start, &mut Negamax::new(ttt::Evaluator, 10), &mut Random::new(), &log);
+    let second = game.play_logged(&start, &mut Random::new(), &mut Random::new(), &log);
+    let games = read().unwrap();
+    assert_eq!(games.len(), 3);
+    assert_eq!(games[0].moves.len(), 5);
+    for (logged, played) in games[1..].iter().zip([first, second]) {
+        assert_eq!(logged.players, ["x", "o"]);
+        assert!(logged.moves.iter().map(|m| m.m).eq(played.moves.iter().map(|m| m.m)));
+        assert_eq!(logged.moves[0].value, played.moves[0].value);
+        assert_eq!((logged.result, logged.termination), (played.result, played.termination));
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // A complete line that can't be read is an error, not a panic.
+    let corrupt = "game 0 x vs o;\nmove 0 1 -1 - @4;\n";
+    assert!(MoveLog::read::<ttt::Game>(corrupt.as_bytes(), &starts).is_err());
+}
+
+#[test]
+fn test_ttt_time_odds() {
+    use minimax::{Termination, TimeControl};
+    use std::time::Duration;
+
+    let control = TimeControl::new(Duration::from_secs(4))
+        .with_increment(Duration::from_millis(100))
+        .with_byo_yomi(Duration::from_secs(1), 3);
+    let doubled = control.scaled(2.0);
+    assert_eq!(doubled.main_time, Duration::from_secs(8));
+    assert_eq!(doubled.increment, Duration::from_millis(200));
+    assert_eq!(doubled.byo_yomi, Duration::from_secs(2));
+    assert_eq!(doubled.byo_yomi_periods, 3);
+
+    // A player given no time at all loses every game on time.
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("full", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("none", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_games_per_pairing(4)
+        .with_time_per_game(Duration::from_secs(10))
+        .with_time_odds("none", 0.0);
+    let results = tournament.run(&ttt::Board::default());
+    assert_eq!(results.wins[0][1], 4);
+    for record in tournament.records() {
+        assert_eq!(record.termination, Some(Termination::TimeForfeit));
+    }
+}
+
+#[test]
+fn test_ttt_openings() {
+    use minimax::util::parse_move;
+
+    let start = ttt::Board::default();
+    let place = |i: &str| parse_move::<ttt::Game>(&start, i).unwrap();
+    let after = |i: &str| {
+        let mut board = start.clone();
+        ttt::Game::apply(&mut board, place(i));
+        board
+    };
+    let corner = after("@0");
+    let mut tournament = Tournament::<ttt::Game>::new()
+        .with_player("a", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_player("b", Box::new(Negamax::new(ttt::Evaluator, 10)))
+        .with_openings(vec![corner.clone()])
+        .with_opening_lines(vec![vec![place("@4")], vec![place("@1"), place("@2")]]);
+    let results = tournament.run(&start);
+
+    // Both colors from each of the three openings, all drawn with perfect play.
+    assert_eq!(results.draws[0][1], 6);
+    let records = tournament.records();
+    assert_eq!(records.len(), 6);
+    assert!(records[0].start == corner && records[1].start == corner);
+    assert_eq!(records[0].players, ["a".to_string(), "b".to_string()]);
+    assert_eq!(records[1].players, ["b".to_string(), "a".to_string()]);
+    assert!(records[2].start == after("@4") && records[3].start == after("@4"));
+    assert!(records[4].start == records[5].start);
+    assert_eq!(records[4].start.to_string().matches(['X', 'O']).count(), 2);
+}
+
+#[test]
+fn test_ttt_corpus() {
+    use minimax::corpus::CorpusStats;
+    use minimax::Match;
+
+    let records: Vec<GameRecord<ttt::Game>> = (0..50)
+        .map(|_| {
+            Match::untimed().play(&ttt::Board::default(), &mut Random::new(), &mut Random::new())
+        })
+        .collect();
+    let stats = CorpusStats::from_records(&records);
+    assert_eq!(stats.games, 50);
+    assert_eq!(stats.results.games(), 50);
+    assert_eq!(stats.lengths.values().sum::<u32>(), 50);
+    assert!((5.0..=9.0).contains(&stats.mean_length()));
+    // Every game has a first move, and never the same square twice.
+    let first: u32 = stats.moves.iter().filter(|((n, _), _)| *n == 1).map(|(_, s)| s.played).sum();
+    assert_eq!(first, 50);
+    let popularity: f64 = (0..9).map(|i| stats.popularity(1, &format!("@{}", i))).sum();
+    assert!((popularity - 1.0).abs() < 1e-9);
+    let total: u32 = stats.moves.values().map(|s| s.played).sum();
+    assert_eq!(total as usize, records.iter().map(|r| r.moves.len()).sum::<usize>());
+
+    let csv = stats.moves_csv();
+    assert_eq!(csv.lines().count(), stats.moves.len() + 1);
+    assert!(csv.starts_with("number,move,played,first_wins,draws,second_wins\n1,@"));
+    assert_eq!(stats.lengths_csv().lines().count(), stats.lengths.len() + 1);
+    assert!(stats.to_json().starts_with("{\"games\":50,"));
+
+    // Just the openings.
+    let openings = CorpusStats::new().with_first_moves(1);
+    let openings = records.iter().fold(openings, |mut openings, record| {
+        openings.add(record);
+        openings
+    });
+    assert!(openings.moves.keys().all(|(n, _)| *n == 1));
+    assert_eq!(openings.lengths, stats.lengths);
+}
+
+#[test]
+fn test_ttt_ponder_hit_elsewhere() {
+    use minimax::Ponder;
+
+    // Find a position with two empty squares, where neither move ends the
+    // game, so each child has only the other square left.
+    let children = |b: &ttt::Board| {
+        let mut moves = Vec::new();
+        ttt::Game::generate_moves(b, &mut moves);
+        moves
+            .iter()
+            .map(|&m| {
+                let mut child = b.clone();
+                ttt::Game::apply(&mut child, m);
+                (m, child)
+            })
+            .collect::<Vec<_>>()
+    };
+    let b = loop {
+        let mut b = ttt::Board::default();
+        let mut random = Random::<ttt::Game>::new();
+        while ttt::Game::get_winner(&b).is_none() && children(&b).len() > 2 {
+            let m = random.choose_move(&b).unwrap();
+            ttt::Game::apply(&mut b, m);
+        }
+        let moves = children(&b);
+        if moves.len() == 2 && moves.iter().all(|(_, c)| ttt::Game::get_winner(c).is_none()) {
+            break b;
+        }
+    };
+    let moves = children(&b);
+    let (predicted, _) = moves[0];
+    let elsewhere = moves[1].1.clone();
+
+    let mut iterative = IterativeSearch::new(ttt::Evaluator, IterativeOptions::new());
+    iterative.set_max_depth(4);
+    let mut mcts = MonteCarloTreeSearch::new(MCTSOptions::default());
+    mcts.set_max_rollouts(100);
+    let strategies: [&mut dyn Ponder<ttt::Game>; 2] = [&mut iterative, &mut mcts];
+    for strategy in strategies {
+        // The pondered reply would be the other square, which is taken
+        // here, so the position reached has to be searched itself.
+        strategy.ponder(&b, predicted);
+        strategy.ponder_hit();
+        assert!(strategy.choose_move(&elsewhere).unwrap() == predicted);
+    }
+}